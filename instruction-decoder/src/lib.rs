@@ -12,8 +12,61 @@ pub struct Decoder {
     instruction_sets: Vec<InstructionSet>,
 }
 
+/// An instruction decoded into the parts shown in its representation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    /// Name of the instruction, e.g. `addi`
+    pub name: String,
+    /// Name of the instruction set the instruction is defined in, e.g. `RV32I`
+    pub set: String,
+    /// The parts shown in the representation, in the order they first appear
+    pub parts: Vec<DecodedPart>,
+    repr: Vec<ReprSegment>,
+}
+
+/// A part of a decoded instruction, e.g. a register or an immediate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedPart {
+    /// Name of the part, e.g. `rd_Register_int`
+    pub name: String,
+    /// Name of the mapping used to show the part, if any, e.g. `Register_int`
+    pub mapping: Option<String>,
+    /// Value of the part before it is formatted or mapped
+    pub value: u128,
+    /// The formatted value of the part, as shown in the representation
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ReprSegment {
+    Text(String),
+    Part(usize),
+}
+
+impl DecodedInstruction {
+    /// Formats the instruction using the representation from the instruction set
+    pub fn text(&self) -> String {
+        self.text_with(|part| part.text.clone())
+    }
+
+    /// Formats the instruction using the representation from the instruction set, with
+    /// `part_text` giving the text for each part
+    pub fn text_with(&self, mut part_text: impl FnMut(&DecodedPart) -> String) -> String {
+        self.repr
+            .iter()
+            .map(|segment| match segment {
+                ReprSegment::Text(text) => text.clone(),
+                ReprSegment::Part(idx) => part_text(&self.parts[*idx]),
+            })
+            .collect()
+    }
+}
+
 struct InstructionSet {
+    name: String,
     bit_width: usize,
+    /// Instructions of this set may be decoded zero-extended to a wider width
+    zero_extended: bool,
     formats: BTreeMap<String, InstructionFormat>,
     parts: HashMap<String, PartDecoder>,
     mappings: HashMap<String, Mapping>,
@@ -117,11 +170,29 @@ fn handle_err_get_multitype(
     }
 }
 
+fn optional_bool(table: &Table, error_stack: &mut Vec<String>, key: &str) -> bool {
+    match table.get(key) {
+        None => false,
+        Some(Value::Boolean(value)) => *value,
+        Some(_) => {
+            error_stack.push(format!("optional field {} is not of type 'boolean'", key));
+            false
+        }
+    }
+}
+
 impl InstructionSet {
     pub fn new(table: &Table, error_stack: &mut Vec<String>) -> Self {
+        let name = table
+            .get("set")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
         let bit_width = handle_err_get(table, error_stack, "width", "", Value::Integer(0))
             .as_integer()
             .unwrap() as usize;
+        let zero_extended = optional_bool(table, error_stack, "zero_extended");
+        let slices_above_width = optional_bool(table, error_stack, "slices_above_width");
 
         let mappings_table_value = handle_err_get(
             table,
@@ -242,6 +313,7 @@ impl InstructionSet {
                         format!("types.{}", x).as_str(),
                         x.as_str().unwrap(),
                         bit_width,
+                        slices_above_width,
                     ),
                 ))
             } else {
@@ -333,7 +405,9 @@ impl InstructionSet {
         }
 
         InstructionSet {
+            name,
             bit_width,
+            zero_extended,
             formats,
             parts,
             mappings: mapping_map,
@@ -827,35 +901,52 @@ impl Instruction {
         (instruction_u128 & self.mask_u128) == self.match_u128
     }
 
-    fn display(
+    fn decode(
         &self,
         values: &HashMap<String, SliceValue>,
         instruction_format: &InstructionFormat,
-        part_decoders: &HashMap<String, PartDecoder>,
-        mappings: &HashMap<String, Mapping>,
-    ) -> String {
-        let mut fmt = if instruction_format.repr.contains_key(&self.name) {
+        instruction_set: &InstructionSet,
+    ) -> DecodedInstruction {
+        let fmt = if instruction_format.repr.contains_key(&self.name) {
             instruction_format.repr.get(&self.name)
         } else {
             instruction_format.repr.get("default")
         }
         .unwrap()
-        .clone();
-
-        fmt = fmt.replace("$name$", &self.name);
-        while fmt.contains('%') {
-            let begin = fmt.find('%').unwrap() + 1;
-            let end = fmt[begin..].find('%').unwrap() + begin;
-            let var_name = &fmt[begin..end];
-
-            fmt = fmt.replace(
-                &fmt[begin - 1..end + 1],
-                values[var_name]
-                    .get_string_value(&part_decoders[var_name], mappings)
-                    .as_str(),
-            );
+        .replace("$name$", &self.name);
+
+        // The representation is checked to have matching %s when the instruction set is
+        // created, so every other segment is a part name
+        let mut parts: Vec<DecodedPart> = vec![];
+        let repr = fmt
+            .split('%')
+            .enumerate()
+            .map(|(i, segment)| {
+                if i % 2 == 0 {
+                    return ReprSegment::Text(segment.to_string());
+                }
+                if let Some(idx) = parts.iter().position(|part| part.name == segment) {
+                    return ReprSegment::Part(idx);
+                }
+                let part_decoder = &instruction_set.parts[segment];
+                parts.push(DecodedPart {
+                    name: segment.to_string(),
+                    mapping: match &part_decoder.part_type {
+                        PartType::Mapping(mapping) => Some(mapping.clone()),
+                        _ => None,
+                    },
+                    value: values[segment].value,
+                    text: values[segment].get_string_value(part_decoder, &instruction_set.mappings),
+                });
+                ReprSegment::Part(parts.len() - 1)
+            })
+            .collect();
+        DecodedInstruction {
+            name: self.name.clone(),
+            set: instruction_set.name.clone(),
+            parts,
+            repr,
         }
-        fmt
     }
 }
 
@@ -872,6 +963,7 @@ impl InstructionType {
         table_prefix: &str,
         type_name: &str,
         bit_width: usize,
+        slices_above_width: bool,
     ) -> Self {
         let mut position = 0;
         let slices = names
@@ -886,8 +978,10 @@ impl InstructionType {
                         error_stack,
                         format!("{}[{}]", table_prefix, i).as_str(),
                         type_name,
-                        bit_width,
                     );
+                    if !slice.fits(bit_width, slices_above_width) {
+                        error_stack.push(format!("instruction slice \"{}\" of type \"{}\" at position {} downto {} is out of range for bit width {}", slice.name, type_name, slice.slice_top-1, slice.slice_bottom, bit_width));
+                    }
                     position += slice.slice_top - slice.slice_bottom;
                     Some(slice)
                 } else {
@@ -960,7 +1054,6 @@ impl InstructionSlice {
         error_stack: &mut Vec<String>,
         table_prefix: &str,
         type_name: &str,
-        bit_width: usize,
     ) -> Self {
         let name = handle_err_get(
             table,
@@ -994,10 +1087,6 @@ impl InstructionSlice {
             0
         };
 
-        if slice_top > bit_width || slice_bottom > bit_width {
-            error_stack.push(format!("instruction slice \"{}\" of type \"{}\" at position {} downto {} is out of range for bit width {}", name, type_name, slice_top-1, slice_bottom, bit_width));
-        };
-
         InstructionSlice {
             name,
             pos: *position,
@@ -1006,6 +1095,18 @@ impl InstructionSlice {
             slice_extend,
         }
     }
+
+    /// Slices must lie within the instruction, unless the instruction set allows placing
+    /// them above it, e.g. to scale an immediate. They then still have to fit in the
+    /// decoded value.
+    fn fits(&self, bit_width: usize, above_width: bool) -> bool {
+        if above_width {
+            self.slice_top.saturating_sub(self.slice_bottom) <= bit_width
+                && self.slice_top <= u128::BITS as usize
+        } else {
+            self.slice_top <= bit_width && self.slice_bottom <= bit_width
+        }
+    }
 }
 
 impl Decoder {
@@ -1094,7 +1195,60 @@ impl Decoder {
     }
 
     pub fn decode_all(&self, instruction: u128, bit_width: usize) -> Vec<String> {
-        let mut finds: Vec<String> = vec![];
+        self.decode_all_structured(instruction, bit_width)
+            .iter()
+            .map(DecodedInstruction::text)
+            .collect()
+    }
+
+    pub fn decode_structured(
+        &self,
+        instruction: u128,
+        bit_width: usize,
+    ) -> Result<DecodedInstruction, String> {
+        self.decode_all_structured(instruction, bit_width)
+            .pop()
+            .ok_or_else(|| "Unknown Instruction".to_string())
+    }
+
+    /// Decodes `instruction` using the instruction sets of width `bit_width`. If none of them
+    /// match, narrower instruction sets marked as `zero_extended` are tried, widest first, as
+    /// long as the instruction fits in them. This allows variable-length instruction sets such
+    /// as Thumb-2, where a short instruction is given zero-extended to the width of the longest
+    /// one.
+    pub fn decode_all_structured(
+        &self,
+        instruction: u128,
+        bit_width: usize,
+    ) -> Vec<DecodedInstruction> {
+        let finds = self.decode_all_with_width(instruction, bit_width);
+        if !finds.is_empty() {
+            return finds;
+        }
+
+        let mut narrower_widths: Vec<usize> = self
+            .instruction_sets
+            .iter()
+            .filter(|instruction_set| instruction_set.zero_extended)
+            .map(|instruction_set| instruction_set.bit_width)
+            .filter(|&width| width < bit_width && instruction >> width == 0)
+            .collect();
+        narrower_widths.sort_unstable();
+        narrower_widths.dedup();
+        narrower_widths
+            .into_iter()
+            .rev()
+            .map(|width| self.decode_all_with_width(instruction, width))
+            .find(|finds| !finds.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn decode_all_with_width(
+        &self,
+        instruction: u128,
+        bit_width: usize,
+    ) -> Vec<DecodedInstruction> {
+        let mut finds: Vec<DecodedInstruction> = vec![];
 
         for instruction_set in &self.instruction_sets {
             if bit_width == instruction_set.bit_width {
@@ -1107,12 +1261,7 @@ impl Decoder {
                                 &instruction_set.parts,
                                 inst.unsigned_imm,
                             );
-                            finds.push(inst.display(
-                                &values,
-                                inst_format,
-                                &instruction_set.parts,
-                                &instruction_set.mappings,
-                            ));
+                            finds.push(inst.decode(&values, inst_format, instruction_set));
                         }
                     }
                 }
@@ -0,0 +1,8 @@
+use crate::isa_test;
+use crate::Decoder;
+use std::fs::read_to_string;
+
+#[test]
+fn test_aarch64() {
+    isa_test!("../../toml/aarch64.toml", "test_data/aarch64/aarch64.test");
+}
@@ -13,3 +13,34 @@ fn test_some_error() {
         vec!["key 'types.B' not found in toml".to_string()]
     );
 }
+
+#[test]
+fn test_slices_above_width_need_opt_in() {
+    let toml = include_str!("../../toml/aarch64.toml").replace("slices_above_width = true", "");
+    match Decoder::new(&[toml]) {
+        Ok(_) => panic!("expected slices above the instruction width to be rejected"),
+        Err(error_stacks) => assert_eq!(
+            error_stacks,
+            vec![vec![
+                "instruction slice \"imm\" of type \"t22\" at position 47 downto 32 is out of range for bit width 32".to_string(),
+                "instruction slice \"wide_x\" of type \"t23\" at position 63 downto 48 is out of range for bit width 32".to_string(),
+                "instruction slice \"offset\" of type \"t27\" at position 32 downto 14 is out of range for bit width 32".to_string(),
+            ]]
+        ),
+    }
+}
+
+#[test]
+fn test_optional_flags_must_be_booleans() {
+    let toml = include_str!("../../toml/thumb16.toml")
+        .replace("zero_extended = true", "zero_extended = 1");
+    match Decoder::new(&[toml]) {
+        Ok(_) => panic!("expected a non-boolean flag to be rejected"),
+        Err(error_stacks) => assert_eq!(
+            error_stacks,
+            vec![vec![
+                "optional field zero_extended is not of type 'boolean'".to_string()
+            ]]
+        ),
+    }
+}
@@ -1,6 +1,8 @@
+mod aarch64;
 mod error;
 mod mips;
 mod rv32;
 mod rv64;
+mod thumb;
 mod la64;
 mod util;
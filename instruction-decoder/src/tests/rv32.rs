@@ -59,3 +59,49 @@ fn test_rv32_c() {
 fn test_rv32_zfa() {
     isa_test!("../../toml/RV32_Zfa.toml", "test_data/rv32/rv32zfa.test");
 }
+
+#[test]
+fn test_rv32_structured() {
+    let decoder = Decoder::new(&[include_str!("../../toml/RV32I.toml").to_string()]).unwrap();
+    let decoded = decoder.decode_structured(0x81350593, 32).unwrap();
+    assert_eq!(decoded.name, "addi");
+    assert_eq!(decoded.set, "RV32I");
+    assert_eq!(decoded.text(), "addi a1, a0, -2029");
+    assert_eq!(
+        decoded
+            .parts
+            .iter()
+            .map(|part| (
+                part.name.as_str(),
+                part.mapping.as_deref(),
+                part.text.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            ("rd_Register_int", Some("Register_int"), "a1"),
+            ("rs1_Register_int", Some("Register_int"), "a0"),
+            ("imm", None, "-2029"),
+        ]
+    );
+    assert_eq!(decoded.parts[0].value, 11);
+    assert_eq!(
+        decoded.text_with(|part| match part.mapping.as_deref() {
+            Some("Register_int") => format!("x{}", part.value),
+            _ => part.text.clone(),
+        }),
+        "addi x11, x10, -2029"
+    );
+    assert!(decoder.decode_structured(0x809f, 32).is_err());
+}
+
+#[test]
+fn test_rv32c_not_zero_extended() {
+    let decoder = Decoder::new(&[
+        include_str!("../../toml/RV32I.toml").to_string(),
+        include_str!("../../toml/RV32C.toml").to_string(),
+    ])
+    .unwrap();
+    assert_eq!(decoder.decode(0x40a5, 16).unwrap(), "c.li ra, 9");
+    // Only instruction sets marked as zero-extended are tried at a wider width
+    assert!(decoder.decode(0x40a5, 32).is_err());
+}
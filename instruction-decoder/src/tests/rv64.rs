@@ -149,3 +149,16 @@ fn test_rv64_zfa() {
 fn test_rvv() {
     isa_test!("../../toml/RVV.toml", "test_data/rv64/rvv.test");
 }
+
+#[test]
+fn test_rv64_zicsr() {
+    isa_test!(
+        "../../toml/RV64_Zicsr.toml",
+        "test_data/rv64/rv64zicsr.test"
+    );
+}
+
+#[test]
+fn test_rv_system() {
+    isa_test!("../../toml/RV_System.toml", "test_data/rv64/rvsystem.test");
+}
@@ -0,0 +1,40 @@
+use crate::isa_test;
+use crate::Decoder;
+use std::fs::read_to_string;
+
+#[test]
+fn test_thumb16() {
+    isa_test!("../../toml/thumb16.toml", "test_data/thumb/thumb16.test");
+}
+
+#[test]
+fn test_thumb32() {
+    isa_test!("../../toml/thumb32.toml", "test_data/thumb/thumb32.test");
+}
+
+#[test]
+fn test_thumb_variable_length() {
+    let decoder = Decoder::new(&[
+        include_str!("../../toml/thumb16.toml").to_string(),
+        include_str!("../../toml/thumb32.toml").to_string(),
+    ])
+    .unwrap();
+    assert_eq!(decoder.decode(0x4770, 32).unwrap(), "bx lr");
+    assert_eq!(decoder.decode(0xb510, 32).unwrap(), "push {r4, lr}");
+    assert_eq!(
+        decoder.decode(0xf8d10004, 32).unwrap(),
+        "ldr.w r0, [r1, #4]"
+    );
+    assert_eq!(decoder.decode(0xf000f800, 32).unwrap(), "bl #0");
+    // Both halves of a 32-bit instruction are required
+    assert!(decoder.decode(0xf8d1, 32).is_err());
+}
+
+#[test]
+fn test_thumb16_zero_extended() {
+    let decoder = Decoder::new(&[include_str!("../../toml/thumb16.toml").to_string()]).unwrap();
+    assert_eq!(decoder.decode(0x4770, 16).unwrap(), "bx lr");
+    assert_eq!(decoder.decode(0x4770, 32).unwrap(), "bx lr");
+    // Instructions that do not fit are not truncated
+    assert!(decoder.decode(0x10004770, 32).is_err());
+}
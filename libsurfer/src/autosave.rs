@@ -0,0 +1,342 @@
+//! Saving of the session in the background, so that it can be restored after a crash or after
+//! Surfer was closed by accident. The session is saved per waveform file and offered to be
+//! restored the next time the same file is opened.
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use camino::Utf8Path;
+use directories::ProjectDirs;
+use eyre::Context;
+use log::warn;
+
+use crate::async_util::perform_work;
+use crate::message::Message;
+use crate::state_file_io::decode_state;
+use crate::wave_source::STATE_FILE_EXTENSION;
+use crate::SystemState;
+
+/// Changes are saved at the latest this long after the first unsaved change, even if the
+/// session keeps changing
+const MAX_AUTOSAVE_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+pub struct AutosaveState {
+    /// The directory the sessions are saved in, nothing is saved if there is none
+    pub(crate) dir: Option<PathBuf>,
+    /// The first and the last change which are not saved yet
+    changes: Option<(Instant, Instant)>,
+    /// The last saved file and the hash of its session, to not write the session again if it
+    /// did not change
+    last_saved: Option<(PathBuf, u64)>,
+    /// Autosaving is paused while asking whether to restore the saved session, which would
+    /// otherwise be overwritten
+    pub(crate) paused: bool,
+}
+
+impl AutosaveState {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        AutosaveState {
+            dir,
+            ..Default::default()
+        }
+    }
+}
+
+/// The directory the sessions are saved in by default
+pub(crate) fn default_autosave_dir() -> Option<PathBuf> {
+    ProjectDirs::from("org", "surfer-project", "surfer")
+        .map(|dirs| dirs.data_local_dir().join("autosave"))
+}
+
+/// FNV-1a hash, which unlike the hasher of the standard library is the same in every version
+fn stable_hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The name of the file the session of `waveform` is saved in
+fn autosave_file_name(waveform: &Utf8Path) -> String {
+    format!(
+        "{}-{:016x}.{STATE_FILE_EXTENSION}",
+        waveform.file_name().unwrap_or("waves"),
+        stable_hash(waveform.as_str())
+    )
+}
+
+/// The file in `dir` the session of `waveform` is saved in
+pub(crate) fn autosave_file(dir: &Path, waveform: &Utf8Path) -> PathBuf {
+    let waveform = waveform
+        .canonicalize_utf8()
+        .unwrap_or_else(|_| waveform.to_owned());
+    dir.join(autosave_file_name(&waveform))
+}
+
+impl Message {
+    /// True for messages which change what is shown, such as adding, removing or formatting
+    /// items, markers, tabs and settings. Scrolling, zooming and moving the cursor happen too
+    /// often to encode the session after each of them, they are saved with the next change
+    pub(crate) fn changes_session(&self) -> bool {
+        match self {
+            Message::Batch(messages) => messages.iter().any(Message::changes_session),
+            Message::AddVariables(_)
+            | Message::AddScope(..)
+            | Message::AddScopeAsGroup(..)
+            | Message::AddStreamOrGenerator(_)
+            | Message::AddStreamOrGeneratorFromName(..)
+            | Message::AddAllFromStreamScope(_)
+            | Message::AddDraggedVariables(_)
+            | Message::AddDivider(..)
+            | Message::AddTimeLine(_)
+            | Message::RemoveItemByIndex(_)
+            | Message::RemoveItems(_)
+            | Message::RemovePlaceholders
+            | Message::MoveFocusedItem(..)
+            | Message::VariableDragFinished
+            | Message::VariableFormatChange(..)
+            | Message::ResetVariableFormat(_)
+            | Message::ItemColorChange(..)
+            | Message::ItemBackgroundColorChange(..)
+            | Message::ItemNameChange(..)
+            | Message::ItemValueColorRuleChange(..)
+            | Message::ItemHeightScalingFactorChange(..)
+            | Message::ChangeVariableNameType(..)
+            | Message::ForceVariableNameTypes(_)
+            | Message::GroupNew { .. }
+            | Message::GroupDissolve(_)
+            | Message::GroupFold(_)
+            | Message::GroupUnfold(_)
+            | Message::GroupFoldRecursive(_)
+            | Message::GroupUnfoldRecursive(_)
+            | Message::GroupFoldAll
+            | Message::GroupUnfoldAll
+            | Message::ApplyViewTemplate(..)
+            | Message::AddMarker { .. }
+            | Message::SetMarker { .. }
+            | Message::RemoveMarker(_)
+            | Message::MoveMarkerToCursor(_)
+            | Message::AddViewport
+            | Message::RemoveViewport
+            | Message::SetViewportLock(_)
+            | Message::SetPerViewportCursors(_)
+            | Message::AddViewBookmark(_)
+            | Message::RemoveViewBookmark(_)
+            | Message::SetTimeAlignment(..)
+            | Message::SwitchTab(_)
+            | Message::CloseTab(_)
+            | Message::WavesLoaded(..)
+            | Message::TransactionStreamsLoaded(..)
+            | Message::LoadState(..)
+            | Message::Undo(_)
+            | Message::Redo(_)
+            | Message::ToggleMenu
+            | Message::ToggleToolbar
+            | Message::ToggleOverview
+            | Message::ToggleStatusbar
+            | Message::ToggleIndices
+            | Message::ToggleDirection
+            | Message::ToggleEmptyScopes
+            | Message::ToggleParametersInScopes
+            | Message::ToggleSidePanel
+            | Message::ToggleDefaultTimeline
+            | Message::ToggleTickLines
+            | Message::ToggleVariableTooltip
+            | Message::ToggleScopeTooltip
+            | Message::SetNameAlignRight(_)
+            | Message::SetClockHighlightType(_)
+            | Message::SetFillHighValues(_)
+            | Message::SetHighlightFocused(_)
+            | Message::SetHierarchyStyle(_)
+            | Message::SetArrowKeyBindings(_)
+            | Message::SetPrimaryMouseDragBehavior(_)
+            | Message::SetTimeUnit(_)
+            | Message::SetTimeStringFormatting(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl SystemState {
+    /// The saved session of the loaded waveform, if there is one
+    pub(crate) fn autosaved_session(&self) -> Option<PathBuf> {
+        let waveform = self.user.waves.as_ref()?.source.path()?;
+        Some(autosave_file(self.autosave.dir.as_ref()?, waveform)).filter(|file| file.exists())
+    }
+
+    /// Notes that the session may have changed
+    pub(crate) fn autosave_changed(&mut self) {
+        let now = Instant::now();
+        let first = self.autosave.changes.map_or(now, |(first, _)| first);
+        self.autosave.changes = Some((first, now));
+    }
+
+    /// Saves the session once it has not changed for the configured time, to be called every
+    /// frame
+    pub(crate) fn autosave_if_due(&mut self, ctx: &egui::Context) {
+        let delay = Duration::from_secs(self.user.config.autosave_seconds);
+        if delay.is_zero() || self.autosave.paused {
+            return;
+        }
+        let Some((first, last)) = self.autosave.changes else {
+            return;
+        };
+        let due = (last + delay).min(first + MAX_AUTOSAVE_DELAY.max(delay));
+        let now = Instant::now();
+        if now < due {
+            // egui only redraws on input, make sure we get back here
+            ctx.request_repaint_after(due - now);
+            return;
+        }
+        self.autosave.changes = None;
+
+        let Some(file) = self
+            .user
+            .waves
+            .as_ref()
+            .and_then(|waves| waves.source.path())
+            .zip(self.autosave.dir.as_ref())
+            .map(|(waveform, dir)| autosave_file(dir, waveform))
+        else {
+            return;
+        };
+        let Some(encoded) = self.encode_state() else {
+            return;
+        };
+        let hash = stable_hash(&encoded);
+        if self
+            .autosave
+            .last_saved
+            .as_ref()
+            .is_some_and(|(saved_file, saved)| *saved_file == file && *saved == hash)
+        {
+            return;
+        }
+        self.autosave.last_saved = Some((file.clone(), hash));
+
+        perform_work(move || {
+            if let Err(e) = file
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::write(&file, encoded))
+            {
+                warn!("Failed to save the session to {}: {e}", file.display());
+            }
+        });
+    }
+
+    /// Loads the saved session of the loaded waveform
+    pub(crate) fn restore_session(&mut self) {
+        let Some(file) = self.autosaved_session() else {
+            return;
+        };
+        // The session is restored, not loaded from a state file the user can save to
+        let state_file = self.user.state_file.clone();
+        let sender = self.channels.msg_sender.clone();
+        perform_work(move || {
+            let decoded = std::fs::read(&file)
+                .context("Failed to read the file")
+                .and_then(|bytes| decode_state(&bytes))
+                .with_context(|| format!("Failed to restore the session from {}", file.display()));
+            match decoded {
+                Ok(decoded) => {
+                    if decoded.report(&file.display().to_string()) {
+                        sender.send(Message::SetLogsVisible(true)).unwrap();
+                    }
+                    sender
+                        .send(Message::LoadState(Box::new(decoded.state), state_file))
+                        .unwrap();
+                }
+                Err(e) => sender.send(Message::Error(e)).unwrap(),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use num::BigInt;
+    use project_root::get_project_root;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    use super::*;
+    use crate::wave_container::WaveContainer;
+    use crate::wave_source::{LoadOptions, WaveFormat, WaveSource};
+
+    #[test]
+    fn autosave_file_names_depend_on_the_whole_path() {
+        let a = autosave_file_name(Utf8Path::new("/work/a/waves.vcd"));
+        let b = autosave_file_name(Utf8Path::new("/work/b/waves.vcd"));
+        assert!(a.starts_with("waves.vcd-"));
+        assert!(a.ends_with(".surf.ron"));
+        assert_ne!(a, b);
+        assert_eq!(a, autosave_file_name(Utf8Path::new("/work/a/waves.vcd")));
+    }
+
+    #[test]
+    fn stable_hash_is_fnv1a() {
+        assert_eq!(stable_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    fn counter_state(autosave_dir: Option<PathBuf>) -> SystemState {
+        let mut state = SystemState::new_default_config().unwrap();
+        state.autosave = AutosaveState::new(autosave_dir);
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        let header = wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap();
+        state.on_waves_loaded(
+            WaveSource::File(path.try_into().unwrap()),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(header.hierarchy))),
+            LoadOptions {
+                keep_variables: false,
+                keep_unavailable: false,
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn sessions_are_only_found_in_the_autosave_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let waveform = get_project_root().unwrap().join("examples/counter.vcd");
+        let file = autosave_file(dir.path(), Utf8Path::from_path(&waveform).unwrap());
+        std::fs::write(&file, "").unwrap();
+
+        let state = counter_state(Some(dir.path().to_path_buf()));
+        assert_eq!(state.autosaved_session(), Some(file));
+        assert_eq!(counter_state(None).autosaved_session(), None);
+    }
+
+    #[test]
+    fn only_changes_to_the_session_are_autosaved() {
+        assert!(Message::ToggleMenu.changes_session());
+        assert!(Message::AddDivider(None, None).changes_session());
+        assert!(!Message::CommandPromptClear.changes_session());
+        assert!(!Message::SetFilterFocused(true).changes_session());
+        assert!(!Message::CursorSet(BigInt::from(10)).changes_session());
+        assert!(!Message::SetScrollOffset(1.0).changes_session());
+        assert!(!Message::Batch(vec![
+            Message::CommandPromptClear,
+            Message::InvalidateDrawCommands
+        ])
+        .changes_session());
+        assert!(
+            Message::Batch(vec![Message::CommandPromptClear, Message::ToggleMenu])
+                .changes_session()
+        );
+
+        let mut state = counter_state(None);
+        state.autosave.changes = None;
+        state.update(Message::CommandPromptClear);
+        state.update(Message::CursorSet(BigInt::from(10)));
+        assert!(state.autosave.changes.is_none());
+        state.update(Message::ToggleMenu);
+        assert!(state.autosave.changes.is_some());
+    }
+}
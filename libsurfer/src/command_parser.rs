@@ -7,8 +7,13 @@ use crate::fzcmd::{Command, ParamGreed};
 use crate::hierarchy::HierarchyStyle;
 use crate::lazy_static;
 use crate::message::MessageTarget;
+use crate::time::TimeAlignment;
 use crate::transaction_container::StreamScopeRef;
-use crate::wave_container::{ScopeRef, ScopeRefExt, VariableRef, VariableRefExt};
+use crate::transaction_export::TransactionExportFormat;
+use crate::value_color::{ValueColorCondition, ValueColorRule};
+use crate::variable_filter::VariableSortType;
+use crate::viewport::ViewportLock;
+use crate::wave_container::{DiagnosticKind, ScopeRef, ScopeRefExt, VariableRef, VariableRefExt};
 use crate::wave_data::ScopeType;
 use crate::wave_source::LoadOptions;
 use crate::{
@@ -21,6 +26,8 @@ use crate::{
 };
 use itertools::Itertools;
 use log::warn;
+use num::BigInt;
+use surfer_translation_types::VariableType;
 
 type RestCommand = Box<dyn Fn(&str) -> Option<Command<Message>>>;
 
@@ -34,6 +41,11 @@ fn is_command_file_extension(ext: &str) -> bool {
     ext == "sucl"
 }
 
+/// Match str with ELF file extensions, currently: elf
+fn is_elf_file_extension(ext: &str) -> bool {
+    ext == "elf"
+}
+
 /// Split part of a query at whitespace
 ///
 /// fzcmd splits at regex "words" which does not include special characters
@@ -139,6 +151,23 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
         })
         .unwrap_or_default();
 
+    let displayed_variables = state
+        .user
+        .waves
+        .as_ref()
+        .map(|waves| {
+            waves
+                .displayed_items
+                .values()
+                .filter_map(|item| match item {
+                    DisplayedItem::Variable(var) => Some(var.variable_ref.full_path_string()),
+                    _ => None,
+                })
+                .unique()
+                .collect_vec()
+        })
+        .unwrap_or_default();
+
     let color_names = state.user.config.theme.colors.keys().cloned().collect_vec();
     let format_names: Vec<String> = state
         .translators
@@ -153,11 +182,18 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
         .as_ref()
         .and_then(|w| w.active_scope.clone());
 
+    // Templates are saved and applied relative to the active scope, or to the top level
+    let template_root = match &active_scope {
+        Some(ScopeType::WaveScope(scope)) => scope.clone(),
+        _ => ScopeRef::empty(),
+    };
+
+    // Sessions with a waveform use the stream and generator commands for transactions
     let is_transaction_container = state
         .user
         .waves
         .as_ref()
-        .is_some_and(|w| w.inner.is_transactions());
+        .is_some_and(|w| w.inner.is_transactions() && !w.inner.is_waves());
 
     fn files_with_ext(matches: fn(&str) -> bool) -> Vec<String> {
         if let Ok(res) = fs::read_dir(".") {
@@ -177,10 +213,22 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
         files_with_ext(is_wave_file_extension)
     }
 
+    fn all_transaction_files() -> Vec<String> {
+        files_with_ext(|ext| ext == "ftr")
+    }
+
     fn all_command_files() -> Vec<String> {
         files_with_ext(is_command_file_extension)
     }
 
+    fn all_elf_files() -> Vec<String> {
+        files_with_ext(is_elf_file_extension)
+    }
+
+    fn all_template_files() -> Vec<String> {
+        files_with_ext(|ext| ext == "ron")
+    }
+
     let markers = if let Some(waves) = &state.user.waves {
         waves
             .items_tree
@@ -195,6 +243,30 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
         Vec::new()
     };
 
+    let tabs = (0..state.num_tabs())
+        .map(|tab| tab.to_string())
+        .collect_vec();
+    let active_tab = state.user.active_tab;
+    let bookmarks = state
+        .user
+        .waves
+        .as_ref()
+        .map(|waves| {
+            waves
+                .view_bookmarks
+                .iter()
+                .map(|bookmark| bookmark.name.clone())
+                .collect_vec()
+        })
+        .unwrap_or_default();
+    let per_viewport_cursors = state
+        .user
+        .waves
+        .as_ref()
+        .is_some_and(|waves| waves.per_viewport_cursors);
+
+    let variable_filter_types = state.user.variable_filter.variable_types.clone();
+
     fn parse_marker(query: &str, markers: &[(Option<String>, u8)]) -> Option<u8> {
         if let Some(id_str) = query.strip_prefix("#") {
             let id = id_str.parse::<u8>().ok()?;
@@ -241,7 +313,13 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "load_state",
             "run_command_file",
             "run_command_file_from_url",
+            "load_elf",
+            "tab_new",
+            "tab_switch",
+            "tab_close",
+            "tab_load",
             "switch_file",
+            "transaction_file_add",
             "variable_add",
             "generator_add",
             "item_focus",
@@ -250,6 +328,8 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "item_set_format",
             "item_unset_color",
             "item_unset_background_color",
+            "item_set_value_color",
+            "item_clear_value_colors",
             "item_unfocus",
             "item_rename",
             "zoom_fit",
@@ -258,6 +338,8 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "scope_add_as_group",
             "scope_add_as_group_recursive",
             "scope_select",
+            "show_hierarchy_search",
+            "show_disassembly",
             "stream_add",
             "stream_select",
             "divider_add",
@@ -275,6 +357,11 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "scroll_to_end",
             "goto_start",
             "goto_end",
+            "navigate_back",
+            "navigate_forward",
+            "bookmark_add",
+            "bookmark_goto",
+            "bookmark_remove",
             "zoom_in",
             "zoom_out",
             "toggle_menu",
@@ -285,6 +372,11 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "generator_add_from_stream",
             "variable_set_name_type",
             "variable_force_name_type",
+            "variable_filter_type",
+            "variable_filter_width",
+            "variable_filter_translator",
+            "variable_filter_clear",
+            "variable_sort",
             "preference_set_clock_highlight",
             "preference_set_hierarchy_style",
             "preference_set_arrow_key_bindings",
@@ -299,6 +391,8 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "group_unfold_all",
             "save_state",
             "save_state_as",
+            "template_save",
+            "template_apply",
             "timeline_add",
             "cursor_set",
             "marker_set",
@@ -306,13 +400,28 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "show_marker_window",
             "viewport_add",
             "viewport_remove",
+            "viewport_lock",
+            "toggle_viewport_cursors",
+            "time_alignment_set",
+            "time_alignment_reset",
+            "show_time_alignment",
             "transition_next",
             "transition_previous",
             "transaction_next",
             "transaction_prev",
+            "transaction_search",
+            "transaction_search_clear",
+            "transaction_next_match",
+            "transaction_prev_match",
+            "transaction_export",
             "copy_value",
             "pause_simulation",
             "unpause_simulation",
+            "run_simulation_until",
+            "run_simulation_until_time",
+            "run_simulation_for",
+            "step_simulation_cycles",
+            "show_diagnostics",
             "undo",
             "redo",
             #[cfg(not(target_arch = "wasm32"))]
@@ -328,6 +437,11 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
             "load_state",
             "run_command_file",
             "run_command_file_from_url",
+            "load_elf",
+            "tab_new",
+            "tab_switch",
+            "tab_close",
+            "tab_load",
             "config_reload",
             "theme_select",
             "toggle_menu",
@@ -356,9 +470,13 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
         commands.into_iter().map(std::convert::Into::into).collect(),
         Box::new(move |query, _| {
             let variables_in_active_scope = variables_in_active_scope.clone();
+            let displayed_variables = displayed_variables.clone();
             let markers = markers.clone();
+            let tabs = tabs.clone();
+            let bookmarks = bookmarks.clone();
             let scopes = scopes.clone();
             let active_scope = active_scope.clone();
+            let template_root = template_root.clone();
             let is_transaction_container = is_transaction_container;
             match query {
                 "load_file" => single_word_delayed_suggestions(
@@ -382,6 +500,18 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                         )))
                     }),
                 ),
+                "transaction_file_add" => single_word_delayed_suggestions(
+                    Box::new(all_transaction_files),
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::LoadFile(
+                            word.into(),
+                            LoadOptions {
+                                keep_variables: true,
+                                keep_unavailable: false,
+                            },
+                        )))
+                    }),
+                ),
                 "load_url" => Some(Command::NonTerminal(
                     ParamGreed::Rest,
                     vec![],
@@ -405,6 +535,44 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                         )))
                     }),
                 )),
+                "load_elf" => single_word_delayed_suggestions(
+                    Box::new(all_elf_files),
+                    Box::new(|word| Some(Command::Terminal(Message::LoadElfFile(word.into())))),
+                ),
+                "tab_new" => Some(Command::Terminal(Message::SwitchTab(tabs.len()))),
+                "tab_switch" => single_word(
+                    tabs,
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::SwitchTab(word.parse().ok()?)))
+                    }),
+                ),
+                "tab_close" => optional_single_word(
+                    tabs,
+                    Box::new(move |word| {
+                        let tab = if word.trim().is_empty() {
+                            active_tab
+                        } else {
+                            word.parse().ok()?
+                        };
+                        Some(Command::Terminal(Message::CloseTab(tab)))
+                    }),
+                ),
+                "tab_load" => Some(Command::NonTerminal(
+                    ParamGreed::Word,
+                    tabs,
+                    Box::new(|tab, _| {
+                        let tab: usize = tab.parse().ok()?;
+                        single_word_delayed_suggestions(
+                            Box::new(all_wave_files),
+                            Box::new(move |word| {
+                                Some(Command::Terminal(Message::Batch(vec![
+                                    Message::SwitchTab(tab),
+                                    Message::LoadFile(word.into(), LoadOptions::clean()),
+                                ])))
+                            }),
+                        )
+                    }),
+                )),
                 "config_reload" => Some(Command::Terminal(Message::ReloadConfig)),
                 "theme_select" => single_word(
                     theme_names.clone(),
@@ -431,6 +599,33 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                     viewport_idx: 0,
                 })),
                 "zoom_fit" => Some(Command::Terminal(Message::ZoomToFit { viewport_idx: 0 })),
+                "navigate_back" => Some(Command::Terminal(Message::NavigateBack(1))),
+                "navigate_forward" => Some(Command::Terminal(Message::NavigateForward(1))),
+                "bookmark_add" => optional_single_word(
+                    vec![],
+                    Box::new(|name| {
+                        let trimmed = name.trim();
+                        Some(Command::Terminal(Message::AddViewBookmark(
+                            (!trimmed.is_empty()).then_some(trimmed.to_owned()),
+                        )))
+                    }),
+                ),
+                "bookmark_goto" => single_word(
+                    bookmarks,
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::GoToViewBookmark(
+                            word.to_string(),
+                        )))
+                    }),
+                ),
+                "bookmark_remove" => single_word(
+                    bookmarks,
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::RemoveViewBookmark(
+                            word.to_string(),
+                        )))
+                    }),
+                ),
                 "toggle_menu" => Some(Command::Terminal(Message::ToggleMenu)),
                 "toggle_side_panel" => Some(Command::Terminal(Message::ToggleSidePanel)),
                 "toggle_fullscreen" => Some(Command::Terminal(Message::ToggleFullscreen)),
@@ -438,7 +633,7 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                 // scope commands
                 "scope_add" | "module_add" | "stream_add" | "scope_add_recursive" => {
                     let recursive = query == "scope_add_recursive";
-                    if is_transaction_container {
+                    if is_transaction_container || query == "stream_add" {
                         if recursive {
                             warn!("Cannot recursively add transaction containers");
                         }
@@ -480,7 +675,7 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                     }
                 }
                 "scope_select" | "stream_select" => {
-                    if is_transaction_container {
+                    if is_transaction_container || query == "stream_select" {
                         single_word(
                             scopes.clone(),
                             Box::new(|word| {
@@ -509,7 +704,7 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                 "remove_unavailable" => Some(Command::Terminal(Message::RemovePlaceholders)),
                 // Variable commands
                 "variable_add" | "generator_add" => {
-                    if is_transaction_container {
+                    if is_transaction_container || query == "generator_add" {
                         single_word(
                             variables.clone(),
                             Box::new(|word| {
@@ -583,6 +778,45 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                 "item_unset_background_color" => Some(Command::Terminal(
                     Message::ItemBackgroundColorChange(MessageTarget::CurrentSelection, None),
                 )),
+                "item_set_value_color" => {
+                    let color_names = color_names.clone();
+                    Some(Command::NonTerminal(
+                        ParamGreed::Custom(&separate_at_space),
+                        vec![
+                            "xz".to_string(),
+                            "each".to_string(),
+                            "!=0".to_string(),
+                            "==0".to_string(),
+                        ],
+                        Box::new(move |condition, _| {
+                            let condition = ValueColorCondition::from_str(condition).ok()?;
+                            if condition == ValueColorCondition::EachValue {
+                                return Some(Command::Terminal(Message::ItemValueColorRuleChange(
+                                    MessageTarget::CurrentSelection,
+                                    Some(ValueColorRule {
+                                        condition,
+                                        color: None,
+                                    }),
+                                )));
+                            }
+                            single_word(
+                                color_names.clone(),
+                                Box::new(move |word| {
+                                    Some(Command::Terminal(Message::ItemValueColorRuleChange(
+                                        MessageTarget::CurrentSelection,
+                                        Some(ValueColorRule {
+                                            condition: condition.clone(),
+                                            color: Some(word.to_string()),
+                                        }),
+                                    )))
+                                }),
+                            )
+                        }),
+                    ))
+                }
+                "item_clear_value_colors" => Some(Command::Terminal(
+                    Message::ItemValueColorRuleChange(MessageTarget::CurrentSelection, None),
+                )),
                 "item_rename" => Some(Command::Terminal(Message::RenameItem(None))),
                 "variable_set_name_type" => single_word(
                     vec![
@@ -609,6 +843,62 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                         )))
                     }),
                 ),
+                "variable_filter_type" => single_word(
+                    enum_iterator::all::<VariableType>()
+                        .map(|t| t.to_string())
+                        .collect_vec(),
+                    Box::new(move |word| {
+                        let variable_type =
+                            enum_iterator::all::<VariableType>().find(|t| t.to_string() == word)?;
+                        let mut types = variable_filter_types.clone();
+                        if !types.contains(&variable_type) {
+                            types.push(variable_type);
+                        }
+                        Some(Command::Terminal(Message::SetVariableTypeFilter(types)))
+                    }),
+                ),
+                "variable_filter_width" => Some(Command::NonTerminal(
+                    ParamGreed::Word,
+                    vec![],
+                    Box::new(|min, _| {
+                        let min: u32 = min.parse().ok()?;
+                        optional_single_word(
+                            vec![],
+                            Box::new(move |max| {
+                                let max: u32 = if max.trim().is_empty() {
+                                    0
+                                } else {
+                                    max.parse().ok()?
+                                };
+                                Some(Command::Terminal(Message::SetVariableWidthFilter(
+                                    (min > 0).then_some(min),
+                                    (max > 0).then_some(max),
+                                )))
+                            }),
+                        )
+                    }),
+                )),
+                "variable_filter_translator" => single_word(
+                    format_names.clone(),
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::SetVariableTranslatorFilter(
+                            Some(word.to_string()),
+                        )))
+                    }),
+                ),
+                "variable_filter_clear" => {
+                    Some(Command::Terminal(Message::ClearVariableMetaFilters))
+                }
+                "variable_sort" => single_word(
+                    enum_iterator::all::<VariableSortType>()
+                        .map(|o| o.to_string())
+                        .collect_vec(),
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::SetVariableSortType(
+                            VariableSortType::from_str(word).ok()?,
+                        )))
+                    }),
+                ),
                 "item_focus" => single_word(
                     displayed_items.clone(),
                     Box::new(|word| {
@@ -652,6 +942,49 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                 "transaction_prev" => {
                     Some(Command::Terminal(Message::MoveTransaction { next: false }))
                 }
+                "transaction_search" => Some(Command::NonTerminal(
+                    ParamGreed::Rest,
+                    vec![],
+                    Box::new(|query, _| {
+                        Some(Command::Terminal(Message::Batch(vec![
+                            Message::SetTransactionQuery(Some(query.to_string())),
+                            Message::SetTransactionSearchVisible(true),
+                        ])))
+                    }),
+                )),
+                "transaction_search_clear" => {
+                    Some(Command::Terminal(Message::SetTransactionQuery(None)))
+                }
+                "transaction_next_match" => {
+                    Some(Command::Terminal(Message::MoveMatchingTransaction {
+                        next: true,
+                    }))
+                }
+                "transaction_prev_match" => {
+                    Some(Command::Terminal(Message::MoveMatchingTransaction {
+                        next: false,
+                    }))
+                }
+                "transaction_export" => Some(Command::NonTerminal(
+                    ParamGreed::Word,
+                    enum_iterator::all::<TransactionExportFormat>()
+                        .map(|format| format.name().to_string())
+                        .collect_vec(),
+                    Box::new(|word, _| {
+                        let format = enum_iterator::all::<TransactionExportFormat>()
+                            .find(|format| format.name() == word)?;
+                        single_word(
+                            vec![],
+                            Box::new(move |path| {
+                                Some(Command::Terminal(Message::ExportTransactions {
+                                    format,
+                                    streams: None,
+                                    path: Some(std::path::Path::new(path).into()),
+                                }))
+                            }),
+                        )
+                    }),
+                )),
                 "copy_value" => single_word(
                     displayed_items.clone(),
                     Box::new(|word| {
@@ -804,6 +1137,18 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                     Some(Command::Terminal(Message::SetCursorWindowVisible(true)))
                 }
                 "show_logs" => Some(Command::Terminal(Message::SetLogsVisible(true))),
+                "show_hierarchy_search" => {
+                    Some(Command::Terminal(Message::SetHierarchySearchVisible(true)))
+                }
+                "show_disassembly" => single_word(
+                    vec![],
+                    Box::new(|word| {
+                        let address = word.trim_start_matches("0x");
+                        Some(Command::Terminal(Message::SetDisassemblyAddress(Some(
+                            u64::from_str_radix(address, 16).ok()?,
+                        ))))
+                    }),
+                ),
                 "save_state" => Some(Command::Terminal(Message::SaveStateFile(
                     state_file.clone(),
                 ))),
@@ -823,15 +1168,122 @@ pub fn get_parser(state: &SystemState) -> Command<Message> {
                         ))))
                     }),
                 ),
+                "template_save" => single_word(
+                    vec![],
+                    Box::new(move |word| {
+                        Some(Command::Terminal(Message::SaveViewTemplate(
+                            template_root.clone(),
+                            Some(std::path::Path::new(word).into()),
+                        )))
+                    }),
+                ),
+                "template_apply" => single_word_delayed_suggestions(
+                    Box::new(all_template_files),
+                    Box::new(move |word| {
+                        Some(Command::Terminal(Message::LoadViewTemplate(
+                            template_root.clone(),
+                            Some(std::path::Path::new(word).into()),
+                        )))
+                    }),
+                ),
                 "viewport_add" => Some(Command::Terminal(Message::AddViewport)),
                 "viewport_remove" => Some(Command::Terminal(Message::RemoveViewport)),
+                "viewport_lock" => single_word(
+                    enum_iterator::all::<ViewportLock>()
+                        .map(|lock| lock.to_string())
+                        .collect_vec(),
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::SetViewportLock(
+                            ViewportLock::from_str(word).ok()?,
+                        )))
+                    }),
+                ),
+                "toggle_viewport_cursors" => Some(Command::Terminal(
+                    Message::SetPerViewportCursors(!per_viewport_cursors),
+                )),
+                "time_alignment_set" => Some(Command::NonTerminal(
+                    ParamGreed::Word,
+                    vec![],
+                    Box::new(|offset, _| {
+                        let offset: BigInt = offset.parse().ok()?;
+                        optional_single_word(
+                            vec![],
+                            Box::new(move |scale| {
+                                let scale = if scale.trim().is_empty() {
+                                    1.
+                                } else {
+                                    scale.parse().ok()?
+                                };
+                                Some(Command::Terminal(Message::SetTimeAlignment(
+                                    None,
+                                    TimeAlignment {
+                                        offset: offset.clone(),
+                                        scale,
+                                    },
+                                )))
+                            }),
+                        )
+                    }),
+                )),
+                "time_alignment_reset" => Some(Command::Terminal(Message::SetTimeAlignment(
+                    None,
+                    TimeAlignment::default(),
+                ))),
+                "show_time_alignment" => Some(Command::Terminal(Message::OpenTimeAlignmentDialog)),
                 "pause_simulation" => Some(Command::Terminal(Message::PauseSimulation)),
                 "unpause_simulation" => Some(Command::Terminal(Message::UnpauseSimulation)),
+                "run_simulation_until" => single_word(
+                    enum_iterator::all::<DiagnosticKind>()
+                        .map(|o| o.to_string())
+                        .collect_vec(),
+                    Box::new(|word| {
+                        Some(Command::Terminal(Message::RunSimulationUntilDiagnostic(
+                            DiagnosticKind::from_str(word).ok()?,
+                        )))
+                    }),
+                ),
+                "run_simulation_until_time" => single_word(
+                    vec![],
+                    Box::new(|time_str| {
+                        Some(Command::Terminal(Message::RunSimulationUntil(
+                            time_str.parse().ok()?,
+                        )))
+                    }),
+                ),
+                "run_simulation_for" => single_word(
+                    vec![],
+                    Box::new(|time_str| {
+                        Some(Command::Terminal(Message::RunSimulationFor(
+                            time_str.parse().ok()?,
+                        )))
+                    }),
+                ),
+                "step_simulation_cycles" => Some(Command::NonTerminal(
+                    ParamGreed::Word,
+                    displayed_variables,
+                    Box::new(|clock, _| {
+                        let clock = VariableRef::from_hierarchy_string(clock);
+                        Some(Command::NonTerminal(
+                            ParamGreed::Word,
+                            vec![],
+                            Box::new(move |cycles, _| {
+                                Some(Command::Terminal(Message::StepSimulationCycles {
+                                    clock: clock.clone(),
+                                    cycles: cycles.parse().ok()?,
+                                }))
+                            }),
+                        ))
+                    }),
+                )),
+                "show_diagnostics" => Some(Command::Terminal(
+                    Message::SetDiagnosticsWindowVisible(true),
+                )),
                 "undo" => Some(Command::Terminal(Message::Undo(1))),
                 "redo" => Some(Command::Terminal(Message::Redo(1))),
                 "wcp_server_start" => Some(Command::Terminal(Message::StartWcpServer {
                     address: None,
                     initiate: false,
+                    transport: None,
                 })),
                 "wcp_server_stop" => Some(Command::Terminal(Message::StopWcpServer)),
                 "exit" => Some(Command::Terminal(Message::Exit)),
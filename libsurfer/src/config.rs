@@ -1,3 +1,4 @@
+use camino::Utf8PathBuf;
 use config::builder::DefaultState;
 use config::{Config, ConfigBuilder};
 #[cfg(not(target_arch = "wasm32"))]
@@ -18,6 +19,7 @@ use std::path::{Path, PathBuf};
 use crate::hierarchy::HierarchyStyle;
 use crate::mousegestures::GestureZones;
 use crate::time::TimeFormat;
+use crate::value_color::ValueColorRuleConfig;
 use crate::{clock_highlighting::ClockHighlightType, variable_name_type::VariableNameType};
 
 /// Select the function of the arrow keys
@@ -75,12 +77,28 @@ pub struct SurferConfig {
     pub snap_distance: f32,
     /// Maximum size of the undo stack
     pub undo_stack_size: usize,
+    /// Maximum number of views to remember for back/forward navigation
+    pub navigation_history_size: usize,
+    /// Show ABI register names, such as `a0`, rather than numeric names, such as `x10`,
+    /// in decoded instructions
+    pub abi_register_names: bool,
     /// Reload changed waves
     autoreload_files: AutoLoad,
     /// Load state file
     autoload_sibling_state_files: AutoLoad,
+    /// Restore the autosaved session of a waveform when opening it again
+    autorestore_sessions: AutoLoad,
+    /// Save the session this many seconds after it last changed, so that it can be restored
+    /// after a crash. 0 disables autosaving.
+    pub autosave_seconds: u64,
     /// WCP Configuration
     pub wcp: WcpConfig,
+    /// Rules for coloring values of variables with matching names
+    #[serde(default)]
+    pub value_color_rules: Vec<ValueColorRuleConfig>,
+    /// ELF file to load symbols and code for disassembly from at startup
+    #[serde(default)]
+    pub elf_file: Option<Utf8PathBuf>,
 }
 
 impl SurferConfig {
@@ -95,6 +113,10 @@ impl SurferConfig {
     pub fn autoreload_files(&self) -> AutoLoad {
         self.autoreload_files
     }
+
+    pub fn autorestore_sessions(&self) -> AutoLoad {
+        self.autorestore_sessions
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -576,8 +598,24 @@ pub struct ThemeColorTriple {
 pub struct WcpConfig {
     /// Controls if a server is started after Surfer is launched
     pub autostart: bool,
-    /// Address to bind to (address:port)
+    /// Address to bind to (address:port), or the socket path for [`WcpTransport::Unix`]
     pub address: String,
+    /// How clients connect to the server
+    #[serde(default)]
+    pub transport: WcpTransport,
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Display, FromStr, PartialEq, Eq, Sequence, Serialize,
+)]
+pub enum WcpTransport {
+    /// Null terminated JSON messages over TCP
+    #[default]
+    Tcp,
+    /// One JSON message per frame over a web socket
+    WebSocket,
+    /// Null terminated JSON messages over a Unix domain socket
+    Unix,
 }
 
 fn default_colors() -> HashMap<String, Color32> {
@@ -1,10 +1,12 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::timestamp::CxxrtlTimestamp;
 
-#[derive(Serialize, Debug)]
-#[allow(non_camel_case_types, unused)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
 pub(crate) enum Diagnostic {
+    #[serde(rename = "break")]
+    breakpoint,
     assert,
     assume,
     print,
@@ -52,6 +52,10 @@ where
                 }
                 count = self.read.read(&mut buf) => {
                     match count {
+                        Ok(0) => {
+                            info!("cxxrtl connection closed. Shutting down client");
+                            break;
+                        }
                         Ok(count) => {
                             trace!("CXXRTL Read {count} from reader");
                             match self.process_stream(count, &mut buf).await {
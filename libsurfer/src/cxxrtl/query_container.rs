@@ -1,11 +1,13 @@
 use std::{
     collections::{BTreeMap, HashMap},
+    ops::Bound::{Excluded, Unbounded},
     sync::Arc,
 };
 
 use base64::{prelude::BASE64_STANDARD, Engine as _};
 use futures::executor::block_on;
-use num::{bigint::ToBigInt as _, BigInt, BigUint};
+use itertools::Itertools as _;
+use num::{bigint::ToBigInt as _, BigInt, BigUint, One as _, Zero as _};
 use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 use surfer_translation_types::VariableValue;
 use tokio::sync::RwLock;
@@ -19,7 +21,16 @@ use crate::{
 
 use super::sc_message::CxxrtlSample;
 
-type ValueList = Arc<RwLock<BTreeMap<BigInt, HashMap<VariableRef, VariableValue>>>>;
+#[derive(Default)]
+struct Samples {
+    /// The value changes of each variable. Consecutive samples with the same value are only
+    /// stored once
+    values: HashMap<VariableRef, BTreeMap<BigInt, VariableValue>>,
+    /// The end of the interval for which samples of each variable have been stored
+    until: HashMap<VariableRef, BigInt>,
+}
+
+type ValueList = Arc<RwLock<Samples>>;
 
 pub struct QueryContainer {
     variable_values: ValueList,
@@ -28,20 +39,23 @@ pub struct QueryContainer {
 impl QueryContainer {
     pub fn empty() -> Self {
         QueryContainer {
-            variable_values: Arc::new(RwLock::new(BTreeMap::new())),
+            variable_values: Arc::new(RwLock::new(Samples::default())),
         }
     }
 
+    /// Decodes and stores `data`, the samples of `variables` in an interval ending at `end`
     pub fn populate(
         &mut self,
         variables: Vec<VariableRef>,
         item_info: Arc<HashMap<VariableRef, CxxrtlItem>>,
         data: Vec<CxxrtlSample>,
+        end: BigInt,
         msg_sender: std::sync::mpsc::Sender<Message>,
     ) {
         let variable_values = self.variable_values.clone();
 
-        let task = fill_variable_values(variables, item_info, data, variable_values, msg_sender);
+        let task =
+            fill_variable_values(variables, item_info, data, end, variable_values, msg_sender);
         #[cfg(target_arch = "wasm32")]
         wasm_bindgen_futures::spawn_local(task);
         #[cfg(not(target_arch = "wasm32"))]
@@ -49,32 +63,128 @@ impl QueryContainer {
     }
 
     pub fn query(&self, var: &VariableRef, query_time: BigInt) -> QueryResult {
-        let values = block_on(self.variable_values.read());
+        let variable_values = block_on(self.variable_values.read());
+        let Some(values) = variable_values.values.get(var) else {
+            return QueryResult::default();
+        };
 
-        if let Some((time, value_map)) = values.range(..query_time.clone()).next_back() {
-            match (time.to_biguint(), value_map.get(var)) {
-                (Some(time), Some(value)) => {
+        if let Some((time, value)) = values.range(..query_time.clone()).next_back() {
+            match time.to_biguint() {
+                Some(time) => {
                     let next = values
                         .range(query_time..)
                         .next()
                         .and_then(|(k, _)| k.to_biguint());
                     QueryResult {
-                        current: Some((time.clone(), value.clone())),
+                        current: Some((time, value.clone())),
                         next,
                     }
                 }
-                _ => QueryResult::default(),
+                None => QueryResult::default(),
             }
         } else {
             QueryResult::default()
         }
     }
+
+    /// The end of the interval for which samples of all `variables` have been stored
+    pub fn sampled_until(&self, variables: &[VariableRef]) -> Option<BigInt> {
+        let variable_values = block_on(self.variable_values.read());
+        variables
+            .iter()
+            .map(|var| variable_values.until.get(var))
+            .min()
+            .flatten()
+            .cloned()
+    }
+
+    /// Counts the rising edges of `clock` after `after` up to and including `until`.
+    /// Returns `None` if the samples up to `until` have not been stored yet
+    pub fn rising_edges_between(
+        &self,
+        clock: &VariableRef,
+        after: &BigInt,
+        until: &BigInt,
+    ) -> Option<usize> {
+        let variable_values = block_on(self.variable_values.read());
+        if variable_values.until.get(clock)? < until {
+            return None;
+        }
+        let values = variable_values.values.get(clock)?;
+        Some(
+            rising_edges(values)
+                .filter(|time| *time > after && *time <= until)
+                .count(),
+        )
+    }
+
+    /// Returns the shortest distance between two consecutive rising edges of `clock`
+    pub fn min_clock_period(&self, clock: &VariableRef) -> Option<BigUint> {
+        let variable_values = block_on(self.variable_values.read());
+        let values = variable_values.values.get(clock)?;
+        rising_edges(values)
+            .tuple_windows()
+            .map(|(previous, next)| next - previous)
+            .min()
+            .and_then(|period| period.to_biguint())
+    }
+}
+
+/// The times at which `values` changes from zero to one
+fn rising_edges(values: &BTreeMap<BigInt, VariableValue>) -> impl Iterator<Item = &BigInt> {
+    values
+        .iter()
+        .tuple_windows()
+        .filter_map(|((_, previous), (time, value))| match (previous, value) {
+            (VariableValue::BigUint(previous), VariableValue::BigUint(value))
+                if previous.is_zero() && value.is_one() =>
+            {
+                Some(time)
+            }
+            _ => None,
+        })
+}
+
+/// Adds the `samples` of a variable in an interval, as times and values in increasing time,
+/// to its value `changes`. Samples are collapsed over all variables in a query, so the
+/// variable may not have changed. The interval may be stored after a later one.
+fn store_samples<'a>(
+    changes: &mut BTreeMap<BigInt, VariableValue>,
+    samples: impl IntoIterator<Item = (&'a BigInt, &'a BigUint)>,
+) {
+    let mut last = None;
+    for (time, value) in samples {
+        let value = VariableValue::BigUint(value.clone());
+        let unchanged = changes
+            .range(..=time)
+            .next_back()
+            .is_some_and(|(_, prev)| *prev == value);
+        if !unchanged {
+            changes.insert(time.clone(), value);
+        }
+        last = Some(time);
+    }
+    // The first change after the interval is redundant if it has the value the interval
+    // ends with
+    let Some(last) = last else {
+        return;
+    };
+    let redundant = changes
+        .range(..=last)
+        .next_back()
+        .zip(changes.range((Excluded(last), Unbounded)).next())
+        .filter(|((_, prev), (_, next))| prev == next)
+        .map(|(_, (time, _))| time.clone());
+    if let Some(time) = redundant {
+        changes.remove(&time);
+    }
 }
 
 async fn fill_variable_values(
     variables: Vec<VariableRef>,
     item_info: Arc<HashMap<VariableRef, CxxrtlItem>>,
     data: Vec<CxxrtlSample>,
+    end: BigInt,
     variable_values: ValueList,
     msg_sender: std::sync::mpsc::Sender<Message>,
 ) {
@@ -91,35 +201,49 @@ async fn fill_variable_values(
             offset += this_size_u32;
         }
 
-        data.par_iter().for_each(|sample| {
-            let u8s = BASE64_STANDARD
-                .decode(&sample.item_values)
-                .map_err(|e| {
-                    panic!(
-                        "Got non-base64 data from cxxrtl at time {}. {e}",
-                        sample.time
-                    )
-                })
-                .unwrap();
-
-            let values = ranges
-                .iter()
-                .zip(&variables)
-                .map(|(range, var)| {
-                    let value = BigUint::from_bytes_le(&u8s[range.clone()]);
-
-                    // FIXME: Probably shouldn't have this indexed by the variable ref here so we can
-                    // avoid the clone
-                    (var.clone(), VariableValue::BigUint(value))
-                })
-                .collect::<HashMap<_, _>>();
-
-            block_on(variable_values.write())
-                .insert(sample.time.as_femtoseconds().to_bigint().unwrap(), values);
-            msg_sender
-                .send(Message::InvalidateDrawCommands)
-                .expect("Message receiver disconnected");
-        });
+        let samples = data
+            .par_iter()
+            .map(|sample| {
+                let u8s = BASE64_STANDARD
+                    .decode(&sample.item_values)
+                    .map_err(|e| {
+                        panic!(
+                            "Got non-base64 data from cxxrtl at time {}. {e}",
+                            sample.time
+                        )
+                    })
+                    .unwrap();
+
+                let values = ranges
+                    .iter()
+                    .map(|range| BigUint::from_bytes_le(&u8s[range.clone()]))
+                    .collect::<Vec<_>>();
+                (sample.time.as_femtoseconds().to_bigint().unwrap(), values)
+            })
+            .collect::<Vec<_>>();
+
+        {
+            let mut variable_values = block_on(variable_values.write());
+            for (idx, var) in variables.iter().enumerate() {
+                let var_values = variable_values.values.entry(var.clone()).or_default();
+                store_samples(
+                    var_values,
+                    samples.iter().map(|(time, values)| (time, &values[idx])),
+                );
+                // The samples of a newly loaded variable up to where the others have been
+                // fetched may be stored after the later ones
+                let until = variable_values
+                    .until
+                    .entry(var.clone())
+                    .or_insert_with(|| end.clone());
+                if *until < end {
+                    until.clone_from(&end);
+                }
+            }
+        }
+        msg_sender
+            .send(Message::InvalidateDrawCommands)
+            .expect("Message receiver disconnected");
 
         if let Some(ctx) = EGUI_CONTEXT.read().unwrap().as_ref() {
             ctx.request_repaint();
@@ -133,3 +257,72 @@ async fn fill_variable_values(
     #[cfg(not(target_arch = "wasm32"))]
     tokio::task::spawn_blocking(work);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clock(changes: &[(u32, u32)]) -> BTreeMap<BigInt, VariableValue> {
+        changes
+            .iter()
+            .map(|(time, value)| (BigInt::from(*time), VariableValue::BigUint((*value).into())))
+            .collect()
+    }
+
+    #[test]
+    fn rising_edges_follow_transitions_of_gated_clock() {
+        // Toggles every 5 until 20, is gated until 60, then toggles every 2
+        let values = clock(&[
+            (0, 0),
+            (5, 1),
+            (10, 0),
+            (15, 1),
+            (20, 0),
+            (60, 1),
+            (62, 0),
+            (64, 1),
+        ]);
+        assert_eq!(
+            rising_edges(&values).cloned().collect::<Vec<_>>(),
+            vec![5.into(), 15.into(), 60.into(), 64.into()]
+        );
+    }
+
+    #[test]
+    fn initial_high_value_is_not_an_edge() {
+        let values = clock(&[(0, 1), (5, 0), (10, 1)]);
+        assert_eq!(
+            rising_edges(&values).cloned().collect::<Vec<_>>(),
+            vec![BigInt::from(10)]
+        );
+    }
+
+    /// The changes after storing the samples of the `intervals` in this order
+    fn store(intervals: &[&[(u32, u32)]]) -> BTreeMap<BigInt, VariableValue> {
+        let mut changes = BTreeMap::new();
+        for interval in intervals {
+            let samples = interval
+                .iter()
+                .map(|(time, value)| (BigInt::from(*time), BigUint::from(*value)))
+                .collect::<Vec<_>>();
+            store_samples(&mut changes, samples.iter().map(|(t, v)| (t, v)));
+        }
+        changes
+    }
+
+    #[test]
+    fn samples_are_stored_once_per_change_in_any_order() {
+        let first: &[(u32, u32)] = &[(0, 0), (5, 1), (8, 1), (10, 0)];
+        let second: &[(u32, u32)] = &[(10, 0), (12, 0), (15, 1)];
+        let expected = clock(&[(0, 0), (5, 1), (10, 0), (15, 1)]);
+        assert_eq!(store(&[first, second]), expected);
+        assert_eq!(store(&[second, first]), expected);
+
+        // The later interval starting with a change back to the value the earlier one ends with
+        let first: &[(u32, u32)] = &[(0, 1), (5, 0)];
+        let second: &[(u32, u32)] = &[(10, 0), (15, 1)];
+        let expected = clock(&[(0, 1), (5, 0), (15, 1)]);
+        assert_eq!(store(&[first, second]), expected);
+        assert_eq!(store(&[second, first]), expected);
+    }
+}
@@ -4,12 +4,25 @@ use serde::{Deserialize, Serialize};
 
 use crate::cxxrtl_container::{CxxrtlItem, CxxrtlScope};
 
-use super::timestamp::CxxrtlTimestamp;
+use super::{command::Diagnostic, timestamp::CxxrtlTimestamp};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct CxxrtlSample {
     pub time: CxxrtlTimestamp,
     pub item_values: String,
+    /// Only present if diagnostics were requested in the query
+    #[serde(default)]
+    pub(crate) diagnostics: Vec<CxxrtlDiagnostic>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct CxxrtlDiagnostic {
+    #[serde(rename = "type")]
+    pub kind: Diagnostic,
+    pub text: String,
+    /// Source location of the statement that emitted the diagnostic, if known
+    #[serde(default)]
+    pub src: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
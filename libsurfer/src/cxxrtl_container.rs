@@ -1,6 +1,6 @@
 use futures::executor::block_on;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
 };
 use tokio::sync::mpsc;
@@ -18,22 +18,26 @@ use crate::wave_container::ScopeRefExt;
 use crate::{
     channels::IngressReceiver,
     cxxrtl::{
-        command::CxxrtlCommand,
+        command::{CxxrtlCommand, Diagnostic},
         cs_message::CSMessage,
         query_container::QueryContainer,
         sc_message::{
-            CommandResponse, CxxrtlSimulationStatus, Event, SCMessage, SimulationStatusType,
+            CommandResponse, CxxrtlSample, CxxrtlSimulationStatus, Event, SCMessage,
+            SimulationStatusType,
         },
         timestamp::CxxrtlTimestamp,
     },
     message::Message,
     wave_container::{
-        QueryResult, ScopeId, ScopeRef, SimulationStatus, VarId, VariableMeta, VariableRef,
-        VariableRefExt,
+        DiagnosticKind, QueryResult, ScopeId, ScopeRef, SimulationDiagnostic, SimulationStatus,
+        VarId, VariableMeta, VariableRef, VariableRefExt,
     },
 };
 
-const DEFAULT_REFERENCE: &str = "ALL_VARIABLES";
+/// Name of the item reference containing all loaded variables
+const REFERENCE: &str = "surfer_items";
+/// Reference for fetching the earlier samples of newly loaded variables
+const CATCH_UP_REFERENCE: &str = "surfer_new_items";
 
 type Callback = Box<dyn FnOnce(CommandResponse, &mut CxxrtlData) + Sync + Send>;
 
@@ -63,28 +67,9 @@ impl<T> CachedData<T> {
         Self::Uncached { prev: None }
     }
 
-    fn make_uncached(&self) -> Self {
-        // Since the internals here are all Arc, clones are cheap
-        match &self {
-            CachedData::Uncached { prev } => CachedData::Uncached { prev: prev.clone() },
-            CachedData::Waiting { prev } => CachedData::Uncached { prev: prev.clone() },
-            CachedData::Filled(prev) => CachedData::Uncached {
-                prev: Some(prev.clone()),
-            },
-        }
-    }
-
     pub fn filled(t: T) -> Self {
         Self::Filled(Arc::new(t))
     }
-
-    fn get(&self) -> Option<Arc<T>> {
-        match self {
-            CachedData::Uncached { prev } => prev.clone(),
-            CachedData::Waiting { prev } => prev.clone(),
-            CachedData::Filled(val) => Some(val.clone()),
-        }
-    }
 }
 
 impl<T> CachedData<T>
@@ -110,23 +95,47 @@ where
     }
 }
 
+/// An ongoing [`CxxrtlContainer::step_cycles`]
+struct CycleStep {
+    clock: VariableRef,
+    /// The time (in femtoseconds) from which rising edges are counted
+    start: BigUint,
+    cycles: usize,
+    /// The time the simulation was last told to run until
+    target: BigUint,
+}
+
 pub struct CxxrtlData {
     scopes_cache: CachedData<HashMap<ScopeRef, CxxrtlScope>>,
     module_item_cache: HashMap<ScopeRef, CachedData<HashMap<VariableRef, CxxrtlItem>>>,
     all_items_cache: CachedData<HashMap<VariableRef, CxxrtlItem>>,
 
-    /// We use the CachedData system to keep track of if we have sent a query request,
-    /// but the actual data is stored in the interval_query_cache.
-    ///
-    /// The held value in the query result is the end timestamp of the current current
-    /// interval_query_cache
-    query_result: CachedData<CxxrtlTimestamp>,
+    /// The variables in the item reference, in the order their values are sent. The
+    /// reference is redefined with all variables whenever new ones are loaded, so that a
+    /// single query fetches the samples of every variable along with the diagnostics. The
+    /// samples themselves are stored in the interval_query_cache.
+    referenced_variables: Vec<VariableRef>,
+    /// Incremented each time the reference is redefined, to tell whether a response
+    /// belongs to the current definition
+    reference_generation: usize,
+    /// End of the interval (in femtoseconds) for which samples of the referenced
+    /// variables have been received
+    fetched_until: Option<BigUint>,
+    /// End of the interval (in femtoseconds) for which diagnostics have been received
+    diagnostics_fetched_until: Option<BigUint>,
+    /// A query has been sent, but the response has not been received yet
+    query_pending: bool,
+    /// The latest timestamp for which the samples of all referenced variables have been
+    /// stored. It never moves backwards.
+    displayed_until: Option<CxxrtlTimestamp>,
     interval_query_cache: QueryContainer,
+    /// Diagnostics received so far
+    diagnostics: Arc<Vec<SimulationDiagnostic>>,
 
-    loaded_signals: Vec<VariableRef>,
-    signal_index_map: HashMap<VariableRef, usize>,
+    loaded_signals: HashSet<VariableRef>,
 
     simulation_status: CachedData<CxxrtlSimulationStatus>,
+    cycle_step: Option<CycleStep>,
 
     msg_channel: std::sync::mpsc::Sender<Message>,
 }
@@ -144,13 +153,6 @@ impl CxxrtlData {
     pub fn on_simulation_status_update(&mut self, status: CxxrtlSimulationStatus) {
         self.simulation_status = CachedData::filled(status);
         self.trigger_redraw();
-        self.invalidate_query_result();
-    }
-
-    pub fn invalidate_query_result(&mut self) {
-        self.query_result = self.query_result.make_uncached();
-        self.trigger_redraw();
-        // self.interval_query_cache.invalidate();
     }
 }
 
@@ -189,6 +191,10 @@ pub struct CxxrtlContainer {
     sending: CSSender,
     sc_messages: IngressReceiver<String>,
     disconnected_reported: bool,
+    /// The simulation process if it was spawned by us. It is killed when the container
+    /// is dropped
+    #[cfg(not(target_arch = "wasm32"))]
+    process: Option<tokio::process::Child>,
 }
 
 impl CxxrtlContainer {
@@ -208,11 +214,17 @@ impl CxxrtlContainer {
             scopes_cache: CachedData::empty(),
             module_item_cache: HashMap::new(),
             all_items_cache: CachedData::empty(),
-            query_result: CachedData::empty(),
+            referenced_variables: vec![],
+            reference_generation: 0,
+            fetched_until: None,
+            diagnostics_fetched_until: None,
+            query_pending: false,
+            displayed_until: None,
             interval_query_cache: QueryContainer::empty(),
-            loaded_signals: vec![],
-            signal_index_map: HashMap::new(),
+            diagnostics: Arc::default(),
+            loaded_signals: HashSet::new(),
             simulation_status: CachedData::empty(),
+            cycle_step: None,
             msg_channel: msg_channel.clone(),
         };
 
@@ -221,6 +233,8 @@ impl CxxrtlContainer {
             sc_messages,
             sending,
             disconnected_reported: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            process: None,
         };
 
         info!("cxxrtl connected");
@@ -261,6 +275,64 @@ impl CxxrtlContainer {
         .await
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn new_process(
+        cmd: &str,
+        args: &[String],
+        msg_channel: std::sync::mpsc::Sender<Message>,
+    ) -> Result<Self> {
+        use eyre::{anyhow, Context};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        use crate::channels::IngressSender;
+        use crate::cxxrtl::io_worker;
+
+        let mut child = tokio::process::Command::new(cmd)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to spawn {cmd}"))?;
+
+        let write = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open simulation stdin"))?;
+        let read = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open simulation stdout"))?;
+        if let Some(stderr) = child.stderr.take() {
+            let name = cmd.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    info!("[{name}] {line}");
+                }
+            });
+        }
+
+        let (cs_tx, cs_rx) = mpsc::channel(100);
+        let (sc_tx, sc_rx) = mpsc::channel(100);
+        tokio::spawn(
+            io_worker::CxxrtlWorker::new(write, read, IngressSender::new(sc_tx), cs_rx).start(),
+        );
+
+        let mut result = Self::new(
+            msg_channel,
+            CSSender {
+                cs_messages: cs_tx,
+                callback_queue: VecDeque::new(),
+            },
+            IngressReceiver::new(sc_rx),
+        )
+        .await?;
+        result.process = Some(child);
+        Ok(result)
+    }
+
     #[cfg(target_arch = "wasm32")]
     pub async fn new_wasm_mailbox(msg_channel: std::sync::mpsc::Sender<Message>) -> Result<Self> {
         use eyre::anyhow;
@@ -314,6 +386,15 @@ impl CxxrtlContainer {
                         }
                         SCMessage::event(event) => match event {
                             Event::simulation_paused { time, cause: _ } => {
+                                // Paused by something other than reaching the step target
+                                if self
+                                    .data
+                                    .cycle_step
+                                    .as_ref()
+                                    .is_some_and(|step| time.as_femtoseconds() < step.target)
+                                {
+                                    self.data.cycle_step = None;
+                                }
                                 self.data
                                     .on_simulation_status_update(CxxrtlSimulationStatus {
                                         status: SimulationStatusType::paused,
@@ -321,6 +402,7 @@ impl CxxrtlContainer {
                                     });
                             }
                             Event::simulation_finished { time } => {
+                                self.data.cycle_step = None;
                                 self.data
                                     .on_simulation_status_update(CxxrtlSimulationStatus {
                                         status: SimulationStatusType::finished,
@@ -342,6 +424,7 @@ impl CxxrtlContainer {
                 }
             }
         }
+        self.continue_cycle_step();
     }
 
     fn get_scopes(&mut self) -> Arc<HashMap<ScopeRef, CxxrtlScope>> {
@@ -555,8 +638,26 @@ impl CxxrtlContainer {
             }))
     }
 
-    pub fn max_displayed_timestamp(&self) -> Option<CxxrtlTimestamp> {
-        self.data.query_result.get().map(|t| (*t).clone())
+    /// The latest time up to which all data has been received and stored
+    pub fn max_displayed_timestamp(&mut self) -> Option<CxxrtlTimestamp> {
+        let data = &mut self.data;
+        let stored_until = if data.referenced_variables.is_empty() {
+            data.diagnostics_fetched_until.clone()
+        } else {
+            data.interval_query_cache
+                .sampled_until(&data.referenced_variables)
+                .and_then(|t| t.to_biguint())
+        };
+        if let Some(stored_until) = stored_until {
+            if data
+                .displayed_until
+                .as_ref()
+                .is_none_or(|t| t.as_femtoseconds() < stored_until)
+            {
+                data.displayed_until = Some(CxxrtlTimestamp::from_femtoseconds(stored_until));
+            }
+        }
+        data.displayed_until.clone()
     }
 
     pub fn max_timestamp(&mut self) -> Option<CxxrtlTimestamp> {
@@ -572,70 +673,198 @@ impl CxxrtlContainer {
         // that we'll early return with no value
         let max_timestamp = self.max_timestamp()?;
         let info = self.fetch_all_items()?;
-        let loaded_signals = self.data.loaded_signals.clone();
+        self.fetch_new_samples(&max_timestamp, &info);
 
-        let res = self
-            .data
-            .query_result
-            .fetch_if_needed(|| {
-                info!("Running query variable");
+        Some(
+            self.data
+                .interval_query_cache
+                .query(variable, time.to_bigint().unwrap()),
+        )
+    }
 
-                self.sending.run_command(
-                    CxxrtlCommand::query_interval {
-                        interval: (CxxrtlTimestamp::zero(), max_timestamp.clone()),
-                        collapse: true,
-                        items: Some(DEFAULT_REFERENCE.to_string()),
-                        item_values_encoding: "base64(u32)",
-                        diagnostics: false,
-                    },
-                    move |response, data| {
-                        expect_response!(CommandResponse::query_interval { samples }, response);
-
-                        data.query_result = CachedData::filled(max_timestamp);
-                        data.interval_query_cache.populate(
-                            loaded_signals.clone(),
-                            info,
-                            samples,
-                            data.msg_channel.clone(),
-                        );
-                    },
-                );
-            })
-            .map(|_cached| {
-                // If we get here, the cache is valid and we we should look into the
-                // interval_query_cache for the query result
-                self.data
-                    .interval_query_cache
-                    .query(variable, time.to_bigint().unwrap())
+    /// Requests the samples and diagnostics between the end of the previous query and
+    /// `max_timestamp` unless they are up-to-date
+    fn fetch_new_samples(
+        &mut self,
+        max_timestamp: &CxxrtlTimestamp,
+        info: &Arc<HashMap<VariableRef, CxxrtlItem>>,
+    ) {
+        let data = &mut self.data;
+        let end = max_timestamp.as_femtoseconds();
+        let has_variables = !data.referenced_variables.is_empty();
+        let up_to_date = |t: &Option<BigUint>| t.as_ref().is_some_and(|t| t >= &end);
+        if data.query_pending
+            || ((!has_variables || up_to_date(&data.fetched_until))
+                && up_to_date(&data.diagnostics_fetched_until))
+        {
+            return;
+        }
+        info!("Querying samples");
+        data.query_pending = true;
+
+        let diagnostics_start = data.diagnostics_fetched_until.clone().unwrap_or_default();
+        let start = if has_variables {
+            data.fetched_until
+                .clone()
+                .unwrap_or_default()
+                .min(diagnostics_start)
+        } else {
+            diagnostics_start
+        };
+        let variables = data.referenced_variables.clone();
+        let generation = data.reference_generation;
+        let info = info.clone();
+        self.sending.run_command(
+            CxxrtlCommand::query_interval {
+                interval: (
+                    CxxrtlTimestamp::from_femtoseconds(start),
+                    max_timestamp.clone(),
+                ),
+                collapse: true,
+                items: has_variables.then(|| REFERENCE.to_string()),
+                item_values_encoding: "base64(u32)",
+                diagnostics: true,
+            },
+            move |response, data| {
+                data.query_pending = false;
+                expect_response!(CommandResponse::query_interval { samples }, response);
+
+                // Diagnostics up to the end of the previous query have already been
+                // collected, the interval may start earlier if variables were added since
+                let previous_end = data.diagnostics_fetched_until.replace(end.clone());
+                Arc::make_mut(&mut data.diagnostics)
+                    .extend(Self::collect_diagnostics(&samples, previous_end.as_ref()));
+
+                if has_variables {
+                    // If the reference was redefined while waiting, the samples are still
+                    // valid but the new variables have only been fetched up to the start
+                    if generation == data.reference_generation {
+                        data.fetched_until = Some(end.clone());
+                    }
+                    data.interval_query_cache.populate(
+                        variables,
+                        info,
+                        samples,
+                        end.to_bigint().unwrap(),
+                        data.msg_channel.clone(),
+                    );
+                }
+                data.trigger_redraw();
+            },
+        );
+    }
+
+    /// The diagnostics of the `samples` after `previous_end`, in the order they were emitted
+    fn collect_diagnostics(
+        samples: &[CxxrtlSample],
+        previous_end: Option<&BigUint>,
+    ) -> Vec<SimulationDiagnostic> {
+        samples
+            .iter()
+            .filter(|sample| previous_end.is_none_or(|prev| &sample.time.as_femtoseconds() > prev))
+            .flat_map(|sample| {
+                sample
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| SimulationDiagnostic {
+                        time: sample.time.as_femtoseconds(),
+                        kind: match diagnostic.kind {
+                            Diagnostic::breakpoint => DiagnosticKind::Break,
+                            Diagnostic::assert => DiagnosticKind::Assert,
+                            Diagnostic::assume => DiagnosticKind::Assume,
+                            Diagnostic::print => DiagnosticKind::Print,
+                        },
+                        text: diagnostic.text.clone(),
+                        source: diagnostic.src.clone(),
+                    })
             })
-            .unwrap_or_default();
-        Some(res)
+            .collect()
+    }
+
+    pub fn diagnostics(&mut self) -> Arc<Vec<SimulationDiagnostic>> {
+        if let (Some(max_timestamp), Some(info)) = (self.max_timestamp(), self.fetch_all_items()) {
+            self.fetch_new_samples(&max_timestamp, &info);
+        }
+        self.data.diagnostics.clone()
     }
 
     pub fn load_variables<S: AsRef<VariableRef>, T: Iterator<Item = S>>(&mut self, variables: T) {
+        let info = self.fetch_all_items();
         let data = &mut self.data;
+        let mut new_variables = vec![];
         for variable in variables {
-            let varref = variable.as_ref().clone();
-
-            if !data.signal_index_map.contains_key(&varref) {
-                let idx = data.loaded_signals.len();
-                data.signal_index_map.insert(varref.clone(), idx);
-                data.loaded_signals.push(varref.clone());
+            let varref = variable.as_ref();
+            if data.loaded_signals.insert(varref.clone()) {
+                new_variables.push(varref.clone());
             }
         }
+        if new_variables.is_empty() {
+            return;
+        }
 
+        // The reference is redefined with all loaded variables, which replaces the previous
+        // definition on the server, and the next query continues where the previous one
+        // ended. The samples of the new variables up to there are fetched separately.
+        data.referenced_variables
+            .extend(new_variables.iter().cloned());
+        data.reference_generation += 1;
         self.sending.run_command(
             CxxrtlCommand::reference_items {
-                reference: DEFAULT_REFERENCE.to_string(),
+                reference: REFERENCE.to_string(),
                 items: data
-                    .loaded_signals
+                    .referenced_variables
                     .iter()
                     .map(|s| vec![s.cxxrtl_repr()])
                     .collect(),
             },
             |_response, data| {
                 info!("Item references updated");
-                data.invalidate_query_result();
+                data.trigger_redraw();
+            },
+        );
+        match (data.fetched_until.clone(), info) {
+            (Some(fetched_until), Some(info)) => {
+                self.fetch_earlier_samples(new_variables, fetched_until, info);
+            }
+            _ => data.fetched_until = None,
+        }
+    }
+
+    /// Fetches the samples of the newly loaded `variables` up to `until`, where the samples
+    /// of the previously loaded variables end, through a reference of their own
+    fn fetch_earlier_samples(
+        &mut self,
+        variables: Vec<VariableRef>,
+        until: BigUint,
+        info: Arc<HashMap<VariableRef, CxxrtlItem>>,
+    ) {
+        self.sending.run_command(
+            CxxrtlCommand::reference_items {
+                reference: CATCH_UP_REFERENCE.to_string(),
+                items: variables.iter().map(|s| vec![s.cxxrtl_repr()]).collect(),
+            },
+            |_response, _data| {},
+        );
+        self.sending.run_command(
+            CxxrtlCommand::query_interval {
+                interval: (
+                    CxxrtlTimestamp::zero(),
+                    CxxrtlTimestamp::from_femtoseconds(until.clone()),
+                ),
+                collapse: true,
+                items: Some(CATCH_UP_REFERENCE.to_string()),
+                item_values_encoding: "base64(u32)",
+                diagnostics: false,
+            },
+            move |response, data| {
+                expect_response!(CommandResponse::query_interval { samples }, response);
+                data.interval_query_cache.populate(
+                    variables,
+                    info,
+                    samples,
+                    until.to_bigint().unwrap(),
+                    data.msg_channel.clone(),
+                );
             },
         );
     }
@@ -662,21 +891,10 @@ impl CxxrtlContainer {
         })
     }
 
-    pub fn unpause(&mut self) {
-        let duration = self
-            .raw_simulation_status()
-            .map(|s| {
-                CxxrtlTimestamp::from_femtoseconds(
-                    s.latest_time.as_femtoseconds() + 100_000_000u32.to_biguint().unwrap(),
-                )
-            })
-            .unwrap_or_else(|| {
-                CxxrtlTimestamp::from_femtoseconds(100_000_000u32.to_biguint().unwrap())
-            });
-
+    fn run(&mut self, until_time: Option<CxxrtlTimestamp>, until_diagnostics: Vec<Diagnostic>) {
         let cmd = CxxrtlCommand::run_simulation {
-            until_time: Some(duration),
-            until_diagnostics: vec![],
+            until_time,
+            until_diagnostics,
             sample_item_values: true,
         };
 
@@ -689,6 +907,119 @@ impl CxxrtlContainer {
         });
     }
 
+    pub fn unpause(&mut self) {
+        self.run_for(&100_000_000u32.to_biguint().unwrap());
+    }
+
+    /// Runs the simulation until the absolute time `time` in femtoseconds
+    pub fn run_until(&mut self, time: &BigUint) {
+        self.run(
+            Some(CxxrtlTimestamp::from_femtoseconds(time.clone())),
+            vec![],
+        );
+    }
+
+    /// Runs the simulation for `duration` femtoseconds past the latest simulated time
+    pub fn run_for(&mut self, duration: &BigUint) {
+        let latest_time = self
+            .raw_simulation_status()
+            .map(|s| s.latest_time.as_femtoseconds())
+            .unwrap_or_default();
+        self.run_until(&(latest_time + duration));
+    }
+
+    /// Runs the simulation until `clock` has had `cycles` more rising edges. Since the
+    /// future edges are not known, the simulation is run in steps of the shortest period
+    /// seen so far, counting the actual edges after each step. This only overshoots if the
+    /// clock becomes faster than it has been, so the clock has to be loaded and must have
+    /// toggled at least twice
+    pub fn step_cycles(&mut self, clock: &VariableRef, cycles: u32) {
+        if !self.data.loaded_signals.contains(clock) {
+            error!(
+                "{} must be displayed to step by its cycles",
+                clock.full_path_string()
+            );
+            return;
+        }
+        let Some(start) = self
+            .raw_simulation_status()
+            .map(|s| s.latest_time.as_femtoseconds())
+        else {
+            return;
+        };
+        self.data.cycle_step = Some(CycleStep {
+            clock: clock.clone(),
+            start: start.clone(),
+            cycles: cycles as usize,
+            target: start.clone(),
+        });
+        self.run_remaining_cycles(&start, cycles as usize);
+    }
+
+    /// Runs the simulation from `latest_time` far enough for `remaining` rising edges of
+    /// the clock of the ongoing step, assuming it keeps its shortest period
+    fn run_remaining_cycles(&mut self, latest_time: &BigUint, remaining: usize) {
+        let Some(step) = &self.data.cycle_step else {
+            return;
+        };
+        let Some(period) = self.data.interval_query_cache.min_clock_period(&step.clock) else {
+            error!(
+                "Could not determine the period of {}, it needs at least two rising edges",
+                step.clock.full_path_string()
+            );
+            self.data.cycle_step = None;
+            return;
+        };
+        let target = latest_time + period * remaining;
+        if let Some(step) = &mut self.data.cycle_step {
+            step.target.clone_from(&target);
+        }
+        self.run_until(&target);
+    }
+
+    /// Once the simulation has paused at the target of the ongoing step and the clock has
+    /// been sampled up to there, either finishes the step or runs for the missing edges
+    fn continue_cycle_step(&mut self) {
+        let Some(step) = &self.data.cycle_step else {
+            return;
+        };
+        let CachedData::Filled(status) = &self.data.simulation_status else {
+            return;
+        };
+        let latest_time = status.latest_time.as_femtoseconds();
+        if !matches!(status.status, SimulationStatusType::paused) || latest_time < step.target {
+            return;
+        }
+        let Some(edges) = self.data.interval_query_cache.rising_edges_between(
+            &step.clock,
+            &step.start.to_bigint().unwrap(),
+            &latest_time.to_bigint().unwrap(),
+        ) else {
+            return;
+        };
+        if edges >= step.cycles {
+            self.data.cycle_step = None;
+        } else {
+            let remaining = step.cycles - edges;
+            self.run_remaining_cycles(&latest_time, remaining);
+        }
+    }
+
+    pub fn run_until_diagnostic(&mut self, kinds: &[DiagnosticKind]) {
+        self.run(
+            None,
+            kinds
+                .iter()
+                .map(|kind| match kind {
+                    DiagnosticKind::Break => Diagnostic::breakpoint,
+                    DiagnosticKind::Assert => Diagnostic::assert,
+                    DiagnosticKind::Assume => Diagnostic::assume,
+                    DiagnosticKind::Print => Diagnostic::print,
+                })
+                .collect(),
+        );
+    }
+
     pub fn pause(&mut self) {
         self.sending
             .run_command(CxxrtlCommand::pause_simulation, |response, data| {
@@ -701,3 +1032,309 @@ impl CxxrtlContainer {
             });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use base64::{prelude::BASE64_STANDARD, Engine as _};
+    use num::ToPrimitive as _;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::channels::IngressSender;
+    use crate::wave_container::VariableRefExt as _;
+
+    const LATEST_TIME: u32 = 100;
+
+    /// Answers the commands sent by a container like a simulation paused at `LATEST_TIME`
+    /// with the items `top clk` and `top data`
+    struct FakeServer {
+        container: CxxrtlContainer,
+        commands: mpsc::Receiver<String>,
+        responses: IngressSender<String>,
+        references: HashMap<String, Vec<String>>,
+        /// All commands received so far
+        received: Vec<Value>,
+        _messages: std::sync::mpsc::Receiver<Message>,
+    }
+
+    impl FakeServer {
+        async fn new() -> Self {
+            let (msg_tx, msg_rx) = std::sync::mpsc::channel();
+            let (cs_tx, commands) = mpsc::channel(100);
+            let (sc_tx, sc_rx) = mpsc::channel(100);
+            let container = CxxrtlContainer::new(
+                msg_tx,
+                CSSender {
+                    cs_messages: cs_tx,
+                    callback_queue: VecDeque::new(),
+                },
+                IngressReceiver::new(sc_rx),
+            )
+            .await
+            .unwrap();
+            Self {
+                container,
+                commands,
+                responses: IngressSender::new(sc_tx),
+                references: HashMap::new(),
+                received: vec![],
+                _messages: msg_rx,
+            }
+        }
+
+        fn time(femtoseconds: u32) -> String {
+            CxxrtlTimestamp::from_femtoseconds(femtoseconds.into()).to_string()
+        }
+
+        fn respond_to(&mut self, command: &Value) -> Option<Value> {
+            let mut response = match command["command"].as_str()? {
+                "get_simulation_status" => json!({
+                    "command": "get_simulation_status",
+                    "status": "paused",
+                    "latest_time": Self::time(LATEST_TIME),
+                }),
+                "list_items" => json!({
+                    "command": "list_items",
+                    "items": {"top clk": {"width": 1}, "top data": {"width": 8}},
+                }),
+                "reference_items" => {
+                    let items = command["items"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|item| item[0].as_str().unwrap().to_string())
+                        .collect();
+                    self.references
+                        .insert(command["reference"].as_str().unwrap().to_string(), items);
+                    json!({"command": "reference_items"})
+                }
+                "query_interval" => {
+                    let num_items = command["items"]
+                        .as_str()
+                        .map_or(0, |reference| self.references[reference].len());
+                    json!({
+                        "command": "query_interval",
+                        "samples": [{
+                            "time": command["interval"][0],
+                            "item_values": BASE64_STANDARD.encode(vec![0u8; 4 * num_items]),
+                        }],
+                    })
+                }
+                _ => return None,
+            };
+            response["type"] = json!("response");
+            Some(response)
+        }
+
+        /// Answers all commands sent so far and lets the container handle the responses
+        async fn serve(&mut self) {
+            while let Ok(command) = self.commands.try_recv() {
+                let command: Value = serde_json::from_str(&command).unwrap();
+                if let Some(response) = self.respond_to(&command) {
+                    self.responses.send(response.to_string()).await.unwrap();
+                }
+                self.received.push(command);
+            }
+            self.container.tick();
+        }
+
+        /// Queries `var` until the container has fetched everything it needs to send the
+        /// query for its samples
+        async fn query(&mut self, var: &VariableRef) {
+            for _ in 0..3 {
+                self.container.query_variable(var, &BigUint::from(0u32));
+                self.serve().await;
+            }
+        }
+
+        fn sent(&self, command: &str) -> Vec<&Value> {
+            self.received
+                .iter()
+                .filter(|c| c["command"] == command)
+                .collect()
+        }
+    }
+
+    fn run(test: impl std::future::Future<Output = ()>) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(test);
+    }
+
+    fn var(name: &str) -> VariableRef {
+        VariableRef::from_strs(&["top", name])
+    }
+
+    #[test]
+    fn loaded_variables_share_one_reference_and_query() {
+        run(async {
+            let mut server = FakeServer::new().await;
+            server.container.load_variables([var("clk")].iter());
+            server.query(&var("clk")).await;
+            assert_eq!(server.sent("query_interval").len(), 1);
+
+            server.container.load_variables([var("data")].iter());
+            server.query(&var("data")).await;
+
+            let references = server.sent("reference_items");
+            assert_eq!(references.len(), 3);
+            assert!(references[..2].iter().all(|c| c["reference"] == REFERENCE));
+            assert_eq!(references[1]["items"], json!([["top clk"], ["top data"]]));
+
+            // The samples of the new variable up to where clk has been fetched are fetched
+            // separately, instead of fetching clk again
+            assert_eq!(references[2]["reference"], CATCH_UP_REFERENCE);
+            assert_eq!(references[2]["items"], json!([["top data"]]));
+            let queries = server.sent("query_interval");
+            assert_eq!(queries.len(), 2);
+            assert_eq!(queries[0]["items"], REFERENCE);
+            assert_eq!(queries[1]["items"], CATCH_UP_REFERENCE);
+            assert_eq!(
+                queries[1]["interval"],
+                json!([FakeServer::time(0), FakeServer::time(LATEST_TIME)])
+            );
+            assert_eq!(queries[1]["diagnostics"], false);
+
+            // Nothing new to fetch
+            server.query(&var("data")).await;
+            assert_eq!(server.sent("query_interval").len(), 2);
+
+            let mut sampled = false;
+            for _ in 0..100 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                sampled = server
+                    .container
+                    .query_variable(&var("data"), &BigUint::from(LATEST_TIME))
+                    .is_some_and(|result| result.current.is_some());
+                if sampled {
+                    break;
+                }
+            }
+            assert!(sampled);
+        });
+    }
+
+    #[test]
+    fn displayed_time_only_advances_once_samples_are_stored() {
+        run(async {
+            let mut server = FakeServer::new().await;
+            server.container.load_variables([var("clk")].iter());
+            server.query(&var("clk")).await;
+            assert_eq!(server.sent("query_interval").len(), 1);
+
+            // The response, including the diagnostics, has been handled but the samples are
+            // decoded by a task which has not had the chance to run yet
+            assert!(server.container.max_displayed_timestamp().is_none());
+
+            let mut displayed = None;
+            for _ in 0..100 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                displayed = server.container.max_displayed_timestamp();
+                if displayed.is_some() {
+                    break;
+                }
+            }
+            assert_eq!(
+                displayed.map(|t| t.as_femtoseconds()),
+                Some(BigUint::from(LATEST_TIME))
+            );
+            assert!(server
+                .container
+                .query_variable(&var("clk"), &BigUint::from(LATEST_TIME))
+                .is_some_and(|result| result.current.is_some()));
+
+            // Loading another variable does not move the displayed time backwards
+            server.container.load_variables([var("data")].iter());
+            server.serve().await;
+            assert_eq!(
+                server
+                    .container
+                    .max_displayed_timestamp()
+                    .map(|t| t.as_femtoseconds()),
+                Some(BigUint::from(LATEST_TIME))
+            );
+        });
+    }
+
+    fn sample(time: u32, diagnostics: Value) -> CxxrtlSample {
+        serde_json::from_value(json!({
+            "time": FakeServer::time(time),
+            "item_values": "",
+            "diagnostics": diagnostics,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn diagnostics_are_deserialized() {
+        let samples = [sample(
+            10,
+            json!([
+                {"type": "break", "text": "stop", "src": "top.v:1.2-1.10"},
+                {"type": "assert", "text": "failed", "src": "top.v:3.4-3.20"},
+                {"type": "assume", "text": "assumed", "src": null},
+                {"type": "print", "text": "hello\n"},
+            ]),
+        )];
+        let diagnostics = CxxrtlContainer::collect_diagnostics(&samples, None);
+        assert_eq!(
+            diagnostics.iter().map(|d| d.kind).collect::<Vec<_>>(),
+            [
+                DiagnosticKind::Break,
+                DiagnosticKind::Assert,
+                DiagnosticKind::Assume,
+                DiagnosticKind::Print
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.time == BigUint::from(10u32)));
+        assert_eq!(diagnostics[0].text, "stop");
+        assert_eq!(diagnostics[0].source.as_deref(), Some("top.v:1.2-1.10"));
+        assert_eq!(diagnostics[1].source.as_deref(), Some("top.v:3.4-3.20"));
+        assert_eq!(diagnostics[2].source, None);
+        assert_eq!(diagnostics[3].text, "hello\n");
+        assert_eq!(diagnostics[3].source, None);
+
+        // Samples from queries without diagnostics have none
+        let sample: CxxrtlSample = serde_json::from_value(json!({
+            "time": FakeServer::time(0),
+            "item_values": "",
+        }))
+        .unwrap();
+        assert!(sample.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn diagnostics_after_the_previous_query_are_collected_in_order() {
+        let samples = [
+            sample(0, json!([{"type": "print", "text": "a"}])),
+            sample(
+                10,
+                json!([
+                    {"type": "print", "text": "b"},
+                    {"type": "assert", "text": "c"},
+                ]),
+            ),
+            sample(20, json!([])),
+            sample(30, json!([{"type": "print", "text": "d"}])),
+        ];
+        let texts = |previous_end: Option<u32>| {
+            CxxrtlContainer::collect_diagnostics(&samples, previous_end.map(BigUint::from).as_ref())
+                .into_iter()
+                .map(|d| (d.time.to_u32().unwrap(), d.text))
+                .collect::<Vec<_>>()
+        };
+        let all = [
+            (0, "a".to_string()),
+            (10, "b".to_string()),
+            (10, "c".to_string()),
+            (30, "d".to_string()),
+        ];
+        assert_eq!(texts(None), all);
+        // Samples up to the end of the previous query have already been collected
+        assert_eq!(texts(Some(0)), all[1..]);
+        assert_eq!(texts(Some(10)), all[3..]);
+        assert!(texts(Some(30)).is_empty());
+    }
+}
@@ -3,12 +3,20 @@ use crate::transaction_container::{StreamScopeRef, TransactionContainer, Transac
 use crate::wave_container::{MetaData, SimulationStatus, VariableRef, WaveContainer};
 use crate::wave_data::ScopeType;
 use crate::wave_data::ScopeType::{StreamScope, WaveScope};
-use num::BigUint;
+use itertools::Itertools;
+use num::{BigInt, BigUint, Integer};
+
+/// Separates the index of the transaction file from the name of a stream or generator when
+/// there are several files, see [`DataContainer::scope_names`]
+const CONTAINER_SEPARATOR: char = ':';
 
 #[allow(clippy::large_enum_variant)]
 pub enum DataContainer {
     Waves(WaveContainer),
     Transactions(TransactionContainer),
+    /// A waveform and one or more transaction files recorded alongside it. The transaction
+    /// times are shown on the time axis of the waveform.
+    Combined(WaveContainer, Vec<TransactionContainer>),
     Empty,
 }
 
@@ -34,7 +42,7 @@ impl DataContainer {
 
     pub fn as_waves(&self) -> Option<&WaveContainer> {
         match self {
-            DataContainer::Waves(w) => Some(w),
+            DataContainer::Waves(w) | DataContainer::Combined(w, _) => Some(w),
             DataContainer::Transactions(_) => None,
             DataContainer::Empty => None,
         }
@@ -42,48 +50,129 @@ impl DataContainer {
 
     pub fn as_waves_mut(&mut self) -> Option<&mut WaveContainer> {
         match self {
-            DataContainer::Waves(w) => Some(w),
+            DataContainer::Waves(w) | DataContainer::Combined(w, _) => Some(w),
             DataContainer::Transactions(_) => None,
             DataContainer::Empty => None,
         }
     }
 
-    pub fn as_transactions(&self) -> Option<&TransactionContainer> {
+    /// All transaction files, indexed by [`TransactionStreamRef::container`]
+    pub fn transaction_containers(&self) -> &[TransactionContainer] {
         match self {
-            DataContainer::Waves(_) => None,
-            DataContainer::Transactions(t) => Some(t),
-            DataContainer::Empty => None,
+            DataContainer::Waves(_) => &[],
+            DataContainer::Transactions(t) => std::slice::from_ref(t),
+            DataContainer::Combined(_, t) => t,
+            DataContainer::Empty => &[],
         }
     }
 
-    pub fn as_transactions_mut(&mut self) -> Option<&mut TransactionContainer> {
+    /// Takes the transaction files out of this container
+    pub fn into_transaction_containers(self) -> Vec<TransactionContainer> {
+        match self {
+            DataContainer::Transactions(t) => vec![t],
+            DataContainer::Combined(_, t) => t,
+            DataContainer::Waves(_) | DataContainer::Empty => vec![],
+        }
+    }
+
+    pub fn transactions(&self, container: usize) -> Option<&TransactionContainer> {
+        self.transaction_containers().get(container)
+    }
+
+    pub fn transactions_mut(&mut self, container: usize) -> Option<&mut TransactionContainer> {
         match self {
             DataContainer::Waves(_) => None,
-            DataContainer::Transactions(t) => Some(t),
+            DataContainer::Transactions(t) => (container == 0).then_some(t),
+            DataContainer::Combined(_, t) => t.get_mut(container),
             DataContainer::Empty => None,
         }
     }
 
+    /// True if there is a waveform, possibly with transactions
     pub fn is_waves(&self) -> bool {
         match self {
-            DataContainer::Waves(_) => true,
+            DataContainer::Waves(_) | DataContainer::Combined(..) => true,
             DataContainer::Transactions(_) => false,
             DataContainer::Empty => false,
         }
     }
 
+    /// True if there are transactions, possibly with a waveform
     pub fn is_transactions(&self) -> bool {
         match self {
             DataContainer::Waves(_) => false,
-            DataContainer::Transactions(_) => true,
+            DataContainer::Transactions(_) | DataContainer::Combined(..) => true,
             DataContainer::Empty => false,
         }
     }
 
+    /// Adds a transaction file to a waveform, returning the index of the new container.
+    /// Gives back the transactions if there is no waveform to add them to.
+    pub fn add_transactions(
+        &mut self,
+        transactions: TransactionContainer,
+    ) -> Result<usize, TransactionContainer> {
+        match std::mem::replace(self, DataContainer::Empty) {
+            DataContainer::Waves(w) => {
+                *self = DataContainer::Combined(w, vec![transactions]);
+                Ok(0)
+            }
+            DataContainer::Combined(w, mut t) => {
+                t.push(transactions);
+                let container = t.len() - 1;
+                *self = DataContainer::Combined(w, t);
+                Ok(container)
+            }
+            other => {
+                *self = other;
+                Err(transactions)
+            }
+        }
+    }
+
+    /// Replaces the transaction file at index `container` of a combined container, used when
+    /// reloading it
+    pub fn replace_transactions(&mut self, container: usize, transactions: TransactionContainer) {
+        if let Some(t) = self.transactions_mut(container) {
+            *t = transactions;
+        }
+    }
+
+    /// The factor `(numerator, denominator)` converting times of the transaction file
+    /// `container` to times of the waveform, if they have different time scales
+    fn transaction_time_factor(&self, container: usize) -> Option<(BigInt, BigInt)> {
+        let DataContainer::Combined(waves, transactions) = self else {
+            return None;
+        };
+        time_factor(
+            waves.metadata().timescale,
+            transactions.get(container)?.metadata().timescale.unit,
+        )
+    }
+
+    /// Converts a time of the transaction file `container` to the time scale of the waveform
+    pub fn transaction_time(&self, container: usize, time: &BigUint) -> BigInt {
+        to_wave_time(self.transaction_time_factor(container), time)
+    }
+
+    /// Converts a time of the waveform to the time scale of the transaction file `container`
+    pub fn transaction_file_time(&self, container: usize, time: &BigInt) -> BigInt {
+        to_file_time(self.transaction_time_factor(container), time)
+    }
+
     pub fn max_timestamp(&self) -> Option<BigUint> {
         match self {
             DataContainer::Waves(w) => w.max_timestamp(),
             DataContainer::Transactions(t) => t.max_timestamp(),
+            DataContainer::Combined(w, t) => t
+                .iter()
+                .enumerate()
+                .filter_map(|(container, t)| {
+                    t.max_timestamp()
+                        .and_then(|time| self.transaction_time(container, &time).to_biguint())
+                })
+                .chain(w.max_timestamp())
+                .max(),
             DataContainer::Empty => None,
         }
     }
@@ -100,24 +189,131 @@ impl DataContainer {
             DataContainer::Transactions(_) => {
                 vec![ScopeType::StreamScope(StreamScopeRef::Root)]
             }
+            DataContainer::Combined(w, _) => w
+                .root_scopes()
+                .iter()
+                .map(|s| ScopeType::WaveScope(s.clone()))
+                .chain([ScopeType::StreamScope(StreamScopeRef::Root)])
+                .collect(),
             DataContainer::Empty => vec![],
         }
     }
 
     pub fn scope_exists(&self, scope: &ScopeType) -> bool {
-        match (self, scope) {
-            (DataContainer::Waves(waves), WaveScope(scope)) => waves.scope_exists(scope),
-            (DataContainer::Transactions(transactions), StreamScope(scope)) => {
-                transactions.stream_scope_exists(scope)
-            }
+        match (self.as_waves(), scope) {
+            (Some(waves), WaveScope(scope)) => waves.scope_exists(scope),
+            (_, StreamScope(StreamScopeRef::Root)) => self.is_transactions(),
+            (_, StreamScope(scope @ StreamScopeRef::Stream(s))) => self
+                .transactions(s.container)
+                .is_some_and(|transactions| transactions.stream_scope_exists(scope)),
             (_, _) => false,
         }
     }
 
+    /// `name` of a stream or generator of the transaction file `container`, qualified by the
+    /// index of the file if there are several
+    fn qualified_name(&self, container: usize, name: &str) -> String {
+        if self.transaction_containers().len() > 1 {
+            format!("{container}{CONTAINER_SEPARATOR}{name}")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Splits a name from [`Self::qualified_name`] into the index of the transaction file, if
+    /// it is qualified, and the name within the file
+    fn split_qualified_name<'a>(&self, name: &'a str) -> (Option<usize>, &'a str) {
+        if self.transaction_containers().len() > 1 {
+            if let Some((container, name)) = name.split_once(CONTAINER_SEPARATOR) {
+                if let Ok(container) = container.parse() {
+                    return (Some(container), name);
+                }
+            }
+        }
+        (None, name)
+    }
+
+    /// Finds a stream by its name from [`Self::scope_names`]. Unqualified names must be
+    /// unique across the transaction files
+    pub fn stream_from_name(&self, name: &str) -> Option<TransactionStreamRef> {
+        let (file, name) = self.split_qualified_name(name);
+        self.transaction_containers()
+            .iter()
+            .enumerate()
+            .filter(|(container, _)| file.is_none_or(|file| file == *container))
+            .filter_map(|(container, t)| {
+                t.get_stream_from_name(name.to_string()).map(|stream| {
+                    TransactionStreamRef::new_stream(stream.id, stream.name.clone())
+                        .in_container(container)
+                })
+            })
+            .exactly_one()
+            .ok()
+    }
+
+    /// Finds a generator by name, in `stream` if given and otherwise by its name from
+    /// [`Self::variable_names`]. Unqualified names must be unique across the transaction files
+    pub fn generator_from_name(
+        &self,
+        stream: Option<&TransactionStreamRef>,
+        name: &str,
+    ) -> Option<TransactionStreamRef> {
+        let (file, name) = match stream {
+            Some(stream) => (Some(stream.container), name),
+            None => self.split_qualified_name(name),
+        };
+        self.transaction_containers()
+            .iter()
+            .enumerate()
+            .filter(|(container, _)| file.is_none_or(|file| file == *container))
+            .filter_map(|(container, t)| {
+                t.get_generator_from_name(stream.map(|s| s.stream_id), name.to_string())
+                    .map(|gen| {
+                        TransactionStreamRef::new_gen(gen.stream_id, gen.id, gen.name.clone())
+                            .in_container(container)
+                    })
+            })
+            .exactly_one()
+            .ok()
+    }
+
+    /// `tr` for the root of all streams followed by the streams of all transaction files
+    fn stream_names(&self) -> Vec<String> {
+        std::iter::once(String::from("tr"))
+            .chain(
+                self.transaction_containers()
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(container, t)| {
+                        t.get_streams()
+                            .into_iter()
+                            .map(move |s| self.qualified_name(container, &s.name))
+                    }),
+            )
+            .collect()
+    }
+
+    fn generator_names(&self) -> Vec<String> {
+        self.transaction_containers()
+            .iter()
+            .enumerate()
+            .flat_map(|(container, t)| {
+                t.get_generators()
+                    .into_iter()
+                    .map(move |g| self.qualified_name(container, &g.name))
+            })
+            .collect()
+    }
+
     pub fn scope_names(&self) -> Vec<String> {
         match self {
             DataContainer::Waves(w) => w.scope_names(),
-            DataContainer::Transactions(t) => t.stream_names(),
+            DataContainer::Transactions(_) => self.stream_names(),
+            DataContainer::Combined(w, _) => w
+                .scope_names()
+                .into_iter()
+                .chain(self.stream_names())
+                .collect(),
             DataContainer::Empty => vec![],
         }
     }
@@ -125,34 +321,46 @@ impl DataContainer {
     pub fn variable_names(&self) -> Vec<String> {
         match self {
             DataContainer::Waves(w) => w.variable_names(),
-            DataContainer::Transactions(t) => t.generator_names(),
+            DataContainer::Transactions(_) => self.generator_names(),
+            DataContainer::Combined(w, _) => w
+                .variable_names()
+                .into_iter()
+                .chain(self.generator_names())
+                .collect(),
             DataContainer::Empty => vec![],
         }
     }
 
     pub fn variables_in_scope(&self, scope: &ScopeType) -> Vec<VariableType> {
-        match (self, scope) {
-            (DataContainer::Waves(w), WaveScope(s)) => {
+        match (self.as_waves(), scope) {
+            (Some(w), WaveScope(s)) => {
                 let variables = w.variables_in_scope(s);
                 variables
                     .iter()
                     .map(|v| VariableType::Variable(v.clone()))
                     .collect()
             }
-            (DataContainer::Transactions(t), StreamScope(s)) => {
-                let variables = t.generators_in_stream(s);
-                variables
-                    .iter()
-                    .map(|g| VariableType::Generator(g.clone()))
-                    .collect()
-            }
+            (_, StreamScope(s)) => self
+                .transaction_containers()
+                .iter()
+                .enumerate()
+                .filter(|(container, _)| match s {
+                    StreamScopeRef::Stream(stream) => stream.container == *container,
+                    _ => true,
+                })
+                .flat_map(|(container, t)| {
+                    t.generators_in_stream(s)
+                        .into_iter()
+                        .map(move |g| VariableType::Generator(g.in_container(container)))
+                })
+                .collect(),
             _ => panic!("Container and Scope are of incompatible types"),
         }
     }
 
     pub fn metadata(&self) -> MetaData {
         match self {
-            DataContainer::Waves(w) => w.metadata(),
+            DataContainer::Waves(w) | DataContainer::Combined(w, _) => w.metadata(),
             DataContainer::Transactions(t) => t.metadata(),
             DataContainer::Empty => MetaData {
                 date: None,
@@ -169,6 +377,9 @@ impl DataContainer {
         match self {
             DataContainer::Waves(w) => w.body_loaded(),
             DataContainer::Transactions(t) => t.body_loaded(),
+            DataContainer::Combined(w, t) => {
+                w.body_loaded() && t.iter().all(TransactionContainer::body_loaded)
+            }
             DataContainer::Empty => true,
         }
     }
@@ -177,15 +388,224 @@ impl DataContainer {
         match self {
             DataContainer::Waves(w) => w.is_fully_loaded(),
             DataContainer::Transactions(t) => t.is_fully_loaded(),
+            DataContainer::Combined(w, t) => {
+                w.is_fully_loaded() && t.iter().all(TransactionContainer::is_fully_loaded)
+            }
             DataContainer::Empty => true,
         }
     }
 
     pub fn simulation_status(&self) -> Option<SimulationStatus> {
         match self {
-            DataContainer::Waves(w) => w.simulation_status(),
+            DataContainer::Waves(w) | DataContainer::Combined(w, _) => w.simulation_status(),
             DataContainer::Transactions(_) => None,
             DataContainer::Empty => None,
         }
     }
 }
+
+/// The factor `(numerator, denominator)` converting times in units of `tx_unit` to times in
+/// `wave_scale`, if both are known
+fn time_factor(wave_scale: TimeScale, tx_unit: TimeUnit) -> Option<(BigInt, BigInt)> {
+    if [wave_scale.unit, tx_unit]
+        .iter()
+        .any(|unit| matches!(unit, TimeUnit::None | TimeUnit::Auto))
+    {
+        return None;
+    }
+    let exponent = i32::from(tx_unit.exponent()) - i32::from(wave_scale.unit.exponent());
+    let multiplier = BigInt::from(wave_scale.multiplier.unwrap_or(1).max(1));
+    let power = BigInt::from(10u32).pow(exponent.unsigned_abs());
+    Some(if exponent >= 0 {
+        (power, multiplier)
+    } else {
+        (BigInt::from(1u32), power * multiplier)
+    })
+}
+
+fn to_wave_time(factor: Option<(BigInt, BigInt)>, time: &BigUint) -> BigInt {
+    let time = BigInt::from(time.clone());
+    match factor {
+        Some((numerator, denominator)) => (time * numerator).div_floor(&denominator),
+        None => time,
+    }
+}
+
+fn to_file_time(factor: Option<(BigInt, BigInt)>, time: &BigInt) -> BigInt {
+    match factor {
+        Some((numerator, denominator)) => (time * denominator).div_floor(&numerator),
+        None => time.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use project_root::get_project_root;
+    use std::sync::Arc;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    fn waves() -> DataContainer {
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        let header = wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap();
+        DataContainer::Waves(WaveContainer::new_waveform(Arc::new(header.hierarchy)))
+    }
+
+    fn transactions() -> TransactionContainer {
+        let path = get_project_root().unwrap().join("examples/my_db.ftr");
+        TransactionContainer {
+            inner: ftr_parser::parse::parse_ftr(path).unwrap(),
+        }
+    }
+
+    fn first_stream_name(t: &TransactionContainer) -> String {
+        t.get_streams()
+            .into_iter()
+            .map(|s| s.name.clone())
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn transaction_files_are_added_next_to_waves() {
+        let mut container = waves();
+        assert!(container
+            .add_transactions(transactions())
+            .is_ok_and(|c| c == 0));
+        assert!(container
+            .add_transactions(transactions())
+            .is_ok_and(|c| c == 1));
+
+        assert!(container.is_waves());
+        assert!(container.is_transactions());
+        assert_eq!(container.transaction_containers().len(), 2);
+        assert!(container.transactions(1).is_some());
+        assert!(container.transactions(2).is_none());
+        assert_eq!(container.into_transaction_containers().len(), 2);
+    }
+
+    #[test]
+    fn transaction_files_are_not_added_without_waves() {
+        let mut container = DataContainer::Transactions(transactions());
+        assert!(container.add_transactions(transactions()).is_err());
+        assert_eq!(container.transaction_containers().len(), 1);
+
+        let mut container = DataContainer::Empty;
+        assert!(container.add_transactions(transactions()).is_err());
+        assert!(container.transaction_containers().is_empty());
+    }
+
+    #[test]
+    fn streams_of_a_single_transaction_file_are_not_qualified() {
+        let mut container = waves();
+        let name = first_stream_name(&transactions());
+        container.add_transactions(transactions()).ok().unwrap();
+
+        let scope_names = container.scope_names();
+        assert!(scope_names.contains(&"tr".to_string()));
+        assert!(scope_names.contains(&name));
+        assert!(container
+            .stream_from_name(&name)
+            .is_some_and(|s| s.container == 0));
+    }
+
+    #[test]
+    fn streams_of_several_transaction_files_are_qualified() {
+        let mut container = waves();
+        let name = first_stream_name(&transactions());
+        container.add_transactions(transactions()).ok().unwrap();
+        container.add_transactions(transactions()).ok().unwrap();
+
+        let scope_names = container.scope_names();
+        assert_eq!(scope_names.iter().filter(|n| *n == "tr").count(), 1);
+        assert!(!scope_names.contains(&name));
+        assert!(scope_names.contains(&format!("0:{name}")));
+        assert!(scope_names.contains(&format!("1:{name}")));
+
+        let stream = container.stream_from_name(&format!("1:{name}")).unwrap();
+        assert_eq!((stream.container, stream.name.clone()), (1, name.clone()));
+        // The same stream is in both files
+        assert!(container.stream_from_name(&name).is_none());
+        assert!(container.stream_from_name(&format!("2:{name}")).is_none());
+
+        let generator = container
+            .variables_in_scope(&StreamScope(StreamScopeRef::Stream(stream.clone())))
+            .into_iter()
+            .next()
+            .unwrap()
+            .name();
+        assert!(container
+            .generator_from_name(Some(&stream), &generator)
+            .is_some_and(|g| g.container == 1));
+        assert!(container
+            .generator_from_name(None, &format!("0:{generator}"))
+            .is_some_and(|g| g.container == 0));
+    }
+
+    #[test]
+    fn transaction_times_are_scaled_to_the_wave_time_scale() {
+        let scale = |unit, multiplier| TimeScale { unit, multiplier };
+
+        // 10 ns waves, ps transactions
+        let factor = time_factor(
+            scale(TimeUnit::NanoSeconds, Some(10)),
+            TimeUnit::PicoSeconds,
+        );
+        assert_eq!(factor, Some((BigInt::from(1u32), BigInt::from(10_000u32))));
+        assert_eq!(
+            to_wave_time(factor.clone(), &BigUint::from(25_000u32)),
+            BigInt::from(2)
+        );
+        assert_eq!(to_file_time(factor, &BigInt::from(2)), BigInt::from(20_000));
+
+        // ps waves, us transactions
+        let factor = time_factor(scale(TimeUnit::PicoSeconds, None), TimeUnit::MicroSeconds);
+        assert_eq!(
+            factor,
+            Some((BigInt::from(1_000_000u32), BigInt::from(1u32)))
+        );
+        assert_eq!(
+            to_wave_time(factor.clone(), &BigUint::from(3u32)),
+            BigInt::from(3_000_000)
+        );
+        assert_eq!(
+            to_file_time(factor, &BigInt::from(3_500_000)),
+            BigInt::from(3)
+        );
+
+        // The same unit
+        let factor = time_factor(scale(TimeUnit::NanoSeconds, None), TimeUnit::NanoSeconds);
+        assert_eq!(to_wave_time(factor, &BigUint::from(7u32)), BigInt::from(7));
+
+        // Unknown units are not scaled
+        assert_eq!(
+            time_factor(scale(TimeUnit::None, None), TimeUnit::PicoSeconds),
+            None
+        );
+        assert_eq!(
+            time_factor(scale(TimeUnit::NanoSeconds, None), TimeUnit::Auto),
+            None
+        );
+        assert_eq!(
+            to_wave_time(None, &BigUint::from(25_000u32)),
+            BigInt::from(25_000)
+        );
+    }
+
+    #[test]
+    fn transaction_times_are_not_scaled_without_waves() {
+        let container = DataContainer::Transactions(transactions());
+        assert_eq!(
+            container.transaction_time(0, &BigUint::from(42u32)),
+            BigInt::from(42)
+        );
+        assert_eq!(
+            container.transaction_file_time(0, &BigInt::from(42)),
+            BigInt::from(42)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Drawing and listing of diagnostics (assertions, prints etc.) emitted by simulations.
+use ecolor::Color32;
+use egui::{Context, RichText, TextEdit, TextWrapMode, Window};
+use egui_extras::{Column, TableBuilder};
+use emath::{Align2, Vec2};
+use enum_iterator::all;
+use epaint::{FontId, Stroke};
+use num::bigint::ToBigInt;
+
+use crate::{
+    config::SurferTheme,
+    message::Message,
+    time::time_string,
+    view::DrawingContext,
+    viewport::Viewport,
+    wave_container::{DiagnosticKind, SimulationDiagnostic},
+    wave_data::WaveData,
+    SystemState,
+};
+
+/// Diagnostic labels longer than this are truncated on the canvas
+const MAX_LABEL_CHARS: usize = 24;
+
+fn diagnostic_color(kind: DiagnosticKind, theme: &SurferTheme) -> Color32 {
+    match kind {
+        DiagnosticKind::Assert | DiagnosticKind::Assume => theme.accent_error.background,
+        DiagnosticKind::Break => theme.accent_warn.background,
+        DiagnosticKind::Print => theme.accent_info.background,
+    }
+}
+
+fn diagnostic_label(diagnostic: &SimulationDiagnostic) -> String {
+    let text = diagnostic.text.trim();
+    if text.chars().count() > MAX_LABEL_CHARS {
+        let truncated: String = text.chars().take(MAX_LABEL_CHARS - 1).collect();
+        format!("{}: {truncated}…", diagnostic.kind)
+    } else {
+        format!("{}: {text}", diagnostic.kind)
+    }
+}
+
+impl WaveData {
+    /// Draws a line with a short label for each diagnostic emitted by the simulation.
+    /// Labels which would overlap the previous one are skipped.
+    pub fn draw_diagnostics(
+        &self,
+        theme: &SurferTheme,
+        ctx: &mut DrawingContext,
+        size: Vec2,
+        viewport: &Viewport,
+    ) {
+        let Some(waves) = self.inner.as_waves() else {
+            return;
+        };
+        let diagnostics = waves.diagnostics();
+        if diagnostics.is_empty() {
+            return;
+        }
+        let num_timestamps = self.num_timestamps().unwrap_or(1.into());
+        let mut last_label_end = f32::NEG_INFINITY;
+        for diagnostic in diagnostics.iter() {
+            let Some(time) = diagnostic.time.to_bigint() else {
+                continue;
+            };
+            let time = self.time_alignment.to_aligned(&time);
+            let x = viewport.pixel_from_time(&time, size.x, &num_timestamps);
+            if x < 0. || x > size.x {
+                continue;
+            }
+            let color = diagnostic_color(diagnostic.kind, theme);
+            ctx.painter.line_segment(
+                [
+                    (ctx.to_screen)(x + 0.5, -0.5),
+                    (ctx.to_screen)(x + 0.5, size.y),
+                ],
+                Stroke { color, width: 1. },
+            );
+            if x > last_label_end {
+                let rect = ctx.painter.text(
+                    (ctx.to_screen)(x + 3., 0.),
+                    Align2::LEFT_TOP,
+                    diagnostic_label(diagnostic),
+                    FontId::proportional(ctx.cfg.text_size),
+                    color,
+                );
+                last_label_end = x + rect.width() + 3.;
+            }
+        }
+    }
+}
+
+impl SystemState {
+    pub fn draw_diagnostics_window(
+        &self,
+        waves: &WaveData,
+        ctx: &Context,
+        msgs: &mut Vec<Message>,
+    ) {
+        let mut open = true;
+        let diagnostics = waves
+            .inner
+            .as_waves()
+            .map(|w| w.diagnostics())
+            .unwrap_or_default();
+        let theme = &self.user.config.theme;
+        let timescale = waves.inner.metadata().timescale;
+        let time_format = self.get_time_format();
+
+        Window::new("Diagnostics")
+            .collapsible(true)
+            .resizable(true)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut hidden_kinds = self.hidden_diagnostic_kinds.borrow_mut();
+                    for kind in all::<DiagnosticKind>() {
+                        let mut shown = !hidden_kinds.contains(&kind);
+                        if ui
+                            .checkbox(
+                                &mut shown,
+                                RichText::new(kind.to_string())
+                                    .color(diagnostic_color(kind, theme)),
+                            )
+                            .changed()
+                        {
+                            if shown {
+                                hidden_kinds.remove(&kind);
+                            } else {
+                                hidden_kinds.insert(kind);
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.add(
+                        TextEdit::singleline(&mut *self.diagnostics_filter.borrow_mut())
+                            .hint_text("Filter"),
+                    );
+                });
+                ui.separator();
+
+                let hidden_kinds = self.hidden_diagnostic_kinds.borrow();
+                let filter = self.diagnostics_filter.borrow().to_lowercase();
+                let shown = diagnostics
+                    .iter()
+                    .filter(|d| !hidden_kinds.contains(&d.kind))
+                    .filter(|d| {
+                        filter.is_empty()
+                            || d.text.to_lowercase().contains(&filter)
+                            || d.source
+                                .as_ref()
+                                .is_some_and(|source| source.to_lowercase().contains(&filter))
+                    })
+                    .collect::<Vec<_>>();
+
+                ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+                TableBuilder::new(ui)
+                    .column(Column::auto().resizable(true))
+                    .column(Column::auto().resizable(true))
+                    .column(Column::auto().resizable(true))
+                    .column(Column::remainder())
+                    .striped(true)
+                    .header(20.0, |mut header| {
+                        for title in ["Time", "Kind", "Message", "Source"] {
+                            header.col(|ui| {
+                                ui.strong(title);
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(18.0, shown.len(), |mut row| {
+                            let diagnostic = shown[row.index()];
+                            let time = waves
+                                .time_alignment
+                                .to_aligned(&diagnostic.time.to_bigint().unwrap_or_default());
+                            row.col(|ui| {
+                                let text = time_string(
+                                    &time,
+                                    &timescale,
+                                    &self.user.wanted_timeunit,
+                                    &time_format,
+                                );
+                                if ui.selectable_label(false, text).clicked() {
+                                    msgs.push(Message::CursorSet(time.clone()));
+                                    msgs.push(Message::GoToTime(Some(time.clone()), 0));
+                                }
+                            });
+                            row.col(|ui| {
+                                ui.colored_label(
+                                    diagnostic_color(diagnostic.kind, theme),
+                                    diagnostic.kind.to_string(),
+                                );
+                            });
+                            row.col(|ui| {
+                                ui.label(RichText::new(diagnostic.text.trim()).monospace());
+                            });
+                            row.col(|ui| {
+                                if let Some(source) = &diagnostic.source {
+                                    ui.label(source);
+                                }
+                            });
+                        });
+                    });
+            });
+        if !open {
+            msgs.push(Message::SetDiagnosticsWindowVisible(false));
+        }
+    }
+}
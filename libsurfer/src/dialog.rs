@@ -1,7 +1,8 @@
 use crate::message::Message;
+use crate::time::TimeAlignment;
 use crate::SystemState;
 use ecolor::Color32;
-use egui::{Layout, RichText};
+use egui::{ComboBox, Grid, Layout, RichText};
 use emath::Align;
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -13,10 +14,60 @@ pub struct ReloadWaveformDialog {
 #[derive(Debug, Default, Copy, Clone)]
 pub struct OpenSiblingStateFileDialog {
     do_not_show_again: bool,
+    /// Asks whether to restore the autosaved session instead of loading a state file
+    pub(crate) restore_session: bool,
+    /// Also offers to load the state file when asking whether to restore the session
+    pub(crate) sibling_state_file: bool,
+}
+
+impl OpenSiblingStateFileDialog {
+    pub fn restore_session(sibling_state_file: bool) -> Self {
+        OpenSiblingStateFileDialog {
+            do_not_show_again: false,
+            restore_session: true,
+            sibling_state_file,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TimeAlignmentDialog {
+    /// The transaction file being aligned, or `None` for the waveform
+    container: Option<usize>,
+    offset: String,
+    scale: String,
+}
+
+impl TimeAlignmentDialog {
+    pub fn new(container: Option<usize>, alignment: &TimeAlignment) -> Self {
+        TimeAlignmentDialog {
+            container,
+            offset: alignment.offset.to_string(),
+            scale: alignment.scale.to_string(),
+        }
+    }
+
+    /// The alignment entered in the dialog, or an error message if it is invalid.
+    fn alignment(&self) -> Result<TimeAlignment, &'static str> {
+        let offset = self
+            .offset
+            .trim()
+            .parse()
+            .map_err(|_| "The offset must be an integer")?;
+        let scale = self
+            .scale
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|scale| scale.is_finite() && *scale > 0.)
+            .ok_or("The scale must be a positive number")?;
+        Ok(TimeAlignment { offset, scale })
+    }
 }
 
 impl SystemState {
-    /// Draw a dialog that asks the user if it wants to load a state file situated in the same directory as the waveform file.
+    /// Draw a dialog that asks the user if it wants to load a state file situated in the same directory as the waveform file,
+    /// or to restore the autosaved session of the waveform file.
     pub(crate) fn draw_open_sibling_state_file_dialog(
         &self,
         ctx: &egui::Context,
@@ -24,12 +75,36 @@ impl SystemState {
         msgs: &mut Vec<Message>,
     ) {
         let mut do_not_show_again = dialog.do_not_show_again;
-        egui::Window::new("State file detected")
+        let offer_both = dialog.restore_session && dialog.sibling_state_file;
+        let (title, text, load_text, dont_load_text) = if offer_both {
+            (
+                "Restore session",
+                "A session was saved the last time the loaded file was open, and a state file was\n\
+                detected in the same directory. Restore the session or load the state file?",
+                "Restore",
+                "Don't load",
+            )
+        } else if dialog.restore_session {
+            (
+                "Restore session",
+                "A session was saved the last time the loaded file was open.\nRestore the session?",
+                "Restore",
+                "Don't restore",
+            )
+        } else {
+            (
+                "State file detected",
+                "A state file was detected in the same directory as the loaded file.\nLoad state?",
+                "Load",
+                "Don't load",
+            )
+        };
+        egui::Window::new(title)
             .auto_sized()
             .collapsible(false)
             .fixed_pos(ctx.available_rect().center())
             .show(ctx, |ui| {
-                let label = ui.label(RichText::new("A state file was detected in the same directory as the loaded file.\nLoad state?").heading());
+                let label = ui.label(RichText::new(text).heading());
                 ui.set_width(label.rect.width());
                 ui.add_space(5.0);
                 ui.checkbox(
@@ -40,8 +115,9 @@ impl SystemState {
                 ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
                     // Sets the style when focused
                     ui.style_mut().visuals.widgets.active.weak_bg_fill = Color32::BLUE;
-                    let load_button = ui.button("Load");
-                    let dont_load_button = ui.button("Don't load");
+                    let load_button = ui.button(load_text);
+                    let state_file_button = offer_both.then(|| ui.button("Load state file"));
+                    let dont_load_button = ui.button(dont_load_text);
                     ctx.memory_mut(|mem| {
                         if mem.focused() != Some(load_button.id)
                             && mem.focused() != Some(dont_load_button.id)
@@ -55,6 +131,19 @@ impl SystemState {
                             load_state: true,
                             do_not_show_again,
                         });
+                    } else if state_file_button.is_some_and(|button| button.clicked()) {
+                        // Closes the dialog as if it only asked about the state file
+                        msgs.push(Message::UpdateOpenSiblingStateFileDialog(
+                            OpenSiblingStateFileDialog {
+                                do_not_show_again,
+                                restore_session: false,
+                                sibling_state_file: false,
+                            },
+                        ));
+                        msgs.push(Message::CloseOpenSiblingStateFileDialog {
+                            load_state: true,
+                            do_not_show_again,
+                        });
                     } else if dont_load_button.clicked() {
                         msgs.push(Message::CloseOpenSiblingStateFileDialog {
                             load_state: false,
@@ -63,6 +152,7 @@ impl SystemState {
                     } else if do_not_show_again != dialog.do_not_show_again {
                         msgs.push(Message::UpdateOpenSiblingStateFileDialog(OpenSiblingStateFileDialog {
                             do_not_show_again,
+                            ..*dialog
                         }));
                     }
                 });
@@ -122,4 +212,111 @@ impl SystemState {
                 });
             });
     }
+
+    /// Draw a dialog for editing the time offset and scale of the loaded waveform and of the
+    /// transaction files loaded next to it.
+    pub(crate) fn draw_time_alignment_dialog(
+        &self,
+        ctx: &egui::Context,
+        dialog: &TimeAlignmentDialog,
+        msgs: &mut Vec<Message>,
+    ) {
+        let mut new_dialog = dialog.clone();
+        let waves = self.user.waves.as_ref();
+        let timescale = waves.map(|waves| waves.inner.metadata().timescale);
+        // Each transaction file next to a waveform has its own alignment
+        let files = waves
+            .filter(|waves| waves.inner.is_waves())
+            .map(|waves| {
+                std::iter::once((None, waves.source.to_string()))
+                    .chain(
+                        waves
+                            .transaction_sources
+                            .iter()
+                            .enumerate()
+                            .map(|(container, source)| (Some(container), source.to_string())),
+                    )
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut open = true;
+        egui::Window::new("Time alignment")
+            .open(&mut open)
+            .auto_sized()
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("Shown time = time in file × scale + offset");
+                ui.add_space(5.0);
+                Grid::new("time_alignment_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        if files.len() > 1 {
+                            ui.label("File");
+                            let mut container = new_dialog.container;
+                            ComboBox::from_id_salt("time_alignment_file")
+                                .selected_text(
+                                    files
+                                        .iter()
+                                        .find(|(c, _)| *c == container)
+                                        .map_or("", |(_, name)| name.as_str()),
+                                )
+                                .show_ui(ui, |ui| {
+                                    for (c, name) in &files {
+                                        ui.selectable_value(&mut container, *c, name);
+                                    }
+                                });
+                            if container != new_dialog.container {
+                                if let Some(waves) = waves {
+                                    new_dialog = TimeAlignmentDialog::new(
+                                        container,
+                                        &waves.alignment(container),
+                                    );
+                                }
+                            }
+                            ui.end_row();
+                        }
+                        ui.label("Offset");
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut new_dialog.offset);
+                            if let Some(timescale) = &timescale {
+                                ui.label(timescale.unit.to_string());
+                            }
+                        });
+                        ui.end_row();
+                        ui.label("Scale");
+                        ui.text_edit_singleline(&mut new_dialog.scale);
+                        ui.end_row();
+                    });
+                let alignment = new_dialog.alignment();
+                if let Err(e) = &alignment {
+                    ui.colored_label(self.user.config.theme.accent_error.background, *e);
+                }
+                ui.add_space(14.0);
+                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                    if ui
+                        .add_enabled(alignment.is_ok(), egui::Button::new("Apply"))
+                        .clicked()
+                    {
+                        if let Ok(alignment) = alignment {
+                            msgs.push(Message::SetTimeAlignment(new_dialog.container, alignment));
+                        }
+                        msgs.push(Message::CloseTimeAlignmentDialog);
+                    }
+                    if ui.button("Reset").clicked() {
+                        new_dialog = TimeAlignmentDialog::new(
+                            new_dialog.container,
+                            &TimeAlignment::default(),
+                        );
+                    }
+                    if ui.button("Cancel").clicked() {
+                        msgs.push(Message::CloseTimeAlignmentDialog);
+                    }
+                });
+            });
+        if !open {
+            msgs.push(Message::CloseTimeAlignmentDialog);
+        } else if new_dialog != *dialog {
+            msgs.push(Message::UpdateTimeAlignmentDialog(new_dialog));
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Loading ELF files and showing the disassembly of the code around an address.
+use std::sync::Arc;
+
+use camino::Utf8PathBuf;
+use egui::{Context, RichText, ScrollArea, TextWrapMode, Window};
+use emath::{Align2, Vec2};
+use eyre::{Context as _, Result};
+use log::error;
+use num::ToPrimitive;
+
+use crate::async_util::perform_work;
+use crate::displayed_item::DisplayedItem;
+use crate::displayed_item_tree::VisibleItemIndex;
+use crate::message::{Message, MessageTarget};
+use crate::translation::{ElfProgram, InstructionTranslator};
+use crate::SystemState;
+
+/// Start the disassembly at the start of the function containing the address if it is
+/// at most this many bytes before it
+const MAX_FUNCTION_OFFSET: u64 = 0x100;
+/// Bytes of code to show before the address if it is not close to the start of a function
+const CONTEXT_BEFORE: u64 = 0x40;
+/// Bytes of code to show after the address
+const CONTEXT_AFTER: u64 = 0x80;
+
+impl SystemState {
+    pub(crate) fn load_elf_file(&self, path: Utf8PathBuf) {
+        let sender = self.channels.msg_sender.clone();
+        perform_work(move || {
+            let program = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {path}"))
+                .and_then(|data| {
+                    ElfProgram::parse(path.file_name().unwrap_or(path.as_str()).to_string(), &data)
+                });
+            send_elf_program(&sender, program);
+        });
+    }
+
+    pub(crate) fn load_elf_data(&self, name: String, bytes: Vec<u8>) {
+        let sender = self.channels.msg_sender.clone();
+        perform_work(move || send_elf_program(&sender, ElfProgram::parse(name, &bytes)));
+    }
+
+    /// Opens the disassembly at the value of a variable at the cursor, typically the PC
+    pub(crate) fn show_disassembly_at_value(&mut self, vidx: MessageTarget<VisibleItemIndex>) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        let vidx = if let MessageTarget::Explicit(vidx) = vidx {
+            vidx
+        } else if let Some(focused) = waves.focused_item {
+            focused
+        } else {
+            return;
+        };
+        let Some(DisplayedItem::Variable(variable)) = waves
+            .items_tree
+            .get_visible(vidx)
+            .and_then(|node| waves.displayed_items.get(&node.item_ref))
+        else {
+            return;
+        };
+        let Some(cursor) = waves.cursor.as_ref().and_then(num::BigInt::to_biguint) else {
+            return;
+        };
+        let address = waves
+            .query_variable(&variable.variable_ref, &cursor)
+            .ok()
+            .flatten()
+            .and_then(|result| result.current)
+            .and_then(|(_, value)| value.parse_biguint().ok())
+            .and_then(|value| value.to_u64());
+        if let Some(address) = address {
+            self.user.disassembly_address = Some(address);
+        }
+    }
+
+    pub fn draw_disassembly_window(&self, ctx: &Context, msgs: &mut Vec<Message>) {
+        let Some(address) = self.user.disassembly_address else {
+            return;
+        };
+        let mut open = true;
+        Window::new("Disassembly")
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(Vec2::new(500., 400.))
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let Some(program) = self.translators.elf.get() else {
+                    ui.label("No ELF file loaded");
+                    return;
+                };
+                let Some(translator) = program.translator() else {
+                    ui.label(format!(
+                        "Disassembling the instruction set of {} is not supported",
+                        program.name
+                    ));
+                    return;
+                };
+                let translator = InstructionTranslator {
+                    abi_register_names: self.user.config.abi_register_names,
+                    ..translator.clone()
+                };
+                ui.label(match program.symbolize(address) {
+                    Some(symbol) => format!("{address:#x} <{symbol}>"),
+                    None => format!("{address:#x}"),
+                });
+                ui.separator();
+
+                let start = program
+                    .symbol_start(address)
+                    .filter(|start| address - start <= MAX_FUNCTION_OFFSET)
+                    .unwrap_or(address.saturating_sub(CONTEXT_BEFORE));
+                let highlight = ui.visuals().selection.bg_fill;
+                ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+                ScrollArea::both().show(ui, |ui| {
+                    let mut current = start;
+                    while current < address.saturating_add(CONTEXT_AFTER) {
+                        let Some((instruction, len)) = program.instruction(current) else {
+                            break;
+                        };
+                        if let Some((symbol, 0)) = program.symbol(current) {
+                            ui.label(RichText::new(format!("<{symbol}>:")).monospace().strong());
+                        }
+                        let text = format!(
+                            "{current:>8x}:  {instruction:0width$x}  {}",
+                            translator.disassemble(current, instruction, Some(&program)),
+                            width = len as usize * 2,
+                        );
+                        let mut text = RichText::new(text).monospace();
+                        if current == address {
+                            text = text.background_color(highlight);
+                        }
+                        ui.label(text);
+                        current += len;
+                    }
+                });
+            });
+        if !open {
+            msgs.push(Message::SetDisassemblyAddress(None));
+        }
+    }
+}
+
+fn send_elf_program(sender: &std::sync::mpsc::Sender<Message>, program: Result<ElfProgram>) {
+    match program {
+        Ok(program) => sender.send(Message::ElfLoaded(Arc::new(program))).unwrap(),
+        Err(e) => error!("Failed to load ELF file: {e:#}"),
+    }
+}
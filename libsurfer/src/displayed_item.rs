@@ -9,6 +9,7 @@ use surfer_translation_types::VariableInfo;
 use crate::config::SurferConfig;
 use crate::displayed_item_tree::VisibleItemIndex;
 use crate::transaction_container::TransactionStreamRef;
+use crate::value_color::ValueColorRule;
 use crate::wave_container::{FieldRef, VariableRef, VariableRefExt, WaveContainer};
 use crate::{
     marker::DEFAULT_MARKER_NAME, message::Message, time::DEFAULT_TIMELINE_NAME,
@@ -82,6 +83,9 @@ pub struct DisplayedVariable {
     pub format: Option<String>,
     pub field_formats: Vec<FieldFormat>,
     pub height_scaling_factor: Option<f32>,
+    /// Rules for coloring the value depending on what it is
+    #[serde(default)]
+    pub color_rules: Vec<ValueColorRule>,
 }
 
 impl DisplayedVariable {
@@ -128,6 +132,7 @@ impl DisplayedVariable {
             format: self.format,
             field_formats: self.field_formats,
             height_scaling_factor: self.height_scaling_factor,
+            color_rules: self.color_rules,
         }
     }
 }
@@ -191,6 +196,9 @@ pub struct DisplayedPlaceholder {
     pub format: Option<String>,
     pub field_formats: Vec<FieldFormat>,
     pub height_scaling_factor: Option<f32>,
+    /// Rules for coloring the value depending on what it is
+    #[serde(default)]
+    pub color_rules: Vec<ValueColorRule>,
 }
 
 impl DisplayedPlaceholder {
@@ -210,6 +218,7 @@ impl DisplayedPlaceholder {
             format: self.format,
             field_formats: self.field_formats,
             height_scaling_factor: self.height_scaling_factor,
+            color_rules: self.color_rules,
         }
     }
 
@@ -294,6 +303,23 @@ impl DisplayedItem {
         }
     }
 
+    /// Adds a value color rule. `None` removes all rules. Only affects variables and placeholders.
+    pub fn set_value_color_rule(&mut self, rule: Option<ValueColorRule>) {
+        let rules = match self {
+            DisplayedItem::Variable(variable) => &mut variable.color_rules,
+            DisplayedItem::Placeholder(placeholder) => &mut placeholder.color_rules,
+            _ => return,
+        };
+        match rule {
+            Some(rule) => {
+                // A rule with the same condition is replaced rather than shadowed
+                rules.retain(|r| r.condition != rule.condition);
+                rules.push(rule);
+            }
+            None => rules.clear(),
+        }
+    }
+
     pub fn name(&self) -> String {
         match self {
             DisplayedItem::Variable(variable) => variable
@@ -18,12 +18,13 @@ use surfer_translation_types::{
 };
 
 use crate::clock_highlighting::draw_clock_edge_marks;
-use crate::config::SurferTheme;
+use crate::config::{SurferConfig, SurferTheme};
 use crate::data_container::DataContainer;
 use crate::displayed_item::{DisplayedFieldRef, DisplayedItemRef, DisplayedVariable};
 use crate::displayed_item_tree::VisibleItemIndex;
-use crate::transaction_container::{TransactionRef, TransactionStreamRef};
+use crate::transaction_container::{TransactionContainer, TransactionRef, TransactionStreamRef};
 use crate::translation::{TranslationResultExt, TranslatorList, ValueKindExt, VariableInfoExt};
+use crate::value_color::apply_value_color_rules;
 use crate::view::{DrawConfig, DrawingContext, ItemDrawingInfo};
 use crate::viewport::Viewport;
 use crate::wave_container::{QueryResult, VariableRefExt};
@@ -84,6 +85,8 @@ pub struct TxDrawingCommands {
     min: Pos2,
     max: Pos2,
     gen_ref: TransactionStreamRef, // makes it easier to later access the actual Transaction object
+    /// The transaction matches the transaction query, always true without a query
+    matches_query: bool,
 }
 
 struct VariableDrawCommands {
@@ -101,10 +104,26 @@ fn variable_draw_commands(
     translators: &TranslatorList,
     view_width: f32,
     viewport_idx: usize,
+    config: &SurferConfig,
 ) -> Option<VariableDrawCommands> {
     let mut clock_edges = vec![];
     let mut local_msgs = vec![];
 
+    // Rules on the variable itself take precedence over rules from the config
+    let full_path = displayed_variable.variable_ref.full_path_string();
+    let color_rules = displayed_variable
+        .color_rules
+        .iter()
+        .map(|rule| (&rule.condition, rule.color.as_ref()))
+        .chain(
+            config
+                .value_color_rules
+                .iter()
+                .filter(|rule| rule.variable.is_match(&full_path))
+                .map(|rule| (&rule.condition, rule.color.as_ref())),
+        )
+        .collect_vec();
+
     let meta = match waves
         .inner
         .as_waves()
@@ -146,11 +165,7 @@ fn variable_draw_commands(
             continue;
         }
 
-        let query_result = waves
-            .inner
-            .as_waves()
-            .unwrap()
-            .query_variable(&displayed_variable.variable_ref, time);
+        let query_result = waves.query_variable(&displayed_variable.variable_ref, time);
         next_change = match &query_result {
             Ok(Some(QueryResult {
                 next: Some(timestamp),
@@ -206,7 +221,13 @@ fn variable_draw_commands(
             translators,
         );
 
-        for SubFieldFlatTranslationResult { names, value } in fields {
+        for SubFieldFlatTranslationResult { names, mut value } in fields {
+            if names.is_empty() && !color_rules.is_empty() {
+                if let Some(value) = value.as_mut() {
+                    apply_value_color_rules(value, &val, &color_rules, &config.theme);
+                }
+            }
+
             let entry = local_commands.entry(names.clone()).or_insert_with(|| {
                 match info.get_subinfo(&names) {
                     VariableInfo::Bool => DrawingCommands::new_bool(),
@@ -284,16 +305,42 @@ impl SystemState {
         self.timing.borrow_mut().start("Generate draw commands");
         if let Some(waves) = &self.user.waves {
             let draw_data = match waves.inner {
-                DataContainer::Waves(_) => {
-                    self.generate_wave_draw_commands(waves, cfg, frame_width, msgs, viewport_idx)
+                DataContainer::Waves(_) => Some(CachedDrawData::WaveDrawData(
+                    self.generate_wave_draw_commands(waves, cfg, frame_width, msgs, viewport_idx),
+                )),
+                DataContainer::Transactions(_) => Some(TransactionDrawData(
+                    self.generate_transaction_draw_commands(
+                        waves,
+                        cfg,
+                        frame_width,
+                        msgs,
+                        viewport_idx,
+                    ),
+                )),
+                DataContainer::Combined(..) => {
+                    // The rows of the streams are as high as in a transaction-only view
+                    let transaction_cfg = DrawConfig::new(
+                        cfg.canvas_height,
+                        self.user.config.layout.transactions_line_height,
+                        cfg.text_size,
+                    );
+                    Some(CachedDrawData::CombinedDrawData(
+                        self.generate_wave_draw_commands(
+                            waves,
+                            cfg,
+                            frame_width,
+                            msgs,
+                            viewport_idx,
+                        ),
+                        self.generate_transaction_draw_commands(
+                            waves,
+                            &transaction_cfg,
+                            frame_width,
+                            msgs,
+                            viewport_idx,
+                        ),
+                    ))
                 }
-                DataContainer::Transactions(_) => self.generate_transaction_draw_commands(
-                    waves,
-                    cfg,
-                    frame_width,
-                    msgs,
-                    viewport_idx,
-                ),
                 DataContainer::Empty => None,
             };
             self.draw_data.borrow_mut()[viewport_idx] = draw_data;
@@ -309,7 +356,7 @@ impl SystemState {
         frame_width: f32,
         msgs: &mut Vec<Message>,
         viewport_idx: usize,
-    ) -> Option<CachedDrawData> {
+    ) -> CachedWaveDrawData {
         let mut draw_commands = HashMap::new();
 
         let num_timestamps = waves.num_timestamps().unwrap_or(1.into());
@@ -355,6 +402,7 @@ impl SystemState {
                     translators,
                     frame_width,
                     viewport_idx,
+                    &self.user.config,
                 )
             })
             .collect::<Vec<_>>();
@@ -388,11 +436,11 @@ impl SystemState {
             &self.user.config,
         );
 
-        Some(CachedDrawData::WaveDrawData(CachedWaveDrawData {
+        CachedWaveDrawData {
             draw_commands,
             clock_edges,
             ticks,
-        }))
+        }
     }
 
     fn generate_transaction_draw_commands(
@@ -402,7 +450,7 @@ impl SystemState {
         frame_width: f32,
         msgs: &mut Vec<Message>,
         viewport_idx: usize,
-    ) -> Option<CachedDrawData> {
+    ) -> CachedTransactionDrawData {
         let mut draw_commands = HashMap::new();
         let mut stream_to_displayed_txs = HashMap::new();
         let mut inc_relation_tx_ids = vec![];
@@ -427,12 +475,12 @@ impl SystemState {
             })
             .collect::<Vec<_>>();
 
-        let first_visible_timestamp = viewport
+        let first_visible_time = viewport
             .curr_left
             .absolute(&num_timestamps)
             .0
-            .to_biguint()
-            .unwrap_or(BigUint::ZERO);
+            .to_bigint()
+            .unwrap_or_default();
 
         for displayed_stream in displayed_streams {
             let tx_stream_ref = &displayed_stream.transaction_stream_ref;
@@ -440,34 +488,24 @@ impl SystemState {
             let mut generators: Vec<&TxGenerator> = vec![];
             let mut displayed_transactions = vec![];
 
+            let container = tx_stream_ref.container;
+            // The stream may be from a transaction file that is not loaded (yet)
+            let Some(inner) = waves.inner.transactions(container) else {
+                continue;
+            };
             if tx_stream_ref.is_stream() {
-                let stream = waves
-                    .inner
-                    .as_transactions()
-                    .unwrap()
-                    .get_stream(tx_stream_ref.stream_id)
-                    .unwrap();
+                let stream = inner.get_stream(tx_stream_ref.stream_id).unwrap();
 
                 for gen_id in &stream.generators {
-                    generators.push(
-                        waves
-                            .inner
-                            .as_transactions()
-                            .unwrap()
-                            .get_generator(*gen_id)
-                            .unwrap(),
-                    );
+                    generators.push(inner.get_generator(*gen_id).unwrap());
                 }
             } else {
-                generators.push(
-                    waves
-                        .inner
-                        .as_transactions()
-                        .unwrap()
-                        .get_generator(tx_stream_ref.gen_id.unwrap())
-                        .unwrap(),
-                );
+                generators.push(inner.get_generator(tx_stream_ref.gen_id.unwrap()).unwrap());
             }
+            let first_visible_timestamp = waves
+                .transaction_file_time(container, &first_visible_time)
+                .to_biguint()
+                .unwrap_or(BigUint::ZERO);
 
             for gen in &generators {
                 // find first visible transaction
@@ -487,9 +525,13 @@ impl SystemState {
                 let mut last_px = f32::NAN;
 
                 for tx in transactions {
-                    let start_time = tx.get_start_time();
-                    let end_time = tx.get_end_time();
+                    let start_time = waves.transaction_time(container, &tx.get_start_time());
+                    let end_time = waves.transaction_time(container, &tx.get_end_time());
                     let curr_tx_id = tx.get_tx_id();
+                    let tx_ref = TransactionRef {
+                        container,
+                        id: curr_tx_id,
+                    };
 
                     // stop drawing after last visible transaction
                     if start_time.to_f64().unwrap()
@@ -498,22 +540,14 @@ impl SystemState {
                         break;
                     }
 
-                    if let Some(focused_tx_ref) = focused_tx_ref {
-                        if curr_tx_id == focused_tx_ref.id {
-                            new_focused_tx = Some(tx);
-                        }
+                    if focused_tx_ref.as_ref() == Some(&tx_ref) {
+                        new_focused_tx = Some(tx);
                     }
 
-                    let min_px = viewport.pixel_from_time(
-                        &start_time.to_bigint().unwrap(),
-                        frame_width - 1.,
-                        &num_timestamps,
-                    );
-                    let max_px = viewport.pixel_from_time(
-                        &end_time.to_bigint().unwrap(),
-                        frame_width - 1.,
-                        &num_timestamps,
-                    );
+                    let min_px =
+                        viewport.pixel_from_time(&start_time, frame_width - 1., &num_timestamps);
+                    let max_px =
+                        viewport.pixel_from_time(&end_time, frame_width - 1., &num_timestamps);
 
                     // skip transactions that are rendered completely in the previous pixel
                     if (min_px == max_px) && (min_px == last_px) {
@@ -522,11 +556,10 @@ impl SystemState {
                     }
                     last_px = max_px;
 
-                    displayed_transactions.push(TransactionRef { id: curr_tx_id });
+                    displayed_transactions.push(tx_ref.clone());
                     let min = Pos2::new(min_px, cfg.line_height * tx.row as f32 + 4.0);
                     let max = Pos2::new(max_px, cfg.line_height * (tx.row + 1) as f32 - 4.0);
 
-                    let tx_ref = TransactionRef { id: curr_tx_id };
                     draw_commands.insert(
                         tx_ref,
                         TxDrawingCommands {
@@ -536,7 +569,12 @@ impl SystemState {
                                 tx_stream_ref.stream_id,
                                 gen.id,
                                 gen.name.clone(),
-                            ),
+                            )
+                            .in_container(container),
+                            matches_query: waves
+                                .transaction_query
+                                .as_ref()
+                                .is_none_or(|query| query.matches(container, tx)),
                         },
                     );
                 }
@@ -544,14 +582,19 @@ impl SystemState {
             stream_to_displayed_txs.insert(tx_stream_ref.clone(), displayed_transactions);
         }
 
-        if let Some(focused_tx) = new_focused_tx {
+        if let (Some(focused_tx), Some(focused_tx_ref)) = (new_focused_tx, focused_tx_ref) {
+            let container = focused_tx_ref.container;
             for rel in &focused_tx.inc_relations {
                 inc_relation_tx_ids.push(TransactionRef {
+                    container,
                     id: rel.source_tx_id,
                 });
             }
             for rel in &focused_tx.out_relations {
-                out_relation_tx_ids.push(TransactionRef { id: rel.sink_tx_id });
+                out_relation_tx_ids.push(TransactionRef {
+                    container,
+                    id: rel.sink_tx_id,
+                });
             }
             if old_focused_tx.is_none() || Some(focused_tx) != old_focused_tx.as_ref() {
                 msgs.push(Message::FocusTransaction(
@@ -561,12 +604,12 @@ impl SystemState {
             }
         }
 
-        Some(TransactionDrawData(CachedTransactionDrawData {
+        CachedTransactionDrawData {
             draw_commands,
             stream_to_displayed_txs,
             inc_relation_tx_ids,
             out_relation_tx_ids,
-        }))
+        }
     }
 
     // Transform from screen coordinates taking timeline into account
@@ -602,7 +645,7 @@ impl SystemState {
         }
 
         let cfg = match waves.inner {
-            DataContainer::Waves(_) => DrawConfig::new(
+            DataContainer::Waves(_) | DataContainer::Combined(..) => DrawConfig::new(
                 response.rect.size().y,
                 self.user.config.layout.waveforms_line_height,
                 self.user.config.layout.waveforms_text_size,
@@ -679,7 +722,14 @@ impl SystemState {
             if let Some(snap_point) =
                 self.snap_to_edge(pointer_pos_canvas, waves, frame_width, viewport_idx)
             {
-                msgs.push(Message::CursorSet(snap_point));
+                if waves.per_viewport_cursors && viewport_idx != 0 {
+                    msgs.push(Message::ViewportCursorSet {
+                        time: snap_point,
+                        viewport_idx,
+                    });
+                } else {
+                    msgs.push(Message::CursorSet(snap_point));
+                }
             }
         }
 
@@ -761,6 +811,22 @@ impl SystemState {
                     viewport_idx,
                     frame_width,
                     &cfg,
+                    true,
+                    ui,
+                    msgs,
+                    &mut ctx,
+                );
+            }
+            Some(CachedDrawData::CombinedDrawData(wave_data, transaction_data)) => {
+                self.draw_wave_data(waves, wave_data, &mut ctx);
+                // The ticks and timelines are drawn with the waves
+                self.draw_transaction_data(
+                    waves,
+                    transaction_data,
+                    viewport_idx,
+                    frame_width,
+                    &cfg,
+                    false,
                     ui,
                     msgs,
                     &mut ctx,
@@ -783,6 +849,7 @@ impl SystemState {
             &mut ctx,
             response.rect.size(),
             &waves.viewports[viewport_idx],
+            viewport_idx,
         );
 
         waves.draw_markers(
@@ -792,6 +859,13 @@ impl SystemState {
             &waves.viewports[viewport_idx],
         );
 
+        waves.draw_diagnostics(
+            &self.user.config.theme,
+            &mut ctx,
+            response.rect.size(),
+            &waves.viewports[viewport_idx],
+        );
+
         self.draw_marker_boxes(
             waves,
             &mut ctx,
@@ -801,6 +875,14 @@ impl SystemState {
             y_zero,
         );
 
+        self.draw_viewport_cursor_delta(
+            waves,
+            &mut ctx,
+            response.rect.size(),
+            &waves.viewports[viewport_idx],
+            viewport_idx,
+        );
+
         if self.show_default_timeline() {
             let rect = Rect {
                 min: Pos2 { x: 0.0, y: y_zero },
@@ -985,6 +1067,7 @@ impl SystemState {
         viewport_idx: usize,
         frame_width: f32,
         cfg: &DrawConfig,
+        draw_ticks: bool,
         ui: &mut Ui,
         msgs: &mut Vec<Message>,
         ctx: &mut DrawingContext,
@@ -998,15 +1081,19 @@ impl SystemState {
         let mut out_relation_starts = vec![];
         let mut focused_transaction_start: Option<Pos2> = None;
 
-        let ticks = &waves.get_ticks(
-            &waves.viewports[viewport_idx],
-            &waves.inner.metadata().timescale,
-            frame_width,
-            cfg.text_size,
-            &self.user.wanted_timeunit,
-            &self.get_time_format(),
-            &self.user.config,
-        );
+        let ticks = &if draw_ticks {
+            waves.get_ticks(
+                &waves.viewports[viewport_idx],
+                &waves.inner.metadata().timescale,
+                frame_width,
+                cfg.text_size,
+                &self.user.wanted_timeunit,
+                &self.get_time_format(),
+                &self.user.config,
+            )
+        } else {
+            vec![]
+        };
 
         if !ticks.is_empty() && self.show_ticks() {
             let stroke = Stroke {
@@ -1087,7 +1174,7 @@ impl SystemState {
                                         ));
                                     }
 
-                                    let tx_fill_color = if is_transaction_focused {
+                                    let mut tx_fill_color = if is_transaction_focused {
                                         let c = color
                                             .unwrap_or(&self.user.config.theme.transaction_default);
                                         Color32::from_rgb(255 - c.r(), 255 - c.g(), 255 - c.b())
@@ -1095,9 +1182,20 @@ impl SystemState {
                                         *color
                                             .unwrap_or(&self.user.config.theme.transaction_default)
                                     };
+                                    if !tx_draw_command.matches_query {
+                                        tx_fill_color = tx_fill_color.gamma_multiply(0.3);
+                                    }
 
-                                    let stroke =
-                                        Stroke::new(1.5, tx_fill_color.gamma_multiply(1.2));
+                                    let stroke = if waves.transaction_query.is_some()
+                                        && tx_draw_command.matches_query
+                                    {
+                                        Stroke::new(
+                                            2.5,
+                                            self.user.config.theme.accent_info.background,
+                                        )
+                                    } else {
+                                        Stroke::new(1.5, tx_fill_color.gamma_multiply(1.2))
+                                    };
                                     ctx.painter.rect(
                                         transaction_rect,
                                         CornerRadiusF32::same(5.0),
@@ -1106,9 +1204,12 @@ impl SystemState {
                                         egui::StrokeKind::Middle,
                                     );
                                 } else {
-                                    let tx_fill_color = color
+                                    let mut tx_fill_color = color
                                         .unwrap_or(&self.user.config.theme.transaction_default)
                                         .gamma_multiply(1.2);
+                                    if !tx_draw_command.matches_query {
+                                        tx_fill_color = tx_fill_color.gamma_multiply(0.3);
+                                    }
 
                                     let stroke = Stroke::new(1.5, tx_fill_color);
                                     ctx.painter.rect(
@@ -1128,7 +1229,7 @@ impl SystemState {
                         );
                     }
                 }
-                ItemDrawingInfo::TimeLine(_) => {
+                ItemDrawingInfo::TimeLine(_) if draw_ticks => {
                     let text_color = color.unwrap_or(
                         // Get background color and determine best text color
                         self.user
@@ -1149,6 +1250,7 @@ impl SystemState {
                         &self.user.config,
                     );
                 }
+                ItemDrawingInfo::TimeLine(_) => {}
                 ItemDrawingInfo::Variable(_) => {}
                 ItemDrawingInfo::Divider(_) => {}
                 ItemDrawingInfo::Marker(_) => {}
@@ -1463,11 +1565,8 @@ impl SystemState {
                     if let Some(DisplayedItem::Variable(variable)) =
                         &waves.displayed_items.get(&node.item_ref)
                     {
-                        if let Ok(Some(res)) = waves
-                            .inner
-                            .as_waves()
-                            .unwrap()
-                            .query_variable(&variable.variable_ref, &utimestamp)
+                        if let Ok(Some(res)) =
+                            waves.query_variable(&variable.variable_ref, &utimestamp)
                         {
                             let prev_time = if let Some(v) = res.current {
                                 v.0.to_bigint().unwrap()
@@ -1571,10 +1670,8 @@ fn handle_transaction_tooltip(
 ) -> Response {
     response
         .on_hover_ui(|ui| {
-            let tx = waves
-                .inner
-                .as_transactions()
-                .unwrap()
+            let inner = waves.inner.transactions(gen_ref.container).unwrap();
+            let tx = inner
                 .get_generator(gen_ref.gen_id.unwrap())
                 .unwrap()
                 .transactions
@@ -1583,7 +1680,7 @@ fn handle_transaction_tooltip(
                 .unwrap();
 
             ui.set_max_width(ui.spacing().tooltip_width);
-            ui.add(egui::Label::new(transaction_tooltip_text(waves, tx)));
+            ui.add(egui::Label::new(transaction_tooltip_text(inner, tx)));
         })
         .on_hover_ui(|ui| {
             // Seemingly a bit redundant to determine tx twice, but since the
@@ -1592,7 +1689,7 @@ fn handle_transaction_tooltip(
             // Feel free to use some Rust magic to only do it once though...
             let tx = waves
                 .inner
-                .as_transactions()
+                .transactions(gen_ref.container)
                 .unwrap()
                 .get_generator(gen_ref.gen_id.unwrap())
                 .unwrap()
@@ -1605,8 +1702,8 @@ fn handle_transaction_tooltip(
         })
 }
 
-fn transaction_tooltip_text(waves: &WaveData, tx: &Transaction) -> String {
-    let time_scale = waves.inner.as_transactions().unwrap().inner.time_scale;
+fn transaction_tooltip_text(inner: &TransactionContainer, tx: &Transaction) -> String {
+    let time_scale = inner.inner.time_scale;
 
     format!(
         "tx#{}: {}{} - {}{}\nType: {}",
@@ -1615,18 +1712,11 @@ fn transaction_tooltip_text(waves: &WaveData, tx: &Transaction) -> String {
         time_scale,
         tx.event.end_time,
         time_scale,
-        waves
-            .inner
-            .as_transactions()
-            .unwrap()
-            .get_generator(tx.get_gen_id())
-            .unwrap()
-            .name
-            .clone(),
+        inner.get_generator(tx.get_gen_id()).unwrap().name.clone(),
     )
 }
 
-fn transaction_tooltip_table(ui: &mut Ui, tx: &Transaction) {
+pub(crate) fn transaction_tooltip_table(ui: &mut Ui, tx: &Transaction) {
     TableBuilder::new(ui)
         .column(Column::exact(80.))
         .column(Column::exact(80.))
@@ -15,6 +15,10 @@ use crate::SystemState;
 pub enum OpenMode {
     Open,
     Switch,
+    /// Adds a transaction file next to the loaded waveform
+    AddTransactions,
+    /// Opens the file in a new tab. The tab is only created once a file has been chosen.
+    NewTab,
 }
 
 impl SystemState {
@@ -49,45 +53,60 @@ impl SystemState {
     pub fn open_file_dialog(&mut self, mode: OpenMode) {
         let keep_unavailable = self.user.config.behavior.keep_during_reload;
         let keep_variables = match mode {
-            OpenMode::Open => false,
-            OpenMode::Switch => true,
+            OpenMode::Open | OpenMode::NewTab => false,
+            OpenMode::Switch | OpenMode::AddTransactions => true,
+        };
+        let new_tab = matches!(mode, OpenMode::NewTab).then(|| self.num_tabs());
+        let in_tab = move |load: Message| match new_tab {
+            Some(tab) => Message::Batch(vec![Message::SwitchTab(tab), load]),
+            None => load,
         };
 
         #[cfg(not(target_arch = "wasm32"))]
         let message = move |file: PathBuf| {
-            Message::LoadFile(
+            in_tab(Message::LoadFile(
                 Utf8PathBuf::from_path_buf(file).unwrap(),
                 LoadOptions {
                     keep_variables,
                     keep_unavailable,
                 },
-            )
+            ))
         };
 
         #[cfg(target_arch = "wasm32")]
         let message = move |file: Vec<u8>| {
-            Message::LoadFromData(
+            in_tab(Message::LoadFromData(
                 file,
                 LoadOptions {
                     keep_variables,
                     keep_unavailable,
                 },
-            )
+            ))
         };
 
-        self.file_dialog(
-            "Open waveform file",
-            (
-                "Waveform/Transaction-files (*.vcd, *.fst, *.ghw, *.ftr)".to_string(),
-                vec![
-                    "vcd".to_string(),
-                    "fst".to_string(),
-                    "ghw".to_string(),
-                    "ftr".to_string(),
-                ],
+        match mode {
+            OpenMode::AddTransactions => self.file_dialog(
+                "Add transaction file",
+                (
+                    "Transaction-files (*.ftr)".to_string(),
+                    vec!["ftr".to_string()],
+                ),
+                message,
             ),
-            message,
-        );
+            OpenMode::Open | OpenMode::Switch | OpenMode::NewTab => self.file_dialog(
+                "Open waveform file",
+                (
+                    "Waveform/Transaction-files (*.vcd, *.fst, *.ghw, *.ftr)".to_string(),
+                    vec![
+                        "vcd".to_string(),
+                        "fst".to_string(),
+                        "ghw".to_string(),
+                        "ftr".to_string(),
+                    ],
+                ),
+                message,
+            ),
+        }
     }
 
     pub fn open_command_file_dialog(&mut self) {
@@ -109,6 +128,21 @@ impl SystemState {
         );
     }
 
+    pub fn open_elf_file_dialog(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let message =
+            move |file: PathBuf| Message::LoadElfFile(Utf8PathBuf::from_path_buf(file).unwrap());
+
+        #[cfg(target_arch = "wasm32")]
+        let message = move |file: Vec<u8>| Message::LoadElfFromData(file);
+
+        self.file_dialog(
+            "Open ELF file",
+            ("ELF files (*.elf)".to_string(), vec!["elf".to_string()]),
+            message,
+        );
+    }
+
     #[cfg(feature = "python")]
     pub fn open_python_file_dialog(&mut self) {
         self.file_dialog(
@@ -143,6 +177,20 @@ pub async fn load_state_dialog() -> Option<rfd::FileHandle> {
     .await
 }
 
+pub async fn save_file_dialog(
+    title: &'static str,
+    filter: (String, Vec<String>),
+) -> Option<rfd::FileHandle> {
+    create_file_dialog(filter, title).save_file().await
+}
+
+pub async fn load_file_dialog(
+    title: &'static str,
+    filter: (String, Vec<String>),
+) -> Option<rfd::FileHandle> {
+    create_file_dialog(filter, title).pick_file().await
+}
+
 fn create_file_dialog(filter: (String, Vec<String>), title: &'static str) -> AsyncFileDialog {
     AsyncFileDialog::new()
         .set_title(title)
@@ -0,0 +1,461 @@
+//! Searching for scopes and variables in the whole design hierarchy.
+use egui::{Button, Context, RichText, TextEdit, TextWrapMode, Window};
+use egui_extras::{Column, TableBuilder};
+use egui_remixicon::icons;
+use emath::{Align2, Vec2};
+use itertools::Itertools;
+use surfer_translation_types::{VariableDirection, VariableType};
+use web_time::{Duration, Instant};
+
+use crate::message::Message;
+use crate::variable_direction::VariableDirectionExt;
+use crate::variable_filter::{VariableFilter, VariableNameFilterType};
+use crate::wave_container::{ScopeRef, ScopeRefExt, VariableRef, VariableRefExt};
+use crate::wave_data::{ScopeType, WaveData};
+use crate::SystemState;
+
+/// Stop collecting matches after this many to keep the window responsive when searching
+/// for something very common in a large design
+const MAX_RESULTS: usize = 10_000;
+
+/// The search only runs once the query has not changed for this long, so that typing
+/// in a large design does not search for every intermediate query
+const SEARCH_DELAY: Duration = Duration::from_millis(150);
+
+/// Name, type and filter settings of a search
+type SearchQuery = (String, String, bool);
+
+enum IndexedItem {
+    Scope(ScopeRef),
+    Variable {
+        variable: VariableRef,
+        variable_type: Option<VariableType>,
+        num_bits: Option<u32>,
+        direction: VariableDirection,
+    },
+}
+
+struct IndexEntry {
+    name: String,
+    item: IndexedItem,
+}
+
+/// All scopes and variables in the design with what is shown about them. Collecting
+/// these is slow for large designs, so it is done once when a waveform is loaded.
+pub(crate) struct HierarchySearchIndex {
+    entries: Vec<IndexEntry>,
+}
+
+impl HierarchySearchIndex {
+    fn new(waves: &WaveData) -> Self {
+        let Some(wave_container) = waves.inner.as_waves() else {
+            return Self { entries: vec![] };
+        };
+        let variables = wave_container.variable_names().into_iter().map(|name| {
+            let variable = VariableRef::from_hierarchy_string(&name);
+            let meta = wave_container.variable_meta(&variable).ok();
+            IndexEntry {
+                name,
+                item: IndexedItem::Variable {
+                    variable_type: meta.as_ref().and_then(|m| m.variable_type),
+                    num_bits: meta.as_ref().and_then(|m| m.num_bits),
+                    direction: meta
+                        .and_then(|m| m.direction)
+                        .unwrap_or(VariableDirection::Unknown),
+                    variable,
+                },
+            }
+        });
+        Self::from_entries(wave_container.scope_names(), variables)
+    }
+
+    fn from_entries(scopes: Vec<String>, variables: impl Iterator<Item = IndexEntry>) -> Self {
+        let entries = scopes
+            .into_iter()
+            .map(|name| IndexEntry {
+                item: IndexedItem::Scope(ScopeRef::from_hierarchy_string(&name)),
+                name,
+            })
+            .chain(variables)
+            .collect();
+        Self { entries }
+    }
+
+    /// Returns the indices of the entries with a name accepted by `matches` and whether
+    /// there were more than [`MAX_RESULTS`] of them. Entries where the last part of the
+    /// name matches on its own come first, then entries higher up in the hierarchy.
+    fn search(&self, mut matches: impl FnMut(&str) -> bool) -> (Vec<usize>, bool) {
+        let mut hits = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                if !matches(&entry.name) {
+                    return None;
+                }
+                let leaf = entry.name.rsplit('.').next().unwrap_or_default();
+                let depth = entry.name.matches('.').count();
+                Some(((!matches(leaf), depth), idx))
+            })
+            .collect_vec();
+        // Stable, so equally ranked entries stay in hierarchy order
+        hits.sort_by_key(|(rank, _)| *rank);
+        let truncated = hits.len() > MAX_RESULTS;
+        hits.truncate(MAX_RESULTS);
+        (hits.into_iter().map(|(_, idx)| idx).collect(), truncated)
+    }
+}
+
+/// The matches for a search, kept until the search changes
+pub(crate) struct HierarchySearchResults {
+    query: SearchQuery,
+    hits: Vec<usize>,
+    num_variables: usize,
+    truncated: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct HierarchySearch {
+    index: Option<HierarchySearchIndex>,
+    results: Option<HierarchySearchResults>,
+    /// A query that has not been searched for yet and when it was entered
+    pending: Option<(SearchQuery, Instant)>,
+}
+
+impl SystemState {
+    /// Forgets the search index and results, to be called when the design changes. The index
+    /// is rebuilt when searching.
+    pub(crate) fn invalidate_hierarchy_search(&self) {
+        *self.hierarchy_search.borrow_mut() = HierarchySearch::default();
+    }
+
+    fn update_hierarchy_search(&self, waves: &WaveData, ctx: &Context) {
+        let filter = self.hierarchy_search_filter.borrow();
+        let query = (
+            filter.name_filter_str.clone(),
+            filter.name_filter_type.to_string(),
+            filter.name_filter_case_insensitive,
+        );
+        let mut search = self.hierarchy_search.borrow_mut();
+        let search = &mut *search;
+        if search
+            .results
+            .as_ref()
+            .is_some_and(|results| results.query == query)
+        {
+            search.pending = None;
+            return;
+        }
+
+        let now = Instant::now();
+        let entered = search
+            .pending
+            .as_ref()
+            .filter(|(pending, _)| *pending == query)
+            .map(|(_, entered)| *entered);
+        let entered = match entered {
+            Some(entered) => entered,
+            None => {
+                search.pending = Some((query.clone(), now));
+                now
+            }
+        };
+        // Showing the first results and clearing the query is cheap, so that is not delayed
+        let remaining = SEARCH_DELAY.saturating_sub(now.duration_since(entered));
+        if search.results.is_some() && !query.0.is_empty() && !remaining.is_zero() {
+            ctx.request_repaint_after(remaining);
+            return;
+        }
+        search.pending = None;
+
+        let index = search
+            .index
+            .get_or_insert_with(|| HierarchySearchIndex::new(waves));
+        let (hits, truncated) = if query.0.is_empty() {
+            (vec![], false)
+        } else {
+            index.search(filter.name_filter_fn())
+        };
+        let num_variables = hits
+            .iter()
+            .filter(|idx| matches!(index.entries[**idx].item, IndexedItem::Variable { .. }))
+            .count();
+        search.results = Some(HierarchySearchResults {
+            query,
+            hits,
+            num_variables,
+            truncated,
+        });
+    }
+
+    pub fn draw_hierarchy_search_window(
+        &self,
+        waves: &WaveData,
+        ctx: &Context,
+        msgs: &mut Vec<Message>,
+    ) {
+        let mut open = true;
+        Window::new("Search hierarchy")
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(Vec2::new(600., 400.))
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut filter = self.hierarchy_search_filter.borrow_mut();
+                    ui.add(
+                        TextEdit::singleline(&mut filter.name_filter_str)
+                            .hint_text("Search scopes and variables"),
+                    );
+                    ui.menu_button(icons::FILTER_FILL, |ui| {
+                        search_type_menu(ui, &mut filter);
+                    });
+                    ui.add(
+                        Button::new(icons::FONT_SIZE)
+                            .selected(!filter.name_filter_case_insensitive),
+                    )
+                    .on_hover_text("Case sensitive search")
+                    .clicked()
+                    .then(|| {
+                        filter.name_filter_case_insensitive = !filter.name_filter_case_insensitive;
+                    });
+                });
+
+                self.update_hierarchy_search(waves, ctx);
+                let search = self.hierarchy_search.borrow();
+                let (Some(results), Some(index)) = (&search.results, &search.index) else {
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    if results.truncated {
+                        ui.label(format!("Showing the first {MAX_RESULTS} matches"));
+                    } else {
+                        ui.label(format!("{} matches", results.hits.len()));
+                    }
+                    if ui
+                        .add_enabled(
+                            results.num_variables > 0,
+                            Button::new(format!("Add {} variables", results.num_variables)),
+                        )
+                        .clicked()
+                    {
+                        let variables = results
+                            .hits
+                            .iter()
+                            .filter_map(|idx| match &index.entries[*idx].item {
+                                IndexedItem::Variable { variable, .. } => Some(variable.clone()),
+                                IndexedItem::Scope(_) => None,
+                            })
+                            .collect_vec();
+                        msgs.push(Message::AddVariables(variables));
+                    }
+                });
+                ui.separator();
+
+                ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+                TableBuilder::new(ui)
+                    .column(Column::auto())
+                    .column(Column::auto().resizable(true))
+                    .column(Column::auto().resizable(true))
+                    .column(Column::auto().resizable(true))
+                    .column(Column::remainder())
+                    .striped(true)
+                    .header(20.0, |mut header| {
+                        for title in ["", "Name", "Type", "Width", "Direction"] {
+                            header.col(|ui| {
+                                ui.strong(title);
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        body.rows(18.0, results.hits.len(), |mut row| {
+                            let entry = &index.entries[results.hits[row.index()]];
+                            match &entry.item {
+                                IndexedItem::Scope(scope) => {
+                                    row.col(|ui| {
+                                        if ui
+                                            .small_button(icons::FOLDER_OPEN_LINE)
+                                            .on_hover_text("Select scope")
+                                            .clicked()
+                                        {
+                                            msgs.push(Message::SetActiveScope(
+                                                ScopeType::WaveScope(scope.clone()),
+                                            ));
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        ui.label(RichText::new(&entry.name).monospace());
+                                    });
+                                    row.col(|ui| {
+                                        ui.label("scope");
+                                    });
+                                }
+                                IndexedItem::Variable {
+                                    variable,
+                                    variable_type,
+                                    num_bits,
+                                    direction,
+                                } => {
+                                    row.col(|ui| {
+                                        if ui
+                                            .small_button(icons::ADD_FILL)
+                                            .on_hover_text("Add variable")
+                                            .clicked()
+                                        {
+                                            msgs.push(Message::AddVariables(
+                                                vec![variable.clone()],
+                                            ));
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        ui.label(RichText::new(&entry.name).monospace());
+                                    });
+                                    row.col(|ui| {
+                                        if let Some(variable_type) = variable_type {
+                                            ui.label(variable_type.to_string());
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        if let Some(num_bits) = num_bits {
+                                            ui.label(num_bits.to_string());
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        if let Some(icon) = direction.get_icon() {
+                                            ui.label(icon).on_hover_text(direction.to_string());
+                                        }
+                                    });
+                                }
+                            }
+                        });
+                    });
+            });
+        if !open {
+            msgs.push(Message::SetHierarchySearchVisible(false));
+        }
+    }
+}
+
+fn search_type_menu(ui: &mut egui::Ui, filter: &mut VariableFilter) {
+    for filter_type in enum_iterator::all::<VariableNameFilterType>() {
+        let selected = filter.name_filter_type == filter_type;
+        let label = match filter_type {
+            VariableNameFilterType::Start => "Name starts with".to_string(),
+            VariableNameFilterType::Contain => "Name contains".to_string(),
+            _ => filter_type.to_string(),
+        };
+        if ui.radio(selected, label).clicked() {
+            filter.name_filter_type = filter_type;
+            ui.close_menu();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn index() -> HierarchySearchIndex {
+        let variables = [
+            "tb.clk",
+            "tb.dut.clk",
+            "tb.dut.core.clk_en",
+            "tb.dut.core.pc",
+            "tb.dut.core.dut_id",
+        ]
+        .into_iter()
+        .map(|name| IndexEntry {
+            name: name.to_string(),
+            item: IndexedItem::Variable {
+                variable: VariableRef::from_hierarchy_string(name),
+                variable_type: None,
+                num_bits: None,
+                direction: VariableDirection::Unknown,
+            },
+        });
+        HierarchySearchIndex::from_entries(
+            vec![
+                "tb".to_string(),
+                "tb.dut".to_string(),
+                "tb.dut.core".to_string(),
+            ],
+            variables,
+        )
+    }
+
+    fn search(filter_type: VariableNameFilterType, query: &str) -> Vec<String> {
+        let index = index();
+        let filter = VariableFilter {
+            name_filter_type: filter_type,
+            name_filter_str: query.to_string(),
+            ..VariableFilter::new()
+        };
+        let (hits, truncated) = index.search(filter.name_filter_fn());
+        assert!(!truncated);
+        hits.into_iter()
+            .map(|idx| index.entries[idx].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn search_matches_full_names() {
+        assert_eq!(
+            search(VariableNameFilterType::Start, "tb.dut.c"),
+            vec![
+                "tb.dut.core",
+                "tb.dut.clk",
+                "tb.dut.core.clk_en",
+                "tb.dut.core.pc",
+                "tb.dut.core.dut_id",
+            ]
+        );
+        assert_eq!(
+            search(VariableNameFilterType::Regex, "core$"),
+            vec!["tb.dut.core"]
+        );
+        assert!(search(VariableNameFilterType::Contain, "missing").is_empty());
+    }
+
+    #[test]
+    fn search_ranks_name_matches_first() {
+        assert_eq!(
+            search(VariableNameFilterType::Contain, "dut"),
+            vec![
+                "tb.dut",
+                "tb.dut.core.dut_id",
+                "tb.dut.core",
+                "tb.dut.clk",
+                "tb.dut.core.clk_en",
+                "tb.dut.core.pc",
+            ]
+        );
+    }
+
+    #[test]
+    fn search_ranks_shallow_entries_first() {
+        assert_eq!(
+            search(VariableNameFilterType::Contain, "c"),
+            vec![
+                "tb.clk",
+                "tb.dut.core",
+                "tb.dut.clk",
+                "tb.dut.core.clk_en",
+                "tb.dut.core.pc",
+                "tb.dut.core.dut_id",
+            ]
+        );
+    }
+
+    #[test]
+    fn search_keeps_variables_for_matches() {
+        let index = index();
+        let (hits, _) = index.search(|name| name.ends_with("pc"));
+        assert!(matches!(
+            &index.entries[hits[0]].item,
+            IndexedItem::Variable { variable, .. }
+                if variable == &VariableRef::from_strs(&["tb", "dut", "core", "pc"])
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 //! Keyboard handling.
 use egui::debug_text::print;
-use egui::{Context, Event, Key, Modifiers};
+use egui::{Context, Event, Key, Modifiers, PointerButton};
 use emath::Vec2;
 
 use crate::config::ArrowKeyBindings;
@@ -210,36 +210,46 @@ impl SystemState {
                         viewport_idx: 0,
                     }),
                     (Key::ArrowRight, true, false, false) => {
-                        msgs.push(match self.user.config.behavior.arrow_key_bindings {
-                            ArrowKeyBindings::Edge => Message::MoveCursorToTransition {
-                                next: true,
-                                variable: None,
-                                skip_zero: modifiers.shift,
-                            },
-                            ArrowKeyBindings::Scroll => Message::CanvasScroll {
-                                delta: Vec2 {
-                                    x: 0.,
-                                    y: -PER_SCROLL_EVENT,
+                        if modifiers.alt {
+                            msgs.push(Message::NavigateForward(self.get_count()));
+                            msgs.push(Message::InvalidateCount);
+                        } else {
+                            msgs.push(match self.user.config.behavior.arrow_key_bindings {
+                                ArrowKeyBindings::Edge => Message::MoveCursorToTransition {
+                                    next: true,
+                                    variable: None,
+                                    skip_zero: modifiers.shift,
                                 },
-                                viewport_idx: 0,
-                            },
-                        });
+                                ArrowKeyBindings::Scroll => Message::CanvasScroll {
+                                    delta: Vec2 {
+                                        x: 0.,
+                                        y: -PER_SCROLL_EVENT,
+                                    },
+                                    viewport_idx: 0,
+                                },
+                            });
+                        }
                     }
                     (Key::ArrowLeft, true, false, false) => {
-                        msgs.push(match self.user.config.behavior.arrow_key_bindings {
-                            ArrowKeyBindings::Edge => Message::MoveCursorToTransition {
-                                next: false,
-                                variable: None,
-                                skip_zero: modifiers.shift,
-                            },
-                            ArrowKeyBindings::Scroll => Message::CanvasScroll {
-                                delta: Vec2 {
-                                    x: 0.,
-                                    y: PER_SCROLL_EVENT,
+                        if modifiers.alt {
+                            msgs.push(Message::NavigateBack(self.get_count()));
+                            msgs.push(Message::InvalidateCount);
+                        } else {
+                            msgs.push(match self.user.config.behavior.arrow_key_bindings {
+                                ArrowKeyBindings::Edge => Message::MoveCursorToTransition {
+                                    next: false,
+                                    variable: None,
+                                    skip_zero: modifiers.shift,
                                 },
-                                viewport_idx: 0,
-                            },
-                        });
+                                ArrowKeyBindings::Scroll => Message::CanvasScroll {
+                                    delta: Vec2 {
+                                        x: 0.,
+                                        y: PER_SCROLL_EVENT,
+                                    },
+                                    viewport_idx: 0,
+                                },
+                            });
+                        }
                     }
                     (Key::J, true, false, false) => {
                         if modifiers.alt {
@@ -331,6 +341,17 @@ impl SystemState {
                 Event::Copy => msgs.push(Message::VariableValueToClipbord(
                     MessageTarget::CurrentSelection,
                 )),
+                // Mouse back/forward buttons
+                Event::PointerButton {
+                    button: PointerButton::Extra1,
+                    pressed: true,
+                    ..
+                } => msgs.push(Message::NavigateBack(1)),
+                Event::PointerButton {
+                    button: PointerButton::Extra2,
+                    pressed: true,
+                    ..
+                } => msgs.push(Message::NavigateForward(1)),
                 _ => {}
             });
         });
@@ -1,6 +1,8 @@
 #![deny(unused_crate_dependencies)]
 
 pub mod async_util;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod autosave;
 pub mod batch_commands;
 #[cfg(feature = "performance_plot")]
 pub mod benchmark;
@@ -12,7 +14,9 @@ pub mod config;
 pub mod cxxrtl;
 pub mod cxxrtl_container;
 pub mod data_container;
+pub mod diagnostics;
 pub mod dialog;
+pub mod disassembly;
 pub mod displayed_item;
 pub mod displayed_item_tree;
 pub mod drawing_canvas;
@@ -22,12 +26,14 @@ pub mod fzcmd;
 pub mod graphics;
 pub mod help;
 pub mod hierarchy;
+pub mod hierarchy_search;
 pub mod keys;
 pub mod logs;
 pub mod marker;
 pub mod menus;
 pub mod message;
 pub mod mousegestures;
+pub mod navigation;
 pub mod overview;
 pub mod remote;
 pub mod state;
@@ -35,19 +41,27 @@ pub mod state_file_io;
 pub mod state_util;
 pub mod statusbar;
 pub mod system_state;
+pub mod tabs;
 #[cfg(test)]
 pub mod tests;
 pub mod time;
 pub mod toolbar;
 pub mod transaction_container;
+pub mod transaction_export;
+pub mod transaction_query;
+pub mod transaction_search;
+#[cfg(feature = "performance_plot")]
+pub mod transaction_statistics;
 pub mod translation;
 pub mod util;
+pub mod value_color;
 pub mod variable_direction;
 pub mod variable_filter;
 mod variable_index;
 pub mod variable_name_type;
 pub mod variable_type;
 pub mod view;
+pub mod view_template;
 pub mod viewport;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_api;
@@ -97,19 +111,20 @@ use wcp::{proto::WcpCSMessage, proto::WcpEvent, proto::WcpSCMessage};
 
 use crate::async_util::perform_work;
 use crate::config::{SurferConfig, SurferTheme};
-use crate::dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog};
+use crate::dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog, TimeAlignmentDialog};
 use crate::displayed_item::{DisplayedFieldRef, DisplayedItem, DisplayedItemRef, FieldFormat};
 use crate::displayed_item_tree::VisibleItemIndex;
 use crate::drawing_canvas::TxDrawingCommands;
 use crate::message::Message;
+use crate::navigation::NavigationState;
 use crate::transaction_container::{StreamScopeRef, TransactionRef, TransactionStreamRef};
 use crate::translation::{all_translators, AnyTranslator};
 use crate::variable_filter::{VariableIOFilterType, VariableNameFilterType};
-use crate::viewport::Viewport;
+use crate::viewport::{sync_viewports, Viewport, ViewportLock};
 use crate::wave_container::VariableRefExt;
 use crate::wave_container::{ScopeRefExt, WaveContainer};
 use crate::wave_data::{ScopeType, WaveData};
-use crate::wave_source::{LoadOptions, WaveFormat, WaveSource};
+use crate::wave_source::{CxxrtlKind, LoadOptions, WaveFormat, WaveSource};
 use crate::wellen::{convert_format, HeaderResult};
 
 lazy_static! {
@@ -170,7 +185,7 @@ pub fn run_egui(cc: &CreationContext, mut state: SystemState) -> Result<Box<dyn
         .set_visuals_of(egui::Theme::Light, state.get_visuals());
     #[cfg(not(target_arch = "wasm32"))]
     if state.user.config.wcp.autostart {
-        state.start_wcp_server(Some(state.user.config.wcp.address.clone()), false);
+        state.start_wcp_server(Some(state.user.config.wcp.address.clone()), false, None);
     }
     setup_custom_font(&cc.egui_ctx);
     Ok(Box::new(state))
@@ -193,6 +208,7 @@ pub enum ColorSpecifier {
 enum CachedDrawData {
     WaveDrawData(CachedWaveDrawData),
     TransactionDrawData(CachedTransactionDrawData),
+    CombinedDrawData(CachedWaveDrawData, CachedTransactionDrawData),
 }
 
 struct CachedWaveDrawData {
@@ -234,6 +250,11 @@ pub struct WcpClientCapabilities {
     pub goto_declaration: bool,
     pub add_drivers: bool,
     pub add_loads: bool,
+    pub cursor_changed: bool,
+    pub markers_changed: bool,
+    pub focus_changed: bool,
+    pub selection_changed: bool,
+    pub viewport_changed: bool,
 }
 impl WcpClientCapabilities {
     fn new() -> Self {
@@ -242,8 +263,22 @@ impl WcpClientCapabilities {
             goto_declaration: false,
             add_drivers: false,
             add_loads: false,
+            cursor_changed: false,
+            markers_changed: false,
+            focus_changed: false,
+            selection_changed: false,
+            viewport_changed: false,
         }
     }
+
+    /// True if the client has subscribed to any of the state change events
+    pub(crate) fn wants_state_events(&self) -> bool {
+        self.cursor_changed
+            || self.markers_changed
+            || self.focus_changed
+            || self.selection_changed
+            || self.viewport_changed
+    }
 }
 
 /// Stores the current canvas state to enable undo/redo operations
@@ -265,13 +300,42 @@ impl SystemState {
             s.shrink_to(100);
             log::info!("{s}");
         }
+        let viewports_before = self
+            .user
+            .waves
+            .as_ref()
+            .filter(|waves| waves.viewport_lock != ViewportLock::Independent)
+            .map(|waves| waves.viewports.iter().map(Viewport::destination).collect_vec());
+        let navigation_before = message.navigation_kind().and_then(|kind| {
+            self.user
+                .waves
+                .as_ref()
+                .map(|waves| (NavigationState::new(waves), kind))
+        });
+        #[cfg(not(target_arch = "wasm32"))]
+        let changes_session = message.changes_session();
+        let result = self.update_inner(message);
+        #[cfg(not(target_arch = "wasm32"))]
+        if changes_session {
+            self.autosave_changed();
+        }
+        if let (Some(before), Some(waves)) = (viewports_before, self.user.waves.as_mut()) {
+            sync_viewports(&mut waves.viewports, &before, waves.viewport_lock);
+        }
+        if let Some((before, kind)) = navigation_before {
+            self.record_navigation(before, kind);
+        }
+        self.send_wcp_state_events();
+        result
+    }
+
+    fn update_inner(&mut self, message: Message) -> Option<()> {
         match message {
             Message::SetActiveScope(scope) => {
                 let waves = self.user.waves.as_mut()?;
                 let scope = if let ScopeType::StreamScope(StreamScopeRef::Empty(name)) = scope {
-                    ScopeType::StreamScope(StreamScopeRef::new_stream_from_name(
-                        waves.inner.as_transactions().unwrap(),
-                        name,
+                    ScopeType::StreamScope(StreamScopeRef::Stream(
+                        waves.inner.stream_from_name(&name)?,
                     ))
                 } else {
                     scope
@@ -364,32 +428,19 @@ impl SystemState {
             Message::AddStreamOrGeneratorFromName(scope, name) => {
                 self.save_current_canvas(format!("Add Stream/Generator from name: {}", &name));
                 let waves = self.user.waves.as_mut()?;
-                let inner = waves.inner.as_transactions()?;
                 match scope {
                     Some(StreamScopeRef::Root) => {
-                        let (stream_id, name) = inner
-                            .get_stream_from_name(name)
-                            .map(|s| (s.id, s.name.clone()))
-                            .unwrap();
-
-                        waves.add_stream(TransactionStreamRef::new_stream(stream_id, name));
+                        let stream = waves.inner.stream_from_name(&name)?;
+                        waves.add_stream(stream);
                     }
                     Some(StreamScopeRef::Stream(stream)) => {
-                        let (stream_id, id, name) = inner
-                            .get_generator_from_name(Some(stream.stream_id), name)
-                            .map(|gen| (gen.stream_id, gen.id, gen.name.clone()))
-                            .unwrap();
-
-                        waves.add_generator(TransactionStreamRef::new_gen(stream_id, id, name));
+                        let gen = waves.inner.generator_from_name(Some(&stream), &name)?;
+                        waves.add_generator(gen);
                     }
                     Some(StreamScopeRef::Empty(_)) => {}
                     None => {
-                        let (stream_id, id, name) = inner
-                            .get_generator_from_name(None, name)
-                            .map(|gen| (gen.stream_id, gen.id, gen.name.clone()))
-                            .unwrap();
-
-                        waves.add_generator(TransactionStreamRef::new_gen(stream_id, id, name));
+                        let gen = waves.inner.generator_from_name(None, &name)?;
+                        waves.add_generator(gen);
                     }
                 }
                 self.invalidate_draw_commands();
@@ -400,8 +451,9 @@ impl SystemState {
                 if scope_name == "tr" {
                     waves.add_all_streams();
                 } else {
-                    let inner = waves.inner.as_transactions()?;
-                    let stream = inner.get_stream_from_name(scope_name)?;
+                    let stream_ref = waves.inner.stream_from_name(&scope_name)?;
+                    let inner = waves.inner.transactions(stream_ref.container)?;
+                    let stream = inner.get_stream(stream_ref.stream_id)?;
                     let gens = stream
                         .generators
                         .iter()
@@ -410,11 +462,10 @@ impl SystemState {
                         .collect_vec();
 
                     for (stream_id, id, name) in gens {
-                        waves.add_generator(TransactionStreamRef::new_gen(
-                            stream_id,
-                            id,
-                            name.clone(),
-                        ))
+                        waves.add_generator(
+                            TransactionStreamRef::new_gen(stream_id, id, name.clone())
+                                .in_container(stream_ref.container),
+                        )
                     }
                 }
                 self.invalidate_draw_commands();
@@ -536,6 +587,30 @@ impl SystemState {
             Message::SetCursorWindowVisible(visibility) => {
                 self.user.show_cursor_window = visibility
             }
+            Message::SetDiagnosticsWindowVisible(visibility) => {
+                self.user.show_diagnostics_window = visibility
+            }
+            Message::SetHierarchySearchVisible(visibility) => {
+                self.user.show_hierarchy_search = visibility
+            }
+            Message::SetTransactionSearchVisible(visibility) => {
+                self.user.show_transaction_search = visibility
+            }
+            Message::SetTransactionQuery(query) => self.set_transaction_query(query),
+            Message::ExportTransactions {
+                format,
+                streams,
+                path,
+            } => self.export_transactions(format, streams, path),
+            Message::SaveViewTemplate(root, path) => self.save_view_template(root, path),
+            Message::LoadViewTemplate(root, path) => self.load_view_template(root, path),
+            Message::ApplyViewTemplate(root, template) => self.apply_view_template(root, template),
+            Message::SetDisassemblyAddress(address) => self.user.disassembly_address = address,
+            #[cfg(feature = "performance_plot")]
+            Message::SetTransactionStatisticsStream(stream) => {
+                self.user.transaction_statistics_stream = stream
+            }
+            Message::ShowDisassemblyAtValue(vidx) => self.show_disassembly_at_value(vidx),
             Message::VerticalScroll(direction, count) => {
                 let waves = self.user.waves.as_mut()?;
                 let current_item = waves.get_top_item();
@@ -809,6 +884,34 @@ impl SystemState {
                     }
                 }
             }
+            Message::ItemValueColorRuleChange(vidx, rule) => {
+                self.save_current_canvas(match &rule {
+                    Some(rule) => format!("Add value color rule {}", rule.condition.description()),
+                    None => "Clear value color rules".to_string(),
+                });
+                self.invalidate_draw_commands();
+                let waves = self.user.waves.as_mut()?;
+
+                let item_refs = match vidx {
+                    MessageTarget::Explicit(vidx) => {
+                        vec![waves.items_tree.get_visible(vidx)?.item_ref]
+                    }
+                    MessageTarget::CurrentSelection => waves
+                        .focused_item
+                        .and_then(|focused| waves.items_tree.get_visible(focused))
+                        .into_iter()
+                        .chain(waves.items_tree.iter_visible_selected())
+                        .map(|node| node.item_ref)
+                        .unique()
+                        .collect(),
+                };
+                for item_ref in item_refs {
+                    waves
+                        .displayed_items
+                        .entry(item_ref)
+                        .and_modify(|item| item.set_value_color_rule(rule.clone()));
+                }
+            }
             Message::ItemNameChange(vidx, name) => {
                 self.save_current_canvas(format!(
                     "Change item name to {}",
@@ -895,6 +998,7 @@ impl SystemState {
                     self.invalidate_draw_commands();
                 }
             }
+            Message::MoveMatchingTransaction { next } => self.move_to_matching_transaction(next),
             Message::MoveTransaction { next } => {
                 let undo_msg = if next {
                     "Move to next transaction"
@@ -903,7 +1007,6 @@ impl SystemState {
                 };
                 self.save_current_canvas(undo_msg.to_string());
                 let waves = self.user.waves.as_mut()?;
-                let inner = waves.inner.as_transactions()?;
                 let mut transactions = waves
                     .items_tree
                     .iter_visible()
@@ -912,12 +1015,18 @@ impl SystemState {
                         match item {
                             DisplayedItem::Stream(s) => {
                                 let stream_ref = &s.transaction_stream_ref;
-                                let stream_id = stream_ref.stream_id;
-                                if let Some(gen_id) = stream_ref.gen_id {
+                                let container = stream_ref.container;
+                                let Some(inner) = waves.inner.transactions(container) else {
+                                    return vec![];
+                                };
+                                let ids = if let Some(gen_id) = stream_ref.gen_id {
                                     inner.get_transactions_from_generator(gen_id)
                                 } else {
-                                    inner.get_transactions_from_stream(stream_id)
-                                }
+                                    inner.get_transactions_from_stream(stream_ref.stream_id)
+                                };
+                                ids.into_iter()
+                                    .map(|id| TransactionRef { container, id })
+                                    .collect_vec()
                             }
                             _ => vec![],
                         }
@@ -929,7 +1038,7 @@ impl SystemState {
                     let next_id = transactions
                         .iter()
                         .enumerate()
-                        .find(|(_, tx)| **tx == focused_tx.id)
+                        .find(|(_, tx)| *tx == focused_tx)
                         .map(|(vec_idx, _)| {
                             if next {
                                 if vec_idx + 1 < transactions.len() {
@@ -944,13 +1053,9 @@ impl SystemState {
                             }
                         })
                         .unwrap_or(if next { transactions.len() - 1 } else { 0 });
-                    Some(TransactionRef {
-                        id: *transactions.get(next_id).unwrap(),
-                    })
+                    transactions.get(next_id).cloned()
                 } else if !transactions.is_empty() {
-                    Some(TransactionRef {
-                        id: *transactions.first().unwrap(),
-                    })
+                    transactions.first().cloned()
                 } else {
                     None
                 };
@@ -980,6 +1085,12 @@ impl SystemState {
             Message::ExpandParameterSection => {
                 self.expand_parameter_section = true;
             }
+            Message::SwitchTab(tab) => {
+                self.switch_tab(tab);
+            }
+            Message::CloseTab(tab) => {
+                self.close_tab(tab);
+            }
             Message::LoadFile(filename, load_options) => {
                 #[cfg(not(target_arch = "wasm32"))]
                 self.load_from_file(filename, load_options).ok();
@@ -1020,6 +1131,13 @@ impl SystemState {
             Message::LoadCommandFromData(bytes) => {
                 self.add_batch_commands(read_command_bytes(bytes));
             }
+            Message::LoadElfFile(path) => self.load_elf_file(path),
+            Message::LoadElfFromData(bytes) => self.load_elf_data("ELF file".to_string(), bytes),
+            Message::ElfLoaded(program) => {
+                info!("Loaded symbols from {}", program.name);
+                self.translators.elf.set(Some(program));
+                self.invalidate_draw_commands();
+            }
             Message::SetupCxxrtl(kind) => self.connect_to_cxxrtl(kind, false),
             Message::SurferServerStatus(_start, server, status) => {
                 self.server_status_to_progress(server, status);
@@ -1029,12 +1147,15 @@ impl SystemState {
                     .map_err(|e| error!("{e:#?}"))
                     .ok();
             }
-            Message::WaveHeaderLoaded(start, source, load_options, header) => {
+            Message::WaveHeaderLoaded(start, tab, source, load_options, header) => {
                 // for files using the `wellen` backend, we load the header before parsing the body
                 info!(
                     "Loaded the hierarchy and meta-data of {source} in {:?}",
                     start.elapsed()
                 );
+                if !self.loads_into_active_tab(tab, &source) {
+                    return None;
+                }
                 match header {
                     HeaderResult::LocalFile(header) => {
                         // register waveform as loaded (but with no variable info yet!)
@@ -1048,7 +1169,13 @@ impl SystemState {
                             load_options,
                         );
                         // start parsing of the body
-                        self.load_wave_body(source, header.body, header.body_len, shared_hierarchy);
+                        self.load_wave_body(
+                            tab,
+                            source,
+                            header.body,
+                            header.body_len,
+                            shared_hierarchy,
+                        );
                     }
                     HeaderResult::LocalBytes(header) => {
                         // register waveform as loaded (but with no variable info yet!)
@@ -1062,7 +1189,13 @@ impl SystemState {
                             load_options,
                         );
                         // start parsing of the body
-                        self.load_wave_body(source, header.body, header.body_len, shared_hierarchy);
+                        self.load_wave_body(
+                            tab,
+                            source,
+                            header.body,
+                            header.body_len,
+                            shared_hierarchy,
+                        );
                     }
                     HeaderResult::Remote(hierarchy, file_format, server) => {
                         // register waveform as loaded (but with no variable info yet!)
@@ -1077,24 +1210,29 @@ impl SystemState {
                             load_options,
                         );
                         // body is already being parsed on the server, we need to request the time table though
-                        Self::get_time_table_from_server(self.channels.msg_sender.clone(), server);
+                        Self::get_time_table_from_server(
+                            self.channels.msg_sender.clone(),
+                            tab,
+                            server,
+                        );
                     }
                 }
             }
-            Message::WaveBodyLoaded(start, source, body) => {
+            Message::WaveBodyLoaded(start, tab, source, body) => {
                 // for files using the `wellen` backend, parse the body in a second step
                 info!("Loaded the body of {source} in {:?}", start.elapsed());
                 self.progress_tracker = None;
-                let waves = self
-                    .user
-                    .waves
-                    .as_mut()
-                    .expect("Waves should be loaded at this point!");
+                // the tab may have been closed, or show another file, by now
+                let Some(waves) = self
+                    .waves_in_tab(tab)
+                    .filter(|waves| waves.source == source && waves.inner.is_waves())
+                else {
+                    warn!("Discarding the body of {source}, its tab no longer shows it");
+                    return None;
+                };
+                let wave_container = waves.inner.as_waves_mut().unwrap();
                 // add source and time table
-                let maybe_cmd = waves // TODO
-                    .inner
-                    .as_waves_mut()
-                    .unwrap()
+                let maybe_cmd = wave_container
                     .wellen_add_body(body)
                     .map_err(|err| {
                         error!("While getting commands to lazy-load signals: {err:?}");
@@ -1102,16 +1240,15 @@ impl SystemState {
                     .ok()
                     .flatten();
                 // Pre-load parameters
-                let param_cmd = waves
-                    .inner
-                    .as_waves_mut()
-                    .unwrap()
+                let param_cmd = wave_container
                     .load_parameters()
                     .map_err(|err| {
                         error!("While getting commands to lazy-load parameters: {err:?}");
                     })
                     .ok()
                     .flatten();
+                // update viewports, now that we have the time table
+                waves.update_viewports();
 
                 if self.wcp_greeted_signal.load(Ordering::Relaxed)
                     && self.wcp_client_capabilities.waveforms_loaded
@@ -1128,52 +1265,57 @@ impl SystemState {
                     });
                 }
 
-                // update viewports, now that we have the time table
-                waves.update_viewports();
                 // make sure we redraw
                 self.invalidate_draw_commands();
                 // start loading parameters
                 if let Some(cmd) = param_cmd {
-                    self.load_variables(cmd);
+                    self.load_variables_in_tab(tab, cmd);
                 }
                 // start loading variables
                 if let Some(cmd) = maybe_cmd {
-                    self.load_variables(cmd);
+                    self.load_variables_in_tab(tab, cmd);
                 }
             }
-            Message::SignalsLoaded(start, res) => {
+            Message::SignalsLoaded(start, tab, res) => {
                 info!("Loaded {} variables in {:?}", res.len(), start.elapsed());
                 self.progress_tracker = None;
-                let waves = self
-                    .user
-                    .waves
-                    .as_mut()
-                    .expect("Waves should be loaded at this point!");
-                match waves.inner.as_waves_mut().unwrap().on_signals_loaded(res) {
+                let Some(wave_container) = self
+                    .waves_in_tab(tab)
+                    .and_then(|waves| waves.inner.as_waves_mut())
+                else {
+                    warn!(
+                        "Discarding {} loaded variables, their tab was closed",
+                        res.len()
+                    );
+                    return None;
+                };
+                match wave_container.on_signals_loaded(res) {
                     Err(err) => error!("{err:?}"),
-                    Ok(Some(cmd)) => self.load_variables(cmd),
+                    Ok(Some(cmd)) => self.load_variables_in_tab(tab, cmd),
                     _ => {}
                 }
                 // make sure we redraw since now more variable data is available
                 self.invalidate_draw_commands();
             }
-            Message::WavesLoaded(filename, format, new_waves, load_options) => {
+            Message::WavesLoaded(tab, filename, format, new_waves, load_options) => {
+                if !self.loads_into_active_tab(tab, &filename) {
+                    return None;
+                }
                 self.on_waves_loaded(filename, format, new_waves, load_options);
                 // here, the body and thus the number of timestamps is already loaded!
-                self.user
-                    .waves
-                    .as_mut()
-                    .expect("Waves should be loaded at this point!")
-                    .update_viewports();
+                if let Some(waves) = self.user.waves.as_mut() {
+                    waves.update_viewports();
+                }
                 self.progress_tracker = None;
             }
-            Message::TransactionStreamsLoaded(filename, format, new_ftr, loaded_options) => {
+            Message::TransactionStreamsLoaded(tab, filename, format, new_ftr, loaded_options) => {
+                if !self.loads_into_active_tab(tab, &filename) {
+                    return None;
+                }
                 self.on_transaction_streams_loaded(filename, format, new_ftr, loaded_options);
-                self.user
-                    .waves
-                    .as_mut()
-                    .expect("Waves should be loaded at this point!")
-                    .update_viewports();
+                if let Some(waves) = self.user.waves.as_mut() {
+                    waves.update_viewports();
+                }
             }
             Message::BlacklistTranslator(idx, translator) => {
                 self.user.blacklisted_translators.insert((idx, translator));
@@ -1257,7 +1399,9 @@ impl SystemState {
                 let config = SurferConfig::new(false)
                     .with_context(|| "Failed to load config file")
                     .ok()?;
-                self.translators = all_translators();
+                let elf = self.translators.elf.get();
+                self.translators = all_translators(&config);
+                self.translators.elf.set(elf);
                 self.user.config = config;
 
                 let ctx = &self.context.as_ref()?;
@@ -1273,7 +1417,11 @@ impl SystemState {
                     WaveSource::File(filename) => {
                         self.load_from_file(filename.clone(), options).ok();
                     }
-                    WaveSource::Data => {}       // can't reload
+                    WaveSource::Data => {} // can't reload
+                    WaveSource::Cxxrtl(kind @ CxxrtlKind::Process { .. }) => {
+                        // Restart the simulation process
+                        self.connect_to_cxxrtl(kind.clone(), true);
+                    }
                     WaveSource::Cxxrtl(..) => {} // can't reload
                     WaveSource::DragAndDrop(filename) => {
                         filename
@@ -1336,17 +1484,85 @@ impl SystemState {
                 load_state,
                 do_not_show_again,
             } => {
+                let restore_session = self
+                    .user
+                    .show_open_sibling_state_file_suggestion
+                    .is_some_and(|dialog| dialog.restore_session);
                 if do_not_show_again {
-                    self.user.autoload_sibling_state_files = Some(AutoLoad::from_bool(load_state));
+                    if restore_session {
+                        self.user.autorestore_sessions = Some(AutoLoad::from_bool(load_state));
+                    } else {
+                        self.user.autoload_sibling_state_files =
+                            Some(AutoLoad::from_bool(load_state));
+                    }
                 }
                 self.user.show_open_sibling_state_file_suggestion = None;
-                if load_state {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.autosave.paused = false;
+                }
+                if load_state && restore_session {
+                    self.update(Message::RestoreSession);
+                } else if load_state {
                     self.update(Message::OpenSiblingStateFile(true));
                 }
             }
             Message::UpdateOpenSiblingStateFileDialog(dialog) => {
                 self.user.show_open_sibling_state_file_suggestion = Some(dialog);
             }
+            Message::RestoreSession => {
+                #[cfg(not(target_arch = "wasm32"))]
+                self.restore_session();
+            }
+            Message::SuggestRestoreSession => {
+                let sibling_state_file = self
+                    .user
+                    .waves
+                    .as_ref()
+                    .and_then(|waves| waves.source.sibling_state_file())
+                    .is_some();
+                match self.autorestore_sessions() {
+                    AutoLoad::Always => {
+                        self.update(Message::RestoreSession);
+                    }
+                    AutoLoad::Never if sibling_state_file => {
+                        self.update(Message::SuggestOpenSiblingStateFile);
+                    }
+                    AutoLoad::Never => {}
+                    AutoLoad::Ask => {
+                        // Keep the saved session until the user has decided
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            self.autosave.paused = true;
+                        }
+                        self.user.show_open_sibling_state_file_suggestion = Some(
+                            OpenSiblingStateFileDialog::restore_session(sibling_state_file),
+                        )
+                    }
+                }
+            }
+            Message::SetTimeAlignment(container, alignment) => {
+                if !alignment.is_valid() {
+                    warn!("Time scale must be positive, got {}", alignment.scale);
+                    return None;
+                }
+                let waves = self.user.waves.as_mut()?;
+                waves.set_alignment(container, alignment);
+                // The matches are sorted by their aligned time
+                self.invalidate_transaction_search();
+                self.invalidate_draw_commands();
+            }
+            Message::OpenTimeAlignmentDialog => {
+                let waves = self.user.waves.as_ref()?;
+                self.user.show_time_alignment_dialog =
+                    Some(TimeAlignmentDialog::new(None, &waves.time_alignment));
+            }
+            Message::UpdateTimeAlignmentDialog(dialog) => {
+                self.user.show_time_alignment_dialog = Some(dialog);
+            }
+            Message::CloseTimeAlignmentDialog => {
+                self.user.show_time_alignment_dialog = None;
+            }
             Message::RemovePlaceholders => {
                 let waves = self.user.waves.as_mut()?;
                 waves.remove_placeholders();
@@ -1460,6 +1676,9 @@ impl SystemState {
             Message::OpenCommandFileDialog => {
                 self.open_command_file_dialog();
             }
+            Message::OpenElfFileDialog => {
+                self.open_elf_file_dialog();
+            }
             #[cfg(feature = "python")]
             Message::OpenPythonPluginDialog => {
                 self.open_python_file_dialog();
@@ -1523,6 +1742,26 @@ impl SystemState {
             Message::SetVariableGroupByDirection(b) => {
                 self.user.variable_filter.group_by_direction = b;
             }
+            Message::SetVariableTypeFilter(types) => {
+                self.user.variable_filter.variable_types = types;
+            }
+            Message::SetVariableWidthFilter(min, max) => {
+                self.user.variable_filter.min_width = min;
+                self.user.variable_filter.max_width = max;
+            }
+            Message::SetVariableTranslatorFilter(translator) => {
+                if let Some(name) = &translator {
+                    if !self.translators.all_translator_names().contains(&name.as_str()) {
+                        warn!("No translator {name}");
+                        return None;
+                    }
+                }
+                self.user.variable_filter.translator = translator;
+            }
+            Message::ClearVariableMetaFilters => self.user.variable_filter.clear_meta_filters(),
+            Message::SetVariableSortType(sort_type) => {
+                self.user.variable_filter.sort_by = sort_type;
+            }
             Message::SetUIZoomFactor(scale) => {
                 if let Some(ctx) = &mut self.context.as_ref() {
                     ctx.set_zoom_factor(scale);
@@ -1556,12 +1795,49 @@ impl SystemState {
             }
             Message::InvalidateDrawCommands => self.invalidate_draw_commands(),
             Message::UnpauseSimulation => {
-                let waves = self.user.waves.as_ref()?;
+                let waves = self.user.waves.as_mut()?;
                 waves.inner.as_waves().unwrap().unpause_simulation();
+                waves.start_following_simulation();
             }
             Message::PauseSimulation => {
-                let waves = self.user.waves.as_ref()?;
+                let waves = self.user.waves.as_mut()?;
                 waves.inner.as_waves().unwrap().pause_simulation();
+                waves.followed_num_timestamps = None;
+            }
+            Message::RunSimulationUntilDiagnostic(kind) => {
+                let waves = self.user.waves.as_mut()?;
+                waves
+                    .inner
+                    .as_waves()
+                    .unwrap()
+                    .run_simulation_until_diagnostic(&[kind]);
+                waves.start_following_simulation();
+            }
+            Message::RunSimulationUntil(time) => {
+                let waves = self.user.waves.as_mut()?;
+                let Some(time) = time.to_biguint() else {
+                    warn!("Cannot run simulation until negative time {time}");
+                    return None;
+                };
+                waves.inner.as_waves()?.run_simulation_until(&time);
+                waves.start_following_simulation();
+            }
+            Message::RunSimulationFor(duration) => {
+                let waves = self.user.waves.as_mut()?;
+                let Some(duration) = duration.to_biguint() else {
+                    warn!("Cannot run simulation for negative duration {duration}");
+                    return None;
+                };
+                waves.inner.as_waves()?.run_simulation_for(&duration);
+                waves.start_following_simulation();
+            }
+            Message::StepSimulationCycles { clock, cycles } => {
+                let waves = self.user.waves.as_mut()?;
+                waves
+                    .inner
+                    .as_waves()?
+                    .step_simulation_cycles(&clock, cycles);
+                waves.start_following_simulation();
             }
             Message::Batch(messages) => {
                 for message in messages {
@@ -1590,9 +1866,17 @@ impl SystemState {
             Message::VariableDragTargetChanged(position) => {
                 self.user.drag_target_idx = Some(position);
             }
+            Message::VariableDragTargetTabChanged(tab) => {
+                self.user.drag_target_tab = tab;
+            }
             Message::VariableDragFinished => {
                 self.user.drag_started = false;
 
+                if let Some(tab) = self.user.drag_target_tab.take() {
+                    self.copy_dragged_items_to_tab(tab);
+                    return Some(());
+                }
+
                 let source_vidx = self.user.drag_source_idx.take()?;
                 let target_position = self.user.drag_target_idx.take()?;
 
@@ -1894,8 +2178,12 @@ impl SystemState {
                 error!("Wcp is not supported on wasm")
             }
             #[cfg(not(target_arch = "wasm32"))]
-            Message::StartWcpServer { address, initiate } => {
-                self.start_wcp_server(address, initiate);
+            Message::StartWcpServer {
+                address,
+                initiate,
+                transport,
+            } => {
+                self.start_wcp_server(address, initiate, transport);
             }
             #[cfg(not(target_arch = "wasm32"))]
             Message::StopWcpServer => {
@@ -1923,9 +2211,35 @@ impl SystemState {
                 let waves = self.user.waves.as_mut()?;
                 if waves.viewports.len() > 1 {
                     waves.viewports.pop();
+                    waves.viewport_cursors.remove(&waves.viewports.len());
                     self.draw_data.borrow_mut().pop();
                 }
             }
+            Message::NavigateBack(count) => self.navigate(true, count),
+            Message::NavigateForward(count) => self.navigate(false, count),
+            Message::AddViewBookmark(name) => self.add_view_bookmark(name),
+            Message::GoToViewBookmark(name) => self.go_to_view_bookmark(&name),
+            Message::RemoveViewBookmark(name) => {
+                let waves = self.user.waves.as_mut()?;
+                waves.view_bookmarks.retain(|bookmark| bookmark.name != name);
+            }
+            Message::SetViewportLock(lock) => {
+                let waves = self.user.waves.as_mut()?;
+                waves.viewport_lock = lock;
+            }
+            Message::SetPerViewportCursors(enabled) => {
+                let waves = self.user.waves.as_mut()?;
+                waves.per_viewport_cursors = enabled;
+                self.invalidate_draw_commands();
+            }
+            Message::ViewportCursorSet { time, viewport_idx } => {
+                let waves = self.user.waves.as_mut()?;
+                if waves.per_viewport_cursors && viewport_idx != 0 {
+                    waves.viewport_cursors.insert(viewport_idx, time);
+                } else {
+                    waves.cursor = Some(time);
+                }
+            }
             Message::SelectTheme(theme_name) => {
                 let theme = SurferTheme::new(theme_name)
                     .with_context(|| "Failed to set theme")
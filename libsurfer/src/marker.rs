@@ -18,14 +18,24 @@ use crate::{
 pub const DEFAULT_MARKER_NAME: &str = "Marker";
 
 impl WaveData {
+    /// The cursor shown in the viewport, see [`WaveData::per_viewport_cursors`]
+    pub fn viewport_cursor(&self, viewport_idx: usize) -> Option<&BigInt> {
+        if self.per_viewport_cursors && viewport_idx != 0 {
+            self.viewport_cursors.get(&viewport_idx)
+        } else {
+            self.cursor.as_ref()
+        }
+    }
+
     pub fn draw_cursor(
         &self,
         theme: &SurferTheme,
         ctx: &mut DrawingContext,
         size: Vec2,
         viewport: &Viewport,
+        viewport_idx: usize,
     ) {
-        if let Some(marker) = &self.cursor {
+        if let Some(marker) = self.viewport_cursor(viewport_idx) {
             let num_timestamps = self.num_timestamps().unwrap_or(1.into());
             let x = viewport.pixel_from_time(marker, size.x, &num_timestamps);
 
@@ -88,9 +98,16 @@ impl WaveData {
         self.markers.len() < 255
     }
 
-    pub fn add_marker(&mut self, location: &BigInt, name: Option<String>, move_focus: bool) {
+    /// Adds a marker at the location and returns its id, or `None` if no more markers
+    /// can be added
+    pub fn add_marker(
+        &mut self,
+        location: &BigInt,
+        name: Option<String>,
+        move_focus: bool,
+    ) -> Option<u8> {
         if !self.can_add_marker() {
-            return;
+            return None;
         }
 
         let idx = (0..=254)
@@ -108,6 +125,7 @@ impl WaveData {
             move_focus,
         );
         self.markers.insert(idx, location.clone());
+        Some(idx)
     }
 
     pub fn remove_marker(&mut self, idx: u8) {
@@ -376,4 +394,49 @@ impl SystemState {
             );
         }
     }
+
+    /// Shows the time from the main cursor to the cursor of the viewport at the top of
+    /// the viewport when per-viewport cursors are used
+    pub fn draw_viewport_cursor_delta(
+        &self,
+        waves: &WaveData,
+        ctx: &mut DrawingContext,
+        size: Vec2,
+        viewport: &Viewport,
+        viewport_idx: usize,
+    ) {
+        if !waves.per_viewport_cursors || viewport_idx == 0 {
+            return;
+        }
+        let (Some(main_cursor), Some(cursor)) =
+            (&waves.cursor, waves.viewport_cursors.get(&viewport_idx))
+        else {
+            return;
+        };
+        let num_timestamps = waves.num_timestamps().unwrap_or(1.into());
+        let x = viewport.pixel_from_time(cursor, size.x, &num_timestamps);
+
+        let delta = time_string(
+            &(cursor - main_cursor),
+            &waves.inner.metadata().timescale,
+            &self.user.wanted_timeunit,
+            &self.get_time_format(),
+        );
+        let background_color = &self.user.config.theme.cursor.color;
+        let text_color = *self.user.config.theme.get_best_text_color(background_color);
+        let galley = ctx.painter.layout_no_wrap(
+            format!("Δ {delta}"),
+            FontId::proportional(ctx.cfg.text_size),
+            text_color,
+        );
+        let min = (ctx.to_screen)(x, 0.);
+        let max = (ctx.to_screen)(x + galley.rect.width() + 4., galley.rect.height() + 2.);
+        ctx.painter.rect_filled(
+            Rect { min, max },
+            CornerRadius::default(),
+            *background_color,
+        );
+        ctx.painter
+            .galley((ctx.to_screen)(x + 2., 1.), galley, text_color);
+    }
 }
@@ -20,7 +20,10 @@ use crate::{
     file_dialog::OpenMode,
     message::Message,
     time::{timeformat_menu, timeunit_menu},
+    transaction_export::TransactionExportFormat,
+    value_color::{ValueColorCondition, ValueColorRule},
     variable_name_type::VariableNameType,
+    viewport::ViewportLock,
     SystemState,
 };
 
@@ -93,11 +96,30 @@ impl SystemState {
         }
 
         let waves_loaded = self.user.waves.is_some();
+        let transactions_loaded = self
+            .user
+            .waves
+            .as_ref()
+            .is_some_and(|waves| waves.inner.is_transactions());
 
         ui.menu_button("File", |ui| {
             b("Open file...", Message::OpenFileDialog(OpenMode::Open)).add_closing_menu(msgs, ui);
             b("Switch file...", Message::OpenFileDialog(OpenMode::Switch))
                 .add_closing_menu(msgs, ui);
+            b(
+                "Add transaction file...",
+                Message::OpenFileDialog(OpenMode::AddTransactions),
+            )
+            .enabled(self.user.waves.as_ref().is_some_and(|w| w.inner.is_waves()))
+            .add_closing_menu(msgs, ui);
+            b(
+                "Open file in new tab...",
+                Message::OpenFileDialog(OpenMode::NewTab),
+            )
+            .add_closing_menu(msgs, ui);
+            b("Close tab", Message::CloseTab(self.user.active_tab))
+                .enabled(!self.user.other_tabs.is_empty())
+                .add_closing_menu(msgs, ui);
             b(
                 "Reload",
                 Message::ReloadWaveform(self.user.config.behavior.keep_during_reload),
@@ -148,6 +170,22 @@ impl SystemState {
                 ),
             )
             .add_closing_menu(msgs, ui);
+            b("Load ELF symbols...", Message::OpenElfFileDialog).add_closing_menu(msgs, ui);
+            ui.add_enabled_ui(transactions_loaded, |ui| {
+                ui.menu_button("Export transactions", |ui| {
+                    for format in enum_iterator::all::<TransactionExportFormat>() {
+                        b(
+                            format!("{}...", format.description()),
+                            Message::ExportTransactions {
+                                format,
+                                streams: None,
+                                path: None,
+                            },
+                        )
+                        .add_closing_menu(msgs, ui);
+                    }
+                });
+            });
 
             #[cfg(feature = "python")]
             {
@@ -200,6 +238,17 @@ impl SystemState {
                 .shortcut("e")
                 .enabled(waves_loaded)
                 .add_closing_menu(msgs, ui);
+            b("Back", Message::NavigateBack(1))
+                .shortcut("Alt+←")
+                .enabled(self.navigation.can_go_back())
+                .add_closing_menu(msgs, ui);
+            b("Forward", Message::NavigateForward(1))
+                .shortcut("Alt+→")
+                .enabled(self.navigation.can_go_forward())
+                .add_closing_menu(msgs, ui);
+            ui.add_enabled_ui(waves_loaded, |ui| {
+                ui.menu_button("Bookmarks", |ui| self.bookmark_menu(ui, msgs));
+            });
             ui.separator();
             b("Add viewport", Message::AddViewport)
                 .enabled(waves_loaded)
@@ -207,8 +256,35 @@ impl SystemState {
             b("Remove viewport", Message::RemoveViewport)
                 .enabled(waves_loaded)
                 .add_closing_menu(msgs, ui);
+            if let Some(waves) = &self.user.waves {
+                ui.menu_button("Lock viewports", |ui| {
+                    for lock in enum_iterator::all::<ViewportLock>() {
+                        ui.radio(waves.viewport_lock == lock, lock.to_string())
+                            .clicked()
+                            .then(|| {
+                                ui.close_menu();
+                                msgs.push(Message::SetViewportLock(lock));
+                            });
+                    }
+                });
+                ui.radio(waves.per_viewport_cursors, "Per-viewport cursors")
+                    .clicked()
+                    .then(|| {
+                        ui.close_menu();
+                        msgs.push(Message::SetPerViewportCursors(!waves.per_viewport_cursors));
+                    });
+            }
+            b("Time alignment...", Message::OpenTimeAlignmentDialog)
+                .enabled(waves_loaded)
+                .add_closing_menu(msgs, ui);
             ui.separator();
 
+            b("Search hierarchy...", Message::SetHierarchySearchVisible(true))
+                .enabled(waves_loaded)
+                .add_closing_menu(msgs, ui);
+            b("Search transactions...", Message::SetTransactionSearchVisible(true))
+                .enabled(transactions_loaded)
+                .add_closing_menu(msgs, ui);
             b("Toggle side panel", Message::ToggleSidePanel)
                 .shortcut("b")
                 .add_closing_menu(msgs, ui);
@@ -527,6 +603,69 @@ impl SystemState {
                 }
             });
 
+            ui.menu_button("Value color", |ui| {
+                for (text, condition) in [
+                    ("X/Z", ValueColorCondition::Undefined),
+                    (
+                        "Non-zero",
+                        ValueColorCondition::ValueNotEquals(num::BigUint::ZERO),
+                    ),
+                ] {
+                    ui.menu_button(text, |ui| {
+                        let selected_color = variable
+                            .color_rules
+                            .iter()
+                            .find(|rule| rule.condition == condition)
+                            .and_then(|rule| rule.color.as_deref());
+                        for color_name in self.user.config.theme.colors.keys() {
+                            ui.radio(selected_color == Some(color_name.as_str()), color_name)
+                                .clicked()
+                                .then(|| {
+                                    ui.close_menu();
+                                    msgs.push(Message::ItemValueColorRuleChange(
+                                        affected_vidxs.into(),
+                                        Some(ValueColorRule {
+                                            condition: condition.clone(),
+                                            color: Some(color_name.clone()),
+                                        }),
+                                    ));
+                                });
+                        }
+                    });
+                }
+                let each_value = variable
+                    .color_rules
+                    .iter()
+                    .any(|rule| rule.condition == ValueColorCondition::EachValue);
+                ui.radio(each_value, "Each value").clicked().then(|| {
+                    ui.close_menu();
+                    msgs.push(Message::ItemValueColorRuleChange(
+                        affected_vidxs.into(),
+                        Some(ValueColorRule {
+                            condition: ValueColorCondition::EachValue,
+                            color: None,
+                        }),
+                    ));
+                });
+                ui.separator();
+                for rule in &variable.color_rules {
+                    ui.label(format!(
+                        "{}: {}",
+                        rule.condition.description(),
+                        rule.color.as_deref().unwrap_or("-")
+                    ));
+                }
+                ui.add_enabled(!variable.color_rules.is_empty(), Button::new("Clear"))
+                    .clicked()
+                    .then(|| {
+                        ui.close_menu();
+                        msgs.push(Message::ItemValueColorRuleChange(
+                            affected_vidxs.into(),
+                            None,
+                        ));
+                    });
+            });
+
             if self.wcp_greeted_signal.load(Ordering::Relaxed) {
                 if self.wcp_client_capabilities.goto_declaration
                     && ui.button("Go to declaration").clicked()
@@ -617,6 +756,45 @@ impl SystemState {
                     ));
                 }
             });
+            if waves.cursor.is_some()
+                && self.translators.elf.get().is_some()
+                && ui.button("Open disassembly at value").clicked()
+            {
+                ui.close_menu();
+                msgs.push(Message::ShowDisassemblyAtValue(MessageTarget::Explicit(vidx)));
+            }
+        }
+        if matches!(displayed_item, DisplayedItem::Stream(_)) {
+            ui.menu_button("Export", |ui| {
+                for format in enum_iterator::all::<TransactionExportFormat>() {
+                    if ui.button(format!("{}...", format.description())).clicked() {
+                        ui.close_menu();
+                        let streams = if affect_selected {
+                            waves
+                                .items_tree
+                                .iter_visible_selected()
+                                .map(|node| node.item_ref)
+                                .collect_vec()
+                        } else {
+                            vec![displayed_item_id]
+                        };
+                        msgs.push(Message::ExportTransactions {
+                            format,
+                            streams: Some(streams),
+                            path: None,
+                        });
+                    }
+                }
+            });
+        }
+        #[cfg(feature = "performance_plot")]
+        if let DisplayedItem::Stream(stream) = displayed_item {
+            if ui.button("Statistics...").clicked() {
+                ui.close_menu();
+                msgs.push(Message::SetTransactionStatisticsStream(Some(
+                    stream.transaction_stream_ref.clone(),
+                )));
+            }
         }
         ui.separator();
         ui.menu_button("Insert", |ui| {
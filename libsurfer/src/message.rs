@@ -7,30 +7,36 @@ use ftr_parser::types::Transaction;
 use num::BigInt;
 use serde::Deserialize;
 use std::path::PathBuf;
+use std::sync::Arc;
+use surfer_translation_types::VariableType;
 use surver::Status;
 
 use crate::async_util::AsyncJob;
-use crate::config::PrimaryMouseDrag;
+use crate::config::{PrimaryMouseDrag, WcpTransport};
 use crate::displayed_item_tree::{ItemIndex, VisibleItemIndex};
 use crate::graphics::{Graphic, GraphicId};
 use crate::state::UserState;
+use crate::tabs::TabId;
 use crate::transaction_container::{
     StreamScopeRef, TransactionContainer, TransactionRef, TransactionStreamRef,
 };
-use crate::translation::DynTranslator;
-use crate::viewport::ViewportStrategy;
+use crate::transaction_export::TransactionExportFormat;
+use crate::translation::{DynTranslator, ElfProgram};
+use crate::view_template::ViewTemplate;
+use crate::viewport::{ViewportLock, ViewportStrategy};
 use crate::wave_data::ScopeType;
 use crate::{
     clock_highlighting::ClockHighlightType,
     config::ArrowKeyBindings,
-    dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog},
+    dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog, TimeAlignmentDialog},
     displayed_item::{DisplayedFieldRef, DisplayedItemRef},
     file_dialog::OpenMode,
     hierarchy::HierarchyStyle,
-    time::{TimeStringFormatting, TimeUnit},
-    variable_filter::VariableIOFilterType,
+    time::{TimeAlignment, TimeStringFormatting, TimeUnit},
+    value_color::ValueColorRule,
+    variable_filter::{VariableIOFilterType, VariableSortType},
     variable_name_type::VariableNameType,
-    wave_container::{ScopeRef, VariableRef, WaveContainer},
+    wave_container::{DiagnosticKind, ScopeRef, VariableRef, WaveContainer},
     wave_source::{CxxrtlKind, LoadOptions, WaveFormat},
     wellen::{BodyResult, HeaderResult, LoadSignalsResult},
     MoveDir, VariableNameFilterType, WaveSource,
@@ -109,6 +115,8 @@ pub enum Message {
     /// Change background color of waves/items. If first argument is None, change for selected items. If second argument is None, change to default value.
     ItemBackgroundColorChange(MessageTarget<VisibleItemIndex>, Option<String>),
     ItemNameChange(Option<VisibleItemIndex>, Option<String>),
+    /// Add a value color rule to variables. If first argument is None, change for selected items. If second argument is None, remove all rules.
+    ItemValueColorRuleChange(MessageTarget<VisibleItemIndex>, Option<ValueColorRule>),
     /// Change scaling factor/height of waves/items. If first argument is None, change for selected items.
     ItemHeightScalingFactorChange(MessageTarget<VisibleItemIndex>, f32),
     /// Change variable name type of waves/items. If first argument is None, change for selected items.
@@ -139,6 +147,9 @@ pub enum Message {
     CursorSet(BigInt),
     #[serde(skip)]
     SurferServerStatus(web_time::Instant, String, Status),
+    /// Switch to the tab with the given index. The index after the last tab opens a new tab.
+    SwitchTab(usize),
+    CloseTab(usize),
     /// Load file from file path.
     LoadFile(Utf8PathBuf, LoadOptions),
     /// Load file from URL.
@@ -158,29 +169,42 @@ pub enum Message {
     LoadCommandFromData(Vec<u8>),
     /// Load command file from URL.
     LoadCommandFileFromUrl(String),
+    /// Load an ELF file whose symbols are used to translate addresses.
+    LoadElfFile(Utf8PathBuf),
+    /// Load an ELF file from data.
+    LoadElfFromData(Vec<u8>),
     SetupCxxrtl(CxxrtlKind),
     #[serde(skip)]
     /// Message sent when waveform file header is loaded.
+    /// The results of loading are tagged with the tab that started the load.
     WaveHeaderLoaded(
         web_time::Instant,
+        TabId,
         WaveSource,
         LoadOptions,
         #[debug(skip)] HeaderResult,
     ),
     #[serde(skip)]
     /// Message sent when waveform file body is loaded.
-    WaveBodyLoaded(web_time::Instant, WaveSource, #[debug(skip)] BodyResult),
+    WaveBodyLoaded(
+        web_time::Instant,
+        TabId,
+        WaveSource,
+        #[debug(skip)] BodyResult,
+    ),
     #[serde(skip)]
     WavesLoaded(
+        TabId,
         WaveSource,
         WaveFormat,
         #[debug(skip)] Box<WaveContainer>,
         LoadOptions,
     ),
     #[serde(skip)]
-    SignalsLoaded(web_time::Instant, #[debug(skip)] LoadSignalsResult),
+    SignalsLoaded(web_time::Instant, TabId, #[debug(skip)] LoadSignalsResult),
     #[serde(skip)]
     TransactionStreamsLoaded(
+        TabId,
         WaveSource,
         WaveFormat,
         #[debug(skip)] TransactionContainer,
@@ -190,6 +214,8 @@ pub enum Message {
     Error(eyre::Error),
     #[serde(skip)]
     TranslatorLoaded(#[debug(skip)] Box<DynTranslator>),
+    #[serde(skip)]
+    ElfLoaded(#[debug(skip)] Arc<ElfProgram>),
     /// Take note that the specified translator errored on a `translates` call on the
     /// specified variable
     BlacklistTranslator(VariableRef, String),
@@ -231,6 +257,20 @@ pub enum Message {
     },
     #[serde(skip)]
     UpdateOpenSiblingStateFileDialog(OpenSiblingStateFileDialog),
+    /// Restore the autosaved session of the loaded waveform
+    RestoreSession,
+    /// When a file with an autosaved session is opened, suggest restoring it
+    #[serde(skip)]
+    SuggestRestoreSession,
+    /// Set the offset and scale applied to the times of the loaded waveform, or of the
+    /// transaction file with the given index.
+    SetTimeAlignment(Option<usize>, TimeAlignment),
+    /// Open a dialog for editing the time alignment of the loaded waveform.
+    OpenTimeAlignmentDialog,
+    #[serde(skip)]
+    UpdateTimeAlignmentDialog(TimeAlignmentDialog),
+    #[serde(skip)]
+    CloseTimeAlignmentDialog,
     RemovePlaceholders,
     ZoomToFit {
         viewport_idx: usize,
@@ -271,6 +311,7 @@ pub enum Message {
     SelectNextCommand,
     OpenFileDialog(OpenMode),
     OpenCommandFileDialog,
+    OpenElfFileDialog,
     #[cfg(feature = "python")]
     OpenPythonPluginDialog,
     #[cfg(feature = "python")]
@@ -298,10 +339,30 @@ pub enum Message {
     SetVariableNameFilterCaseInsensitive(bool),
     SetVariableIOFilter(VariableIOFilterType, bool),
     SetVariableGroupByDirection(bool),
+    /// Only show variables of these types, or all types if empty
+    SetVariableTypeFilter(Vec<VariableType>),
+    /// Only show variables with a bit width in this range
+    SetVariableWidthFilter(Option<u32>, Option<u32>),
+    /// Only show variables which can be shown using this translator
+    SetVariableTranslatorFilter(Option<String>),
+    ClearVariableMetaFilters,
+    SetVariableSortType(VariableSortType),
     SetUIZoomFactor(f32),
     SetPerformanceVisible(bool),
     SetContinuousRedraw(bool),
     SetCursorWindowVisible(bool),
+    SetDiagnosticsWindowVisible(bool),
+    SetHierarchySearchVisible(bool),
+    SetTransactionSearchVisible(bool),
+    /// Show the disassembly of the loaded ELF file around an address, or hide it
+    SetDisassemblyAddress(Option<u64>),
+    /// Show the disassembly of the loaded ELF file around the value of a variable at the
+    /// cursor
+    ShowDisassemblyAtValue(MessageTarget<VisibleItemIndex>),
+    /// Show the durations and throughput of the transactions of a stream or generator, or
+    /// hide them
+    #[cfg(feature = "performance_plot")]
+    SetTransactionStatisticsStream(Option<TransactionStreamRef>),
     SetHierarchyStyle(HierarchyStyle),
     SetArrowKeyBindings(ArrowKeyBindings),
     SetPrimaryMouseDragBehavior(PrimaryMouseDrag),
@@ -336,6 +397,27 @@ pub enum Message {
     MoveTransaction {
         next: bool,
     },
+    /// Focus the next or previous transaction, by start time, matching the transaction query
+    MoveMatchingTransaction {
+        next: bool,
+    },
+    /// Highlight the transactions matching the query, or clear the highlighting
+    SetTransactionQuery(Option<String>),
+    /// Export the transactions of the streams, or of all streams if `None`. Opens a save
+    /// dialog if no path is given.
+    ExportTransactions {
+        format: TransactionExportFormat,
+        streams: Option<Vec<DisplayedItemRef>>,
+        path: Option<PathBuf>,
+    },
+    /// Save a template of the displayed items with the variables relative to the scope. Opens a
+    /// save dialog if no path is given.
+    SaveViewTemplate(ScopeRef, Option<PathBuf>),
+    /// Load a template and apply it to the scope. Opens a file dialog if no path is given.
+    LoadViewTemplate(ScopeRef, Option<PathBuf>),
+    /// Add the items of the template after the displayed items, with the variables looked up
+    /// below the scope
+    ApplyViewTemplate(ScopeRef, ViewTemplate),
     VariableValueToClipbord(MessageTarget<VisibleItemIndex>),
     VariableNameToClipboard(MessageTarget<VisibleItemIndex>),
     VariableFullNameToClipboard(MessageTarget<VisibleItemIndex>),
@@ -347,6 +429,9 @@ pub enum Message {
     VariableDragStarted(VisibleItemIndex),
     VariableDragTargetChanged(crate::displayed_item_tree::TargetPosition),
     VariableDragFinished,
+    /// Set while dragging variables over a tab other than the active one. Finishing the
+    /// drag then copies the variables to that tab.
+    VariableDragTargetTabChanged(Option<usize>),
     AddDraggedVariables(Vec<VariableRef>),
     /// Unpauses the simulation if the wave source supports this kind of interactivity. Otherwise
     /// does nothing
@@ -354,6 +439,18 @@ pub enum Message {
     /// Pause the simulation if the wave source supports this kind of interactivity. Otherwise
     /// does nothing
     PauseSimulation,
+    /// Unpauses the simulation and keeps it running until it emits a diagnostic of the
+    /// specified kind
+    RunSimulationUntilDiagnostic(DiagnosticKind),
+    /// Runs the simulation until the specified absolute time
+    RunSimulationUntil(BigInt),
+    /// Runs the simulation for the specified time past the latest simulated time
+    RunSimulationFor(BigInt),
+    /// Runs the simulation for the specified number of periods of `clock`
+    StepSimulationCycles {
+        clock: VariableRef,
+        cycles: u32,
+    },
     /// Expand the displayed item into subfields. Levels controls how many layers of subfields
     /// are expanded. 0 unexpands it completely
     ExpandDrawnItem {
@@ -369,6 +466,24 @@ pub enum Message {
     Batch(Vec<Message>),
     AddViewport,
     RemoveViewport,
+    /// Go back the given number of steps in the navigation history
+    NavigateBack(usize),
+    /// Go forward the given number of steps in the navigation history
+    NavigateForward(usize),
+    /// Save the current view as a bookmark. A name is generated if none is given.
+    AddViewBookmark(Option<String>),
+    GoToViewBookmark(String),
+    RemoveViewBookmark(String),
+    /// Set how the viewports follow each other when one of them is zoomed or scrolled
+    SetViewportLock(ViewportLock),
+    /// Give each viewport other than the first its own cursor
+    SetPerViewportCursors(bool),
+    /// Set the cursor of the viewport. Sets the main cursor unless per-viewport cursors
+    /// are enabled.
+    ViewportCursorSet {
+        time: BigInt,
+        viewport_idx: usize,
+    },
     /// Select Theme
     SelectTheme(Option<String>),
     /// Undo the last n changes
@@ -397,6 +512,9 @@ pub enum Message {
     StartWcpServer {
         address: Option<String>,
         initiate: bool,
+        /// Transport to use, `None` uses the one from the config
+        #[serde(default)]
+        transport: Option<WcpTransport>,
     },
     StopWcpServer,
     /// Configures the WCP system to listen for messages over internal channels.
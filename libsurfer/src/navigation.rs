@@ -0,0 +1,347 @@
+//! Back/forward navigation between previously shown views, and named view bookmarks.
+//!
+//! The navigation history is kept separately from the undo stack since it only tracks
+//! what is shown, not changes to the displayed items.
+use egui::Ui;
+use log::warn;
+use num::BigInt;
+use serde::{Deserialize, Serialize};
+use web_time::{Duration, Instant};
+
+use crate::message::Message;
+use crate::viewport::Viewport;
+use crate::wave_data::WaveData;
+use crate::SystemState;
+
+/// Navigation messages arriving closer than this to each other are considered to be part
+/// of the same gesture and only result in one history entry.
+const GESTURE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The part of the view that is tracked by the navigation history and bookmarks
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NavigationState {
+    pub viewports: Vec<Viewport>,
+    pub cursor: Option<BigInt>,
+}
+
+impl NavigationState {
+    pub fn new(waves: &WaveData) -> Self {
+        Self {
+            viewports: waves.viewports.clone(),
+            cursor: waves.cursor.clone(),
+        }
+    }
+
+    fn shows_same_as(&self, other: &NavigationState) -> bool {
+        self.cursor == other.cursor
+            && self.viewports.len() == other.viewports.len()
+            && self
+                .viewports
+                .iter()
+                .zip(&other.viewports)
+                .all(|(a, b)| a.destination() == b.destination())
+    }
+
+    fn restore(&self, waves: &mut WaveData) {
+        waves.viewports.clone_from(&self.viewports);
+        waves.cursor.clone_from(&self.cursor);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ViewBookmark {
+    pub name: String,
+    pub view: NavigationState,
+}
+
+/// How a message changes what is shown
+#[derive(PartialEq)]
+pub(crate) enum NavigationKind {
+    /// A single jump, for example to a marker
+    Jump,
+    /// Part of a continuous movement, for example scrolling or dragging the cursor
+    Gesture,
+}
+
+impl Message {
+    pub(crate) fn navigation_kind(&self) -> Option<NavigationKind> {
+        match self {
+            Message::ZoomToFit { .. }
+            | Message::ZoomToRange { .. }
+            | Message::GoToStart { .. }
+            | Message::GoToEnd { .. }
+            | Message::GoToTime(..)
+            | Message::GoToMarkerPosition(..)
+            | Message::GoToCursorIfNotInView
+            | Message::GoToViewBookmark(..) => Some(NavigationKind::Jump),
+            Message::CanvasZoom { .. }
+            | Message::CanvasScroll { .. }
+            | Message::CursorSet(..)
+            | Message::MoveCursorToTransition { .. } => Some(NavigationKind::Gesture),
+            _ => None,
+        }
+    }
+}
+
+/// Previously shown views that can be navigated back and forward between.
+///
+/// Generic over the stored state so the history logic can be tested on its own.
+pub(crate) struct NavigationHistory<T = NavigationState> {
+    back: Vec<T>,
+    forward: Vec<T>,
+    last_gesture: Option<Instant>,
+}
+
+impl<T> Default for NavigationHistory<T> {
+    fn default() -> Self {
+        Self {
+            back: vec![],
+            forward: vec![],
+            last_gesture: None,
+        }
+    }
+}
+
+impl<T> NavigationHistory<T> {
+    /// Adds `before` to the history, keeping at most `limit` entries. Messages continuing
+    /// a gesture started less than [`GESTURE_TIMEOUT`] ago are merged into its entry.
+    pub(crate) fn record(&mut self, before: T, kind: NavigationKind, now: Instant, limit: usize) {
+        let continues_gesture = kind == NavigationKind::Gesture
+            && self
+                .last_gesture
+                .is_some_and(|last| now.duration_since(last) < GESTURE_TIMEOUT);
+        self.last_gesture = (kind == NavigationKind::Gesture).then_some(now);
+        if continues_gesture {
+            return;
+        }
+
+        self.back.push(before);
+        if self.back.len() > limit {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    /// Steps `count` entries back in the history, or forward if `back` is false. `current`
+    /// is what is shown now. Returns the state to show, or `None` if there was nothing
+    /// to step to.
+    pub(crate) fn step(&mut self, back: bool, count: usize, current: T) -> Option<T> {
+        let (from, to) = if back {
+            (&mut self.back, &mut self.forward)
+        } else {
+            (&mut self.forward, &mut self.back)
+        };
+        self.last_gesture = None;
+        let mut shown = current;
+        let mut moved = false;
+        for _ in 0..count {
+            let Some(state) = from.pop() else {
+                break;
+            };
+            to.push(std::mem::replace(&mut shown, state));
+            moved = true;
+        }
+        moved.then_some(shown)
+    }
+
+    pub(crate) fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub(crate) fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl SystemState {
+    /// Adds `before` to the navigation history if the view changed since then
+    pub(crate) fn record_navigation(&mut self, before: NavigationState, kind: NavigationKind) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        if before.shows_same_as(&NavigationState::new(waves)) {
+            return;
+        }
+        self.navigation.record(
+            before,
+            kind,
+            Instant::now(),
+            self.user.config.navigation_history_size,
+        );
+    }
+
+    /// Steps `count` entries back in the navigation history, or forward if `back` is false
+    pub(crate) fn navigate(&mut self, back: bool, count: usize) {
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        let Some(state) = self
+            .navigation
+            .step(back, count, NavigationState::new(waves))
+        else {
+            return;
+        };
+        state.restore(waves);
+        self.draw_data
+            .borrow_mut()
+            .resize_with(waves.viewports.len(), || None);
+        self.invalidate_draw_commands();
+    }
+
+    pub(crate) fn add_view_bookmark(&mut self, name: Option<String>) {
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        let name = name.unwrap_or_else(|| {
+            (1..)
+                .map(|n| format!("Bookmark {n}"))
+                .find(|name| waves.view_bookmarks.iter().all(|b| &b.name != name))
+                .unwrap()
+        });
+        let view = NavigationState::new(waves);
+        if let Some(bookmark) = waves.view_bookmarks.iter_mut().find(|b| b.name == name) {
+            bookmark.view = view;
+        } else {
+            waves.view_bookmarks.push(ViewBookmark { name, view });
+        }
+    }
+
+    pub(crate) fn go_to_view_bookmark(&mut self, name: &str) {
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        let Some(bookmark) = waves.view_bookmarks.iter().find(|b| b.name == name) else {
+            warn!("No bookmark named {name}");
+            return;
+        };
+        bookmark.view.clone().restore(waves);
+        self.draw_data
+            .borrow_mut()
+            .resize_with(waves.viewports.len(), || None);
+        self.invalidate_draw_commands();
+    }
+
+    pub(crate) fn bookmark_menu(&self, ui: &mut Ui, msgs: &mut Vec<Message>) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        if ui.button("Add bookmark").clicked() {
+            msgs.push(Message::AddViewBookmark(None));
+            ui.close_menu();
+        }
+        if !waves.view_bookmarks.is_empty() {
+            ui.separator();
+        }
+        for bookmark in &waves.view_bookmarks {
+            let response = ui.button(&bookmark.name);
+            if response.clicked() {
+                msgs.push(Message::GoToViewBookmark(bookmark.name.clone()));
+                ui.close_menu();
+            }
+            response.context_menu(|ui| {
+                if ui.button("Remove").clicked() {
+                    msgs.push(Message::RemoveViewBookmark(bookmark.name.clone()));
+                    ui.close_menu();
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMIT: usize = 3;
+
+    fn history(entries: &[u32]) -> NavigationHistory<u32> {
+        let mut history = NavigationHistory::default();
+        for &entry in entries {
+            history.record(entry, NavigationKind::Jump, Instant::now(), LIMIT);
+        }
+        history
+    }
+
+    #[test]
+    fn recording_drops_oldest_entries_beyond_limit() {
+        let history = history(&[1, 2, 3, 4]);
+        assert_eq!(history.back, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn recording_clears_forward_history() {
+        let mut history = history(&[1, 2]);
+        assert_eq!(history.step(true, 1, 3), Some(2));
+        assert!(history.can_go_forward());
+        history.record(2, NavigationKind::Jump, Instant::now(), LIMIT);
+        assert!(!history.can_go_forward());
+    }
+
+    #[test]
+    fn gestures_are_coalesced_until_timeout() {
+        let mut history = NavigationHistory::default();
+        let start = Instant::now();
+        history.record(1, NavigationKind::Gesture, start, LIMIT);
+        history.record(
+            2,
+            NavigationKind::Gesture,
+            start + GESTURE_TIMEOUT / 2,
+            LIMIT,
+        );
+        history.record(3, NavigationKind::Gesture, start + GESTURE_TIMEOUT, LIMIT);
+        // The timeout counts from the last message of the gesture
+        assert_eq!(history.back, vec![1]);
+
+        history.record(
+            4,
+            NavigationKind::Gesture,
+            start + GESTURE_TIMEOUT * 3,
+            LIMIT,
+        );
+        assert_eq!(history.back, vec![1, 4]);
+    }
+
+    #[test]
+    fn jumps_are_never_coalesced() {
+        let mut history = NavigationHistory::default();
+        let now = Instant::now();
+        history.record(1, NavigationKind::Gesture, now, LIMIT);
+        history.record(2, NavigationKind::Jump, now, LIMIT);
+        history.record(3, NavigationKind::Gesture, now, LIMIT);
+        assert_eq!(history.back, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn stepping_moves_between_back_and_forward() {
+        let mut history = history(&[1, 2, 3]);
+        assert_eq!(history.step(true, 2, 4), Some(2));
+        assert_eq!((&history.back, &history.forward), (&vec![1], &vec![4, 3]));
+
+        assert_eq!(history.step(false, 1, 2), Some(3));
+        assert_eq!((&history.back, &history.forward), (&vec![1, 2], &vec![4]));
+    }
+
+    #[test]
+    fn stepping_stops_at_end_of_history() {
+        let mut history = history(&[1]);
+        assert_eq!(history.step(true, 5, 2), Some(1));
+        assert_eq!(history.forward, vec![2]);
+        assert_eq!(history.step(true, 1, 1), None);
+        assert_eq!(history.step(false, 0, 1), None);
+    }
+
+    #[test]
+    fn stepping_ends_gesture() {
+        let mut history = NavigationHistory::default();
+        let now = Instant::now();
+        history.record(1, NavigationKind::Gesture, now, LIMIT);
+        history.step(true, 1, 2);
+        history.record(1, NavigationKind::Gesture, now, LIMIT);
+        assert_eq!(history.back, vec![1]);
+        assert!(!history.can_go_forward());
+    }
+}
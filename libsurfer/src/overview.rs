@@ -71,6 +71,7 @@ impl SystemState {
             &mut ctx,
             response.rect.size(),
             &viewport_all,
+            0,
         );
 
         let mut ticks = waves.get_ticks(
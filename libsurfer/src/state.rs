@@ -4,19 +4,23 @@ use std::{
     path::PathBuf,
 };
 
+#[cfg(feature = "performance_plot")]
+use crate::transaction_container::TransactionStreamRef;
 use crate::{
     clock_highlighting::ClockHighlightType,
-    config::{ArrowKeyBindings, AutoLoad, PrimaryMouseDrag, SurferConfig},
+    config::{ArrowKeyBindings, AutoLoad, PrimaryMouseDrag, SurferConfig, WcpTransport},
     data_container::DataContainer,
-    dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog},
+    dialog::{OpenSiblingStateFileDialog, ReloadWaveformDialog, TimeAlignmentDialog},
     displayed_item_tree::{DisplayedItemTree, VisibleItemIndex},
     hierarchy::HierarchyStyle,
     message::Message,
+    state_file_io::StateFileVersion,
     system_state::SystemState,
-    time::{TimeStringFormatting, TimeUnit},
+    tabs::Tab,
+    time::{TimeAlignment, TimeStringFormatting, TimeUnit},
     transaction_container::TransactionContainer,
     variable_filter::VariableFilter,
-    viewport::Viewport,
+    viewport::{Viewport, ViewportLock},
     wave_container::{ScopeRef, VariableRef, WaveContainer},
     wave_data::WaveData,
     wave_source::{LoadOptions, WaveFormat, WaveSource},
@@ -34,6 +38,9 @@ use surfer_translation_types::Translator;
 /// The parts of the program state that need to be serialized when loading/saving state
 #[derive(Serialize, Deserialize)]
 pub struct UserState {
+    /// Version of the state file format, see [`crate::state_file_io::STATE_FILE_VERSION`]
+    #[serde(default)]
+    pub(crate) version: StateFileVersion,
     #[serde(skip)]
     pub config: SurferConfig,
 
@@ -68,6 +75,8 @@ pub struct UserState {
     pub(crate) autoload_sibling_state_files: Option<AutoLoad>,
     #[serde(default)]
     pub(crate) autoreload_files: Option<AutoLoad>,
+    #[serde(default)]
+    pub(crate) autorestore_sessions: Option<AutoLoad>,
 
     pub(crate) waves: Option<WaveData>,
     pub(crate) drag_started: bool,
@@ -76,6 +85,15 @@ pub struct UserState {
 
     pub(crate) previous_waves: Option<WaveData>,
 
+    /// Waveforms open in tabs other than the active one, in tab order. The active tab is
+    /// kept in `waves` and would be at index `active_tab` of this list.
+    #[serde(default)]
+    pub(crate) other_tabs: Vec<Tab>,
+    #[serde(default)]
+    pub(crate) active_tab: usize,
+    #[serde(skip)]
+    pub(crate) drag_target_tab: Option<usize>,
+
     /// Count argument for movements
     pub(crate) count: Option<String>,
 
@@ -90,6 +108,12 @@ pub struct UserState {
     pub(crate) show_performance: bool,
     pub(crate) show_logs: bool,
     pub(crate) show_cursor_window: bool,
+    #[serde(default)]
+    pub(crate) show_diagnostics_window: bool,
+    #[serde(default)]
+    pub(crate) show_hierarchy_search: bool,
+    #[serde(default)]
+    pub(crate) show_transaction_search: bool,
     pub(crate) wanted_timeunit: TimeUnit,
     pub(crate) time_string_format: Option<TimeStringFormatting>,
     pub(crate) show_url_entry: bool,
@@ -99,6 +123,15 @@ pub struct UserState {
     pub(crate) show_reload_suggestion: Option<ReloadWaveformDialog>,
     #[serde(skip, default)]
     pub(crate) show_open_sibling_state_file_suggestion: Option<OpenSiblingStateFileDialog>,
+    #[serde(skip, default)]
+    pub(crate) show_time_alignment_dialog: Option<TimeAlignmentDialog>,
+    /// Address to show the disassembly of the loaded ELF file around
+    #[serde(skip, default)]
+    pub(crate) disassembly_address: Option<u64>,
+    /// Stream or generator to show the durations and throughput of
+    #[cfg(feature = "performance_plot")]
+    #[serde(skip, default)]
+    pub(crate) transaction_statistics_stream: Option<TransactionStreamRef>,
     pub(crate) variable_name_filter_focused: bool,
     pub(crate) variable_filter: VariableFilter,
     pub(crate) rename_target: Option<VisibleItemIndex>,
@@ -154,6 +187,7 @@ impl SystemState {
             self.add_batch_message(Message::StartWcpServer {
                 address: Some(addr),
                 initiate: true,
+                transport: Some(WcpTransport::Tcp),
             });
         }
 
@@ -253,7 +287,17 @@ impl SystemState {
                             total_height: 0.,
                             display_item_ref_counter: 0,
                             old_num_timestamps: None,
+                            followed_num_timestamps: None,
                             graphics: HashMap::new(),
+                            time_alignment: TimeAlignment::default(),
+                            viewport_lock: ViewportLock::default(),
+                            per_viewport_cursors: false,
+                            viewport_cursors: HashMap::new(),
+                            view_bookmarks: vec![],
+                            transaction_query: None,
+                            transaction_sources: vec![],
+                            transaction_alignments: vec![],
+                            pending_transaction_items: vec![],
                         },
                         None,
                     ),
@@ -267,13 +311,22 @@ impl SystemState {
         self.invalidate_draw_commands();
 
         self.user.waves = Some(new_wave);
+        self.invalidate_hierarchy_search();
 
         if !is_reload {
+            // The history refers to viewports of the previous file
+            self.navigation.clear();
+            #[cfg(not(target_arch = "wasm32"))]
+            let has_autosaved_session = self.autosaved_session().is_some();
+            #[cfg(target_arch = "wasm32")]
+            let has_autosaved_session = false;
             if let Some(waves) = &mut self.user.waves {
                 // Set time unit
                 self.user.wanted_timeunit = waves.inner.metadata().timescale.unit;
-                // Possibly open state file load dialog
-                if waves.source.sibling_state_file().is_some() {
+                // Possibly open the dialog restoring the last session or loading a state file
+                if has_autosaved_session {
+                    self.update(Message::SuggestRestoreSession);
+                } else if waves.source.sibling_state_file().is_some() {
                     self.update(Message::SuggestOpenSiblingStateFile);
                 }
             }
@@ -285,15 +338,36 @@ impl SystemState {
         filename: WaveSource,
         format: WaveFormat,
         new_ftr: TransactionContainer,
-        _loaded_options: LoadOptions,
+        load_options: LoadOptions,
     ) {
         info!("Transaction streams are loaded.");
+        self.invalidate_transaction_search();
+        #[cfg(feature = "performance_plot")]
+        self.invalidate_transaction_statistics();
+
+        // Transactions loaded into a session with a waveform are shown next to its variables
+        if let Some(waves) = self
+            .user
+            .waves
+            .as_mut()
+            .filter(|waves| load_options.keep_variables && waves.inner.is_waves())
+        {
+            let old_num_timestamps = waves.num_timestamps();
+            waves.add_transaction_source(filename, new_ftr);
+            waves.old_num_timestamps = old_num_timestamps;
+            waves.update_viewports();
+            self.invalidate_draw_commands();
+            return;
+        }
 
         let viewport = Viewport::new();
         let viewports = [viewport].to_vec();
 
         let new_transaction_streams = WaveData {
             inner: DataContainer::Transactions(new_ftr),
+            transaction_sources: vec![filename.clone()],
+            transaction_alignments: vec![],
+            pending_transaction_items: vec![],
             source: filename,
             format,
             active_scope: None,
@@ -312,7 +386,14 @@ impl SystemState {
             total_height: 0.,
             display_item_ref_counter: 0,
             old_num_timestamps: None,
+            followed_num_timestamps: None,
             graphics: HashMap::new(),
+            time_alignment: TimeAlignment::default(),
+            viewport_lock: ViewportLock::default(),
+            per_viewport_cursors: false,
+            viewport_cursors: HashMap::new(),
+            view_bookmarks: vec![],
+            transaction_query: None,
         };
 
         self.invalidate_draw_commands();
@@ -320,6 +401,8 @@ impl SystemState {
         self.user.config.theme.alt_frequency = 0;
         self.user.wanted_timeunit = new_transaction_streams.inner.metadata().timescale.unit;
         self.user.waves = Some(new_transaction_streams);
+        self.invalidate_hierarchy_search();
+        self.navigation.clear();
     }
 
     pub(crate) fn handle_async_messages(&mut self) {
@@ -390,32 +473,66 @@ impl SystemState {
         // swap back waves for inner, source, format since we want to keep the file
         // fix up all wave references from paths if a wave is loaded
         mem::swap(&mut loaded_state.waves, &mut self.user.waves);
-        let load_commands = if let (Some(waves), Some(new_waves)) =
+        let (load_commands, missing_transactions) = if let (Some(waves), Some(new_waves)) =
             (&mut self.user.waves, &mut loaded_state.waves)
         {
             mem::swap(&mut waves.active_scope, &mut new_waves.active_scope);
-            let items = std::mem::take(&mut new_waves.displayed_items);
+            let mut items = std::mem::take(&mut new_waves.displayed_items);
+            let mut missing_transactions =
+                waves.map_transaction_sources(&new_waves.transaction_sources, &mut items);
+            // Transaction files can only be added next to a waveform
+            if !waves.inner.is_waves() {
+                missing_transactions.clear();
+                waves.pending_transaction_items.clear();
+            }
             let items_tree = std::mem::take(&mut new_waves.items_tree);
             let load_commands = waves.update_with_items(&items, items_tree, &self.translators);
 
             mem::swap(&mut waves.viewports, &mut new_waves.viewports);
             mem::swap(&mut waves.cursor, &mut new_waves.cursor);
             mem::swap(&mut waves.markers, &mut new_waves.markers);
+            mem::swap(&mut waves.time_alignment, &mut new_waves.time_alignment);
+            mem::swap(
+                &mut waves.transaction_alignments,
+                &mut new_waves.transaction_alignments,
+            );
+            waves.reset_invalid_alignments();
+            mem::swap(&mut waves.viewport_cursors, &mut new_waves.viewport_cursors);
+            mem::swap(&mut waves.view_bookmarks, &mut new_waves.view_bookmarks);
+            waves.viewport_lock = new_waves.viewport_lock;
+            waves.per_viewport_cursors = new_waves.per_viewport_cursors;
             mem::swap(&mut waves.focused_item, &mut new_waves.focused_item);
             waves.default_variable_name_type = new_waves.default_variable_name_type;
             waves.scroll_offset = new_waves.scroll_offset;
-            load_commands
+            (load_commands, missing_transactions)
         } else {
-            None
+            (None, vec![])
         };
         if let Some(load_commands) = load_commands {
             self.load_variables(load_commands);
         };
+        for source in missing_transactions {
+            let WaveSource::File(path) = source else {
+                warn!("Cannot restore transactions from {source}, only files are supported");
+                continue;
+            };
+            let load_options = LoadOptions {
+                keep_variables: true,
+                keep_unavailable: true,
+            };
+            if let Err(e) = self.load_transactions_from_file(path, load_options) {
+                error!("{e:#?}");
+            }
+        }
 
         // reset drag to avoid confusion
         self.user.drag_started = false;
         self.user.drag_source_idx = None;
         self.user.drag_target_idx = None;
+        self.user.drag_target_tab = None;
+
+        // the active tab holds the currently loaded file, so it must refer to a valid position
+        self.user.active_tab = self.user.active_tab.min(self.user.other_tabs.len());
 
         // reset previous_waves & count to prevent unintuitive state here
         self.user.previous_waves = None;
@@ -467,7 +584,12 @@ impl SystemState {
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    pub(crate) fn start_wcp_server(&mut self, address: Option<String>, initiate: bool) {
+    pub(crate) fn start_wcp_server(
+        &mut self,
+        address: Option<String>,
+        initiate: bool,
+        transport: Option<WcpTransport>,
+    ) {
         use wcp::wcp_server::WcpServer;
 
         use crate::wcp;
@@ -495,11 +617,13 @@ impl SystemState {
 
         let ctx = self.context.clone();
         let address = address.unwrap_or(self.user.config.wcp.address.clone());
+        let transport = transport.unwrap_or(self.user.config.wcp.transport);
         self.wcp_server_address = Some(address.clone());
         self.wcp_server_thread = Some(tokio::spawn(async move {
             let server = WcpServer::new(
                 address,
                 initiate,
+                transport,
                 wcp_c2s_sender,
                 wcp_s2c_receiver,
                 stop_signal_copy,
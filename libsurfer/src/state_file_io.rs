@@ -1,14 +1,424 @@
+//! Loading and saving of state files.
+//!
+//! State files are [`UserState`] serialized as RON, starting with a `version` field. The top
+//! level fields are read using RON's own parser, keeping the text of their values, so that
+//! fields which cannot be deserialized are left out and reported and a state file with a
+//! single broken setting still loads. The waves of each tab are recovered the same way, down
+//! to the single displayed items, markers and viewports. Files written by older versions are
+//! migrated after they are deserialized.
+use std::collections::HashMap;
+use std::fmt::Formatter;
 use std::path::PathBuf;
 
 use eyre::Context;
+use itertools::Itertools;
+use log::warn;
+use num::BigInt;
+use ron::value::RawValue;
+use serde::de::{DeserializeOwned, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     async_util::{perform_async_work, AsyncJob},
+    displayed_item::{DisplayedItem, DisplayedItemRef},
     file_dialog::{load_state_dialog, save_state_dialog},
     message::Message,
+    state::UserState,
+    viewport::Viewport,
+    wave_data::WaveData,
+    wave_source::WaveFormat,
     SystemState,
 };
 
+/// The version of the state files written by this version of Surfer. Increase it and add a
+/// migration to [`MIGRATIONS`] when a change to [`UserState`] changes the meaning of older
+/// state files. Renamed or added fields are handled by `#[serde(alias)]` and
+/// `#[serde(default)]` instead.
+pub const STATE_FILE_VERSION: u32 = 1;
+
+/// Migrations between state file versions, the migration at index `n` takes a state file of
+/// version `n` to version `n + 1`
+const MIGRATIONS: [fn(&mut UserState); STATE_FILE_VERSION as usize] = [record_transaction_source];
+
+/// Before version 1, a session had at most one transaction file, the loaded file itself, and
+/// streams did not record which file they were from. Now they refer to the file by its index
+/// in `transaction_sources`.
+fn record_transaction_source(state: &mut UserState) {
+    if let Some(waves) = &mut state.waves {
+        if waves.format == WaveFormat::Ftr && waves.transaction_sources.is_empty() {
+            waves.transaction_sources.push(waves.source.clone());
+        }
+    }
+}
+
+/// The version field of [`UserState`]. Always serialized as [`STATE_FILE_VERSION`], since the
+/// version of a loaded file is handled by [`decode_state`] before deserializing it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateFileVersion;
+
+impl Serialize for StateFileVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(STATE_FILE_VERSION)
+    }
+}
+
+impl<'de> Deserialize<'de> for StateFileVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(|_| StateFileVersion)
+    }
+}
+
+/// The top level fields of a state file with the RON text of their values
+#[derive(Debug, PartialEq)]
+struct StateFields {
+    fields: Vec<(String, String)>,
+}
+
+/// The name of a field, which RON reads as an identifier rather than a string
+struct FieldName(String);
+
+impl<'de> Deserialize<'de> for FieldName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldNameVisitor;
+
+        impl Visitor<'_> for FieldNameVisitor {
+            type Value = FieldName;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a field name")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<FieldName, E> {
+                Ok(FieldName(name.to_string()))
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldNameVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for StateFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StateFieldsVisitor;
+
+        impl<'de> Visitor<'de> for StateFieldsVisitor {
+            type Value = StateFields;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a state struct")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<StateFields, A::Error> {
+                let mut fields = vec![];
+                while let Some((FieldName(name), value)) =
+                    map.next_entry::<FieldName, Box<RawValue>>()?
+                {
+                    fields.push((name, value.get_ron().trim().to_string()));
+                }
+                Ok(StateFields { fields })
+            }
+        }
+
+        deserializer.deserialize_struct("UserState", &[], StateFieldsVisitor)
+    }
+}
+
+impl StateFields {
+    fn parse(content: &str) -> eyre::Result<Self> {
+        ron::from_str(content).map_err(|e| eyre::eyre!("{e}"))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of the field `name`, adding it first if there is no such field
+    fn set(&mut self, name: &str, value: String) {
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, old)) => *old = value,
+            None => self.fields.insert(0, (name.to_string(), value)),
+        }
+    }
+
+    fn to_ron(&self) -> String {
+        let mut ron = String::from("(\n");
+        for (name, value) in &self.fields {
+            ron += &format!("    {name}: {value},\n");
+        }
+        ron + ")\n"
+    }
+
+    /// The index of the field on `line` of [`Self::to_ron`], counting from 1
+    fn field_on_line(&self, line: usize) -> Option<usize> {
+        let mut first_line = 2;
+        self.fields.iter().position(|(_, value)| {
+            let last_line = first_line + value.lines().count().max(1) - 1;
+            let found = (first_line..=last_line).contains(&line);
+            first_line = last_line + 1;
+            found
+        })
+    }
+}
+
+/// The entries of a map with the RON text of their keys and values
+struct MapEntries(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for MapEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MapEntriesVisitor;
+
+        impl<'de> Visitor<'de> for MapEntriesVisitor {
+            type Value = MapEntries;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<MapEntries, A::Error> {
+                let mut entries = vec![];
+                while let Some((key, value)) = map.next_entry::<Box<RawValue>, Box<RawValue>>()? {
+                    entries.push((
+                        key.get_ron().trim().to_string(),
+                        value.get_ron().trim().to_string(),
+                    ));
+                }
+                Ok(MapEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(MapEntriesVisitor)
+    }
+}
+
+/// Leaves out the entries of the map `value` which cannot be deserialized as part of a `M`,
+/// adding them to `skipped`. Returns the remaining map if any were left out.
+fn recover_map<M: DeserializeOwned>(
+    value: &str,
+    path: &str,
+    skipped: &mut Vec<(String, String)>,
+) -> Option<String> {
+    let MapEntries(entries) = ron::from_str(value).ok()?;
+    let num_entries = entries.len();
+    let kept = entries
+        .into_iter()
+        .filter(
+            |(key, value)| match ron::from_str::<M>(&format!("{{{key}: {value}}}")) {
+                Ok(_) => true,
+                Err(e) => {
+                    skipped.push((format!("{path}[{key}]"), e.code.to_string()));
+                    false
+                }
+            },
+        )
+        .map(|(key, value)| format!("{key}: {value}"))
+        .collect_vec();
+    (kept.len() < num_entries).then(|| format!("{{{}}}", kept.join(", ")))
+}
+
+/// Leaves out the elements of the sequence `value` which cannot be deserialized as part of a
+/// `S`, adding them to `skipped`. Returns the remaining sequence if any were left out.
+fn recover_seq<S: DeserializeOwned>(
+    value: &str,
+    path: &str,
+    skipped: &mut Vec<(String, String)>,
+) -> Option<String> {
+    let elements: Vec<Box<RawValue>> = ron::from_str(value).ok()?;
+    let num_elements = elements.len();
+    let kept = elements
+        .iter()
+        .enumerate()
+        .filter(
+            |(idx, element)| match ron::from_str::<S>(&format!("[{}]", element.get_ron())) {
+                Ok(_) => true,
+                Err(e) => {
+                    skipped.push((format!("{path}[{idx}]"), e.code.to_string()));
+                    false
+                }
+            },
+        )
+        .map(|(_, element)| element.get_ron().trim())
+        .collect_vec();
+    (kept.len() < num_elements).then(|| format!("[{}]", kept.join(", ")))
+}
+
+/// Recovers the value of the field `name` of [`WaveData`] by leaving out its broken entries
+fn recover_wave_data_field(
+    name: &str,
+    value: &str,
+    path: &str,
+    skipped: &mut Vec<(String, String)>,
+) -> Option<String> {
+    match name {
+        "displayed_items" => {
+            recover_map::<HashMap<DisplayedItemRef, DisplayedItem>>(value, path, skipped)
+        }
+        "markers" => recover_map::<HashMap<u8, BigInt>>(value, path, skipped),
+        "viewports" => recover_seq::<Vec<Viewport>>(value, path, skipped),
+        _ => None,
+    }
+}
+
+/// Recovers the `Option<WaveData>` in `value` by leaving out the fields of the [`WaveData`]
+/// which cannot be deserialized and the broken entries of its collections, adding them to
+/// `skipped`. Returns `None` if that is not enough.
+fn recover_waves(value: &str, path: &str, skipped: &mut Vec<(String, String)>) -> Option<String> {
+    let inner = ron::from_str::<Option<Box<RawValue>>>(value).ok()??;
+    let mut fields = StateFields::parse(inner.get_ron()).ok()?;
+    let mut recovered = vec![];
+    loop {
+        let error = match ron::from_str::<WaveData>(&fields.to_ron()) {
+            Ok(_) => {
+                skipped.extend(recovered);
+                return Some(format!("Some({})", fields.to_ron()));
+            }
+            Err(error) => error,
+        };
+        let idx = fields.field_on_line(error.span.start.line)?;
+        let (name, value) = &fields.fields[idx];
+        let field_path = format!("{path}.{name}");
+        match recover_wave_data_field(name, value, &field_path, &mut recovered) {
+            Some(value) => fields.fields[idx].1 = value,
+            None => {
+                fields.fields.remove(idx);
+                recovered.push((field_path, error.code.to_string()));
+            }
+        }
+    }
+}
+
+/// Recovers the top level field `name` by leaving out the broken parts of the waves in it
+fn recover_field(name: &str, value: &str, skipped: &mut Vec<(String, String)>) -> Option<String> {
+    match name {
+        "waves" => recover_waves(value, name, skipped),
+        "other_tabs" => {
+            let tabs: Vec<Box<RawValue>> = ron::from_str(value).ok()?;
+            let tabs = tabs
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, tab)| {
+                    let path = format!("{name}[{idx}]");
+                    let tab = tab.get_ron().trim();
+                    let Err(error) = ron::from_str::<Option<WaveData>>(tab) else {
+                        return Some(tab.to_string());
+                    };
+                    let recovered = recover_waves(tab, &path, skipped);
+                    if recovered.is_none() {
+                        skipped.push((path, error.code.to_string()));
+                    }
+                    recovered
+                })
+                .collect_vec();
+            Some(format!("[{}]", tabs.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+/// A state file decoded by [`decode_state`]
+pub struct DecodedState {
+    pub state: UserState,
+    /// The version the file was written with
+    pub version: u32,
+    /// Fields and entries which could not be loaded and were left out, with the reason.
+    /// Nested fields and entries are named like `waves.markers[2]`.
+    pub skipped_fields: Vec<(String, String)>,
+}
+
+impl DecodedState {
+    /// Warns about the fields which were left out when loading `file`, returning true if
+    /// there were any
+    pub fn report(&self, file: &str) -> bool {
+        for (field, reason) in &self.skipped_fields {
+            warn!("Ignored '{field}' in {file}: {reason}");
+        }
+        if !self.skipped_fields.is_empty() {
+            warn!(
+                "{file} was only partially loaded, {} setting(s) or item(s) were left out",
+                self.skipped_fields.len()
+            );
+        }
+        !self.skipped_fields.is_empty()
+    }
+}
+
+/// Decodes a state file of any version up to [`STATE_FILE_VERSION`], migrating it if it is
+/// older. Fields which cannot be deserialized are skipped if they are optional, see
+/// [`DecodedState::skipped_fields`].
+pub fn decode_state(bytes: &[u8]) -> eyre::Result<DecodedState> {
+    let content = std::str::from_utf8(bytes).context("The state file is not valid UTF-8")?;
+    let mut fields = StateFields::parse(content).context("Not a Surfer state file")?;
+    let version = match fields.get("version") {
+        Some(version) => version
+            .parse::<u32>()
+            .with_context(|| format!("Invalid state file version '{version}'"))?,
+        None => 0,
+    };
+    if version > STATE_FILE_VERSION {
+        warn!(
+            "The state file is from a newer version of Surfer (version {version}, \
+            this version supports up to {STATE_FILE_VERSION}), some settings may be lost"
+        );
+    }
+    fields.set("version", STATE_FILE_VERSION.to_string());
+
+    let mut skipped_fields = vec![];
+    loop {
+        let error = match ron::from_str::<UserState>(&fields.to_ron()) {
+            Ok(mut state) => {
+                for migration in MIGRATIONS.iter().skip(version as usize) {
+                    migration(&mut state);
+                }
+                let tabs = state.other_tabs.iter_mut().map(|tab| &mut tab.waves);
+                for waves in std::iter::once(&mut state.waves).chain(tabs).flatten() {
+                    waves.repair_partial_state();
+                }
+                return Ok(DecodedState {
+                    state,
+                    version,
+                    skipped_fields,
+                });
+            }
+            Err(error) => error,
+        };
+        match fields.field_on_line(error.span.start.line) {
+            Some(idx) if fields.fields[idx].0 != "version" => {
+                let (name, value) = &fields.fields[idx];
+                let mut recovered = vec![];
+                match recover_field(name, value, &mut recovered) {
+                    Some(recovered_value) if &recovered_value != value => {
+                        fields.fields[idx].1 = recovered_value;
+                        skipped_fields.extend(recovered);
+                    }
+                    _ => {
+                        let (name, _) = fields.fields.remove(idx);
+                        skipped_fields.push((name, error.code.to_string()));
+                    }
+                }
+            }
+            _ => {
+                // A required field was left out, report why instead of it being missing
+                let error = match skipped_fields.pop() {
+                    Some((name, reason)) => eyre::eyre!("Failed to load '{name}': {reason}"),
+                    None => eyre::eyre!("{error}"),
+                };
+                return Err(error).with_context(|| {
+                    format!(
+                        "Failed to load state file of version {version}{}",
+                        if version > STATE_FILE_VERSION {
+                            ", which is newer than this version of Surfer supports"
+                        } else {
+                            ""
+                        }
+                    )
+                });
+            }
+        }
+    }
+}
+
 impl SystemState {
     pub fn load_state_file(&mut self, path: Option<PathBuf>) {
         let sender = self.channels.msg_sender.clone();
@@ -30,16 +440,21 @@ impl SystemState {
                 return;
             };
             let bytes = source.read().await;
-            let new_state = match ron::de::from_bytes(&bytes)
+            let decoded = match decode_state(&bytes)
                 .context(format!("Failed loading {}", source.file_name()))
             {
-                Ok(s) => s,
+                Ok(decoded) => decoded,
                 Err(e) => {
-                    log::error!("Failed to load state: {e:#?}");
+                    sender.send(Message::Error(e)).unwrap();
                     return;
                 }
             };
-            sender.send(Message::LoadState(new_state, path)).unwrap();
+            if decoded.report(&source.file_name()) {
+                sender.send(Message::SetLogsVisible(true)).unwrap();
+            }
+            sender
+                .send(Message::LoadState(Box::new(decoded.state), path))
+                .unwrap();
         });
     }
 
@@ -81,7 +496,7 @@ impl SystemState {
         });
     }
 
-    fn encode_state(&self) -> Option<String> {
+    pub(crate) fn encode_state(&self) -> Option<String> {
         let opt = ron::Options::default();
 
         opt.to_string_pretty(&self.user, ron::ser::PrettyConfig::default())
@@ -90,3 +505,244 @@ impl SystemState {
             .ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::transaction_container::TransactionContainer;
+    use crate::wave_container::WaveContainer;
+    use crate::wave_source::{LoadOptions, WaveSource};
+    use project_root::get_project_root;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    fn default_state_file() -> String {
+        SystemState::new_default_config()
+            .unwrap()
+            .encode_state()
+            .unwrap()
+    }
+
+    #[test]
+    fn fields_are_split_without_parsing_values() {
+        let content = r##"UserState( // comment with (
+    a: Some((x: "str, with ) and \" quote", y: ['(', '\'', ')'])),
+    /* nested /* block ( */ comment */
+    b: r#"raw ")" string"#,
+    c: [1, 2,
+        3],
+)"##;
+        let fields = StateFields::parse(content).unwrap();
+        assert_eq!(
+            fields.fields,
+            vec![
+                (
+                    "a".to_string(),
+                    r#"Some((x: "str, with ) and \" quote", y: ['(', '\'', ')']))"#.to_string()
+                ),
+                ("b".to_string(), r##"r#"raw ")" string"#"##.to_string()),
+                ("c".to_string(), "[1, 2,\n        3]".to_string()),
+            ]
+        );
+        assert_eq!(StateFields::parse(&fields.to_ron()).unwrap(), fields);
+        assert_eq!(fields.field_on_line(1), None);
+        assert_eq!(fields.field_on_line(3), Some(1));
+        assert_eq!(fields.field_on_line(5), Some(2));
+        assert_eq!(fields.field_on_line(6), None);
+
+        assert!(StateFields::parse("(a: 1").is_err());
+        assert!(StateFields::parse("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn state_files_are_versioned() {
+        let content = default_state_file();
+        let fields = StateFields::parse(&content).unwrap();
+        assert_eq!(fields.fields[0], ("version".to_string(), "1".to_string()));
+
+        let decoded = decode_state(content.as_bytes()).unwrap();
+        assert_eq!(decoded.version, STATE_FILE_VERSION);
+        assert!(decoded.skipped_fields.is_empty());
+    }
+
+    #[test]
+    fn unversioned_state_files_are_migrated() {
+        let mut fields = StateFields::parse(&default_state_file()).unwrap();
+        fields.fields.retain(|(name, _)| name != "version");
+
+        let decoded = decode_state(fields.to_ron().as_bytes()).unwrap();
+        assert_eq!(decoded.version, 0);
+        assert!(decoded.skipped_fields.is_empty());
+    }
+
+    #[test]
+    fn unversioned_transaction_sessions_record_their_file() {
+        let mut state = SystemState::new_default_config().unwrap();
+        let path = get_project_root().unwrap().join("examples/my_db.ftr");
+        state.on_transaction_streams_loaded(
+            WaveSource::File(path.clone().try_into().unwrap()),
+            WaveFormat::Ftr,
+            TransactionContainer {
+                inner: ftr_parser::parse::parse_ftr(path).unwrap(),
+            },
+            LoadOptions {
+                keep_variables: false,
+                keep_unavailable: false,
+            },
+        );
+        let mut decoded = decode_state(state.encode_state().unwrap().as_bytes()).unwrap();
+        let waves = decoded.state.waves.as_mut().unwrap();
+        let sources = std::mem::take(&mut waves.transaction_sources);
+        assert_eq!(sources, vec![waves.source.clone()]);
+
+        record_transaction_source(&mut decoded.state);
+        assert_eq!(decoded.state.waves.unwrap().transaction_sources, sources);
+    }
+
+    #[test]
+    fn broken_optional_fields_are_skipped() {
+        let mut fields = StateFields::parse(&default_state_file()).unwrap();
+        fields.set("ui_zoom_factor", "Some(\"large\")".to_string());
+        fields.set("sidepanel_width", "Some(200.0)".to_string());
+
+        let decoded = decode_state(fields.to_ron().as_bytes()).unwrap();
+        assert_eq!(
+            decoded
+                .skipped_fields
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ui_zoom_factor"]
+        );
+        assert_eq!(decoded.state.ui_zoom_factor, None);
+        assert_eq!(decoded.state.sidepanel_width, Some(200.0));
+    }
+
+    #[test]
+    fn broken_required_fields_are_reported() {
+        let mut fields = StateFields::parse(&default_state_file()).unwrap();
+        fields.set("show_about", "\"yes\"".to_string());
+
+        let error = decode_state(fields.to_ron().as_bytes()).unwrap_err();
+        assert!(format!("{error:#}").contains("Failed to load 'show_about'"));
+    }
+
+    #[test]
+    fn newer_state_files_are_loaded_if_possible() {
+        let mut fields = StateFields::parse(&default_state_file()).unwrap();
+        fields.set("version", (STATE_FILE_VERSION + 1).to_string());
+        let decoded = decode_state(fields.to_ron().as_bytes()).unwrap();
+        assert_eq!(decoded.version, STATE_FILE_VERSION + 1);
+
+        fields.set("show_about", "\"yes\"".to_string());
+        let error = decode_state(fields.to_ron().as_bytes()).unwrap_err();
+        assert!(format!("{error:#}").contains("newer than this version of Surfer supports"));
+    }
+
+    /// A state file of `examples/counter.vcd` with two dividers, two markers and two
+    /// viewports
+    fn state_file_with_items() -> StateFields {
+        let mut state = SystemState::new_default_config().unwrap();
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        let header = wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap();
+        state.on_waves_loaded(
+            WaveSource::File(path.try_into().unwrap()),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(header.hierarchy))),
+            LoadOptions::clean(),
+        );
+        let waves = state.user.waves.as_mut().unwrap();
+        waves.add_divider(Some("first".to_string()), None);
+        waves.add_divider(Some("second".to_string()), None);
+        waves.add_marker(&BigInt::from(10), None, false);
+        waves.add_marker(&BigInt::from(20), None, false);
+        waves.viewports.push(Viewport::new());
+        StateFields::parse(&state.encode_state().unwrap()).unwrap()
+    }
+
+    /// Replaces the value of the field `name` of the waves in `fields` by `edit` of it
+    fn edit_waves_field(fields: &mut StateFields, name: &str, edit: impl FnOnce(&str) -> String) {
+        let waves = ron::from_str::<Option<Box<RawValue>>>(fields.get("waves").unwrap())
+            .unwrap()
+            .unwrap();
+        let mut wave_fields = StateFields::parse(waves.get_ron()).unwrap();
+        let value = edit(wave_fields.get(name).unwrap());
+        wave_fields.set(name, value);
+        fields.set("waves", format!("Some({})", wave_fields.to_ron()));
+    }
+
+    #[test]
+    fn broken_items_markers_and_viewports_are_skipped() {
+        let mut fields = state_file_with_items();
+        edit_waves_field(&mut fields, "displayed_items", |items| {
+            items.replace("Some(\"second\")", "Some(2)")
+        });
+        edit_waves_field(&mut fields, "markers", |markers| {
+            let MapEntries(entries) = ron::from_str(markers).unwrap();
+            let entries = entries
+                .into_iter()
+                .map(|(idx, position)| match idx.as_str() {
+                    "1" => format!("{idx}: \"twenty\""),
+                    _ => format!("{idx}: {position}"),
+                })
+                .join(", ");
+            format!("{{{entries}}}")
+        });
+        edit_waves_field(&mut fields, "viewports", |viewports| {
+            let viewports: Vec<Box<RawValue>> = ron::from_str(viewports).unwrap();
+            format!("[{}, \"wide\"]", viewports[0].get_ron())
+        });
+
+        let decoded = decode_state(fields.to_ron().as_bytes()).unwrap();
+        let skipped = decoded
+            .skipped_fields
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect_vec();
+        assert_eq!(skipped.len(), 3, "{skipped:?}");
+        assert!(skipped[0].starts_with("waves.displayed_items["));
+        assert_eq!(skipped[1..], ["waves.viewports[1]", "waves.markers[1]"]);
+
+        let waves = decoded.state.waves.unwrap();
+        let names = waves
+            .items_tree
+            .iter()
+            .map(|node| waves.displayed_items[&node.item_ref].name())
+            .collect_vec();
+        // The marker without a position is left out as well
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0], "first");
+        assert_eq!(waves.displayed_items.len(), 2);
+        assert_eq!(waves.markers, HashMap::from([(0, BigInt::from(10))]));
+        assert_eq!(waves.viewports.len(), 1);
+    }
+
+    #[test]
+    fn broken_tabs_are_recovered() {
+        let mut fields = state_file_with_items();
+        edit_waves_field(&mut fields, "viewports", |_| "[\"wide\"]".to_string());
+        let waves = fields.get("waves").unwrap().to_string();
+        fields.set("other_tabs", format!("[{waves}, None]"));
+
+        let decoded = decode_state(fields.to_ron().as_bytes()).unwrap();
+        assert_eq!(
+            decoded
+                .skipped_fields
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect_vec(),
+            ["waves.viewports[0]", "other_tabs[0].viewports[0]"]
+        );
+        // A viewport is added if none could be loaded
+        assert_eq!(decoded.state.waves.unwrap().viewports.len(), 1);
+        let tabs = decoded.state.other_tabs;
+        assert_eq!(tabs.len(), 2);
+        assert_eq!(tabs[0].waves.as_ref().unwrap().viewports.len(), 1);
+        assert!(tabs[1].waves.is_none());
+    }
+}
@@ -182,4 +182,11 @@ impl SystemState {
             .autoload_sibling_state_files
             .unwrap_or_else(|| self.user.config.autoload_sibling_state_files())
     }
+
+    #[inline]
+    pub fn autorestore_sessions(&self) -> AutoLoad {
+        self.user
+            .autorestore_sessions
+            .unwrap_or_else(|| self.user.config.autorestore_sessions())
+    }
 }
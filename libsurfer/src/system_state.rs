@@ -13,18 +13,28 @@ use crate::{
     command_prompt,
     config::SurferConfig,
     displayed_item::DisplayedItemRef,
+    hierarchy_search::HierarchySearch,
     message::Message,
+    navigation::NavigationHistory,
     state::UserState,
+    state_file_io::StateFileVersion,
+    tabs::TabId,
     time::TimeUnit,
+    transaction_search::TransactionSearchResults,
     translation::{all_translators, TranslatorList},
     variable_filter::VariableFilter,
-    wave_container::VariableRef,
+    wave_container::{DiagnosticKind, VariableRef},
     wave_source::LoadProgress,
+    wcp::wcp_handler::WcpObservedState,
     CachedDrawData, CanvasState, Channels, WcpClientCapabilities,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::autosave::{default_autosave_dir, AutosaveState};
 #[cfg(feature = "performance_plot")]
 use crate::benchmark::Timing;
+#[cfg(feature = "performance_plot")]
+use crate::transaction_statistics::TransactionStatistics;
 
 pub struct SystemState {
     pub user: UserState,
@@ -58,6 +68,8 @@ pub struct SystemState {
     pub(crate) wcp_running_signal: Arc<AtomicBool>,
     pub(crate) wcp_greeted_signal: Arc<AtomicBool>,
     pub(crate) wcp_client_capabilities: WcpClientCapabilities,
+    /// The state last reported to the WCP client through events
+    pub(crate) wcp_observed_state: WcpObservedState,
 
     /// The draw commands for every variable currently selected
     // For performance reasons, these need caching so we have them in a RefCell for interior
@@ -75,6 +87,17 @@ pub struct SystemState {
     pub(crate) command_prompt_text: RefCell<String>,
     pub(crate) last_canvas_rect: RefCell<Option<Rect>>,
     pub(crate) item_renaming_string: RefCell<String>,
+    pub(crate) diagnostics_filter: RefCell<String>,
+    pub(crate) hidden_diagnostic_kinds: RefCell<HashSet<DiagnosticKind>>,
+    pub(crate) hierarchy_search_filter: RefCell<VariableFilter>,
+    pub(crate) hierarchy_search: RefCell<HierarchySearch>,
+    pub(crate) transaction_search_text: RefCell<String>,
+    pub(crate) transaction_search_results: RefCell<Option<TransactionSearchResults>>,
+    #[cfg(feature = "performance_plot")]
+    pub(crate) transaction_statistics: RefCell<Option<TransactionStatistics>>,
+    pub(crate) simulation_run_time: RefCell<u64>,
+    pub(crate) simulation_step_cycles: RefCell<u32>,
+    pub(crate) simulation_clock: RefCell<Option<VariableRef>>,
 
     /// These items should be expanded into subfields in the next frame. Cleared after each
     /// frame
@@ -95,8 +118,17 @@ pub struct SystemState {
     pub(crate) undo_stack: Vec<CanvasState>,
     pub(crate) redo_stack: Vec<CanvasState>,
 
+    // Navigation history, see the navigation module
+    pub(crate) navigation: NavigationHistory,
+
+    /// Identifies the active tab, see the tabs module
+    pub(crate) active_tab_id: TabId,
+
     pub(crate) url_callback: Option<Box<dyn Fn(String) -> Message + Send + 'static>>,
 
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) autosave: AutosaveState,
+
     // Only used for testing
     pub(crate) expand_parameter_section: bool,
 }
@@ -117,13 +149,17 @@ impl SystemState {
         let channels = Channels::new();
 
         // Basic translators that we can load quickly
-        let translators = all_translators();
+        let translators = all_translators(&config);
 
         let result = SystemState {
             user: UserState {
+                version: StateFileVersion,
                 config,
                 waves: None,
                 previous_waves: None,
+                other_tabs: vec![],
+                active_tab: 0,
+                drag_target_tab: None,
                 count: None,
                 blacklisted_translators: HashSet::new(),
                 show_about: false,
@@ -133,6 +169,9 @@ impl SystemState {
                 show_license: false,
                 show_logs: false,
                 show_cursor_window: false,
+                show_diagnostics_window: false,
+                show_hierarchy_search: false,
+                show_transaction_search: false,
                 wanted_timeunit: TimeUnit::None,
                 time_string_format: None,
                 show_url_entry: false,
@@ -154,6 +193,10 @@ impl SystemState {
                 show_statusbar: None,
                 show_variable_direction: None,
                 show_open_sibling_state_file_suggestion: None,
+                show_time_alignment_dialog: None,
+                disassembly_address: None,
+                #[cfg(feature = "performance_plot")]
+                transaction_statistics_stream: None,
                 align_names_right: None,
                 show_variable_indices: None,
                 show_empty_scopes: None,
@@ -170,6 +213,7 @@ impl SystemState {
                 hierarchy_style: None,
                 autoload_sibling_state_files: None,
                 autoreload_files: None,
+                autorestore_sessions: None,
             },
             translators,
             channels,
@@ -189,6 +233,7 @@ impl SystemState {
             wcp_running_signal: Arc::new(AtomicBool::new(false)),
             wcp_greeted_signal: Arc::new(AtomicBool::new(false)),
             wcp_client_capabilities: WcpClientCapabilities::new(),
+            wcp_observed_state: WcpObservedState::default(),
             gesture_start_location: None,
             measure_start_location: None,
             batch_messages: VecDeque::new(),
@@ -199,6 +244,17 @@ impl SystemState {
             variable_name_info_cache: RefCell::new(HashMap::new()),
             last_canvas_rect: RefCell::new(None),
             item_renaming_string: RefCell::new(String::new()),
+            diagnostics_filter: RefCell::new(String::new()),
+            hidden_diagnostic_kinds: RefCell::new(HashSet::new()),
+            hierarchy_search_filter: RefCell::new(VariableFilter::new()),
+            hierarchy_search: RefCell::new(HierarchySearch::default()),
+            transaction_search_text: RefCell::new(String::new()),
+            transaction_search_results: RefCell::new(None),
+            #[cfg(feature = "performance_plot")]
+            transaction_statistics: RefCell::new(None),
+            simulation_run_time: RefCell::new(100_000_000),
+            simulation_step_cycles: RefCell::new(1),
+            simulation_clock: RefCell::new(None),
 
             items_to_expand: RefCell::new(vec![]),
             char_to_add_to_prompt: RefCell::new(None),
@@ -212,6 +268,15 @@ impl SystemState {
             timing: RefCell::new(Timing::new()),
             undo_stack: vec![],
             redo_stack: vec![],
+            navigation: NavigationHistory::default(),
+            active_tab_id: TabId::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            // Tests save sessions in a directory of their own, if at all
+            autosave: AutosaveState::new(if force_default_config {
+                None
+            } else {
+                default_autosave_dir()
+            }),
         };
 
         Ok(result)
@@ -0,0 +1,564 @@
+//! Multiple simultaneously open waveforms, shown as tabs.
+//!
+//! The waveform of the active tab is kept in [`UserState::waves`](crate::state::UserState)
+//! so that the rest of the code does not have to care about tabs. The other tabs are kept
+//! in `other_tabs`, which holds all tabs except the active one in order.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use egui::{Context, Frame, Margin, RichText, Sense, Stroke, TopBottomPanel, Ui};
+use egui_remixicon::icons;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::data_container::DataContainer;
+use crate::displayed_item::DisplayedItem;
+use crate::message::Message;
+use crate::navigation::NavigationHistory;
+use crate::wave_data::WaveData;
+use crate::wave_source::{LoadOptions, WaveSource};
+use crate::{CanvasState, SystemState};
+
+/// Identifies an open tab. Unlike the index of the tab, it does not change when other tabs
+/// are opened or closed, so the results of loading can be routed to the tab that started it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TabId(usize);
+
+impl Default for TabId {
+    /// Returns a new id, different from all previous ones
+    fn default() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        TabId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A tab which is not the active one.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct Tab {
+    pub(crate) waves: Option<WaveData>,
+    /// Tabs restored from a state file get new ids
+    #[serde(skip)]
+    pub(crate) id: TabId,
+    /// The history is only kept while Surfer is running
+    #[serde(skip)]
+    pub(crate) history: TabHistory,
+}
+
+/// The undo and navigation history of a tab. The history of the active tab is kept in
+/// [`SystemState`].
+#[derive(Default)]
+pub(crate) struct TabHistory {
+    undo_stack: Vec<CanvasState>,
+    redo_stack: Vec<CanvasState>,
+    navigation: NavigationHistory,
+}
+
+fn tab_title(waves: Option<&WaveData>) -> String {
+    match waves.map(|w| &w.source) {
+        Some(WaveSource::File(path)) | Some(WaveSource::DragAndDrop(Some(path))) => path
+            .file_name()
+            .map_or_else(|| path.to_string(), str::to_string),
+        Some(source) => source.to_string(),
+        None => "New tab".to_string(),
+    }
+}
+
+/// Makes `tab` active, where `others` holds all tabs except the active one at
+/// `active_tab`, whose content is `current`. Returns the content of the new active tab,
+/// which is created by `new` if `tab` is the index after the last tab.
+fn switch_tab_in<T>(
+    others: &mut Vec<T>,
+    active_tab: &mut usize,
+    current: T,
+    tab: usize,
+    new: impl FnOnce() -> T,
+) -> T {
+    others.insert(*active_tab, current);
+    *active_tab = tab;
+    if tab == others.len() {
+        new()
+    } else {
+        others.remove(tab)
+    }
+}
+
+/// What happened to the active tab when closing a tab
+#[derive(Debug, PartialEq)]
+enum ClosedTab<T> {
+    /// An inactive tab was closed, the active tab stays active
+    Inactive,
+    /// The active tab was closed and the contained tab, if any, becomes active
+    Active(Option<T>),
+}
+
+/// Closes `tab`, where `others` holds all tabs except the active one at `active_tab`. If
+/// the active tab is closed, the tab after it, or the last tab, becomes active.
+fn close_tab_in<T>(others: &mut Vec<T>, active_tab: &mut usize, tab: usize) -> ClosedTab<T> {
+    if tab != *active_tab {
+        let idx = if tab < *active_tab {
+            *active_tab -= 1;
+            tab
+        } else {
+            tab - 1
+        };
+        others.remove(idx);
+        return ClosedTab::Inactive;
+    }
+    if others.is_empty() {
+        return ClosedTab::Active(None);
+    }
+    let new_active = tab.min(others.len() - 1);
+    *active_tab = new_active;
+    ClosedTab::Active(Some(others.remove(new_active)))
+}
+
+impl SystemState {
+    /// The number of open tabs, including the active one.
+    pub fn num_tabs(&self) -> usize {
+        self.user.other_tabs.len() + 1
+    }
+
+    /// Makes `tab` the active tab. Switching to the index after the last tab opens a new,
+    /// empty tab.
+    pub(crate) fn switch_tab(&mut self, tab: usize) {
+        let num_tabs = self.num_tabs();
+        if tab > num_tabs {
+            warn!("Cannot switch to tab {tab}, there are only {num_tabs} tabs");
+            return;
+        }
+        if tab == self.user.active_tab {
+            return;
+        }
+        let current = Tab {
+            waves: self.user.waves.take(),
+            id: self.active_tab_id,
+            history: TabHistory {
+                undo_stack: std::mem::take(&mut self.undo_stack),
+                redo_stack: std::mem::take(&mut self.redo_stack),
+                navigation: std::mem::take(&mut self.navigation),
+            },
+        };
+        let new = switch_tab_in(
+            &mut self.user.other_tabs,
+            &mut self.user.active_tab,
+            current,
+            tab,
+            Tab::default,
+        );
+        self.activate_tab(new);
+    }
+
+    /// Closes `tab`, activating its neighbour if it was the active tab.
+    pub(crate) fn close_tab(&mut self, tab: usize) {
+        let num_tabs = self.num_tabs();
+        if tab >= num_tabs {
+            warn!("Cannot close tab {tab}, there are only {num_tabs} tabs");
+            return;
+        }
+        match close_tab_in(&mut self.user.other_tabs, &mut self.user.active_tab, tab) {
+            ClosedTab::Inactive => {}
+            ClosedTab::Active(new) => {
+                self.user.waves = None;
+                self.activate_tab(new.unwrap_or_default());
+            }
+        }
+    }
+
+    /// Sets up the state for a newly activated tab. Tabs restored from a state file
+    /// have not loaded their waveform yet, so that is done here.
+    fn activate_tab(&mut self, tab: Tab) {
+        self.active_tab_id = tab.id;
+        self.undo_stack = tab.history.undo_stack;
+        self.redo_stack = tab.history.redo_stack;
+        self.navigation = tab.history.navigation;
+        // The drag state belongs to the previous tab
+        self.user.drag_started = false;
+        self.user.drag_source_idx = None;
+        self.user.drag_target_idx = None;
+        self.user.drag_target_tab = None;
+        self.user.rename_target = None;
+        self.invalidate_transaction_search();
+        #[cfg(feature = "performance_plot")]
+        {
+            self.user.transaction_statistics_stream = None;
+            self.invalidate_transaction_statistics();
+        }
+
+        match tab.waves {
+            Some(waves) if matches!(waves.inner, DataContainer::Empty) => {
+                let load_options = LoadOptions {
+                    keep_variables: true,
+                    keep_unavailable: false,
+                };
+                let source = waves.source.clone();
+                self.user.previous_waves = Some(waves);
+                match source {
+                    WaveSource::File(path) | WaveSource::DragAndDrop(Some(path)) => {
+                        if let Err(e) = self.load_from_file(path, load_options) {
+                            warn!("Failed to load {source}: {e:#}");
+                        }
+                    }
+                    WaveSource::Url(url) => self.load_wave_from_url(url, load_options),
+                    WaveSource::Cxxrtl(kind) => self.connect_to_cxxrtl(kind, true),
+                    WaveSource::Data | WaveSource::DragAndDrop(None) => {
+                        warn!("Cannot reload {source}");
+                        self.user.previous_waves = None;
+                    }
+                }
+            }
+            waves => self.user.waves = waves,
+        }
+        self.invalidate_hierarchy_search();
+
+        if let Some(waves) = &self.user.waves {
+            for translator in self.translators.all_translators() {
+                translator.set_wave_source(Some(waves.source.into_translation_type()));
+            }
+            self.user.wanted_timeunit = waves.inner.metadata().timescale.unit;
+            self.draw_data
+                .borrow_mut()
+                .resize_with(waves.viewports.len(), || None);
+        }
+        self.invalidate_draw_commands();
+    }
+
+    /// The waveform shown in the tab with `id`, if the tab is still open.
+    pub(crate) fn waves_in_tab(&mut self, id: TabId) -> Option<&mut WaveData> {
+        if id == self.active_tab_id {
+            self.user.waves.as_mut()
+        } else {
+            self.user
+                .other_tabs
+                .iter_mut()
+                .find(|tab| tab.id == id)?
+                .waves
+                .as_mut()
+        }
+    }
+
+    /// Files can only be loaded into the active tab. Returns false, after warning about it,
+    /// if the `source` loaded for `tab` has to be discarded since the tab is no longer active.
+    pub(crate) fn loads_into_active_tab(&mut self, tab: TabId, source: &WaveSource) -> bool {
+        if tab == self.active_tab_id {
+            return true;
+        }
+        warn!("Discarding {source}, the tab it was loaded for is no longer active");
+        self.progress_tracker = None;
+        false
+    }
+
+    /// Adds copies of the dragged, focused and selected variables to `tab` and activates it.
+    pub(crate) fn copy_dragged_items_to_tab(&mut self, tab: usize) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        let dragged_refs = [self.user.drag_source_idx, waves.focused_item]
+            .into_iter()
+            .flatten()
+            .filter_map(|vidx| waves.items_tree.get_visible(vidx))
+            .map(|node| node.item_ref)
+            .collect::<Vec<_>>();
+        let variables = waves
+            .items_tree
+            .iter_visible()
+            .filter(|node| node.selected || dragged_refs.contains(&node.item_ref))
+            .filter_map(|node| match waves.displayed_items.get(&node.item_ref) {
+                Some(DisplayedItem::Variable(variable)) => {
+                    Some((variable.variable_ref.clone(), variable.format.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        self.switch_tab(tab);
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        let Some(wave_container) = waves.inner.as_waves() else {
+            warn!("Cannot add variables to a tab which has not finished loading");
+            return;
+        };
+        let (variable_refs, formats): (Vec<_>, Vec<_>) = variables
+            .into_iter()
+            .filter_map(|(variable, format)| {
+                let found = wave_container.update_variable_ref(&variable);
+                if found.is_none() {
+                    warn!(
+                        "{} does not exist in {}",
+                        variable.full_path_string(),
+                        waves.source
+                    );
+                }
+                found.map(|variable| (variable, format))
+            })
+            .unzip();
+
+        let (cmd, item_refs) = waves.add_variables(&self.translators, variable_refs, None, true);
+        for (item_ref, format) in item_refs.iter().zip(formats) {
+            if let Some(DisplayedItem::Variable(variable)) = waves.displayed_items.get_mut(item_ref)
+            {
+                variable.format = format;
+            }
+        }
+        if let Some(cmd) = cmd {
+            self.load_variables(cmd);
+        }
+        self.invalidate_draw_commands();
+    }
+
+    pub fn add_tab_panel(&self, ctx: &Context, msgs: &mut Vec<Message>) {
+        TopBottomPanel::top("tabs")
+            .frame(Frame {
+                fill: self.user.config.theme.primary_ui_color.background,
+                inner_margin: Margin::symmetric(5, 2),
+                ..Default::default()
+            })
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| self.draw_tabs(ui, msgs));
+            });
+    }
+
+    fn draw_tabs(&self, ui: &mut Ui, msgs: &mut Vec<Message>) {
+        ui.visuals_mut().override_text_color =
+            Some(self.user.config.theme.primary_ui_color.foreground);
+        let pointer_released = ui.input(|i| i.pointer.any_released());
+        let mut drag_target = None;
+        for tab in 0..self.num_tabs() {
+            let waves = if tab == self.user.active_tab {
+                self.user.waves.as_ref()
+            } else {
+                let idx = if tab < self.user.active_tab {
+                    tab
+                } else {
+                    tab - 1
+                };
+                self.user.other_tabs[idx].waves.as_ref()
+            };
+            let title = tab_title(waves);
+            let active = tab == self.user.active_tab;
+            let response = ui
+                .selectable_label(active, RichText::new(&title))
+                .on_hover_text(waves.map_or_else(|| title.clone(), |w| w.source.to_string()));
+            if response.clicked() {
+                msgs.push(Message::SwitchTab(tab));
+            }
+            if response.middle_clicked() {
+                msgs.push(Message::CloseTab(tab));
+            }
+            response.context_menu(|ui| {
+                if ui.button("Close tab").clicked() {
+                    msgs.push(Message::CloseTab(tab));
+                    ui.close_menu();
+                }
+            });
+            if self.user.drag_started && !active && response.contains_pointer() {
+                ui.painter().rect_stroke(
+                    response.rect,
+                    2.,
+                    Stroke::new(
+                        self.user.config.theme.linewidth,
+                        self.user.config.theme.drag_hint_color,
+                    ),
+                    egui::StrokeKind::Inside,
+                );
+                drag_target = Some(tab);
+            }
+            if ui
+                .add(egui::Label::new(icons::CLOSE_LINE).sense(Sense::click()))
+                .on_hover_text("Close tab")
+                .clicked()
+            {
+                msgs.push(Message::CloseTab(tab));
+            }
+            ui.separator();
+        }
+        if ui
+            .add(egui::Label::new(icons::ADD_LINE).sense(Sense::click()))
+            .on_hover_text("New tab")
+            .clicked()
+        {
+            msgs.push(Message::SwitchTab(self.num_tabs()));
+        }
+
+        if drag_target != self.user.drag_target_tab && !pointer_released {
+            msgs.push(Message::VariableDragTargetTabChanged(drag_target));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use project_root::get_project_root;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    use super::*;
+    use crate::wave_container::WaveContainer;
+    use crate::wave_source::WaveFormat;
+    use crate::wellen::BodyResult;
+
+    /// Tabs `a` to `d`, with `b` active
+    fn tabs() -> (Vec<&'static str>, usize) {
+        (vec!["a", "c", "d"], 1)
+    }
+
+    #[test]
+    fn switching_moves_active_tab_into_others() {
+        let (mut others, mut active) = tabs();
+        let new = switch_tab_in(&mut others, &mut active, "b", 3, || "new");
+        assert_eq!((new, active, others), ("d", 3, vec!["a", "b", "c"]));
+
+        let (mut others, mut active) = tabs();
+        let new = switch_tab_in(&mut others, &mut active, "b", 0, || "new");
+        assert_eq!((new, active, others), ("a", 0, vec!["b", "c", "d"]));
+    }
+
+    #[test]
+    fn switching_past_last_tab_creates_new_tab() {
+        let (mut others, mut active) = tabs();
+        let new = switch_tab_in(&mut others, &mut active, "b", 4, || "new");
+        assert_eq!((new, active, others), ("new", 4, vec!["a", "b", "c", "d"]));
+    }
+
+    #[test]
+    fn closing_tab_before_active_shifts_active_index() {
+        let (mut others, mut active) = tabs();
+        let closed = close_tab_in(&mut others, &mut active, 0);
+        assert_eq!(
+            (closed, active, others),
+            (ClosedTab::Inactive, 0, vec!["c", "d"])
+        );
+    }
+
+    #[test]
+    fn closing_tab_after_active_keeps_active_index() {
+        let (mut others, mut active) = tabs();
+        let closed = close_tab_in(&mut others, &mut active, 3);
+        assert_eq!(
+            (closed, active, others),
+            (ClosedTab::Inactive, 1, vec!["a", "c"])
+        );
+    }
+
+    #[test]
+    fn closing_active_tab_activates_next_tab() {
+        let (mut others, mut active) = tabs();
+        let closed = close_tab_in(&mut others, &mut active, 1);
+        assert_eq!(
+            (closed, active, others),
+            (ClosedTab::Active(Some("c")), 1, vec!["a", "d"])
+        );
+    }
+
+    #[test]
+    fn closing_last_active_tab_activates_previous_tab() {
+        let mut others = vec!["a", "b", "c"];
+        let mut active = 3;
+        let closed = close_tab_in(&mut others, &mut active, 3);
+        assert_eq!(
+            (closed, active, others),
+            (ClosedTab::Active(Some("c")), 2, vec!["a", "b"])
+        );
+    }
+
+    #[test]
+    fn closing_only_tab_leaves_nothing_to_activate() {
+        let mut others: Vec<&str> = vec![];
+        let mut active = 0;
+        let closed = close_tab_in(&mut others, &mut active, 0);
+        assert_eq!((closed, active), (ClosedTab::Active(None), 0));
+    }
+
+    fn counter_source() -> WaveSource {
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        WaveSource::File(path.try_into().unwrap())
+    }
+
+    fn counter_header() -> wellen::viewers::HeaderResult<std::io::BufReader<std::fs::File>> {
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bodies_are_added_to_the_tab_that_loaded_them() {
+        let mut state = SystemState::new_default_config().unwrap();
+        let header = counter_header();
+        let hierarchy = Arc::new(header.hierarchy);
+        state.on_waves_loaded(
+            counter_source(),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(hierarchy.clone())),
+            LoadOptions::clean(),
+        );
+        let tab = state.active_tab_id;
+        state.switch_tab(1);
+
+        let body = wellen::viewers::read_body(header.body, &hierarchy, None).unwrap();
+        state.update(Message::WaveBodyLoaded(
+            web_time::Instant::now(),
+            tab,
+            counter_source(),
+            BodyResult::Local(body),
+        ));
+
+        assert!(state.user.waves.is_none());
+        let waves = state.user.other_tabs[0].waves.as_ref().unwrap();
+        assert!(waves.inner.body_loaded());
+    }
+
+    #[test]
+    fn files_loaded_for_inactive_tabs_are_discarded() {
+        let mut state = SystemState::new_default_config().unwrap();
+        let tab = state.active_tab_id;
+        state.switch_tab(1);
+
+        let header = counter_header();
+        state.update(Message::WavesLoaded(
+            tab,
+            counter_source(),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(header.hierarchy))),
+            LoadOptions::clean(),
+        ));
+
+        assert!(state.user.waves.is_none());
+        assert!(state.user.other_tabs[0].waves.is_none());
+    }
+
+    #[test]
+    fn tabs_keep_their_undo_history() {
+        let mut state = SystemState::new_default_config().unwrap();
+        state.on_waves_loaded(
+            counter_source(),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(
+                counter_header().hierarchy,
+            ))),
+            LoadOptions::clean(),
+        );
+        state.save_current_canvas("Before switching".to_string());
+
+        state.switch_tab(1);
+        assert!(state.undo_stack.is_empty());
+        state.switch_tab(0);
+        assert_eq!(state.undo_stack.len(), 1);
+        assert_eq!(state.undo_stack[0].message, "Before switching");
+    }
+
+    #[test]
+    fn tab_ids_are_unique() {
+        let mut state = SystemState::new_default_config().unwrap();
+        let first = state.active_tab_id;
+        state.switch_tab(1);
+        assert_ne!(state.active_tab_id, first);
+        assert_eq!(state.user.other_tabs[0].id, first);
+        state.close_tab(0);
+        assert!(state.waves_in_tab(first).is_none());
+        assert!(state.user.other_tabs.is_empty());
+    }
+}
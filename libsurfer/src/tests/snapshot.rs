@@ -2231,7 +2231,7 @@ snapshot_ui_with_file_and_msgs! {focus_transaction, "examples/my_db.ftr", [
     Message::AddStreamOrGenerator(TransactionStreamRef::new_gen(1, 5, "pipelined_stream.write".to_string())),
     Message::AddStreamOrGenerator(TransactionStreamRef::new_gen(2, 6, "addr_stream.addr".to_string())),
     Message::FocusTransaction(
-        Some(TransactionRef { id: 4 }),
+        Some(TransactionRef { container: 0, id: 4 }),
         None,
     ),
 ]}
@@ -2242,7 +2242,7 @@ snapshot_ui_with_file_and_msgs! {tx_stream_multiple_viewport_works, "examples/my
     Message::AddStreamOrGenerator(TransactionStreamRef::new_stream(3, "data_stream".to_string())),
     Message::AddViewport,
     Message::CanvasScroll {delta: Vec2::new(-300., 0.),viewport_idx: 1},
-    Message::FocusTransaction(Some(TransactionRef { id: 34 }), None),
+    Message::FocusTransaction(Some(TransactionRef { container: 0, id: 34 }), None),
 ]}
 
 snapshot_ui_with_file_and_msgs! {parameter_in_scopes, "examples/picorv32.vcd", [
@@ -134,10 +134,49 @@ async fn send_commands(tx: &Sender<WcpCSMessage>, cmds: Vec<WcpCommand>) -> Resu
     Ok(())
 }
 
+/// Polls the value of `id` until the variable's signal has been loaded, instead of
+/// sleeping for a fixed time.
+async fn wait_for_variable(
+    tx: &Sender<WcpCSMessage>,
+    rx: &mut Receiver<WcpSCMessage>,
+    id: proto::DisplayedItemRef,
+) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        send_commands(
+            tx,
+            vec![WcpCommand::get_value_at {
+                id,
+                time: BigInt::from(0),
+            }],
+        )
+        .await?;
+        expect_response!(
+            rx,
+            WcpSCMessage::response(WcpResponse::get_value_at { value })
+        );
+        if value.is_some() {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() > deadline {
+            bail!("Timeout waiting for variable {id:?} to load");
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
+
 async fn greet(tx: &Sender<WcpCSMessage>, rx: &mut Receiver<WcpSCMessage>) -> Result<()> {
-    let commands = vec!["waveforms_loaded", "goto_declaration"]
-        .into_iter()
-        .map(str::to_string)
+    greet_with(tx, rx, &["waveforms_loaded", "goto_declaration"]).await
+}
+
+async fn greet_with(
+    tx: &Sender<WcpCSMessage>,
+    rx: &mut Receiver<WcpSCMessage>,
+    capabilities: &[&str],
+) -> Result<()> {
+    let commands = capabilities
+        .iter()
+        .map(|s| s.to_string())
         .collect_vec();
     tx.send(WcpCSMessage::greeting {
         version: "0".to_string(),
@@ -167,6 +206,16 @@ async fn greet(tx: &Sender<WcpCSMessage>, rx: &mut Receiver<WcpSCMessage>) -> Re
         "clear",
         "load",
         "zoom_to_fit",
+        "zoom_to_range",
+        "get_value_at",
+        "set_cursor",
+        "add_marker",
+        "get_markers",
+        "set_item_format",
+        "add_group",
+        "rename_item",
+        "get_hierarchy",
+        "list_variables",
     ];
     assert_eq!(commands, e_commands);
 
@@ -182,6 +231,7 @@ async fn load_file(
 
     tx.send(WcpCSMessage::command(proto::WcpCommand::load {
         source: file.to_string(),
+        tab: None,
     }))
     .await?;
     expect_ack(rx).await?;
@@ -230,7 +280,8 @@ wcp_test! {
         greet(&tx, &mut rx).await?;
 
         tx.send(WcpCSMessage::command(proto::WcpCommand::load {
-            source: "../examples/counter.vcd".to_string()
+            source: "../examples/counter.vcd".to_string(),
+            tab: None,
         })).await?;
         expect_ack(&mut rx).await?;
 
@@ -253,7 +304,8 @@ wcp_test! {
         greet(&tx, &mut rx).await?;
 
         tx.send(WcpCSMessage::command(proto::WcpCommand::load {
-            source: "../examples/counter.vcd".to_string()
+            source: "../examples/counter.vcd".to_string(),
+            tab: None,
         })).await?;
         expect_ack(&mut rx).await?;
 
@@ -442,6 +494,36 @@ wcp_test! {
     }
 }
 
+wcp_test! {
+    load_in_tab,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+            WcpCommand::load { source: "../examples/counter2.vcd".to_string(), tab: Some(1) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: _ }));
+        expect_ack(&mut rx).await?;
+        expect_response!(rx, WcpSCMessage::event(WcpEvent::waveforms_loaded{source}));
+        assert_eq!(source, "../examples/counter2.vcd".to_string());
+
+        // The new tab does not show the items of the first one
+        send_commands(&tx, vec![WcpCommand::get_item_list]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_item_list{ ids }));
+        assert!(ids.is_empty());
+
+        send_commands(&tx, vec![
+            WcpCommand::load { source: "../examples/counter.vcd".to_string(), tab: Some(3) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::error{error, arguments: _, message});
+        assert_eq!(error, "load");
+        assert_eq!(message, "Tab 3 does not exist");
+
+        Ok(())
+    }
+}
+
 wcp_test! {
     no_greeting,
     (tx, rx) {
@@ -454,3 +536,244 @@ wcp_test! {
         Ok(())
     }
 }
+
+wcp_test! {
+    zoom_to_range,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+            WcpCommand::zoom_to_range { start: BigInt::from(100), end: BigInt::from(200), viewport_idx: 0 },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: _ }));
+        expect_ack(&mut rx).await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::zoom_to_range { start: BigInt::from(100), end: BigInt::from(200), viewport_idx: 1 },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::error{error, ..});
+        assert_eq!(error, "zoom_to_range");
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    get_value_at,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_variables { variables: vec!["tb.dut.counter".to_string()] },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_variables{ ids }));
+        wait_for_variable(&tx, &mut rx, ids[0]).await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::get_value_at { id: ids[0], time: BigInt::from(135) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_value_at{ value }));
+        assert_eq!(value, Some("2".to_string()));
+
+        send_commands(&tx, vec![
+            WcpCommand::get_value_at { id: proto::DisplayedItemRef(usize::MAX), time: BigInt::from(135) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::error{error, ..});
+        assert_eq!(error, "get_value_at");
+
+        send_commands(&tx, vec![
+            WcpCommand::get_value_at { id: ids[0], time: BigInt::from(-1) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::error{error, ..});
+        assert_eq!(error, "get_value_at");
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    set_cursor,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+            WcpCommand::set_cursor { timestamp: BigInt::from(250) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: _ }));
+        expect_ack(&mut rx).await
+    }
+}
+
+wcp_test! {
+    add_and_get_markers,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+            WcpCommand::add_marker { timestamp: BigInt::from(100), name: Some("start".to_string()) },
+            WcpCommand::add_marker { timestamp: BigInt::from(300), name: None },
+            WcpCommand::get_markers,
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: _ }));
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_marker{ id: first }));
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_marker{ id: second }));
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_markers{ markers }));
+
+        let expected = vec![
+            proto::MarkerInfo { id: first, name: Some("start".to_string()), time: BigInt::from(100) },
+            proto::MarkerInfo { id: second, name: None, time: BigInt::from(300) },
+        ];
+        assert_eq!(markers, expected);
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    set_item_format,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_variables { variables: vec!["tb.dut.counter".to_string()] },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_variables{ ids }));
+        wait_for_variable(&tx, &mut rx, ids[0]).await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::set_item_format { id: ids[0], format: "Binary".to_string() },
+            WcpCommand::get_value_at { id: ids[0], time: BigInt::from(135) },
+            WcpCommand::set_item_format { id: ids[0], format: "NotAFormat".to_string() },
+        ]).await?;
+        expect_ack(&mut rx).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_value_at{ value }));
+        assert_eq!(value, Some("0010".to_string()));
+        expect_response!(rx, WcpSCMessage::error{error, ..});
+        assert_eq!(error, "set_item_format");
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    add_group,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: refs }));
+
+        send_commands(&tx, vec![
+            WcpCommand::add_group { name: "Control".to_string(), ids: vec![refs[1], refs[3]] },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_group{ id }));
+
+        send_commands(&tx, vec![
+            WcpCommand::get_item_info { ids: vec![id] },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_item_info{ results: info }));
+        assert_eq!(info, vec![proto::ItemInfo {
+            name: "Control".to_string(),
+            t: "Group".to_string(),
+            id,
+        }]);
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    rename_item,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_scope {scope: "tb".to_string(), recursive: false},
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_scope{ ids: refs }));
+
+        send_commands(&tx, vec![
+            WcpCommand::rename_item { id: refs[1], name: "clock".to_string() },
+            WcpCommand::get_item_info { ids: vec![refs[1]] },
+        ]).await?;
+        expect_ack(&mut rx).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_item_info{ results: info }));
+        assert_eq!(info[0].name, "clock");
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    get_hierarchy,
+    (tx, rx) {
+        load_file(&tx, &mut rx, "../examples/counter.vcd").await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::get_hierarchy,
+            WcpCommand::list_variables { scope: "tb.dut".to_string() },
+            WcpCommand::list_variables { scope: "tb.nope".to_string() },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::get_hierarchy{ scopes }));
+        assert_eq!(scopes, vec!["tb".to_string(), "tb.dut".to_string()]);
+
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::list_variables{ variables }));
+        let mut names = variables.iter().map(|v| v.name.clone()).collect_vec();
+        names.sort();
+        assert_eq!(names, vec!["tb.dut.clk", "tb.dut.counter", "tb.dut.overflow", "tb.dut.reset"]);
+        let counter = variables.iter().find(|v| v.name == "tb.dut.counter").unwrap();
+        assert_eq!(counter.width, Some(4));
+
+        expect_response!(rx, WcpSCMessage::error{error, ..});
+        assert_eq!(error, "list_variables");
+
+        Ok(())
+    }
+}
+
+wcp_test! {
+    state_change_events,
+    (tx, rx) {
+        greet_with(&tx, &mut rx, &["waveforms_loaded", "cursor_changed", "markers_changed"]).await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::load { source: "../examples/counter.vcd".to_string(), tab: None },
+        ]).await?;
+        expect_ack(&mut rx).await?;
+        expect_response!(rx, WcpSCMessage::event(WcpEvent::waveforms_loaded{ source: _ }));
+
+        send_commands(&tx, vec![
+            WcpCommand::set_cursor { timestamp: BigInt::from(250) },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::event(WcpEvent::cursor_changed{ time }));
+        assert_eq!(time, BigInt::from(250));
+        expect_ack(&mut rx).await?;
+
+        send_commands(&tx, vec![
+            WcpCommand::add_marker { timestamp: BigInt::from(100), name: None },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_marker{ id }));
+        expect_response!(rx, WcpSCMessage::event(WcpEvent::markers_changed{ markers, removed }));
+        assert_eq!(markers, vec![
+            proto::MarkerInfo { id, name: None, time: BigInt::from(100) },
+        ]);
+        assert!(removed.is_empty());
+
+        // Only the new marker is reported
+        send_commands(&tx, vec![
+            WcpCommand::add_marker { timestamp: BigInt::from(200), name: None },
+        ]).await?;
+        expect_response!(rx, WcpSCMessage::response(WcpResponse::add_marker{ id: second }));
+        expect_response!(rx, WcpSCMessage::event(WcpEvent::markers_changed{ markers, removed: _ }));
+        assert_eq!(markers, vec![
+            proto::MarkerInfo { id: second, name: None, time: BigInt::from(200) },
+        ]);
+
+        Ok(())
+    }
+}
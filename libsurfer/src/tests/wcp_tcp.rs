@@ -1,15 +1,18 @@
+use crate::config::WcpTransport;
 use crate::message::Message;
 use crate::wave_source::LoadOptions;
-use crate::wcp::proto::{WcpCSMessage, WcpCommand, WcpSCMessage};
+use crate::wcp::proto::{WcpCSMessage, WcpEvent, WcpSCMessage};
 use crate::SystemState;
 
 use port_check::free_local_ipv4_port_in_range;
 use serde_json::Error as serde_Error;
+use surfer_wcp::client::WcpClient;
 use test_log::test;
 use tokio::io::AsyncWriteExt;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::{sleep, timeout, Duration};
 
+use futures::{SinkExt, StreamExt};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use std::future::Future;
@@ -110,6 +113,34 @@ where
     });
 }
 
+/// Runs `fut` while letting `state` process WCP messages
+async fn with_state<T>(state: &mut SystemState, fut: impl Future<Output = T>) -> T {
+    tokio::pin!(fut);
+    loop {
+        tokio::select! {
+            result = &mut fut => return result,
+            _ = sleep(Duration::from_millis(100)) => {
+                state.handle_wcp_commands();
+                state.handle_async_messages();
+            }
+        }
+    }
+}
+
+async fn connect_client(port: u16, state: &mut SystemState) -> WcpClient {
+    with_state(state, async {
+        loop {
+            if let Ok(client) =
+                WcpClient::connect(format!("127.0.0.1:{port}"), &["waveforms_loaded"]).await
+            {
+                return client;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+}
+
 #[test]
 fn load() {
     run_test(async {
@@ -118,25 +149,15 @@ fn load() {
         state.update(Message::StartWcpServer {
             address: Some(format!("127.0.0.1:{port}").to_string()),
             initiate: false,
+            transport: None,
         });
-        let mut stream = connect(port).await;
-        get_json_response(&stream, &mut state)
-            .await
-            .expect("failed to get WCP greeting");
-        send_message(
-            &mut stream,
-            &WcpCSMessage::command(WcpCommand::load {
-                source: "../examples/counter.vcd".to_string(),
-            }),
-        )
-        .await;
-        // ack and waveforms_loaded messages
-        get_json_response(&stream, &mut state)
-            .await
-            .expect("failed to get WCP greeting");
-        get_json_response(&stream, &mut state)
+        let mut client = connect_client(port, &mut state).await;
+        let mut events = client.take_events().unwrap();
+        with_state(&mut state, client.load("../examples/counter.vcd"))
             .await
-            .expect("failed to get WCP greeting");
+            .expect("failed to load");
+        let event = with_state(&mut state, events.recv()).await;
+        assert!(matches!(event, Some(WcpEvent::waveforms_loaded { .. })));
     });
 }
 
@@ -149,6 +170,7 @@ fn stop_and_reconnect() {
             state.update(Message::StartWcpServer {
                 address: Some(format!("127.0.0.1:{port}").to_string()),
                 initiate: false,
+                transport: None,
             });
             let stream = connect(port).await;
             get_json_response(&stream, &mut state)
@@ -174,6 +196,7 @@ fn reconnect() {
         state.update(Message::StartWcpServer {
             address: Some(format!("127.0.0.1:{port}").to_string()),
             initiate: false,
+            transport: None,
         });
         for _ in 0..2 {
             let stream = connect(port).await;
@@ -194,6 +217,7 @@ fn initiate() {
         state.update(Message::StartWcpServer {
             address: Some(address),
             initiate: true,
+            transport: None,
         });
         if let Ok((mut stream, _addr)) = listener.accept().await {
             greet(&mut stream).await;
@@ -227,6 +251,7 @@ fn long_pause() {
         state.update(Message::StartWcpServer {
             address: Some(format!("127.0.0.1:{port}").to_string()),
             initiate: false,
+            transport: None,
         });
         let stream = connect(port).await;
         get_json_response(&stream, &mut state)
@@ -249,6 +274,7 @@ fn start_stop() {
         state.update(Message::StartWcpServer {
             address: Some(format!("127.0.0.1:{port}").to_string()),
             initiate: false,
+            transport: None,
         });
         tokio::time::sleep(Duration::from_millis(1000)).await;
         state.update(Message::StopWcpServer);
@@ -267,18 +293,12 @@ fn response_and_event() {
         state.update(Message::StartWcpServer {
             address: Some(format!("127.0.0.1:{port}").to_string()),
             initiate: false,
+            transport: None,
         });
         let msg_sender = state.channels.msg_sender.clone();
-        let mut stream = connect(port).await;
-        get_json_response(&stream, &mut state)
-            .await
-            .expect("failed to get WCP greeting");
-        send_message(
-            &mut stream,
-            &(WcpCSMessage::command(WcpCommand::get_item_list)),
-        )
-        .await;
-        get_json_response(&stream, &mut state)
+        let mut client = connect_client(port, &mut state).await;
+        let mut events = client.take_events().unwrap();
+        with_state(&mut state, client.get_item_list())
             .await
             .expect("failed to get get_item_list response");
         msg_sender
@@ -287,8 +307,93 @@ fn response_and_event() {
                 LoadOptions::clean(),
             ))
             .unwrap();
-        get_json_response(&stream, &mut state)
-            .await
-            .expect("failed to get waveforms_loaded response");
+        let event = with_state(&mut state, events.recv()).await;
+        assert!(matches!(event, Some(WcpEvent::waveforms_loaded { .. })));
+    });
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_socket() {
+    run_test(async {
+        let mut state = SystemState::new_default_config().unwrap();
+        let path = std::env::temp_dir().join(format!("surfer-wcp-{}.sock", get_test_port()));
+        state.update(Message::StartWcpServer {
+            address: Some(path.to_string_lossy().to_string()),
+            initiate: false,
+            transport: Some(WcpTransport::Unix),
+        });
+        let client = with_state(&mut state, async {
+            loop {
+                if let Ok(client) = WcpClient::connect_unix(&path, &[]).await {
+                    return client;
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+        assert!(client.supports("get_item_list"));
+        state.update(Message::StopWcpServer);
+    });
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_socket_replaces_only_stale_sockets() {
+    use crate::wcp::wcp_server::WcpServer;
+
+    run_test(async {
+        let dir = tempfile::tempdir().unwrap();
+
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "data").unwrap();
+        let result = WcpServer::bind(file.to_string_lossy().to_string(), WcpTransport::Unix).await;
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+
+        let socket = dir.path().join("stale.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let result =
+            WcpServer::bind(socket.to_string_lossy().to_string(), WcpTransport::Unix).await;
+        assert!(result.is_ok());
+    });
+}
+
+#[test]
+fn web_socket() {
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    run_test(async {
+        let mut state = SystemState::new_default_config().unwrap();
+        let port = get_test_port();
+        state.update(Message::StartWcpServer {
+            address: Some(format!("127.0.0.1:{port}")),
+            initiate: false,
+            transport: Some(WcpTransport::WebSocket),
+        });
+        let mut stream = loop {
+            if let Ok((c, _)) =
+                tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{port}")).await
+            {
+                break c;
+            }
+            sleep(Duration::from_millis(100)).await;
+        };
+        let greeting = serde_json::to_string(&WcpCSMessage::create_greeting(0, vec![])).unwrap();
+        stream.send(WsMessage::Text(greeting.into())).await.unwrap();
+        loop {
+            state.handle_wcp_commands();
+            state.handle_async_messages();
+            if let Ok(Some(msg)) = timeout(Duration::from_millis(100), stream.next()).await {
+                let WsMessage::Text(text) = msg.expect("Read failure") else {
+                    continue;
+                };
+                let msg: WcpSCMessage =
+                    serde_json::from_str(&text).expect("failed to get WCP greeting");
+                assert!(matches!(msg, WcpSCMessage::greeting { .. }));
+                break;
+            }
+        }
+        state.update(Message::StopWcpServer);
     });
 }
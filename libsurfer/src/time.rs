@@ -1,4 +1,6 @@
 //! Time handling and formatting.
+use std::str::FromStr;
+
 use derive_more::Display;
 use ecolor::Color32;
 use egui::Ui;
@@ -7,7 +9,7 @@ use enum_iterator::Sequence;
 use epaint::{FontId, Stroke};
 use ftr_parser::types::Timescale;
 use itertools::Itertools;
-use num::{BigInt, BigRational, ToPrimitive};
+use num::{BigInt, BigRational, One, ToPrimitive, Zero};
 use pure_rust_locales::{locale_match, Locale};
 use serde::{Deserialize, Serialize};
 use sys_locale::get_locale;
@@ -85,7 +87,7 @@ impl From<ftr_parser::types::Timescale> for TimeUnit {
 
 impl TimeUnit {
     /// Get the power-of-ten exponent for a time unit.
-    fn exponent(&self) -> i8 {
+    pub(crate) fn exponent(&self) -> i8 {
         match self {
             TimeUnit::FemtoSeconds => -15,
             TimeUnit::PicoSeconds => -12,
@@ -123,6 +125,72 @@ pub fn timeunit_menu(ui: &mut Ui, msgs: &mut Vec<Message>, wanted_timeunit: &Tim
     }
 }
 
+/// Maps times in a waveform file to the times shown in the viewer. Used to line up
+/// waveforms which do not start at the same time or use different timescales.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TimeAlignment {
+    /// Added to the time after scaling, in timescale units.
+    pub offset: BigInt,
+    /// Factor the time in the file is multiplied with. Must be positive.
+    pub scale: f64,
+}
+
+impl Default for TimeAlignment {
+    fn default() -> Self {
+        TimeAlignment {
+            offset: BigInt::zero(),
+            scale: 1.,
+        }
+    }
+}
+
+impl TimeAlignment {
+    pub fn is_identity(&self) -> bool {
+        self.offset.is_zero() && self.scale == 1.
+    }
+
+    /// The scale must be positive for times to be converted
+    pub fn is_valid(&self) -> bool {
+        self.scale.is_finite() && self.scale > 0.
+    }
+
+    /// The scale as an exact fraction. The decimal representation of the scale is used
+    /// rather than its binary value so that e.g. 0.1 is exactly one tenth.
+    fn scale_ratio(&self) -> BigRational {
+        let decimal = self.scale.to_string();
+        let (integer, fraction) = decimal.split_once('.').unwrap_or((&decimal, ""));
+        BigInt::from_str(&format!("{integer}{fraction}"))
+            .ok()
+            .map(|numerator| {
+                BigRational::new(numerator, BigInt::from(10u32).pow(fraction.len() as u32))
+            })
+            .or_else(|| BigRational::from_float(self.scale))
+            .unwrap_or_else(BigRational::one)
+    }
+
+    /// Converts a time in the file to the aligned time, rounding up.
+    pub fn to_aligned(&self, time: &BigInt) -> BigInt {
+        if self.scale == 1. {
+            time + &self.offset
+        } else {
+            let scaled = BigRational::from_integer(time.clone()) * self.scale_ratio();
+            scaled.ceil().to_integer() + &self.offset
+        }
+    }
+
+    /// Converts an aligned time to the time in the file, rounding down.
+    pub fn to_file(&self, time: &BigInt) -> BigInt {
+        let shifted = time - &self.offset;
+        if self.scale == 1. {
+            shifted
+        } else {
+            (BigRational::from_integer(shifted) / self.scale_ratio())
+                .floor()
+                .to_integer()
+        }
+    }
+}
+
 /// How to format the time stamps.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TimeFormat {
@@ -450,9 +518,11 @@ impl SystemState {
 
 #[cfg(test)]
 mod test {
-    use num::BigInt;
+    use num::{BigInt, Zero};
 
-    use crate::time::{time_string, TimeFormat, TimeScale, TimeStringFormatting, TimeUnit};
+    use crate::time::{
+        time_string, TimeAlignment, TimeFormat, TimeScale, TimeStringFormatting, TimeUnit,
+    };
 
     #[test]
     fn print_time_standard() {
@@ -738,4 +808,47 @@ mod test {
             "220"
         );
     }
+
+    #[test]
+    fn time_alignment_is_exact_for_large_times() {
+        let alignment = TimeAlignment {
+            offset: BigInt::from(1),
+            scale: 2.,
+        };
+        // Not representable as f64
+        let time = BigInt::from(2u64.pow(60) + 1);
+        assert_eq!(alignment.to_aligned(&time), BigInt::from(2u64.pow(61) + 3));
+        assert_eq!(alignment.to_file(&alignment.to_aligned(&time)), time);
+    }
+
+    #[test]
+    fn time_alignment_offset_and_scale() {
+        let alignment = TimeAlignment {
+            offset: BigInt::from(-10),
+            scale: 2.5,
+        };
+        assert_eq!(alignment.to_aligned(&BigInt::from(4)), BigInt::from(0));
+        assert_eq!(alignment.to_aligned(&BigInt::from(5)), BigInt::from(3));
+        assert_eq!(alignment.to_file(&BigInt::from(0)), BigInt::from(4));
+        assert_eq!(alignment.to_file(&BigInt::from(2)), BigInt::from(4));
+        assert_eq!(alignment.to_file(&BigInt::from(-11)), BigInt::from(-1));
+        // A change reported at an aligned time must be visible when querying at that time
+        for time in 0..100 {
+            let time = BigInt::from(time);
+            assert!(alignment.to_file(&alignment.to_aligned(&time)) >= time);
+        }
+        assert!(TimeAlignment::default().is_identity());
+        assert_eq!(
+            TimeAlignment {
+                offset: BigInt::zero(),
+                scale: 0.1,
+            }
+            .to_aligned(&BigInt::from(10)),
+            BigInt::from(1)
+        );
+        assert_eq!(
+            TimeAlignment::default().to_file(&BigInt::from(7)),
+            BigInt::from(7)
+        );
+    }
 }
@@ -1,15 +1,18 @@
 //! Toolbar handling.
-use egui::{Button, Context, Layout, RichText, TopBottomPanel, Ui};
+use egui::{Button, ComboBox, Context, DragValue, Layout, RichText, TopBottomPanel, Ui};
 use egui_remixicon::icons;
 use emath::{Align, Vec2};
+use num::BigInt;
+use surfer_translation_types::VariableInfo;
 
+use crate::displayed_item::DisplayedItem;
 use crate::message::MessageTarget;
-use crate::wave_container::SimulationStatus;
+use crate::wave_container::{DiagnosticKind, SimulationStatus};
 use crate::wave_source::LoadOptions;
 use crate::{
     file_dialog::OpenMode,
     message::Message,
-    wave_data::{PER_SCROLL_EVENT, SCROLL_EVENTS_PER_PAGE},
+    wave_data::{WaveData, PER_SCROLL_EVENT, SCROLL_EVENTS_PER_PAGE},
     SystemState,
 };
 
@@ -49,14 +52,33 @@ impl SystemState {
 
         ui.label("Simulation ");
         match status {
-            SimulationStatus::Paused => add_toolbar_button(
-                ui,
-                msgs,
-                icons::PLAY_CIRCLE_FILL,
-                "Run simulation",
-                Message::UnpauseSimulation,
-                true,
-            ),
+            SimulationStatus::Paused => {
+                add_toolbar_button(
+                    ui,
+                    msgs,
+                    icons::PLAY_CIRCLE_FILL,
+                    "Run simulation",
+                    Message::UnpauseSimulation,
+                    true,
+                );
+                add_toolbar_button(
+                    ui,
+                    msgs,
+                    icons::ERROR_WARNING_FILL,
+                    "Run simulation until next assertion",
+                    Message::RunSimulationUntilDiagnostic(DiagnosticKind::Assert),
+                    true,
+                );
+                add_toolbar_button(
+                    ui,
+                    msgs,
+                    icons::CHAT_1_FILL,
+                    "Run simulation until next print",
+                    Message::RunSimulationUntilDiagnostic(DiagnosticKind::Print),
+                    true,
+                );
+                self.simulation_run_controls(waves, ui, msgs);
+            }
             SimulationStatus::Running => add_toolbar_button(
                 ui,
                 msgs,
@@ -69,6 +91,108 @@ impl SystemState {
                 ui.label("Finished");
             }
         }
+        add_toolbar_button(
+            ui,
+            msgs,
+            icons::FILE_LIST_3_FILL,
+            "Show diagnostics",
+            Message::SetDiagnosticsWindowVisible(!self.user.show_diagnostics_window),
+            true,
+        );
+    }
+
+    /// Controls for running a paused simulation for a time or a number of clock cycles
+    fn simulation_run_controls(&self, waves: &WaveData, ui: &mut Ui, msgs: &mut Vec<Message>) {
+        ui.separator();
+        let unit = waves.inner.metadata().timescale.unit;
+        let mut run_time = self.simulation_run_time.borrow_mut();
+        ui.add(
+            DragValue::new(&mut *run_time)
+                .range(1..=u64::MAX)
+                .suffix(format!(" {unit}")),
+        )
+        .on_hover_text("Time to run the simulation for");
+        add_toolbar_button(
+            ui,
+            msgs,
+            icons::TIME_FILL,
+            "Run simulation for the specified time",
+            Message::RunSimulationFor(BigInt::from(*run_time)),
+            true,
+        );
+        let num_timestamps = waves.num_timestamps().unwrap_or_default();
+        add_toolbar_button(
+            ui,
+            msgs,
+            icons::MAP_PIN_2_LINE,
+            "Run simulation until the cursor",
+            Message::RunSimulationUntil(
+                waves
+                    .time_alignment
+                    .to_file(&waves.cursor.clone().unwrap_or_default()),
+            ),
+            waves
+                .cursor
+                .as_ref()
+                .is_some_and(|cursor| cursor > &num_timestamps),
+        );
+
+        ui.separator();
+        let clocks = waves
+            .items_tree
+            .iter()
+            .filter_map(|node| match waves.displayed_items.get(&node.item_ref) {
+                Some(DisplayedItem::Variable(variable))
+                    if matches!(variable.info, VariableInfo::Clock | VariableInfo::Bool) =>
+                {
+                    Some(variable)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut clock = self.simulation_clock.borrow_mut();
+        if clock
+            .as_ref()
+            .is_none_or(|clock| !clocks.iter().any(|v| &v.variable_ref == clock))
+        {
+            *clock = clocks.first().map(|v| v.variable_ref.clone());
+        }
+        ComboBox::from_id_salt("simulation_clock")
+            .selected_text(
+                clock
+                    .as_ref()
+                    .map_or("No clock".to_string(), |c| c.name.clone()),
+            )
+            .show_ui(ui, |ui| {
+                for variable in &clocks {
+                    ui.selectable_value(
+                        &mut *clock,
+                        Some(variable.variable_ref.clone()),
+                        &variable.display_name,
+                    );
+                }
+            })
+            .response
+            .on_hover_text("Clock to step the simulation by");
+        let mut cycles = self.simulation_step_cycles.borrow_mut();
+        ui.add(
+            DragValue::new(&mut *cycles)
+                .range(1..=u32::MAX)
+                .suffix(" cycles"),
+        );
+        if let Some(clock) = clock.as_ref() {
+            add_toolbar_button(
+                ui,
+                msgs,
+                icons::SKIP_FORWARD_FILL,
+                "Step simulation by the specified number of clock cycles",
+                Message::StepSimulationCycles {
+                    clock: clock.clone(),
+                    cycles: *cycles,
+                },
+                true,
+            );
+        }
     }
 
     fn draw_toolbar(&self, ui: &mut Ui, msgs: &mut Vec<Message>) {
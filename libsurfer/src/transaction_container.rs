@@ -96,11 +96,7 @@ impl TransactionContainer {
             StreamScopeRef::Root => self
                 .get_streams()
                 .into_iter()
-                .map(|s| TransactionStreamRef {
-                    gen_id: None,
-                    stream_id: s.id,
-                    name: s.name.clone(),
-                })
+                .map(|s| TransactionStreamRef::new_stream(s.id, s.name.clone()))
                 .collect(),
             StreamScopeRef::Stream(stream_ref) => self
                 .get_stream(stream_ref.stream_id)
@@ -109,11 +105,7 @@ impl TransactionContainer {
                 .iter()
                 .map(|id| {
                     let gen = self.get_generator(*id).unwrap();
-                    TransactionStreamRef {
-                        gen_id: Some(gen.id),
-                        stream_id: stream_ref.stream_id,
-                        name: gen.name.clone(),
-                    }
+                    TransactionStreamRef::new_gen(stream_ref.stream_id, gen.id, gen.name.clone())
                 })
                 .collect(),
             StreamScopeRef::Empty(_) => vec![],
@@ -162,26 +154,21 @@ impl Display for StreamScopeRef {
     }
 }
 
-impl StreamScopeRef {
-    pub fn new_stream_from_name(transactions: &TransactionContainer, name: String) -> Self {
-        let stream = transactions
-            .inner
-            .get_stream_from_name(name.clone())
-            .unwrap();
-        StreamScopeRef::Stream(TransactionStreamRef::new_stream(stream.id, name))
-    }
-}
-
 /// If `gen_id` is `Some` this `TransactionStreamRef` is a generator, otherwise it's a stream
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TransactionStreamRef {
     pub stream_id: usize,
     pub gen_id: Option<usize>,
     pub name: String,
+    /// Index of the transaction file the stream is from, see
+    /// [`DataContainer::transaction_containers`](crate::data_container::DataContainer::transaction_containers)
+    #[serde(default)]
+    pub container: usize,
 }
 
 impl Hash for TransactionStreamRef {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        self.container.hash(state);
         self.gen_id.unwrap_or(self.stream_id).hash(state);
         self.name.hash(state);
     }
@@ -209,6 +196,7 @@ impl TransactionStreamRef {
             stream_id,
             gen_id: None,
             name,
+            container: 0,
         }
     }
     pub fn new_gen(stream_id: usize, gen_id: usize, name: String) -> Self {
@@ -216,9 +204,15 @@ impl TransactionStreamRef {
             stream_id,
             gen_id: Some(gen_id),
             name,
+            container: 0,
         }
     }
 
+    /// The same stream or generator in the transaction file with index `container`
+    pub fn in_container(self, container: usize) -> Self {
+        TransactionStreamRef { container, ..self }
+    }
+
     pub fn is_generator(&self) -> bool {
         self.gen_id.is_some()
     }
@@ -228,7 +222,10 @@ impl TransactionStreamRef {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, Serialize, Deserialize, PartialEq, PartialOrd, Ord)]
 pub struct TransactionRef {
+    /// Index of the transaction file, transaction ids are only unique within a file
+    #[serde(default)]
+    pub container: usize,
     pub id: usize,
 }
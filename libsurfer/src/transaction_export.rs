@@ -0,0 +1,624 @@
+//! Exporting transactions to CSV, JSON and the Chrome/Perfetto trace event format.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use enum_iterator::Sequence;
+use eyre::Result;
+use ftr_parser::types::{Transaction, TxGenerator, TxStream};
+use itertools::Itertools;
+use num::{BigUint, ToPrimitive};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::async_util::perform_async_work;
+use crate::data_container::DataContainer;
+use crate::displayed_item::{DisplayedItem, DisplayedItemRef};
+use crate::file_dialog::save_file_dialog;
+use crate::time::TimeUnit;
+use crate::SystemState;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Sequence)]
+pub enum TransactionExportFormat {
+    /// One row per transaction with a column per attribute
+    Csv,
+    /// Transactions with their attributes and relations
+    Json,
+    /// Chrome/Perfetto trace event JSON, with a track per generator
+    Perfetto,
+}
+
+impl TransactionExportFormat {
+    /// Name used in commands
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionExportFormat::Csv => "csv",
+            TransactionExportFormat::Json => "json",
+            TransactionExportFormat::Perfetto => "perfetto",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TransactionExportFormat::Csv => "CSV",
+            TransactionExportFormat::Json => "JSON",
+            TransactionExportFormat::Perfetto => "Perfetto trace",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TransactionExportFormat::Csv => "csv",
+            TransactionExportFormat::Json | TransactionExportFormat::Perfetto => "json",
+        }
+    }
+}
+
+struct ExportedTransaction<'a> {
+    /// Index of the transaction file
+    container: usize,
+    time_unit: TimeUnit,
+    stream: &'a TxStream,
+    generator: &'a TxGenerator,
+    tx: &'a Transaction,
+}
+
+/// The transactions of `generators`, given as container and generator id, sorted by start
+/// time and id
+fn exported_transactions<'a>(
+    inner: &'a DataContainer,
+    generators: &[(usize, usize)],
+) -> Vec<ExportedTransaction<'a>> {
+    let streams = inner
+        .transaction_containers()
+        .iter()
+        .enumerate()
+        .flat_map(|(container, transactions)| {
+            transactions
+                .get_streams()
+                .into_iter()
+                .flat_map(move |stream| {
+                    stream
+                        .generators
+                        .iter()
+                        .map(move |gen_id| ((container, *gen_id), stream))
+                })
+        })
+        .collect::<HashMap<_, _>>();
+    generators
+        .iter()
+        .unique()
+        .filter_map(|(container, gen_id)| {
+            let transactions = inner.transactions(*container)?;
+            Some((
+                *container,
+                TimeUnit::from(transactions.inner.time_scale),
+                *streams.get(&(*container, *gen_id))?,
+                transactions.get_generator(*gen_id)?,
+            ))
+        })
+        .flat_map(|(container, time_unit, stream, generator)| {
+            generator
+                .transactions
+                .iter()
+                .map(move |tx| ExportedTransaction {
+                    container,
+                    time_unit,
+                    stream,
+                    generator,
+                    tx,
+                })
+        })
+        .sorted_by_key(|exported| {
+            (
+                inner.transaction_time(exported.container, &exported.tx.get_start_time()),
+                exported.container,
+                exported.tx.get_tx_id(),
+            )
+        })
+        .collect()
+}
+
+/// The time unit as written to CSV and JSON files, empty if the file has no unit
+fn time_unit_name(time_unit: TimeUnit) -> String {
+    match time_unit {
+        TimeUnit::None | TimeUnit::Auto => String::new(),
+        // Avoid the Greek letter for tools expecting ASCII
+        TimeUnit::MicroSeconds => "us".to_string(),
+        unit => unit.to_string(),
+    }
+}
+
+/// Converts `time` in `time_unit` to the microseconds used by trace events. Times without
+/// a unit are treated as microseconds.
+fn to_microseconds(time: &BigUint, time_unit: TimeUnit) -> f64 {
+    let exponent = match time_unit {
+        TimeUnit::None | TimeUnit::Auto => -6,
+        unit => unit.exponent(),
+    };
+    let time = time.to_f64().unwrap_or(f64::MAX);
+    let exponent = i32::from(exponent) + 6;
+    // Dividing keeps times like 1500 ns exact
+    if exponent < 0 {
+        time / 10f64.powi(-exponent)
+    } else {
+        time * 10f64.powi(exponent)
+    }
+}
+
+/// Quotes `field` if needed according to RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn attribute_value(tx: &Transaction, name: &str) -> Option<String> {
+    tx.attributes
+        .iter()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.value().to_string())
+}
+
+fn to_csv(transactions: &[ExportedTransaction]) -> String {
+    let attribute_names = transactions
+        .iter()
+        .flat_map(|exported| exported.tx.attributes.iter().map(|a| a.name.clone()))
+        .unique()
+        .collect_vec();
+
+    let mut csv = [
+        "file",
+        "id",
+        "stream",
+        "generator",
+        "start",
+        "end",
+        "time_unit",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    // Prefixed to not clash with the columns above
+    .chain(
+        attribute_names
+            .iter()
+            .map(|name| csv_field(&format!("attr.{name}"))),
+    )
+    .join(",");
+    csv.push('\n');
+    for ExportedTransaction {
+        container,
+        time_unit,
+        stream,
+        generator,
+        tx,
+    } in transactions
+    {
+        let row = [
+            container.to_string(),
+            tx.get_tx_id().to_string(),
+            csv_field(&stream.name),
+            csv_field(&generator.name),
+            tx.get_start_time().to_string(),
+            tx.get_end_time().to_string(),
+            time_unit_name(*time_unit),
+        ]
+        .into_iter()
+        .chain(
+            attribute_names
+                .iter()
+                .map(|name| csv_field(&attribute_value(tx, name).unwrap_or_default())),
+        )
+        .join(",");
+        writeln!(csv, "{row}").unwrap();
+    }
+    csv
+}
+
+fn attributes_json(tx: &Transaction) -> Map<String, Value> {
+    tx.attributes
+        .iter()
+        .map(|attribute| {
+            (
+                attribute.name.clone(),
+                Value::String(attribute.value().to_string()),
+            )
+        })
+        .collect()
+}
+
+fn to_json(transactions: &[ExportedTransaction]) -> Value {
+    let transactions = transactions
+        .iter()
+        .map(
+            |ExportedTransaction {
+                 container,
+                 time_unit,
+                 stream,
+                 generator,
+                 tx,
+             }| {
+                json!({
+                    "file": container,
+                    "id": tx.get_tx_id(),
+                    "stream": stream.name,
+                    "generator": generator.name,
+                    // Times can exceed the integers supported by many JSON parsers
+                    "start": tx.get_start_time().to_string(),
+                    "end": tx.get_end_time().to_string(),
+                    "time_unit": time_unit_name(*time_unit),
+                    "attributes": attributes_json(tx),
+                    "relations": tx.out_relations.iter().map(|relation| relation.sink_tx_id).collect_vec(),
+                })
+            },
+        )
+        .collect_vec();
+    json!({ "transactions": transactions })
+}
+
+/// Trace events where the streams are processes and the generators threads. Overlapping
+/// transactions of a generator are put in separate threads, one per row, since slices in
+/// a thread must nest. Relations become flow events.
+fn to_perfetto(transactions: &[ExportedTransaction]) -> Value {
+    let mut events = vec![];
+    let mut processes = HashMap::new();
+    let mut threads = HashMap::new();
+    let mut slices = HashMap::new();
+    for ExportedTransaction {
+        container,
+        time_unit,
+        stream,
+        generator,
+        tx,
+    } in transactions
+    {
+        let next_pid = processes.len() + 1;
+        let pid = *processes.entry((*container, stream.id)).or_insert_with(|| {
+            events.push(json!({
+                "ph": "M", "name": "process_name", "pid": next_pid,
+                "args": { "name": stream.name },
+            }));
+            next_pid
+        });
+        let next_tid = threads.len() + 1;
+        let tid = *threads
+            .entry((*container, generator.id, tx.row))
+            .or_insert_with(|| {
+                let name = if tx.row == 0 {
+                    generator.name.clone()
+                } else {
+                    format!("{} ({})", generator.name, tx.row)
+                };
+                events.push(json!({
+                    "ph": "M", "name": "thread_name", "pid": pid, "tid": next_tid,
+                    "args": { "name": name },
+                }));
+                events.push(json!({
+                    "ph": "M", "name": "thread_sort_index", "pid": pid, "tid": next_tid,
+                    "args": { "sort_index": next_tid },
+                }));
+                next_tid
+            });
+
+        let start = to_microseconds(&tx.get_start_time(), *time_unit);
+        let end = to_microseconds(&tx.get_end_time(), *time_unit);
+        let mut args = attributes_json(tx);
+        args.insert("tx_id".to_string(), json!(tx.get_tx_id()));
+        events.push(json!({
+            "ph": "X",
+            "name": generator.name,
+            "cat": stream.name,
+            "pid": pid,
+            "tid": tid,
+            "ts": start,
+            "dur": end - start,
+            "args": args,
+        }));
+        slices.insert((*container, tx.get_tx_id()), (pid, tid, start));
+    }
+
+    // Flow events bind to the slices enclosing their timestamps
+    let mut flow_id = 0;
+    for ExportedTransaction { container, tx, .. } in transactions {
+        for relation in &tx.out_relations {
+            let (Some(source), Some(sink)) = (
+                slices.get(&(*container, relation.source_tx_id)),
+                slices.get(&(*container, relation.sink_tx_id)),
+            ) else {
+                continue;
+            };
+            flow_id += 1;
+            events.push(json!({
+                "ph": "s", "name": "relation", "cat": "relation", "id": flow_id,
+                "pid": source.0, "tid": source.1, "ts": source.2,
+            }));
+            events.push(json!({
+                "ph": "f", "bp": "e", "name": "relation", "cat": "relation", "id": flow_id,
+                "pid": sink.0, "tid": sink.1, "ts": sink.2,
+            }));
+        }
+    }
+
+    json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ns",
+    })
+}
+
+/// Exports the transactions of `generators`, given as container and generator id, ordered
+/// by start time. Times are written in the time unit of their transaction file.
+pub fn export_transactions(
+    inner: &DataContainer,
+    generators: &[(usize, usize)],
+    format: TransactionExportFormat,
+) -> Result<String> {
+    let exported = exported_transactions(inner, generators);
+    Ok(match format {
+        TransactionExportFormat::Csv => to_csv(&exported),
+        TransactionExportFormat::Json => serde_json::to_string_pretty(&to_json(&exported))?,
+        TransactionExportFormat::Perfetto => serde_json::to_string(&to_perfetto(&exported))?,
+    })
+}
+
+impl SystemState {
+    /// Exports the transactions of the displayed `streams`, or of all streams if `None`
+    pub(crate) fn export_transactions(
+        &self,
+        format: TransactionExportFormat,
+        streams: Option<Vec<DisplayedItemRef>>,
+        path: Option<PathBuf>,
+    ) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        let generators = match streams {
+            None => waves
+                .inner
+                .transaction_containers()
+                .iter()
+                .enumerate()
+                .flat_map(|(container, inner)| {
+                    inner
+                        .get_generators()
+                        .into_iter()
+                        .map(move |gen| (container, gen.id))
+                })
+                .collect_vec(),
+            Some(streams) => streams
+                .iter()
+                .filter_map(|item_ref| match waves.displayed_items.get(item_ref) {
+                    Some(DisplayedItem::Stream(s)) => Some(&s.transaction_stream_ref),
+                    _ => None,
+                })
+                .flat_map(|stream_ref| match stream_ref.gen_id {
+                    Some(gen_id) => vec![(stream_ref.container, gen_id)],
+                    None => waves
+                        .inner
+                        .transactions(stream_ref.container)
+                        .and_then(|inner| inner.get_stream(stream_ref.stream_id))
+                        .map(|stream| {
+                            stream
+                                .generators
+                                .iter()
+                                .map(|gen_id| (stream_ref.container, *gen_id))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect_vec(),
+        };
+        if generators.is_empty() {
+            return;
+        }
+        let encoded = match export_transactions(&waves.inner, &generators, format) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                log::error!("Failed to export transactions: {e:#}");
+                return;
+            }
+        };
+
+        perform_async_work(async move {
+            let destination = if let Some(path) = path {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    Some(path.into())
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    None
+                }
+            } else {
+                save_file_dialog(
+                    "Export transactions",
+                    (
+                        format!("{} files (*.{})", format.description(), format.extension()),
+                        vec![format.extension().to_string()],
+                    ),
+                )
+                .await
+            };
+            let Some(destination) = destination else {
+                return;
+            };
+            destination
+                .write(encoded.as_bytes())
+                .await
+                .map_err(|e| {
+                    log::error!("Failed to export transactions to {destination:#?} {e:#?}")
+                })
+                .ok();
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use project_root::get_project_root;
+
+    use super::*;
+    use crate::transaction_container::TransactionContainer;
+
+    /// `examples/my_db.ftr` exported with all its generators
+    fn export_my_db(format: TransactionExportFormat) -> (TransactionContainer, String) {
+        let path = get_project_root().unwrap().join("examples/my_db.ftr");
+        let transactions = TransactionContainer {
+            inner: ftr_parser::parse::parse_ftr(path).unwrap(),
+        };
+        let generators = transactions
+            .get_generators()
+            .into_iter()
+            .map(|gen| (0, gen.id))
+            .sorted()
+            .collect_vec();
+        let inner = DataContainer::Transactions(transactions);
+        let exported = export_transactions(&inner, &generators, format).unwrap();
+        let DataContainer::Transactions(transactions) = inner else {
+            unreachable!()
+        };
+        (transactions, exported)
+    }
+
+    fn first_transaction(transactions: &TransactionContainer) -> (&TxGenerator, &Transaction) {
+        transactions
+            .get_generators()
+            .into_iter()
+            .flat_map(|gen| gen.transactions.iter().map(move |tx| (gen, tx)))
+            .find(|(_, tx)| tx.get_tx_id() == 1)
+            .unwrap()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("OKAY"), "OKAY");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn times_are_converted_to_microseconds() {
+        let time = BigUint::from(1500u32);
+        assert_eq!(to_microseconds(&time, TimeUnit::NanoSeconds), 1.5);
+        assert_eq!(to_microseconds(&time, TimeUnit::MicroSeconds), 1500.);
+        assert_eq!(to_microseconds(&time, TimeUnit::MilliSeconds), 1_500_000.);
+        assert_eq!(to_microseconds(&time, TimeUnit::None), 1500.);
+    }
+
+    #[test]
+    fn time_units_are_ascii() {
+        assert_eq!(time_unit_name(TimeUnit::MicroSeconds), "us");
+        assert_eq!(time_unit_name(TimeUnit::PicoSeconds), "ps");
+        assert_eq!(time_unit_name(TimeUnit::None), "");
+    }
+
+    #[test]
+    fn csv_has_a_row_per_transaction() {
+        let (transactions, csv) = export_my_db(TransactionExportFormat::Csv);
+        let lines = csv.lines().collect_vec();
+        assert_eq!(
+            lines[0],
+            "file,id,stream,generator,start,end,time_unit,attr.addr,attr.data_size,attr.data"
+        );
+        let total = transactions
+            .get_generators()
+            .iter()
+            .map(|gen| gen.transactions.len())
+            .sum::<usize>();
+        assert_eq!(lines.len(), total + 1);
+
+        // Transactions 1 and 2 both start at 0
+        let (gen, tx) = first_transaction(&transactions);
+        let stream = transactions
+            .get_streams()
+            .into_iter()
+            .find(|stream| stream.generators.contains(&gen.id))
+            .unwrap();
+        let value = |name: &str| attribute_value(tx, name).unwrap();
+        assert_eq!(
+            lines[1],
+            format!(
+                "0,1,{},{},0,280000,ps,{},{},{}",
+                stream.name,
+                gen.name,
+                value("addr"),
+                value("data_size"),
+                value("data")
+            )
+        );
+        // Attributes missing in a transaction are left empty
+        assert!(lines[2].starts_with("0,2,"));
+        assert!(lines[2].ends_with(",,"));
+    }
+
+    #[test]
+    fn json_has_attributes_and_relations() {
+        let (transactions, json) = export_my_db(TransactionExportFormat::Json);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let exported = json["transactions"].as_array().unwrap();
+        assert_eq!(exported.len(), 54);
+
+        let (gen, tx) = first_transaction(&transactions);
+        let first = &exported[0];
+        assert_eq!(first["file"], 0);
+        assert_eq!(first["id"], 1);
+        assert_eq!(first["generator"], gen.name.as_str());
+        assert_eq!(first["start"], "0");
+        assert_eq!(first["end"], "280000");
+        assert_eq!(first["time_unit"], "ps");
+        let attributes = first["attributes"].as_object().unwrap();
+        assert_eq!(
+            attributes.keys().sorted().collect_vec(),
+            ["addr", "data", "data_size"]
+        );
+        assert_eq!(
+            attributes["data_size"],
+            attribute_value(tx, "data_size").unwrap()
+        );
+        let mut relations = first["relations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|id| id.as_u64().unwrap())
+            .collect_vec();
+        relations.sort();
+        assert_eq!(relations, [2, 3]);
+    }
+
+    #[test]
+    fn perfetto_has_slices_in_microseconds_and_flows() {
+        let (transactions, trace) = export_my_db(TransactionExportFormat::Perfetto);
+        let trace: Value = serde_json::from_str(&trace).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let with_phase = |phase: &str| events.iter().filter(|e| e["ph"] == phase).collect_vec();
+
+        let slices = with_phase("X");
+        assert_eq!(slices.len(), 54);
+        let (gen, _) = first_transaction(&transactions);
+        let first = slices
+            .iter()
+            .find(|slice| slice["args"]["tx_id"] == 1)
+            .unwrap();
+        assert_eq!(first["name"], gen.name.as_str());
+        // 0 ps to 280000 ps
+        assert_eq!(first["ts"], 0.);
+        assert_eq!(first["dur"], 0.28);
+
+        // Every relation of the file becomes a flow from its source to its sink
+        let starts = with_phase("s");
+        let finishes = with_phase("f");
+        assert_eq!(starts.len(), 36);
+        assert_eq!(finishes.len(), 36);
+        for (start, finish) in starts.iter().zip(&finishes) {
+            assert_eq!(start["id"], finish["id"]);
+        }
+        let from_first = starts
+            .iter()
+            .filter(|start| start["pid"] == first["pid"] && start["tid"] == first["tid"])
+            .filter(|start| start["ts"] == 0.)
+            .count();
+        assert_eq!(from_first, 2);
+    }
+}
@@ -0,0 +1,385 @@
+//! Finding transactions by the values of their attributes.
+//!
+//! A query compares attributes with values, for example `addr == 0x4000` or
+//! `resp != OKAY && len > 4`. Comparisons can be combined with `&&`/`and`, `||`/`or`
+//! and `!`/`not`, and grouped using parentheses.
+//!
+//! Transactions without an attribute called `duration` get one with the difference between
+//! their end and start time, in the time unit of their file.
+//!
+//! Attributes starting with `$` are built in and never refer to attributes of the
+//! transaction: `$duration` is the duration as above, `$generator` the id of the generator
+//! and `$file` the index of the transaction file.
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use ftr_parser::types::Transaction;
+use num::{BigInt, Num};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    /// The attribute value contains the string
+    Contains,
+}
+
+impl Display for CompareOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            CompareOp::Equal => "==",
+            CompareOp::NotEqual => "!=",
+            CompareOp::Less => "<",
+            CompareOp::LessEqual => "<=",
+            CompareOp::Greater => ">",
+            CompareOp::GreaterEqual => ">=",
+            CompareOp::Contains => "~=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionQuery {
+    Compare {
+        attribute: String,
+        op: CompareOp,
+        value: String,
+    },
+    And(Box<TransactionQuery>, Box<TransactionQuery>),
+    Or(Box<TransactionQuery>, Box<TransactionQuery>),
+    Not(Box<TransactionQuery>),
+}
+
+/// Parses integers in decimal, or hexadecimal and binary with a `0x` or `0b` prefix
+fn parse_integer(s: &str) -> Option<BigInt> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let value = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        BigInt::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        BigInt::from_str_radix(bin, 2).ok()
+    } else {
+        BigInt::from_str_radix(s, 10).ok()
+    }?;
+    Some(if negative { -value } else { value })
+}
+
+impl TransactionQuery {
+    /// Evaluates the query using `attribute_value` to look up the values of attributes.
+    /// Comparisons with attributes that do not exist never match.
+    pub fn matches_with(&self, attribute_value: &impl Fn(&str) -> Option<String>) -> bool {
+        match self {
+            TransactionQuery::Compare {
+                attribute,
+                op,
+                value,
+            } => {
+                let Some(actual) = attribute_value(attribute) else {
+                    return false;
+                };
+                if *op == CompareOp::Contains {
+                    return actual.contains(value.as_str());
+                }
+                // Compare as numbers if both sides are, to make `0x10 == 16` hold
+                let ordering = match (parse_integer(actual.trim()), parse_integer(value)) {
+                    (Some(actual), Some(value)) => actual.cmp(&value),
+                    _ => actual.trim().cmp(value.as_str()),
+                };
+                match op {
+                    CompareOp::Equal => ordering == Ordering::Equal,
+                    CompareOp::NotEqual => ordering != Ordering::Equal,
+                    CompareOp::Less => ordering == Ordering::Less,
+                    CompareOp::LessEqual => ordering != Ordering::Greater,
+                    CompareOp::Greater => ordering == Ordering::Greater,
+                    CompareOp::GreaterEqual => ordering != Ordering::Less,
+                    CompareOp::Contains => unreachable!(),
+                }
+            }
+            TransactionQuery::And(lhs, rhs) => {
+                lhs.matches_with(attribute_value) && rhs.matches_with(attribute_value)
+            }
+            TransactionQuery::Or(lhs, rhs) => {
+                lhs.matches_with(attribute_value) || rhs.matches_with(attribute_value)
+            }
+            TransactionQuery::Not(query) => !query.matches_with(attribute_value),
+        }
+    }
+
+    /// Whether `tx` from the transaction file with index `container` matches the query
+    pub fn matches(&self, container: usize, tx: &Transaction) -> bool {
+        let duration =
+            || (BigInt::from(tx.get_end_time()) - BigInt::from(tx.get_start_time())).to_string();
+        self.matches_with(&|name| match name {
+            "$duration" => Some(duration()),
+            "$generator" => Some(tx.get_gen_id().to_string()),
+            "$file" => Some(container.to_string()),
+            _ => tx
+                .attributes
+                .iter()
+                .find(|attribute| attribute.name == name)
+                .map(|attribute| attribute.value().to_string())
+                .or_else(|| (name == "duration").then(duration)),
+        })
+    }
+}
+
+impl Display for TransactionQuery {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionQuery::Compare {
+                attribute,
+                op,
+                value,
+            } => write!(f, "{} {op} {}", quoted(attribute), quoted(value)),
+            TransactionQuery::And(lhs, rhs) => write!(f, "({lhs} && {rhs})"),
+            TransactionQuery::Or(lhs, rhs) => write!(f, "({lhs} || {rhs})"),
+            TransactionQuery::Not(query) => write!(f, "!{query}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | ':' | '[' | ']' | '/' | '$')
+}
+
+/// `word` in quotes if it would not be parsed as a single word otherwise
+fn quoted(word: &str) -> String {
+    let keyword = ["and", "or", "not"].contains(&word.to_lowercase().as_str());
+    if word.is_empty() || keyword || word.contains(|c: char| !is_word_char(c)) {
+        format!("\"{word}\"")
+    } else {
+        word.to_string()
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    // Longer operators first so that `<=` is not read as `<`
+    let operators = [
+        ("==", Token::Op(CompareOp::Equal)),
+        ("!=", Token::Op(CompareOp::NotEqual)),
+        ("<=", Token::Op(CompareOp::LessEqual)),
+        (">=", Token::Op(CompareOp::GreaterEqual)),
+        ("~=", Token::Op(CompareOp::Contains)),
+        ("&&", Token::And),
+        ("||", Token::Or),
+        ("=", Token::Op(CompareOp::Equal)),
+        ("<", Token::Op(CompareOp::Less)),
+        (">", Token::Op(CompareOp::Greater)),
+        ("!", Token::Not),
+        ("(", Token::LParen),
+        (")", Token::RParen),
+    ];
+
+    let mut tokens = vec![];
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if let Some((op, token)) = operators.iter().find(|(op, _)| rest.starts_with(op)) {
+            tokens.push(token.clone());
+            rest = &rest[op.len()..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| format!("Missing closing {c}"))?;
+            tokens.push(Token::Quoted(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else if is_word_char(c) {
+            let end = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            tokens.push(match rest[..end].to_lowercase().as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                _ => Token::Word(rest[..end].to_string()),
+            });
+            rest = &rest[end..];
+        } else {
+            return Err(format!("Unexpected '{c}'"));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser where `&&` binds tighter than `||`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<TransactionQuery, String> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            query = TransactionQuery::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<TransactionQuery, String> {
+        let mut query = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            query = TransactionQuery::And(Box::new(query), Box::new(self.unary()?));
+        }
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<TransactionQuery, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(TransactionQuery::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(query),
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(Token::Word(attribute)) | Some(Token::Quoted(attribute)) => {
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(format!("Expected comparison after '{attribute}'")),
+                };
+                let value = match self.next() {
+                    Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+                    _ => return Err(format!("Expected value after '{attribute} {op}'")),
+                };
+                Ok(TransactionQuery::Compare {
+                    attribute,
+                    op,
+                    value,
+                })
+            }
+            Some(token) => Err(format!("Unexpected {token:?}")),
+            None => Err("Unexpected end of query".to_string()),
+        }
+    }
+}
+
+impl FromStr for TransactionQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(format!("Unexpected {token:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matches(query: &str, attributes: &[(&str, &str)]) -> bool {
+        TransactionQuery::from_str(query)
+            .unwrap()
+            .matches_with(&|name| {
+                attributes
+                    .iter()
+                    .find(|(attribute, _)| *attribute == name)
+                    .map(|(_, value)| value.to_string())
+            })
+    }
+
+    #[test]
+    fn comparisons_match() {
+        let tx = [("addr", "16384"), ("resp", "OKAY"), ("data", "0xff")];
+        assert!(matches("addr == 0x4000", &tx));
+        assert!(matches("addr = 16384", &tx));
+        assert!(!matches("addr != 0x4000", &tx));
+        assert!(matches("addr > 0x3fff", &tx));
+        assert!(matches("addr <= 16384", &tx));
+        assert!(!matches("addr < 16384", &tx));
+        assert!(matches("data >= 255", &tx));
+        assert!(matches("resp == OKAY", &tx));
+        assert!(matches("resp != SLVERR", &tx));
+        assert!(matches("resp ~= KA", &tx));
+        // Missing attributes never match
+        assert!(!matches("len != 4", &tx));
+    }
+
+    #[test]
+    fn queries_combine() {
+        let tx = [("addr", "16384"), ("resp", "OKAY")];
+        assert!(matches("addr == 0x4000 && resp == OKAY", &tx));
+        assert!(!matches("addr == 0x4000 and resp != OKAY", &tx));
+        assert!(matches("addr == 0 || resp == OKAY", &tx));
+        assert!(matches("!(addr == 0)", &tx));
+        assert!(matches(
+            "not addr == 0 and (resp == ERR or resp == OKAY)",
+            &tx
+        ));
+        // && binds tighter than ||
+        assert!(matches("resp == OKAY || addr == 0 && resp == ERR", &tx));
+    }
+
+    #[test]
+    fn quoted_values_are_parsed() {
+        assert_eq!(
+            TransactionQuery::from_str("cmd == \"read burst\""),
+            Ok(TransactionQuery::Compare {
+                attribute: "cmd".to_string(),
+                op: CompareOp::Equal,
+                value: "read burst".to_string(),
+            })
+        );
+        assert!(matches("cmd ~= 'burst'", &[("cmd", "read burst")]));
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        assert!(TransactionQuery::from_str("").is_err());
+        assert!(TransactionQuery::from_str("addr").is_err());
+        assert!(TransactionQuery::from_str("addr ==").is_err());
+        assert!(TransactionQuery::from_str("(addr == 1").is_err());
+        assert!(TransactionQuery::from_str("addr == 1 resp").is_err());
+        assert!(TransactionQuery::from_str("cmd == \"read").is_err());
+    }
+
+    #[test]
+    fn queries_are_displayed() {
+        let query = TransactionQuery::from_str("!a == 1 && b ~= \"x y\" || c < \"or\"").unwrap();
+        assert_eq!(
+            query.to_string(),
+            "((!a == 1 && b ~= \"x y\") || c < \"or\")"
+        );
+        assert_eq!(TransactionQuery::from_str(&query.to_string()), Ok(query));
+    }
+}
@@ -0,0 +1,350 @@
+//! Searching for transactions matching a [`TransactionQuery`].
+use std::str::FromStr;
+
+use egui::{CollapsingHeader, Context, RichText, ScrollArea, TextEdit, Window};
+use egui_remixicon::icons;
+use emath::{Align2, Vec2};
+use ftr_parser::types::Transaction;
+use itertools::Itertools;
+use log::error;
+use num::BigInt;
+
+use crate::displayed_item::DisplayedItem;
+use crate::drawing_canvas::transaction_tooltip_table;
+use crate::message::Message;
+use crate::transaction_container::TransactionRef;
+use crate::transaction_query::TransactionQuery;
+use crate::wave_data::WaveData;
+use crate::SystemState;
+
+/// Stop collecting matches after this many, each match is a row in the results
+const MAX_RESULTS: usize = 1_000;
+
+/// The transactions matching a query, kept until the query changes
+pub(crate) struct TransactionSearchResults {
+    query: TransactionQuery,
+    /// Container, generator id and index of the transaction in the generator, sorted by
+    /// start time
+    hits: Vec<(usize, usize, usize)>,
+    truncated: bool,
+}
+
+/// A transaction matching a query
+struct Match<'a> {
+    container: usize,
+    gen_id: usize,
+    idx: usize,
+    /// Aligned start time, on the time axis of the waveform
+    start: BigInt,
+    tx: &'a Transaction,
+}
+
+impl Match<'_> {
+    fn key(&self) -> (BigInt, usize, usize) {
+        (self.start.clone(), self.container, self.tx.get_tx_id())
+    }
+
+    fn tx_ref(&self) -> TransactionRef {
+        TransactionRef {
+            container: self.container,
+            id: self.tx.get_tx_id(),
+        }
+    }
+}
+
+/// Matching transactions in the generators, given as container and generator id, sorted by
+/// start time and id
+fn matching_transactions<'a>(
+    waves: &'a WaveData,
+    generators: impl IntoIterator<Item = (usize, usize)>,
+    query: &TransactionQuery,
+) -> Vec<Match<'a>> {
+    generators
+        .into_iter()
+        .filter_map(|(container, gen_id)| {
+            Some((
+                container,
+                waves.inner.transactions(container)?.get_generator(gen_id)?,
+            ))
+        })
+        .flat_map(|(container, gen)| {
+            gen.transactions
+                .iter()
+                .enumerate()
+                .filter(move |(_, tx)| query.matches(container, tx))
+                .map(move |(idx, tx)| Match {
+                    container,
+                    gen_id: gen.id,
+                    idx,
+                    start: waves.transaction_time(container, &tx.get_start_time()),
+                    tx,
+                })
+        })
+        .sorted_by_key(Match::key)
+        .collect()
+}
+
+/// The generators of the visible streams as container and generator id
+pub(crate) fn visible_generators(waves: &WaveData) -> Vec<(usize, usize)> {
+    waves
+        .items_tree
+        .iter_visible()
+        .flat_map(|node| match &waves.displayed_items[&node.item_ref] {
+            DisplayedItem::Stream(s) => {
+                let stream_ref = &s.transaction_stream_ref;
+                match stream_ref.gen_id {
+                    Some(gen_id) => vec![(stream_ref.container, gen_id)],
+                    None => waves
+                        .inner
+                        .transactions(stream_ref.container)
+                        .and_then(|inner| inner.get_stream(stream_ref.stream_id))
+                        .map(|stream| {
+                            stream
+                                .generators
+                                .iter()
+                                .map(|gen_id| (stream_ref.container, *gen_id))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                }
+            }
+            _ => vec![],
+        })
+        .unique()
+        .collect()
+}
+
+impl SystemState {
+    /// Forgets the search results, to be called when the transactions change
+    pub(crate) fn invalidate_transaction_search(&self) {
+        *self.transaction_search_results.borrow_mut() = None;
+    }
+
+    pub(crate) fn set_transaction_query(&mut self, query: Option<String>) {
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        waves.transaction_query = match query.as_deref().map(TransactionQuery::from_str) {
+            None => None,
+            Some(Ok(query)) => Some(query),
+            Some(Err(e)) => {
+                error!("Invalid transaction query: {e}");
+                return;
+            }
+        };
+        if let Some(query) = query {
+            *self.transaction_search_text.borrow_mut() = query;
+        }
+        self.invalidate_draw_commands();
+    }
+
+    /// Focuses the next or previous transaction matching the query in the displayed
+    /// streams and moves the cursor to it
+    pub(crate) fn move_to_matching_transaction(&mut self, next: bool) {
+        let undo_msg = if next {
+            "Move to next matching transaction"
+        } else {
+            "Move to previous matching transaction"
+        };
+        self.save_current_canvas(undo_msg.to_string());
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        let Some(query) = &waves.transaction_query else {
+            return;
+        };
+        let matches = matching_transactions(waves, visible_generators(waves), query);
+
+        let focused = match &waves.focused_transaction {
+            (Some(tx_ref), Some(tx)) if tx_ref.id == tx.get_tx_id() => Some((
+                waves.transaction_time(tx_ref.container, &tx.get_start_time()),
+                tx_ref.container,
+                tx_ref.id,
+            )),
+            _ => None,
+        };
+        let target = match (focused, next) {
+            (Some(focused), true) => matches.iter().find(|m| m.key() > focused),
+            (Some(focused), false) => matches.iter().rev().find(|m| m.key() < focused),
+            (None, true) => matches.first(),
+            (None, false) => matches.last(),
+        };
+        let Some(target) = target else {
+            return;
+        };
+        let tx_ref = target.tx_ref();
+        let tx = target.tx.clone();
+        let start = target.start.clone();
+
+        waves.cursor = Some(start);
+        waves.focused_transaction = (Some(tx_ref), Some(tx));
+        waves.go_to_cursor_if_not_in_view();
+        self.invalidate_draw_commands();
+    }
+
+    fn update_transaction_search(&self, waves: &WaveData) {
+        let Some(query) = &waves.transaction_query else {
+            *self.transaction_search_results.borrow_mut() = None;
+            return;
+        };
+        if self
+            .transaction_search_results
+            .borrow()
+            .as_ref()
+            .is_some_and(|results| &results.query == query)
+        {
+            return;
+        }
+        let generators = waves
+            .inner
+            .transaction_containers()
+            .iter()
+            .enumerate()
+            .flat_map(|(container, inner)| {
+                inner
+                    .get_generators()
+                    .into_iter()
+                    .map(move |gen| (container, gen.id))
+            })
+            .collect_vec();
+        let mut hits = matching_transactions(waves, generators, query)
+            .into_iter()
+            .map(|m| (m.container, m.gen_id, m.idx))
+            .collect_vec();
+        let truncated = hits.len() > MAX_RESULTS;
+        hits.truncate(MAX_RESULTS);
+        *self.transaction_search_results.borrow_mut() = Some(TransactionSearchResults {
+            query: query.clone(),
+            hits,
+            truncated,
+        });
+    }
+
+    pub fn draw_transaction_search_window(
+        &self,
+        waves: &WaveData,
+        ctx: &Context,
+        msgs: &mut Vec<Message>,
+    ) {
+        if !waves.inner.is_transactions() {
+            return;
+        }
+        self.update_transaction_search(waves);
+
+        let mut open = true;
+        Window::new("Search transactions")
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(Vec2::new(500., 400.))
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                let mut text = self.transaction_search_text.borrow_mut();
+                let error = ui
+                    .horizontal(|ui| {
+                        let response = ui.add(
+                            TextEdit::singleline(&mut *text)
+                                .hint_text("addr == 0x4000 && resp != OKAY"),
+                        );
+                        let error = if text.trim().is_empty() {
+                            None
+                        } else {
+                            TransactionQuery::from_str(&text).err()
+                        };
+                        if response.changed() {
+                            if text.trim().is_empty() {
+                                msgs.push(Message::SetTransactionQuery(None));
+                            } else if error.is_none() {
+                                msgs.push(Message::SetTransactionQuery(Some(text.clone())));
+                            }
+                        }
+                        let has_query = waves.transaction_query.is_some();
+                        if ui
+                            .add_enabled(has_query, egui::Button::new(icons::CONTRACT_LEFT_FILL))
+                            .on_hover_text("Previous match")
+                            .clicked()
+                        {
+                            msgs.push(Message::MoveMatchingTransaction { next: false });
+                        }
+                        if ui
+                            .add_enabled(has_query, egui::Button::new(icons::CONTRACT_RIGHT_FILL))
+                            .on_hover_text("Next match")
+                            .clicked()
+                        {
+                            msgs.push(Message::MoveMatchingTransaction { next: true });
+                        }
+                        error
+                    })
+                    .inner;
+                if let Some(error) = error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                let results = self.transaction_search_results.borrow();
+                let Some(results) = results.as_ref() else {
+                    return;
+                };
+                if results.truncated {
+                    ui.label(format!("Showing the first {MAX_RESULTS} matches"));
+                } else {
+                    ui.label(format!("{} matches", results.hits.len()));
+                }
+                ui.separator();
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    for (container, gen_id, idx) in &results.hits {
+                        let Some(inner) = waves.inner.transactions(*container) else {
+                            continue;
+                        };
+                        let time_scale = inner.inner.time_scale;
+                        let Some(gen) = inner.get_generator(*gen_id) else {
+                            continue;
+                        };
+                        let Some(tx) = gen.transactions.get(*idx) else {
+                            continue;
+                        };
+                        let tx_ref = TransactionRef {
+                            container: *container,
+                            id: tx.get_tx_id(),
+                        };
+                        let focused = waves.focused_transaction.0.as_ref() == Some(&tx_ref);
+                        ui.push_id((tx_ref.container, tx_ref.id), |ui| {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .small_button("Focus")
+                                    .on_hover_text("Focus transaction")
+                                    .clicked()
+                                {
+                                    msgs.push(Message::FocusTransaction(
+                                        Some(tx_ref.clone()),
+                                        Some(tx.clone()),
+                                    ));
+                                    let start =
+                                        waves.transaction_time(*container, &tx.get_start_time());
+                                    msgs.push(Message::GoToTime(Some(start), 0));
+                                }
+                                let mut title = RichText::new(format!(
+                                    "tx#{}: {} {}{} - {}{}",
+                                    tx_ref.id,
+                                    gen.name,
+                                    tx.get_start_time(),
+                                    time_scale,
+                                    tx.get_end_time(),
+                                    time_scale,
+                                ));
+                                if focused {
+                                    title = title.strong();
+                                }
+                                CollapsingHeader::new(title).show(ui, |ui| {
+                                    transaction_tooltip_table(ui, tx);
+                                });
+                            });
+                        });
+                    }
+                });
+            });
+        if !open {
+            msgs.push(Message::SetTransactionSearchVisible(false));
+        }
+    }
+}
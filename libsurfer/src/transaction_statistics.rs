@@ -0,0 +1,431 @@
+//! Durations and throughput of the transactions of a stream or generator.
+use std::str::FromStr;
+
+use egui::{Context, Grid, Window};
+use egui_plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints};
+use emath::Vec2;
+use itertools::Itertools;
+use num::ToPrimitive;
+
+use crate::message::Message;
+use crate::transaction_container::{TransactionContainer, TransactionRef, TransactionStreamRef};
+use crate::transaction_query::TransactionQuery;
+use crate::wave_data::WaveData;
+use crate::SystemState;
+
+/// Number of buckets in the duration histogram
+const HISTOGRAM_BUCKETS: u64 = 32;
+/// Number of windows the transactions are counted in for the throughput plot
+const THROUGHPUT_WINDOWS: u64 = 100;
+const PERCENTILES: [u64; 4] = [50, 90, 95, 99];
+
+/// A transaction of the analyzed generators, with times in the unit of its file
+struct Sample {
+    gen_id: usize,
+    idx: usize,
+    start: u64,
+    duration: u64,
+}
+
+/// Durations in `start..end`
+#[derive(Debug, PartialEq)]
+struct Bucket {
+    start: u64,
+    end: u64,
+    count: usize,
+}
+
+/// The statistics of a stream or generator, kept until it changes
+pub(crate) struct TransactionStatistics {
+    stream: TransactionStreamRef,
+    /// The analyzed generators
+    gen_ids: Vec<usize>,
+    /// Sorted by start time
+    samples: Vec<Sample>,
+    sorted_durations: Vec<u64>,
+    buckets: Vec<Bucket>,
+    /// The length of the windows the throughput is counted in
+    window: u64,
+    /// Start of each window and the number of transactions starting in it
+    throughput: Vec<(u64, usize)>,
+}
+
+/// The value below which `percent` percent of the `sorted` values are, using the nearest rank
+fn percentile(sorted: &[u64], percent: u64) -> Option<u64> {
+    let rank = (sorted.len() as u64 * percent).div_ceil(100).max(1);
+    sorted.get(rank as usize - 1).copied()
+}
+
+/// Divides the range of the `sorted` durations into at most `buckets` buckets of equal width
+fn histogram(sorted: &[u64], buckets: u64) -> Vec<Bucket> {
+    let (Some(min), Some(max)) = (sorted.first(), sorted.last()) else {
+        return vec![];
+    };
+    let width = (max - min) / buckets + 1;
+    (0..=(max - min) / width)
+        .map(|bucket| {
+            let start = min + bucket * width;
+            let end = start + width;
+            Bucket {
+                start,
+                end,
+                count: sorted.partition_point(|d| *d < end)
+                    - sorted.partition_point(|d| *d < start),
+            }
+        })
+        .collect()
+}
+
+/// Counts the `sorted` start times in at most `windows` windows of equal length, returning the
+/// length and the start and count of each window
+fn throughput(sorted: &[u64], windows: u64) -> (u64, Vec<(u64, usize)>) {
+    let (Some(first), Some(last)) = (sorted.first(), sorted.last()) else {
+        return (1, vec![]);
+    };
+    let window = (last - first) / windows + 1;
+    let mut counts = vec![0; ((last - first) / window + 1) as usize];
+    for start in sorted {
+        counts[((start - first) / window) as usize] += 1;
+    }
+    let counts = counts
+        .into_iter()
+        .enumerate()
+        .map(|(idx, count)| (first + idx as u64 * window, count))
+        .collect();
+    (window, counts)
+}
+
+impl TransactionStatistics {
+    fn new(inner: &TransactionContainer, stream: &TransactionStreamRef) -> Self {
+        let gen_ids = match stream.gen_id {
+            Some(gen_id) => vec![gen_id],
+            None => inner
+                .get_stream(stream.stream_id)
+                .map(|s| s.generators.clone())
+                .unwrap_or_default(),
+        };
+        let mut samples = gen_ids
+            .iter()
+            .filter_map(|gen_id| inner.get_generator(*gen_id))
+            .flat_map(|gen| {
+                gen.transactions.iter().enumerate().map(|(idx, tx)| {
+                    let start = tx.get_start_time().to_u64().unwrap_or(u64::MAX);
+                    let end = tx.get_end_time().to_u64().unwrap_or(u64::MAX);
+                    Sample {
+                        gen_id: gen.id,
+                        idx,
+                        start,
+                        duration: end.saturating_sub(start),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        samples.sort_by_key(|sample| sample.start);
+
+        let mut sorted_durations = samples.iter().map(|s| s.duration).collect::<Vec<_>>();
+        sorted_durations.sort_unstable();
+        let buckets = histogram(&sorted_durations, HISTOGRAM_BUCKETS);
+        let starts = samples.iter().map(|s| s.start).collect::<Vec<_>>();
+        let (window, throughput) = throughput(&starts, THROUGHPUT_WINDOWS);
+
+        TransactionStatistics {
+            stream: stream.clone(),
+            gen_ids,
+            samples,
+            sorted_durations,
+            buckets,
+            window,
+            throughput,
+        }
+    }
+
+    /// The query highlighting the transactions of the analyzed generators in `bucket`
+    fn bucket_query(&self, bucket: &Bucket) -> String {
+        let generators = self
+            .gen_ids
+            .iter()
+            .map(|gen_id| format!("$generator == {gen_id}"))
+            .join(" || ");
+        format!(
+            "$file == {} && ({generators}) && $duration >= {} && $duration < {}",
+            self.stream.container, bucket.start, bucket.end
+        )
+    }
+}
+
+impl SystemState {
+    /// Forgets the statistics, to be called when the transactions change
+    pub(crate) fn invalidate_transaction_statistics(&self) {
+        *self.transaction_statistics.borrow_mut() = None;
+    }
+
+    fn update_transaction_statistics(
+        &self,
+        inner: &TransactionContainer,
+        stream: &TransactionStreamRef,
+    ) {
+        if self
+            .transaction_statistics
+            .borrow()
+            .as_ref()
+            .is_some_and(|statistics| &statistics.stream == stream)
+        {
+            return;
+        }
+        *self.transaction_statistics.borrow_mut() = Some(TransactionStatistics::new(inner, stream));
+    }
+
+    pub fn draw_transaction_statistics_window(
+        &self,
+        waves: &WaveData,
+        stream: &TransactionStreamRef,
+        ctx: &Context,
+        msgs: &mut Vec<Message>,
+    ) {
+        let Some(inner) = waves.inner.transactions(stream.container) else {
+            msgs.push(Message::SetTransactionStatisticsStream(None));
+            return;
+        };
+        self.update_transaction_statistics(inner, stream);
+        let statistics = self.transaction_statistics.borrow();
+        let Some(statistics) = statistics.as_ref() else {
+            return;
+        };
+        let time_scale = inner.inner.time_scale;
+
+        let mut open = true;
+        Window::new(format!("Transaction statistics: {}", stream.name))
+            .id(egui::Id::new("Transaction statistics"))
+            .open(&mut open)
+            .collapsible(true)
+            .resizable(true)
+            .default_size(Vec2::new(600., 500.))
+            .show(ctx, |ui| {
+                let durations = &statistics.sorted_durations;
+                if durations.is_empty() {
+                    ui.label("No transactions");
+                    return;
+                }
+                Grid::new("transaction statistics")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Transactions");
+                        ui.monospace(durations.len().to_string());
+                        ui.end_row();
+                        ui.label("Min");
+                        ui.monospace(format!("{}{time_scale}", durations[0]));
+                        ui.end_row();
+                        ui.label("Max");
+                        ui.monospace(format!("{}{time_scale}", durations[durations.len() - 1]));
+                        ui.end_row();
+                        let mean = durations.iter().map(|d| *d as f64).sum::<f64>()
+                            / durations.len() as f64;
+                        ui.label("Mean");
+                        ui.monospace(format!("{mean:.1}{time_scale}"));
+                        ui.end_row();
+                        for percent in PERCENTILES {
+                            if let Some(value) = percentile(durations, percent) {
+                                ui.label(format!("{percent}th percentile"));
+                                ui.monospace(format!("{value}{time_scale}"));
+                                ui.end_row();
+                            }
+                        }
+                    });
+
+                ui.separator();
+                ui.label("Durations, click a bar to highlight its transactions");
+                let selected_query = waves.transaction_query.as_ref();
+                let bars = statistics
+                    .buckets
+                    .iter()
+                    .map(|bucket| {
+                        let bar =
+                            Bar::new((bucket.start + bucket.end) as f64 / 2., bucket.count as f64)
+                                .width((bucket.end - bucket.start) as f64)
+                                .name(format!(
+                                    "{}{time_scale} - {}{time_scale}",
+                                    bucket.start, bucket.end
+                                ));
+                        if selected_query
+                            == TransactionQuery::from_str(&statistics.bucket_query(bucket))
+                                .ok()
+                                .as_ref()
+                        {
+                            bar.fill(ui.visuals().selection.bg_fill)
+                        } else {
+                            bar
+                        }
+                    })
+                    .collect();
+                let clicked = Plot::new("transaction durations")
+                    .height(200.)
+                    .allow_scroll(false)
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(BarChart::new("Transactions", bars));
+                        if plot_ui.response().clicked() {
+                            plot_ui.pointer_coordinate()
+                        } else {
+                            None
+                        }
+                    })
+                    .inner;
+                let clicked_bucket = clicked.and_then(|point| {
+                    statistics
+                        .buckets
+                        .iter()
+                        .find(|b| b.start as f64 <= point.x && point.x < b.end as f64)
+                });
+                if let Some(bucket) = clicked_bucket.filter(|b| b.count > 0) {
+                    msgs.push(Message::SetTransactionQuery(Some(
+                        statistics.bucket_query(bucket),
+                    )));
+                    // Go to the first transaction in the bucket
+                    let first = statistics
+                        .samples
+                        .iter()
+                        .find(|s| bucket.start <= s.duration && s.duration < bucket.end)
+                        .and_then(|s| Some((s, inner.get_generator(s.gen_id)?)))
+                        .and_then(|(s, gen)| gen.transactions.get(s.idx));
+                    if let Some(tx) = first {
+                        msgs.push(Message::FocusTransaction(
+                            Some(TransactionRef {
+                                container: stream.container,
+                                id: tx.get_tx_id(),
+                            }),
+                            Some(tx.clone()),
+                        ));
+                        let start = waves.transaction_time(stream.container, &tx.get_start_time());
+                        msgs.push(Message::GoToTime(Some(start), 0));
+                    }
+                }
+
+                ui.separator();
+                ui.label(format!(
+                    "Transactions started per {}{time_scale}",
+                    statistics.window
+                ));
+                let points = statistics
+                    .throughput
+                    .iter()
+                    .map(|(start, count)| [*start as f64, *count as f64])
+                    .collect::<Vec<_>>();
+                Plot::new("transaction throughput")
+                    .legend(Legend::default())
+                    .allow_scroll(false)
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(Line::new("Transactions", PlotPoints::new(points)));
+                    });
+            });
+        if !open {
+            msgs.push(Message::SetTransactionStatisticsStream(None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use project_root::get_project_root;
+
+    use super::*;
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let durations = (1..=10).collect::<Vec<_>>();
+        assert_eq!(percentile(&durations, 50), Some(5));
+        assert_eq!(percentile(&durations, 90), Some(9));
+        assert_eq!(percentile(&durations, 99), Some(10));
+        assert_eq!(percentile(&durations, 0), Some(1));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn histogram_covers_all_durations() {
+        let durations = [2, 3, 3, 7, 10];
+        let buckets = histogram(&durations, 4);
+        assert_eq!(
+            buckets,
+            vec![
+                Bucket {
+                    start: 2,
+                    end: 5,
+                    count: 3
+                },
+                Bucket {
+                    start: 5,
+                    end: 8,
+                    count: 1
+                },
+                Bucket {
+                    start: 8,
+                    end: 11,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            histogram(&[4, 4], 32),
+            vec![Bucket {
+                start: 4,
+                end: 5,
+                count: 2
+            }]
+        );
+        assert!(histogram(&[], 32).is_empty());
+    }
+
+    #[test]
+    fn throughput_counts_starts_per_window() {
+        let (window, counts) = throughput(&[0, 1, 5, 9, 10], 2);
+        assert_eq!(window, 6);
+        assert_eq!(counts, vec![(0, 3), (6, 2)]);
+    }
+
+    /// Checks that the bucket queries of the statistics of `stream` together match exactly the
+    /// transactions of the generators `gen_ids` in the first file
+    fn assert_bucket_queries_match(stream: TransactionStreamRef, gen_ids: &[usize]) {
+        let path = get_project_root().unwrap().join("examples/my_db.ftr");
+        let inner = TransactionContainer {
+            inner: ftr_parser::parse::parse_ftr(path).unwrap(),
+        };
+        let statistics = TransactionStatistics::new(&inner, &stream);
+        assert!(!statistics.buckets.is_empty());
+        let queries = statistics
+            .buckets
+            .iter()
+            .map(|bucket| TransactionQuery::from_str(&statistics.bucket_query(bucket)).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut matched = 0;
+        for gen in inner.get_generators() {
+            for tx in &gen.transactions {
+                let matching = queries.iter().filter(|q| q.matches(0, tx)).count();
+                let expected = usize::from(gen_ids.contains(&gen.id));
+                assert_eq!(matching, expected, "{} tx#{}", gen.name, tx.get_tx_id());
+                assert!(!queries.iter().any(|q| q.matches(1, tx)));
+                matched += matching;
+            }
+        }
+        assert_eq!(matched, statistics.samples.len());
+    }
+
+    #[test]
+    fn bucket_queries_only_match_the_analyzed_generator() {
+        assert_bucket_queries_match(
+            TransactionStreamRef::new_gen(1, 4, "pipelined_stream.read".to_string()),
+            &[4],
+        );
+        assert_bucket_queries_match(
+            TransactionStreamRef::new_gen(1, 5, "pipelined_stream.write".to_string()),
+            &[5],
+        );
+    }
+
+    #[test]
+    fn bucket_queries_match_all_generators_of_the_stream() {
+        assert_bucket_queries_match(
+            TransactionStreamRef::new_stream(1, "pipelined_stream".to_string()),
+            &[4, 5],
+        );
+    }
+}
@@ -1,44 +1,222 @@
-use super::{check_single_wordlength, no_of_digits, TranslationPreference, ValueKind};
+use std::sync::Arc;
+
+use super::{
+    check_single_wordlength, no_of_digits, ElfHandle, ElfProgram, TranslationPreference, ValueKind,
+};
+use crate::message::Message;
 use crate::wave_container::{ScopeId, VarId, VariableMeta};
 
 use eyre::Result;
-use instruction_decoder::Decoder;
-use surfer_translation_types::{check_vector_variable, BasicTranslator, VariableValue};
+use instruction_decoder::{DecodedInstruction, DecodedPart, Decoder};
+use surfer_translation_types::{
+    check_vector_variable, BasicTranslator, SubFieldTranslationResult, TranslationResult,
+    Translator, ValueRepr, VariableInfo, VariableValue,
+};
+
+/// Numeric register names for the register mappings used by the RISC-V decoders, as
+/// (mapping, prefix, number of the first register). The compressed instructions can only
+/// use x8 to x15.
+const RISCV_REGISTERS: &[(&str, &str, u128)] = &[
+    ("Register_int", "x", 0),
+    ("Register_int_c", "x", 8),
+    ("Register_float", "f", 0),
+    ("Register_float_c", "f", 8),
+];
+const MIPS_REGISTERS: &[(&str, &str, u128)] = &[("Register_int", "$", 0)];
+const LA64_REGISTERS: &[(&str, &str, u128)] = &[("gr", "$r", 0), ("fr", "$f", 0)];
+const THUMB_REGISTERS: &[(&str, &str, u128)] = &[("Register", "r", 0)];
+
+/// The parts of PC-relative instructions holding the offset of the target, as (instructions,
+/// part, offset of the address the target is relative to, alignment of that address). Only
+/// used when the address of the instruction is known.
+pub type PcRelative = &'static [(&'static [&'static str], &'static str, u64, u64)];
+
+const RISCV_PC_RELATIVE: PcRelative = &[(
+    &[
+        "jal", "beq", "bne", "blt", "bge", "bltu", "bgeu", "c.j", "c.jal", "c.beqz", "c.bnez",
+    ],
+    "imm",
+    0,
+    1,
+)];
+const THUMB_PC_RELATIVE: PcRelative = &[
+    (&["b", "b.w", "bl"], "offset", 4, 1),
+    (&["cbz", "cbnz"], "imm", 4, 1),
+];
+/// Conditional branches are decoded as `b` with the condition in the representation
+const AARCH64_PC_RELATIVE: PcRelative = &[
+    (
+        &[
+            "b", "bl", "cbz", "cbnz", "tbz", "tbnz", "adr", "ldr", "ldrsw",
+        ],
+        "offset",
+        0,
+        1,
+    ),
+    (&["adrp"], "offset", 0, 0x1000),
+];
+
+/// The subfields shown by [`InstructionFieldsTranslator`]
+const INSTRUCTION_FIELDS: [&str; 6] = ["mnemonic", "rd", "rs1", "rs2", "imm", "extension"];
 
+#[derive(Clone)]
 pub struct InstructionTranslator {
     pub name: String,
-    pub decoder: Decoder,
+    pub decoder: Arc<Decoder>,
     pub num_bits: u64,
+    /// Show ABI register names such as `a0` rather than numeric names such as `x10`
+    pub abi_register_names: bool,
+    /// Numeric register names for the register mappings of the decoder, as
+    /// (mapping, prefix, number of the first register)
+    pub numeric_registers: &'static [(&'static str, &'static str, u128)],
+    /// The PC-relative instructions of the decoder
+    pub pc_relative: PcRelative,
+    /// The ELF file used to find the address of instructions, to resolve the targets of
+    /// PC-relative instructions in the waveform
+    pub elf: ElfHandle,
 }
 
-impl BasicTranslator<VarId, ScopeId> for InstructionTranslator {
-    fn name(&self) -> String {
-        self.name.clone()
-    }
-
-    fn basic_translate(&self, num_bits: u64, value: &VariableValue) -> (String, ValueKind) {
+impl InstructionTranslator {
+    /// Decodes `value`, returning the instruction and its encoding
+    fn decode(
+        &self,
+        num_bits: u64,
+        value: &VariableValue,
+    ) -> Result<(DecodedInstruction, u64), (String, ValueKind)> {
         let u64_value = match value {
             VariableValue::BigUint(v) => v.to_u64_digits().last().cloned(),
             VariableValue::String(s) => match check_vector_variable(s) {
-                Some(v) => return v,
+                Some(v) => return Err(v),
                 None => u64::from_str_radix(s, 2).ok(),
             },
         }
         .unwrap_or(0);
 
-        match self
-            .decoder
-            .decode_from_i64(u64_value as i64, num_bits as usize)
-        {
-            Ok(iform) => (iform, ValueKind::Normal),
-            _ => (
-                format!(
-                    "UNKNOWN INSN ({:#0width$x})",
-                    u64_value,
-                    width = no_of_digits(num_bits, 4) + 2
-                ),
-                ValueKind::Warn,
-            ),
+        self.decoder
+            .decode_structured(u64_value as i64 as u128, num_bits as usize)
+            .map(|instruction| (instruction, u64_value))
+            .map_err(|_| {
+                (
+                    format!(
+                        "UNKNOWN INSN ({:#0width$x})",
+                        u64_value,
+                        width = no_of_digits(num_bits, 4) + 2
+                    ),
+                    ValueKind::Warn,
+                )
+            })
+    }
+
+    fn part_text(&self, part: &DecodedPart) -> String {
+        let numeric_register = part.mapping.as_deref().and_then(|mapping| {
+            self.numeric_registers
+                .iter()
+                .find(|(name, _, _)| *name == mapping)
+        });
+        match numeric_register {
+            Some((_, prefix, first)) if !self.abi_register_names => {
+                format!("{prefix}{}", part.value + first)
+            }
+            _ => part.text.clone(),
+        }
+    }
+
+    /// The text of `instruction` with encoding `value` as shown in the waveform. If the
+    /// loaded ELF file is for this instruction set and contains the instruction at exactly
+    /// one address, the targets of PC-relative instructions are resolved.
+    fn text(&self, instruction: &DecodedInstruction, value: u64) -> String {
+        let program = self
+            .elf
+            .get()
+            .filter(|program| program.translator().is_some_and(|t| t.name == self.name));
+        let address = program
+            .as_ref()
+            .and_then(|program| program.address_of(value));
+        self.text_at(instruction, address, program.as_deref())
+    }
+
+    /// The text of `instruction`, showing the targets of PC-relative instructions as
+    /// addresses if the `address` of the instruction is known, followed by their symbol in
+    /// `program` if there is one
+    fn text_at(
+        &self,
+        instruction: &DecodedInstruction,
+        address: Option<u64>,
+        program: Option<&ElfProgram>,
+    ) -> String {
+        instruction.text_with(|part| {
+            let Some(target) =
+                address.and_then(|address| self.pc_relative_target(instruction, part, address))
+            else {
+                return self.part_text(part);
+            };
+            match program.and_then(|program| program.symbolize(target)) {
+                Some(symbol) => format!("{target:#x} <{symbol}>"),
+                None => format!("{target:#x}"),
+            }
+        })
+    }
+
+    /// The target address of `part` if it is the offset of a PC-relative instruction at
+    /// `address`
+    fn pc_relative_target(
+        &self,
+        instruction: &DecodedInstruction,
+        part: &DecodedPart,
+        address: u64,
+    ) -> Option<u64> {
+        let (_, _, base, align) = self.pc_relative.iter().find(|(names, name, _, _)| {
+            *name == part.name && names.contains(&instruction.name.as_str())
+        })?;
+        let (negative, magnitude) = match part.text.strip_prefix('-') {
+            Some(magnitude) => (true, magnitude),
+            None => (false, part.text.as_str()),
+        };
+        let magnitude = match magnitude.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => magnitude.parse(),
+        }
+        .ok()?;
+        let target = (address & !(align - 1)).wrapping_add(*base);
+        Some(if negative {
+            target.wrapping_sub(magnitude)
+        } else {
+            target.wrapping_add(magnitude)
+        })
+    }
+
+    /// Decodes `instruction` located at `address`, showing the targets of PC-relative
+    /// instructions as addresses, followed by their symbol in `program` if there is one
+    pub fn disassemble(
+        &self,
+        address: u64,
+        instruction: u64,
+        program: Option<&ElfProgram>,
+    ) -> String {
+        match self.decode(self.num_bits, &VariableValue::BigUint(instruction.into())) {
+            Ok((decoded, _)) => self.text_at(&decoded, Some(address), program),
+            Err((text, _)) => text,
+        }
+    }
+
+    /// A translator for the same instructions which also shows their operands as subfields
+    pub fn fields_translator(&self) -> InstructionFieldsTranslator {
+        InstructionFieldsTranslator {
+            name: format!("{} fields", self.name),
+            inner: self.clone(),
+        }
+    }
+}
+
+impl BasicTranslator<VarId, ScopeId> for InstructionTranslator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn basic_translate(&self, num_bits: u64, value: &VariableValue) -> (String, ValueKind) {
+        match self.decode(num_bits, value) {
+            Ok((instruction, value)) => (self.text(&instruction, value), ValueKind::Normal),
+            Err(result) => result,
         }
     }
 
@@ -47,6 +225,102 @@ impl BasicTranslator<VarId, ScopeId> for InstructionTranslator {
     }
 }
 
+/// The subfield of [`INSTRUCTION_FIELDS`] a part of a decoded instruction is shown in.
+/// The part names differ between instruction sets, so this is a best effort.
+fn instruction_field(part: &str) -> Option<&'static str> {
+    let part = part.strip_prefix("c_").unwrap_or(part);
+    if part.starts_with("rd") {
+        Some("rd")
+    } else if part.starts_with("rs1") || part == "rs" || part == "rj" {
+        Some("rs1")
+    } else if part.starts_with("rs2") || part == "rt" || part == "rk" {
+        Some("rs2")
+    } else if part.contains("imm")
+        || part.starts_with("shamt")
+        || part.starts_with("offs")
+        || part.starts_with("si")
+        || part.starts_with("ui")
+    {
+        Some("imm")
+    } else {
+        None
+    }
+}
+
+/// Shows decoded instructions with the mnemonic, registers, immediate and extension as
+/// subfields, so that they can be searched and colored separately
+pub struct InstructionFieldsTranslator {
+    name: String,
+    inner: InstructionTranslator,
+}
+
+impl Translator<VarId, ScopeId, Message> for InstructionFieldsTranslator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn translate(
+        &self,
+        variable: &VariableMeta,
+        value: &VariableValue,
+    ) -> Result<TranslationResult> {
+        let field = |name: &str, val: Option<String>| {
+            SubFieldTranslationResult::new(
+                name,
+                TranslationResult {
+                    val: val.map_or(ValueRepr::NotPresent, ValueRepr::String),
+                    subfields: vec![],
+                    kind: ValueKind::Normal,
+                },
+            )
+        };
+        let num_bits = variable.num_bits.unwrap_or(0) as u64;
+        let result = match self.inner.decode(num_bits, value) {
+            Ok((instruction, value)) => TranslationResult {
+                val: ValueRepr::String(self.inner.text(&instruction, value)),
+                subfields: INSTRUCTION_FIELDS
+                    .iter()
+                    .map(|&name| {
+                        let val = match name {
+                            "mnemonic" => Some(instruction.name.clone()),
+                            "extension" => Some(instruction.set.clone()),
+                            _ => instruction
+                                .parts
+                                .iter()
+                                .find(|part| instruction_field(&part.name) == Some(name))
+                                .map(|part| self.inner.part_text(part)),
+                        };
+                        field(name, val)
+                    })
+                    .collect(),
+                kind: ValueKind::Normal,
+            },
+            Err((val, kind)) => TranslationResult {
+                val: ValueRepr::String(val),
+                subfields: INSTRUCTION_FIELDS
+                    .iter()
+                    .map(|name| field(name, None))
+                    .collect(),
+                kind,
+            },
+        };
+        Ok(result)
+    }
+
+    fn variable_info(&self, _variable: &VariableMeta) -> Result<VariableInfo> {
+        Ok(VariableInfo::Compound {
+            subfields: INSTRUCTION_FIELDS
+                .iter()
+                .map(|name| (name.to_string(), VariableInfo::String))
+                .collect(),
+        })
+    }
+
+    fn translates(&self, variable: &VariableMeta) -> Result<TranslationPreference> {
+        check_single_wordlength(variable.num_bits, self.inner.num_bits as u32)
+    }
+}
+
 pub fn new_rv32_translator() -> InstructionTranslator {
     InstructionTranslator {
         name: "RV32".into(),
@@ -62,6 +336,7 @@ pub fn new_rv32_translator() -> InstructionTranslator {
             include_str!("../../../instruction-decoder/toml/RV32_Zkne.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV32_Zfa.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV32_Zicsr.toml").to_string(),
+            include_str!("../../../instruction-decoder/toml/RV_System.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV32C-lower.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV32_Zcb-lower.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV32_Zcf-lower.toml").to_string(),
@@ -83,8 +358,13 @@ pub fn new_rv32_translator() -> InstructionTranslator {
             include_str!("../../../instruction-decoder/toml/RV_Zimop.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV_Zihintntl.toml").to_string(),
         ])
-        .expect("Can't build RV32 decoder"),
+        .expect("Can't build RV32 decoder")
+        .into(),
         num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: RISCV_REGISTERS,
+        pc_relative: RISCV_PC_RELATIVE,
+        elf: ElfHandle::default(),
     }
 }
 
@@ -105,6 +385,8 @@ pub fn new_rv64_translator() -> InstructionTranslator {
             include_str!("../../../instruction-decoder/toml/RV64_Zkne.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV64_Zacas.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV64_Zfa.toml").to_string(),
+            include_str!("../../../instruction-decoder/toml/RV64_Zicsr.toml").to_string(),
+            include_str!("../../../instruction-decoder/toml/RV_System.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV64C-lower.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV64_Zcb-lower.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV64_Zcd-lower.toml").to_string(),
@@ -134,8 +416,13 @@ pub fn new_rv64_translator() -> InstructionTranslator {
             include_str!("../../../instruction-decoder/toml/RV_Zimop.toml").to_string(),
             include_str!("../../../instruction-decoder/toml/RV_Zihintntl.toml").to_string(),
         ])
-        .expect("Can't build RV64 decoder"),
+        .expect("Can't build RV64 decoder")
+        .into(),
         num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: RISCV_REGISTERS,
+        pc_relative: RISCV_PC_RELATIVE,
+        elf: ElfHandle::default(),
     }
 }
 
@@ -145,8 +432,13 @@ pub fn new_mips_translator() -> InstructionTranslator {
         decoder: Decoder::new(&[
             include_str!("../../../instruction-decoder/toml/mips.toml").to_string()
         ])
-        .expect("Can't build mips decoder"),
+        .expect("Can't build mips decoder")
+        .into(),
         num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: MIPS_REGISTERS,
+        pc_relative: &[],
+        elf: ElfHandle::default(),
     }
 }
 
@@ -156,8 +448,48 @@ pub fn new_la64_translator() -> InstructionTranslator {
         decoder: Decoder::new(&[
             include_str!("../../../instruction-decoder/toml/la64.toml").to_string()
         ])
-        .expect("Can't build LA64 decoder"),
+        .expect("Can't build LA64 decoder")
+        .into(),
+        num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: LA64_REGISTERS,
+        pc_relative: &[],
+        elf: ElfHandle::default(),
+    }
+}
+
+/// Thumb-2 instructions, with the first halfword of 32-bit instructions in the upper bits
+/// and 16-bit instructions zero-extended
+pub fn new_thumb_translator() -> InstructionTranslator {
+    InstructionTranslator {
+        name: "Thumb-2".into(),
+        decoder: Decoder::new(&[
+            include_str!("../../../instruction-decoder/toml/thumb16.toml").to_string(),
+            include_str!("../../../instruction-decoder/toml/thumb32.toml").to_string(),
+        ])
+        .expect("Can't build Thumb-2 decoder")
+        .into(),
+        num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: THUMB_REGISTERS,
+        pc_relative: THUMB_PC_RELATIVE,
+        elf: ElfHandle::default(),
+    }
+}
+
+pub fn new_aarch64_translator() -> InstructionTranslator {
+    InstructionTranslator {
+        name: "AArch64".into(),
+        decoder: Decoder::new(&[
+            include_str!("../../../instruction-decoder/toml/aarch64.toml").to_string(),
+        ])
+        .expect("Can't build AArch64 decoder")
+        .into(),
         num_bits: 32,
+        abi_register_names: true,
+        numeric_registers: &[],
+        pc_relative: AARCH64_PC_RELATIVE,
+        elf: ElfHandle::default(),
     }
 }
 
@@ -260,6 +592,37 @@ mod test {
         );
     }
 
+    #[test]
+    fn riscv_numeric_register_names() {
+        let rv32_translator = InstructionTranslator {
+            abi_register_names: false,
+            ..new_rv32_translator()
+        };
+        assert_eq!(
+            rv32_translator
+                .basic_translate(32, &VariableValue::BigUint(0x81350593u32.into()))
+                .0,
+            "addi x11, x10, -2029"
+        );
+        assert_eq!(
+            rv32_translator
+                .basic_translate(32, &VariableValue::BigUint(0x4014u32.into()))
+                .0,
+            "c.lw x13, 0x0(x8)"
+        );
+    }
+
+    #[test]
+    fn instruction_fields_from_parts() {
+        assert_eq!(instruction_field("rd_Register_int"), Some("rd"));
+        assert_eq!(instruction_field("rd_p_Register_int_c"), Some("rd"));
+        assert_eq!(instruction_field("c_rs2_Register_int"), Some("rs2"));
+        assert_eq!(instruction_field("rs1_p_Register_int_c"), Some("rs1"));
+        assert_eq!(instruction_field("himm"), Some("imm"));
+        assert_eq!(instruction_field("rt"), Some("rs2"));
+        assert_eq!(instruction_field("rm"), None);
+    }
+
     #[test]
     fn mips_from_bigunit() {
         let mips_translator = new_mips_translator();
@@ -375,4 +738,117 @@ mod test {
             "DON'T CARE"
         );
     }
+
+    #[test]
+    fn riscv_system_instructions() {
+        let rv64_translator = new_rv64_translator();
+        assert_eq!(
+            rv64_translator
+                .basic_translate(32, &VariableValue::BigUint(0x30200073u32.into()))
+                .0,
+            "mret"
+        );
+        assert_eq!(
+            rv64_translator
+                .basic_translate(32, &VariableValue::BigUint(0x30002573u32.into()))
+                .0,
+            "csrrs a0, mstatus, zero"
+        );
+    }
+
+    #[test]
+    fn thumb_from_bigunit() {
+        let thumb_translator = new_thumb_translator();
+        assert_eq!(
+            thumb_translator
+                .basic_translate(32, &VariableValue::BigUint(0x4770u32.into()))
+                .0,
+            "bx lr"
+        );
+        assert_eq!(
+            thumb_translator
+                .basic_translate(32, &VariableValue::BigUint(0xf8d10004u32.into()))
+                .0,
+            "ldr.w r0, [r1, #4]"
+        );
+        assert_eq!(
+            thumb_translator
+                .basic_translate(32, &VariableValue::BigUint(0xf8d1u32.into()))
+                .0,
+            "UNKNOWN INSN (0x0000f8d1)"
+        );
+        let thumb_translator = InstructionTranslator {
+            abi_register_names: false,
+            ..new_thumb_translator()
+        };
+        assert_eq!(
+            thumb_translator
+                .basic_translate(32, &VariableValue::BigUint(0x4770u32.into()))
+                .0,
+            "bx r14"
+        );
+    }
+
+    #[test]
+    fn aarch64_from_bigunit() {
+        let aarch64_translator = new_aarch64_translator();
+        assert_eq!(
+            aarch64_translator
+                .basic_translate(32, &VariableValue::BigUint(0xd65f03c0u32.into()))
+                .0,
+            "ret"
+        );
+        assert_eq!(
+            aarch64_translator
+                .basic_translate(32, &VariableValue::BigUint(0x910043e0u32.into()))
+                .0,
+            "add x0, sp, #16"
+        );
+    }
+
+    #[test]
+    fn disassemble_pc_relative() {
+        let rv32_translator = new_rv32_translator();
+        assert_eq!(
+            rv32_translator.disassemble(0x1000, 0xfe9ff0ef, None),
+            "jal ra, 0xfe8"
+        );
+        assert_eq!(
+            rv32_translator.disassemble(0x1000, 0x00b50463, None),
+            "beq a0, a1, 0x1008"
+        );
+        assert_eq!(
+            rv32_translator.disassemble(0x1000, 0xbfe5, None),
+            "c.j 0xff8"
+        );
+        assert_eq!(
+            rv32_translator.disassemble(0x1000, 0x81350593, None),
+            "addi a1, a0, -2029"
+        );
+
+        let thumb_translator = new_thumb_translator();
+        assert_eq!(
+            thumb_translator.disassemble(0x1000, 0xf7fffffe, None),
+            "bl #0x1000"
+        );
+        assert_eq!(
+            thumb_translator.disassemble(0x100, 0xb108, None),
+            "cbz r0, #0x106"
+        );
+
+        let aarch64_translator = new_aarch64_translator();
+        assert_eq!(
+            aarch64_translator.disassemble(0x2000, 0x94000004, None),
+            "bl #0x2010"
+        );
+        assert_eq!(
+            aarch64_translator.disassemble(0x2000, 0x54000040, None),
+            "b.eq #0x2008"
+        );
+        // The target of adrp is relative to the page of the instruction
+        assert_eq!(
+            aarch64_translator.disassemble(0x2345, 0xb0000001, None),
+            "adrp x1, #0x3000"
+        );
+    }
 }
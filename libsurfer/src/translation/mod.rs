@@ -20,6 +20,7 @@ mod instruction_translators;
 pub mod numeric_translators;
 #[cfg(feature = "python")]
 mod python_translators;
+mod symbol_translator;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod wasm_translator;
 
@@ -35,8 +36,9 @@ use surfer_translation_types::{
     TranslationPreference, TranslationResult, Translator, ValueKind, ValueRepr, VariableEncoding,
     VariableInfo, VariableValue,
 };
+pub use symbol_translator::{ElfHandle, ElfProgram, SymbolTranslator};
 
-use crate::config::SurferTheme;
+use crate::config::{SurferConfig, SurferTheme};
 use crate::translation::enum_translator::EnumTranslator;
 use crate::wave_container::{ScopeId, VarId};
 use crate::{message::Message, wave_container::VariableMeta};
@@ -232,8 +234,12 @@ fn find_user_decoders_at_path(path: &Path) -> Vec<Box<DynBasicTranslator>> {
                 match Decoder::new_from_table(tomls) {
                     Ok(decoder) => decoders.push(Box::new(InstructionTranslator {
                         name,
-                        decoder,
+                        decoder: decoder.into(),
                         num_bits: width.unsigned_abs(),
+                        abi_register_names: true,
+                        numeric_registers: &[],
+                        pc_relative: &[],
+                        elf: ElfHandle::default(),
                     })),
                     Err(e) => {
                         error!("Error while building decoder {name}");
@@ -250,7 +256,22 @@ fn find_user_decoders_at_path(path: &Path) -> Vec<Box<DynBasicTranslator>> {
     decoders
 }
 
-pub fn all_translators() -> TranslatorList {
+pub fn all_translators(config: &SurferConfig) -> TranslatorList {
+    let elf = ElfHandle::default();
+    let instruction_translators = [
+        new_rv32_translator(),
+        new_rv64_translator(),
+        new_mips_translator(),
+        new_la64_translator(),
+        new_thumb_translator(),
+        new_aarch64_translator(),
+    ]
+    .map(|translator| InstructionTranslator {
+        abi_register_names: config.abi_register_names,
+        elf: elf.clone(),
+        ..translator
+    });
+
     // WASM does not need mut, non-wasm does so we'll allow it
     #[allow(unused_mut)]
     let mut basic_translators: Vec<Box<DynBasicTranslator>> = vec![
@@ -260,10 +281,6 @@ pub fn all_translators() -> TranslatorList {
         Box::new(GroupingBinaryTranslator {}),
         Box::new(BinaryTranslator {}),
         Box::new(ASCIITranslator {}),
-        Box::new(new_rv32_translator()),
-        Box::new(new_rv64_translator()),
-        Box::new(new_mips_translator()),
-        Box::new(new_la64_translator()),
         Box::new(LebTranslator {}),
         Box::new(UnsignedTranslator {}),
         Box::new(SignedTranslator {}),
@@ -291,16 +308,25 @@ pub fn all_translators() -> TranslatorList {
     #[cfg(not(target_arch = "wasm32"))]
     basic_translators.append(&mut find_user_decoders());
 
-    TranslatorList::new(
-        basic_translators,
-        vec![
-            Box::new(ClockTranslator::new()),
-            Box::new(StringTranslator {}),
-            Box::new(EnumTranslator {}),
-            Box::new(UnsignedFixedPointTranslator),
-            Box::new(SignedFixedPointTranslator),
-        ],
-    )
+    let mut translators: Vec<Box<DynTranslator>> = vec![
+        Box::new(ClockTranslator::new()),
+        Box::new(StringTranslator {}),
+        Box::new(EnumTranslator {}),
+        Box::new(UnsignedFixedPointTranslator),
+        Box::new(SignedFixedPointTranslator),
+    ];
+
+    for translator in instruction_translators {
+        translators.push(Box::new(translator.fields_translator()));
+        basic_translators.push(Box::new(translator));
+    }
+
+    basic_translators.push(Box::new(SymbolTranslator { elf: elf.clone() }));
+
+    TranslatorList {
+        elf,
+        ..TranslatorList::new(basic_translators, translators)
+    }
 }
 
 #[derive(Default)]
@@ -309,6 +335,8 @@ pub struct TranslatorList {
     #[cfg(feature = "python")]
     python_translator: Option<(camino::Utf8PathBuf, String, AnyTranslator)>,
     pub default: String,
+    /// The ELF file used for symbolizing addresses
+    pub elf: ElfHandle,
 }
 
 impl TranslatorList {
@@ -326,6 +354,7 @@ impl TranslatorList {
                 .collect(),
             #[cfg(feature = "python")]
             python_translator: None,
+            elf: ElfHandle::default(),
         }
     }
 
@@ -0,0 +1,442 @@
+//! Translating addresses to `function+offset` using the symbol table of an ELF file.
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use eyre::{Context, Result};
+use num::ToPrimitive;
+use object::{Architecture, Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use surfer_translation_types::{BasicTranslator, ValueKind, VariableValue};
+
+use super::{
+    new_aarch64_translator, new_la64_translator, new_mips_translator, new_rv32_translator,
+    new_rv64_translator, new_thumb_translator, no_of_digits, InstructionTranslator,
+    TranslationPreference,
+};
+use crate::wave_container::{ScopeId, VarId, VariableMeta};
+
+struct Symbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+struct CodeSection {
+    address: u64,
+    data: Vec<u8>,
+}
+
+/// The symbols and code of an ELF file
+pub struct ElfProgram {
+    pub name: String,
+    architecture: Architecture,
+    little_endian: bool,
+    /// Sorted by address
+    symbols: Vec<Symbol>,
+    /// The addresses covered by the symbols and code
+    address_range: Range<u64>,
+    code: Vec<CodeSection>,
+    /// The address of each instruction in the code, or `None` if it occurs several times
+    instruction_addresses: HashMap<u64, Option<u64>>,
+    /// Decoder for the instruction set of the program, if there is one
+    translator: Option<InstructionTranslator>,
+}
+
+impl ElfProgram {
+    pub fn parse(name: String, data: &[u8]) -> Result<Self> {
+        let file = object::File::parse(data).context("Failed to parse ELF file")?;
+        let architecture = file.architecture();
+
+        let symbols = file
+            .symbols()
+            .filter(|symbol| {
+                symbol.is_definition()
+                    && matches!(symbol.kind(), SymbolKind::Text | SymbolKind::Data)
+            })
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+                // Skip mapping symbols such as `$t` and local labels
+                if name.is_empty() || name.starts_with('$') || name.starts_with(".L") {
+                    return None;
+                }
+                let mut address = symbol.address();
+                // The lowest bit of Thumb function addresses is set
+                if architecture == Architecture::Arm && symbol.kind() == SymbolKind::Text {
+                    address &= !1;
+                }
+                Some(Symbol {
+                    address,
+                    size: symbol.size(),
+                    name: name.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let code = file
+            .sections()
+            .filter(|section| section.kind() == SectionKind::Text)
+            .filter_map(|section| {
+                Some(CodeSection {
+                    address: section.address(),
+                    data: section.data().ok()?.to_vec(),
+                })
+            })
+            .collect();
+
+        Ok(Self::new(
+            name,
+            architecture,
+            file.is_little_endian(),
+            symbols,
+            code,
+        ))
+    }
+
+    fn new(
+        name: String,
+        architecture: Architecture,
+        little_endian: bool,
+        mut symbols: Vec<Symbol>,
+        code: Vec<CodeSection>,
+    ) -> Self {
+        // Prefer sized symbols, e.g. functions over labels, if several share an address
+        symbols.sort_by(|a, b| (a.address, b.size, &a.name).cmp(&(b.address, a.size, &b.name)));
+        symbols.dedup_by_key(|symbol| symbol.address);
+
+        let start = symbols
+            .iter()
+            .map(|symbol| symbol.address)
+            .chain(code.iter().map(|section| section.address))
+            .min()
+            .unwrap_or_default();
+        let end = symbols
+            .iter()
+            .map(|symbol| symbol.address.saturating_add(symbol.size.max(1)))
+            .chain(
+                code.iter()
+                    .map(|section| section.address.saturating_add(section.data.len() as u64)),
+            )
+            .max()
+            .unwrap_or_default();
+
+        let translator = match architecture {
+            Architecture::Riscv32 => Some(new_rv32_translator()),
+            Architecture::Riscv64 => Some(new_rv64_translator()),
+            Architecture::Arm => Some(new_thumb_translator()),
+            Architecture::Aarch64 => Some(new_aarch64_translator()),
+            Architecture::Mips | Architecture::Mips64 => Some(new_mips_translator()),
+            Architecture::LoongArch64 => Some(new_la64_translator()),
+            _ => None,
+        };
+
+        let mut program = Self {
+            name,
+            architecture,
+            little_endian,
+            symbols,
+            address_range: start..end,
+            code,
+            instruction_addresses: HashMap::new(),
+            translator,
+        };
+        if program.translator.is_some() {
+            program.instruction_addresses = program.index_instructions();
+        }
+        program
+    }
+
+    fn index_instructions(&self) -> HashMap<u64, Option<u64>> {
+        let mut addresses = HashMap::new();
+        for section in &self.code {
+            let end = section.address + section.data.len() as u64;
+            let mut address = section.address;
+            while address < end {
+                let Some((instruction, len)) = self.instruction(address) else {
+                    break;
+                };
+                addresses
+                    .entry(instruction)
+                    .and_modify(|unique| *unique = None)
+                    .or_insert(Some(address));
+                address += len;
+            }
+        }
+        addresses
+    }
+
+    /// The address of `instruction` if it occurs exactly once in the code
+    pub fn address_of(&self, instruction: u64) -> Option<u64> {
+        self.instruction_addresses
+            .get(&instruction)
+            .copied()
+            .flatten()
+    }
+
+    /// The number of bits needed to hold the addresses of the program
+    pub fn address_bits(&self) -> u32 {
+        u64::BITS - self.address_range.end.saturating_sub(1).leading_zeros()
+    }
+
+    /// The closest symbol at or before `address`, and the offset of `address` from it.
+    /// Symbols with a known size are only used for addresses inside them, and addresses
+    /// outside the program have no symbol.
+    pub fn symbol(&self, address: u64) -> Option<(&str, u64)> {
+        if !self.address_range.contains(&address) {
+            return None;
+        }
+        let idx = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[idx];
+        let offset = address - symbol.address;
+        (symbol.size == 0 || offset < symbol.size).then_some((symbol.name.as_str(), offset))
+    }
+
+    /// `address` as `function+0x10`, or `function` if it is the start of the symbol
+    pub fn symbolize(&self, address: u64) -> Option<String> {
+        self.symbol(address).map(|(name, offset)| {
+            if offset == 0 {
+                name.to_string()
+            } else {
+                format!("{name}+{offset:#x}")
+            }
+        })
+    }
+
+    fn read(&self, address: u64, len: u64) -> Option<&[u8]> {
+        self.code.iter().find_map(|section| {
+            let start = address.checked_sub(section.address)?;
+            section
+                .data
+                .get(start as usize..start.checked_add(len)? as usize)
+        })
+    }
+
+    fn read_u16(&self, address: u64) -> Option<u64> {
+        let bytes = self.read(address, 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        } as u64)
+    }
+
+    fn read_u32(&self, address: u64) -> Option<u64> {
+        let bytes = self.read(address, 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        } as u64)
+    }
+
+    /// The instruction at `address` and its length in bytes, in the form expected by the
+    /// decoder of the instruction set
+    pub fn instruction(&self, address: u64) -> Option<(u64, u64)> {
+        match self.architecture {
+            Architecture::Riscv32 | Architecture::Riscv64 => {
+                let low = self.read_u16(address)?;
+                if low & 0b11 == 0b11 {
+                    Some((self.read_u32(address)?, 4))
+                } else {
+                    Some((low, 2))
+                }
+            }
+            Architecture::Arm => {
+                let first = self.read_u16(address)?;
+                if first >> 11 >= 0b11101 {
+                    Some((first << 16 | self.read_u16(address + 2)?, 4))
+                } else {
+                    Some((first, 2))
+                }
+            }
+            _ => Some((self.read_u32(address)?, 4)),
+        }
+    }
+
+    pub fn translator(&self) -> Option<&InstructionTranslator> {
+        self.translator.as_ref()
+    }
+
+    /// The start of the symbol containing `address`, if there is one
+    pub fn symbol_start(&self, address: u64) -> Option<u64> {
+        self.symbol(address).map(|(_, offset)| address - offset)
+    }
+}
+
+/// The currently loaded ELF file, shared between the translators using it
+#[derive(Clone, Default)]
+pub struct ElfHandle(Arc<RwLock<Option<Arc<ElfProgram>>>>);
+
+impl ElfHandle {
+    pub fn get(&self) -> Option<Arc<ElfProgram>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, program: Option<Arc<ElfProgram>>) {
+        *self.0.write().unwrap() = program;
+    }
+}
+
+/// Shows addresses as `function+offset` using the symbols of the loaded ELF file
+pub struct SymbolTranslator {
+    pub elf: ElfHandle,
+}
+
+impl BasicTranslator<VarId, ScopeId> for SymbolTranslator {
+    fn name(&self) -> String {
+        String::from("Symbol")
+    }
+
+    fn basic_translate(&self, num_bits: u64, value: &VariableValue) -> (String, ValueKind) {
+        let address = match value.clone().parse_biguint() {
+            Ok(v) => v,
+            Err(result) => return result,
+        };
+        let symbol = self
+            .elf
+            .get()
+            .zip(address.to_u64())
+            .and_then(|(program, address)| program.symbolize(address));
+        match symbol {
+            Some(symbol) => (symbol, ValueKind::Normal),
+            None => (
+                format!("{address:#0width$x}", width = no_of_digits(num_bits, 4) + 2),
+                ValueKind::Normal,
+            ),
+        }
+    }
+
+    /// Only variables wide enough to hold the addresses of the program are translated,
+    /// which rules out most variables that are not addresses
+    fn translates(&self, variable: &VariableMeta) -> Result<TranslationPreference> {
+        let translates = self.elf.get().is_some_and(|program| {
+            variable
+                .num_bits
+                .is_some_and(|num_bits| (program.address_bits()..=64).contains(&num_bits))
+        });
+        Ok(if translates {
+            TranslationPreference::Yes
+        } else {
+            TranslationPreference::No
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use surfer_translation_types::VariableEncoding;
+
+    use super::*;
+    use crate::wave_container::{VariableRef, VariableRefExt};
+
+    const JAL_FUNC: u32 = 0x008000ef;
+    const NOP: u32 = 0x00000013;
+    const RET: u32 = 0x00008067;
+
+    /// A RISC-V program with `main` and `func` at 0x1000 and 0x1008
+    fn program(code: &[u32]) -> Arc<ElfProgram> {
+        let symbols = vec![
+            Symbol {
+                address: 0x1008,
+                size: 0,
+                name: "func".to_string(),
+            },
+            Symbol {
+                address: 0x1000,
+                size: 8,
+                name: "main".to_string(),
+            },
+        ];
+        let code = vec![CodeSection {
+            address: 0x1000,
+            data: code.iter().flat_map(|i| i.to_le_bytes()).collect(),
+        }];
+        Arc::new(ElfProgram::new(
+            "test".to_string(),
+            Architecture::Riscv32,
+            true,
+            symbols,
+            code,
+        ))
+    }
+
+    fn meta(num_bits: u32) -> VariableMeta {
+        VariableMeta {
+            var: VariableRef::from_strs(&["top", "pc"]),
+            num_bits: Some(num_bits),
+            variable_type: None,
+            variable_type_name: None,
+            index: None,
+            direction: None,
+            enum_map: HashMap::new(),
+            encoding: VariableEncoding::BitVector,
+        }
+    }
+
+    #[test]
+    fn addresses_outside_program_have_no_symbol() {
+        let program = program(&[JAL_FUNC, NOP, RET]);
+        assert_eq!(program.symbolize(0x1004).as_deref(), Some("main+0x4"));
+        assert_eq!(program.symbolize(0x1008).as_deref(), Some("func"));
+        assert_eq!(program.symbolize(0x100b).as_deref(), Some("func+0x3"));
+        assert_eq!(program.symbolize(0x100c), None);
+        assert_eq!(program.symbolize(0xfff), None);
+    }
+
+    #[test]
+    fn only_variables_holding_addresses_are_translated() {
+        let elf = ElfHandle::default();
+        let translator = SymbolTranslator { elf: elf.clone() };
+        assert!(matches!(
+            translator.translates(&meta(32)).unwrap(),
+            TranslationPreference::No
+        ));
+
+        elf.set(Some(program(&[JAL_FUNC, NOP, RET])));
+        assert_eq!(elf.get().unwrap().address_bits(), 13);
+        for (num_bits, translates) in [(1, false), (12, false), (13, true), (64, true), (65, false)]
+        {
+            assert_eq!(
+                matches!(
+                    translator.translates(&meta(num_bits)).unwrap(),
+                    TranslationPreference::Yes
+                ),
+                translates,
+                "{num_bits} bits"
+            );
+        }
+    }
+
+    #[test]
+    fn waveform_instructions_resolve_unique_targets() {
+        let elf = ElfHandle::default();
+        let translator = InstructionTranslator {
+            elf: elf.clone(),
+            ..new_rv32_translator()
+        };
+        let value = VariableValue::BigUint(JAL_FUNC.into());
+        assert_eq!(translator.basic_translate(32, &value).0, "jal ra, 8");
+
+        elf.set(Some(program(&[JAL_FUNC, NOP, RET])));
+        assert_eq!(
+            translator.basic_translate(32, &value).0,
+            "jal ra, 0x1008 <func>"
+        );
+
+        // The address of an instruction occurring several times is unknown
+        elf.set(Some(program(&[JAL_FUNC, JAL_FUNC, RET])));
+        assert_eq!(translator.basic_translate(32, &value).0, "jal ra, 8");
+
+        // Programs for other instruction sets are not used
+        let translator = InstructionTranslator {
+            elf: elf.clone(),
+            ..new_rv64_translator()
+        };
+        elf.set(Some(program(&[JAL_FUNC, NOP, RET])));
+        assert_eq!(translator.basic_translate(32, &value).0, "jal ra, 8");
+    }
+}
@@ -0,0 +1,328 @@
+//! Coloring of variable values based on user defined rules.
+use std::str::FromStr;
+
+use ecolor::{Color32, Hsva};
+use num::{BigUint, Num};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use surfer_translation_types::{TranslatedValue, ValueKind, VariableValue};
+
+use crate::config::SurferTheme;
+
+/// The condition under which a [`ValueColorRule`] applies.
+///
+/// Numeric conditions compare the value of the variable itself, so they do not depend on
+/// how the value is formatted. Conditions on text compare the value as displayed.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ValueColorCondition {
+    /// The value, as displayed, is equal to the string
+    Equals(String),
+    /// The value, as displayed, is not equal to the string
+    NotEquals(String),
+    /// The value is equal to the number
+    ValueEquals(#[serde(with = "number")] BigUint),
+    /// The value is a number which is not equal to the number
+    ValueNotEquals(#[serde(with = "number")] BigUint),
+    /// The value is less than the number
+    LessThan(#[serde(with = "number")] BigUint),
+    /// The value is greater than the number
+    GreaterThan(#[serde(with = "number")] BigUint),
+    /// The value contains undefined, high-impedance or don't care bits
+    Undefined,
+    /// Every distinct value gets its own color, useful for state machines
+    EachValue,
+}
+
+impl ValueColorCondition {
+    /// `number` is the value of the variable, if it is a number.
+    fn matches(&self, number: Option<&BigUint>, value: &TranslatedValue) -> bool {
+        match self {
+            ValueColorCondition::Equals(s) => value.value.trim() == s.trim(),
+            ValueColorCondition::NotEquals(s) => value.value.trim() != s.trim(),
+            ValueColorCondition::ValueEquals(n) => number == Some(n),
+            ValueColorCondition::ValueNotEquals(n) => number.is_some_and(|number| number != n),
+            ValueColorCondition::LessThan(n) => number.is_some_and(|number| number < n),
+            ValueColorCondition::GreaterThan(n) => number.is_some_and(|number| number > n),
+            ValueColorCondition::Undefined => matches!(
+                value.kind,
+                ValueKind::Undef | ValueKind::HighImp | ValueKind::DontCare
+            ),
+            ValueColorCondition::EachValue => true,
+        }
+    }
+
+    /// Returns the color that `value` should be drawn in if the condition matches.
+    /// `color` is the name of a theme color and is ignored for [`ValueColorCondition::EachValue`].
+    pub fn color_for(
+        &self,
+        color: Option<&String>,
+        number: Option<&BigUint>,
+        value: &TranslatedValue,
+        theme: &SurferTheme,
+    ) -> Option<Color32> {
+        if !self.matches(number, value) {
+            return None;
+        }
+        match self {
+            ValueColorCondition::EachValue => Some(color_from_value(&value.value)),
+            _ => color.and_then(|color| theme.get_color(color)).copied(),
+        }
+    }
+
+    /// Short human readable description, used in menus
+    pub fn description(&self) -> String {
+        match self {
+            ValueColorCondition::Equals(s) => format!("== {s}"),
+            ValueColorCondition::NotEquals(s) => format!("!= {s}"),
+            ValueColorCondition::ValueEquals(n) => format!("== {n}"),
+            ValueColorCondition::ValueNotEquals(n) => format!("!= {n}"),
+            ValueColorCondition::LessThan(n) => format!("< {n}"),
+            ValueColorCondition::GreaterThan(n) => format!("> {n}"),
+            ValueColorCondition::Undefined => "X/Z".to_string(),
+            ValueColorCondition::EachValue => "Each value".to_string(),
+        }
+    }
+}
+
+impl FromStr for ValueColorCondition {
+    type Err = String;
+
+    /// Parses `==VALUE`, `!=VALUE`, `<NUMBER`, `>NUMBER`, `xz` or `each`. A `VALUE` which is a
+    /// number is compared numerically, otherwise it is compared to the displayed value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(value) = s.strip_prefix("==") {
+            Ok(parse_number(value).map_or_else(
+                || ValueColorCondition::Equals(value.to_string()),
+                ValueColorCondition::ValueEquals,
+            ))
+        } else if let Some(value) = s.strip_prefix("!=") {
+            Ok(parse_number(value).map_or_else(
+                || ValueColorCondition::NotEquals(value.to_string()),
+                ValueColorCondition::ValueNotEquals,
+            ))
+        } else if let Some(value) = s.strip_prefix('<') {
+            parse_number(value)
+                .map(ValueColorCondition::LessThan)
+                .ok_or_else(|| format!("'{value}' is not a number"))
+        } else if let Some(value) = s.strip_prefix('>') {
+            parse_number(value)
+                .map(ValueColorCondition::GreaterThan)
+                .ok_or_else(|| format!("'{value}' is not a number"))
+        } else {
+            match s {
+                "x" | "z" | "xz" => Ok(ValueColorCondition::Undefined),
+                "each" => Ok(ValueColorCondition::EachValue),
+                _ => Err(format!("'{s}' is not a valid value color condition")),
+            }
+        }
+    }
+}
+
+/// Parses a decimal number, or a hexadecimal or binary number prefixed by `0x` or `0b`.
+fn parse_number(s: &str) -> Option<BigUint> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x") {
+        BigUint::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        BigUint::from_str_radix(bin, 2).ok()
+    } else {
+        BigUint::from_str_radix(s, 10).ok()
+    }
+}
+
+/// Numbers in conditions are written as strings, so they can be of any width and are
+/// readable in the config file.
+mod number {
+    use num::BigUint;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(n: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&n.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_number(&s).ok_or_else(|| de::Error::custom(format!("'{s}' is not a number")))
+    }
+}
+
+/// A value coloring rule attached to a single variable.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ValueColorRule {
+    pub condition: ValueColorCondition,
+    /// Name of the theme color to use
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// A value coloring rule from the config file, applied to all variables with a full path
+/// matching `variable`.
+#[derive(Debug, Deserialize)]
+pub struct ValueColorRuleConfig {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub variable: Regex,
+    pub condition: ValueColorCondition,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let buf = String::deserialize(deserializer)?;
+    Regex::new(&buf).map_err(de::Error::custom)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher` since that is not guaranteed to
+/// be stable between Rust releases, and colors should not change between builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Picks a color for a value by hashing it. The same value always gets the same color.
+fn color_from_value(value: &str) -> Color32 {
+    let hue = (fnv1a(value.as_bytes()) % 360) as f32 / 360.;
+    Hsva::new(hue, 0.6, 0.9, 1.).into()
+}
+
+/// Overrides the kind of `value` with the color of the first matching rule.
+/// `raw` is the value of the variable that `value` is translated from.
+pub fn apply_value_color_rules(
+    value: &mut TranslatedValue,
+    raw: &VariableValue,
+    rules: &[(&ValueColorCondition, Option<&String>)],
+    theme: &SurferTheme,
+) {
+    let number = raw.clone().parse_biguint().ok();
+    if let Some(color) = rules
+        .iter()
+        .find_map(|(condition, color)| condition.color_for(*color, number.as_ref(), value, theme))
+    {
+        value.kind = ValueKind::Custom(color);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use surfer_translation_types::BasicTranslator;
+
+    use crate::translation::{BinaryTranslator, HexTranslator};
+    use crate::wave_container::{ScopeId, VarId};
+
+    use super::*;
+
+    fn value(s: &str, kind: ValueKind) -> TranslatedValue {
+        TranslatedValue {
+            value: s.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn conditions_match_correctly() {
+        let zero = value("0", ValueKind::Normal);
+        let undef = value("x", ValueKind::Undef);
+
+        assert!(ValueColorCondition::Equals("0".to_string()).matches(None, &zero));
+        assert!(!ValueColorCondition::NotEquals("0".to_string()).matches(None, &zero));
+        assert!(ValueColorCondition::NotEquals("0".to_string()).matches(None, &undef));
+        assert!(ValueColorCondition::Undefined.matches(None, &undef));
+        assert!(!ValueColorCondition::Undefined.matches(None, &zero));
+    }
+
+    #[test]
+    fn numeric_conditions_ignore_the_format() {
+        let raw = VariableValue::BigUint(BigUint::ZERO);
+        let number = raw.clone().parse_biguint().ok();
+        for translator in [
+            &HexTranslator {} as &dyn BasicTranslator<VarId, ScopeId>,
+            &BinaryTranslator {},
+        ] {
+            let (shown, kind) = translator.basic_translate(8, &raw);
+            assert_ne!(shown, "0");
+            let shown = value(&shown, kind);
+
+            assert!(
+                ValueColorCondition::ValueEquals(BigUint::ZERO).matches(number.as_ref(), &shown)
+            );
+            assert!(!ValueColorCondition::ValueNotEquals(BigUint::ZERO)
+                .matches(number.as_ref(), &shown));
+            assert!(
+                ValueColorCondition::LessThan(BigUint::from(1u8)).matches(number.as_ref(), &shown)
+            );
+            assert!(
+                !ValueColorCondition::GreaterThan(BigUint::ZERO).matches(number.as_ref(), &shown)
+            );
+        }
+    }
+
+    #[test]
+    fn numeric_conditions_do_not_match_undefined_values() {
+        let raw = VariableValue::String("0000x000".to_string());
+        let number = raw.clone().parse_biguint().ok();
+        let shown = value("x", ValueKind::Undef);
+
+        assert!(!ValueColorCondition::ValueEquals(BigUint::ZERO).matches(number.as_ref(), &shown));
+        assert!(
+            !ValueColorCondition::ValueNotEquals(BigUint::ZERO).matches(number.as_ref(), &shown)
+        );
+    }
+
+    #[test]
+    fn value_hash_is_stable() {
+        // Reference values for 64-bit FNV-1a
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(color_from_value("IDLE"), color_from_value("IDLE"));
+    }
+
+    #[test]
+    fn conditions_are_parsed() {
+        assert_eq!(
+            ValueColorCondition::from_str("!=0"),
+            Ok(ValueColorCondition::ValueNotEquals(BigUint::ZERO))
+        );
+        assert_eq!(
+            ValueColorCondition::from_str(">0x1f"),
+            Ok(ValueColorCondition::GreaterThan(BigUint::from(31u8)))
+        );
+        assert_eq!(
+            ValueColorCondition::from_str("<0b11"),
+            Ok(ValueColorCondition::LessThan(BigUint::from(3u8)))
+        );
+        assert!(ValueColorCondition::from_str("<IDLE").is_err());
+        assert_eq!(
+            ValueColorCondition::from_str("==IDLE"),
+            Ok(ValueColorCondition::Equals("IDLE".to_string()))
+        );
+        assert_eq!(
+            ValueColorCondition::from_str("xz"),
+            Ok(ValueColorCondition::Undefined)
+        );
+        assert!(ValueColorCondition::from_str("foo").is_err());
+    }
+
+    #[test]
+    fn numbers_are_written_as_strings() {
+        let condition = ValueColorCondition::ValueNotEquals(BigUint::from(10u8));
+        let serialized = ron::to_string(&condition).unwrap();
+        assert_eq!(serialized, r#"ValueNotEquals("10")"#);
+        assert_eq!(
+            ron::from_str::<ValueColorCondition>(&serialized).unwrap(),
+            condition
+        );
+    }
+
+    #[test]
+    fn each_value_color_is_stable() {
+        assert_eq!(color_from_value("IDLE"), color_from_value("IDLE"));
+        assert_ne!(color_from_value("IDLE"), color_from_value("BUSY"));
+    }
+}
@@ -1,6 +1,6 @@
 //! Filtering of the variable list.
-use derive_more::Display;
-use egui::{Button, Layout, TextEdit, Ui};
+use derive_more::{Display, FromStr};
+use egui::{Button, DragValue, Layout, TextEdit, TextWrapMode, Ui};
 use egui_remixicon::icons;
 use emath::{Align, Vec2};
 use enum_iterator::Sequence;
@@ -9,13 +9,13 @@ use itertools::Itertools;
 use regex::{escape, Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 
-use crate::data_container::DataContainer::Transactions;
 use crate::transaction_container::{StreamScopeRef, TransactionStreamRef};
+use crate::translation::TranslatorList;
 use crate::variable_direction::VariableDirectionExt;
-use crate::wave_container::WaveContainer;
+use crate::wave_container::{VariableMeta, WaveContainer};
 use crate::wave_data::ScopeType;
 use crate::{message::Message, wave_container::VariableRef, SystemState};
-use surfer_translation_types::VariableDirection;
+use surfer_translation_types::{VariableDirection, VariableType};
 
 use std::cmp::Ordering;
 
@@ -34,6 +34,17 @@ pub enum VariableNameFilterType {
     Contain,
 }
 
+/// What, apart from the direction, the variable list is sorted by
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Display, FromStr, PartialEq, Eq, Sequence, Serialize,
+)]
+pub enum VariableSortType {
+    #[default]
+    Name,
+    Type,
+    Width,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VariableFilter {
     pub(crate) name_filter_type: VariableNameFilterType,
@@ -46,6 +57,19 @@ pub struct VariableFilter {
     pub(crate) include_others: bool,
 
     pub(crate) group_by_direction: bool,
+
+    /// Only show variables of these types. All types are shown if empty.
+    #[serde(default)]
+    pub(crate) variable_types: Vec<VariableType>,
+    #[serde(default)]
+    pub(crate) min_width: Option<u32>,
+    #[serde(default)]
+    pub(crate) max_width: Option<u32>,
+    /// Only show variables which can be shown using this translator
+    #[serde(default)]
+    pub(crate) translator: Option<String>,
+    #[serde(default)]
+    pub(crate) sort_by: VariableSortType,
 }
 
 #[derive(Debug, Deserialize)]
@@ -75,10 +99,57 @@ impl VariableFilter {
             include_others: true,
 
             group_by_direction: false,
+
+            variable_types: vec![],
+            min_width: None,
+            max_width: None,
+            translator: None,
+            sort_by: VariableSortType::Name,
+        }
+    }
+
+    /// True if the type, width or translator filter hides any variables
+    pub(crate) fn has_meta_filters(&self) -> bool {
+        !self.variable_types.is_empty()
+            || self.min_width.is_some()
+            || self.max_width.is_some()
+            || self.translator.is_some()
+    }
+
+    pub(crate) fn clear_meta_filters(&mut self) {
+        self.variable_types.clear();
+        self.min_width = None;
+        self.max_width = None;
+        self.translator = None;
+    }
+
+    pub(crate) fn includes_type(&self, variable_type: VariableType) -> bool {
+        self.variable_types.is_empty() || self.variable_types.contains(&variable_type)
+    }
+
+    /// The shown types after showing or hiding `variable_type`, in declaration order
+    pub(crate) fn types_with(
+        &self,
+        variable_type: VariableType,
+        include: bool,
+    ) -> Vec<VariableType> {
+        let types = enum_iterator::all::<VariableType>()
+            .filter(|t| {
+                if *t == variable_type {
+                    include
+                } else {
+                    self.includes_type(*t)
+                }
+            })
+            .collect_vec();
+        if types.len() == enum_iterator::cardinality::<VariableType>() {
+            vec![]
+        } else {
+            types
         }
     }
 
-    fn name_filter_fn(&self) -> Box<dyn FnMut(&str) -> bool> {
+    pub(crate) fn name_filter_fn(&self) -> Box<dyn FnMut(&str) -> bool> {
         if self.name_filter_str.is_empty() {
             return Box::new(|_var_name| true);
         }
@@ -129,12 +200,67 @@ impl VariableFilter {
         }
     }
 
-    fn kind_filter(&self, vr: &VariableRef, wave_container_opt: Option<&WaveContainer>) -> bool {
-        match get_variable_direction(vr, wave_container_opt) {
+    fn kind_filter(
+        &self,
+        meta: Option<&VariableMeta>,
+        translator: Option<(&TranslatorList, &str)>,
+    ) -> bool {
+        let include_direction = match variable_direction(meta) {
             VariableDirection::Input => self.include_inputs,
             VariableDirection::Output => self.include_outputs,
             VariableDirection::InOut => self.include_inouts,
             _ => self.include_others,
+        };
+        if !include_direction {
+            return false;
+        }
+        let Some(meta) = meta else {
+            return !self.has_meta_filters();
+        };
+
+        let include_type = meta
+            .variable_type
+            .map_or(self.variable_types.is_empty(), |t| self.includes_type(t));
+        let include_width = match meta.num_bits {
+            Some(num_bits) => {
+                self.min_width.is_none_or(|min| num_bits >= min)
+                    && self.max_width.is_none_or(|max| num_bits <= max)
+            }
+            None => self.min_width.is_none() && self.max_width.is_none(),
+        };
+        let include_translator = translator
+            .is_none_or(|(translators, name)| translators.is_valid_translator(meta, name));
+        include_type && include_width && include_translator
+    }
+
+    /// Orders variables by direction if they are grouped by it, then by the sort type
+    /// and finally by name
+    fn variable_cmp(
+        &self,
+        (a, a_meta): (&VariableRef, Option<&VariableMeta>),
+        (b, b_meta): (&VariableRef, Option<&VariableMeta>),
+    ) -> Ordering {
+        let a_direction = variable_direction(a_meta);
+        let b_direction = variable_direction(b_meta);
+
+        if !self.group_by_direction || a_direction == b_direction {
+            let meta_order = match self.sort_by {
+                VariableSortType::Name => Ordering::Equal,
+                VariableSortType::Type => {
+                    let type_name = |meta: Option<&VariableMeta>| {
+                        meta.and_then(|m| m.variable_type).map(|t| t.to_string())
+                    };
+                    type_name(a_meta).cmp(&type_name(b_meta))
+                }
+                VariableSortType::Width => a_meta
+                    .and_then(|m| m.num_bits)
+                    .cmp(&b_meta.and_then(|m| m.num_bits)),
+            };
+            meta_order.then_with(|| numeric_sort::cmp(&a.name, &b.name))
+        } else if a_direction < b_direction {
+            Ordering::Less
+        } else {
+            Ordering::Greater
         }
     }
 
@@ -142,14 +268,35 @@ impl VariableFilter {
         &self,
         variables: &[VariableRef],
         wave_container_opt: Option<&WaveContainer>,
+        translators: &TranslatorList,
     ) -> Vec<VariableRef> {
+        self.matching_variables_with_meta(variables, wave_container_opt, translators)
+            .into_iter()
+            .map(|(vr, _)| vr)
+            .collect_vec()
+    }
+
+    fn matching_variables_with_meta(
+        &self,
+        variables: &[VariableRef],
+        wave_container_opt: Option<&WaveContainer>,
+        translators: &TranslatorList,
+    ) -> Vec<(VariableRef, Option<VariableMeta>)> {
         let mut name_filter = self.name_filter_fn();
+        // Translators can disappear between runs, for example if they were loaded from a
+        // Python file, so do not filter on unknown ones
+        let translator = self
+            .translator
+            .as_deref()
+            .filter(|name| translators.all_translator_names().contains(name))
+            .map(|name| (translators, name));
 
         variables
             .iter()
             .filter(|&vr| name_filter(&vr.name))
-            .filter(|&vr| self.kind_filter(vr, wave_container_opt))
-            .cloned()
+            .map(|vr| (vr, get_variable_meta(vr, wave_container_opt)))
+            .filter(|(_, meta)| self.kind_filter(meta.as_ref(), translator))
+            .map(|(vr, meta)| (vr.clone(), meta))
             .collect_vec()
     }
 }
@@ -183,39 +330,44 @@ impl SystemState {
                                         &self.user.variable_filter,
                                     )));
                                 }
-                                ScopeType::StreamScope(active_scope) => {
-                                    let Transactions(inner) = &waves.inner else {
-                                        return;
-                                    };
-                                    match active_scope {
-                                        StreamScopeRef::Root => {
+                                ScopeType::StreamScope(active_scope) => match active_scope {
+                                    StreamScopeRef::Root => {
+                                        for (container, inner) in
+                                            waves.inner.transaction_containers().iter().enumerate()
+                                        {
                                             for stream in inner.get_streams() {
                                                 msgs.push(Message::AddStreamOrGenerator(
                                                     TransactionStreamRef::new_stream(
                                                         stream.id,
                                                         stream.name.clone(),
-                                                    ),
+                                                    )
+                                                    .in_container(container),
                                                 ));
                                             }
                                         }
-                                        StreamScopeRef::Stream(s) => {
-                                            for gen_id in
-                                                &inner.get_stream(s.stream_id).unwrap().generators
-                                            {
-                                                let gen = inner.get_generator(*gen_id).unwrap();
-
-                                                msgs.push(Message::AddStreamOrGenerator(
-                                                    TransactionStreamRef::new_gen(
-                                                        gen.stream_id,
-                                                        gen.id,
-                                                        gen.name.clone(),
-                                                    ),
-                                                ));
-                                            }
+                                    }
+                                    StreamScopeRef::Stream(s) => {
+                                        let Some(inner) = waves.inner.transactions(s.container)
+                                        else {
+                                            return;
+                                        };
+                                        for gen_id in
+                                            &inner.get_stream(s.stream_id).unwrap().generators
+                                        {
+                                            let gen = inner.get_generator(*gen_id).unwrap();
+
+                                            msgs.push(Message::AddStreamOrGenerator(
+                                                TransactionStreamRef::new_gen(
+                                                    gen.stream_id,
+                                                    gen.id,
+                                                    gen.name.clone(),
+                                                )
+                                                .in_container(s.container),
+                                            ));
                                         }
-                                        StreamScopeRef::Empty(_) => {}
                                     }
-                                }
+                                    StreamScopeRef::Empty(_) => {}
+                                },
                             }
                         }
                     }
@@ -355,24 +507,86 @@ impl SystemState {
                 ));
             });
         });
+
+        ui.separator();
+
+        self.variable_meta_filter_menu(ui, msgs);
     }
 
-    pub fn variable_cmp(
-        &self,
-        a: &VariableRef,
-        b: &VariableRef,
-        wave_container: Option<&WaveContainer>,
-    ) -> Ordering {
-        let a_direction = get_variable_direction(a, wave_container);
-        let b_direction = get_variable_direction(b, wave_container);
+    fn variable_meta_filter_menu(&self, ui: &mut Ui, msgs: &mut Vec<Message>) {
+        let filter = &self.user.variable_filter;
 
-        if !self.user.variable_filter.group_by_direction || a_direction == b_direction {
-            numeric_sort::cmp(&a.name, &b.name)
-        } else if a_direction < b_direction {
-            Ordering::Less
-        } else {
-            Ordering::Greater
-        }
+        ui.menu_button("Variable types", |ui| {
+            ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+            for variable_type in enum_iterator::all::<VariableType>() {
+                let mut included = filter.includes_type(variable_type);
+                if ui
+                    .checkbox(&mut included, variable_type.to_string())
+                    .clicked()
+                {
+                    msgs.push(Message::SetVariableTypeFilter(
+                        filter.types_with(variable_type, included),
+                    ));
+                }
+            }
+        });
+
+        ui.menu_button("Translator", |ui| {
+            ui.style_mut().wrap_mode = Some(TextWrapMode::Extend);
+            ui.radio(filter.translator.is_none(), "Any")
+                .clicked()
+                .then(|| {
+                    ui.close_menu();
+                    msgs.push(Message::SetVariableTranslatorFilter(None));
+                });
+            for name in self.translators.all_translator_names().into_iter().sorted() {
+                ui.radio(filter.translator.as_deref() == Some(name), name)
+                    .clicked()
+                    .then(|| {
+                        ui.close_menu();
+                        msgs.push(Message::SetVariableTranslatorFilter(Some(name.to_string())));
+                    });
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut min_width = filter.min_width.unwrap_or(0);
+            let mut max_width = filter.max_width.unwrap_or(0);
+            ui.label("Width");
+            let min_changed = ui
+                .add(DragValue::new(&mut min_width))
+                .on_hover_text("Minimum number of bits, 0 for no limit")
+                .changed();
+            ui.label("to");
+            let max_changed = ui
+                .add(DragValue::new(&mut max_width))
+                .on_hover_text("Maximum number of bits, 0 for no limit")
+                .changed();
+            if min_changed || max_changed {
+                msgs.push(Message::SetVariableWidthFilter(
+                    (min_width > 0).then_some(min_width),
+                    (max_width > 0).then_some(max_width),
+                ));
+            }
+        });
+
+        ui.menu_button("Sort by", |ui| {
+            for sort_type in enum_iterator::all::<VariableSortType>() {
+                ui.radio(filter.sort_by == sort_type, sort_type.to_string())
+                    .clicked()
+                    .then(|| {
+                        ui.close_menu();
+                        msgs.push(Message::SetVariableSortType(sort_type));
+                    });
+            }
+        });
+
+        ui.add_enabled(filter.has_meta_filters(), Button::new("Clear type filters"))
+            .clicked()
+            .then(|| {
+                ui.close_menu();
+                msgs.push(Message::ClearVariableMetaFilters);
+            });
     }
 
     pub fn filtered_variables(
@@ -386,24 +600,195 @@ impl SystemState {
         };
 
         variable_filter
-            .matching_variables(variables, wave_container)
-            .iter()
-            .sorted_by(|a, b| self.variable_cmp(a, b, wave_container))
-            .cloned()
+            .matching_variables_with_meta(variables, wave_container, &self.translators)
+            .into_iter()
+            .sorted_by(|(a, a_meta), (b, b_meta)| {
+                variable_filter.variable_cmp((a, a_meta.as_ref()), (b, b_meta.as_ref()))
+            })
+            .map(|(vr, _)| vr)
             .collect_vec()
     }
 }
 
-fn get_variable_direction(
+fn get_variable_meta(
     vr: &VariableRef,
     wave_container_opt: Option<&WaveContainer>,
-) -> VariableDirection {
-    match wave_container_opt {
-        Some(wave_container) => wave_container
-            .variable_meta(vr)
-            .map_or(VariableDirection::Unknown, |m| {
-                m.direction.unwrap_or(VariableDirection::Unknown)
-            }),
-        None => VariableDirection::Unknown,
+) -> Option<VariableMeta> {
+    wave_container_opt.and_then(|wave_container| wave_container.variable_meta(vr).ok())
+}
+
+fn variable_direction(meta: Option<&VariableMeta>) -> VariableDirection {
+    meta.and_then(|m| m.direction)
+        .unwrap_or(VariableDirection::Unknown)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use surfer_translation_types::VariableEncoding;
+
+    use super::*;
+    use crate::wave_container::VariableRefExt;
+
+    fn meta(
+        name: &str,
+        variable_type: VariableType,
+        num_bits: u32,
+        direction: VariableDirection,
+    ) -> (VariableRef, VariableMeta) {
+        let var = VariableRef::from_strs(&["top", name]);
+        let meta = VariableMeta {
+            var: var.clone(),
+            num_bits: Some(num_bits),
+            variable_type: Some(variable_type),
+            variable_type_name: None,
+            index: None,
+            direction: Some(direction),
+            enum_map: HashMap::new(),
+            encoding: VariableEncoding::BitVector,
+        };
+        (var, meta)
+    }
+
+    fn sorted(filter: &VariableFilter, variables: &[(VariableRef, VariableMeta)]) -> Vec<String> {
+        variables
+            .iter()
+            .sorted_by(|(a, a_meta), (b, b_meta)| {
+                filter.variable_cmp((a, Some(a_meta)), (b, Some(b_meta)))
+            })
+            .map(|(vr, _)| vr.name.clone())
+            .collect()
+    }
+
+    fn variables() -> Vec<(VariableRef, VariableMeta)> {
+        vec![
+            meta(
+                "data10",
+                VariableType::VCDWire,
+                8,
+                VariableDirection::Output,
+            ),
+            meta("data2", VariableType::VCDWire, 8, VariableDirection::Output),
+            meta("clk", VariableType::Logic, 1, VariableDirection::Input),
+            meta("state", VariableType::Enum, 3, VariableDirection::Unknown),
+        ]
+    }
+
+    #[test]
+    fn kind_filter_checks_direction() {
+        let (_, input) = meta("clk", VariableType::Logic, 1, VariableDirection::Input);
+        let mut filter = VariableFilter::new();
+        assert!(filter.kind_filter(Some(&input), None));
+        filter.include_inputs = false;
+        assert!(!filter.kind_filter(Some(&input), None));
+        // Variables without meta data count as having an unknown direction
+        assert!(filter.kind_filter(None, None));
+        filter.include_others = false;
+        assert!(!filter.kind_filter(None, None));
+    }
+
+    #[test]
+    fn kind_filter_checks_type_and_width() {
+        let (_, clk) = meta("clk", VariableType::Logic, 1, VariableDirection::Input);
+        let (_, data) = meta("data", VariableType::VCDWire, 8, VariableDirection::Output);
+        let mut filter = VariableFilter::new();
+        filter.variable_types = vec![VariableType::VCDWire];
+        assert!(!filter.kind_filter(Some(&clk), None));
+        assert!(filter.kind_filter(Some(&data), None));
+        // Without meta data it is unknown whether the variable passes the filters
+        assert!(!filter.kind_filter(None, None));
+
+        filter.variable_types.clear();
+        filter.min_width = Some(2);
+        assert!(!filter.kind_filter(Some(&clk), None));
+        assert!(filter.kind_filter(Some(&data), None));
+        filter.max_width = Some(4);
+        assert!(!filter.kind_filter(Some(&data), None));
+    }
+
+    #[test]
+    fn types_with_keeps_declaration_order() {
+        let mut filter = VariableFilter::new();
+        filter.variable_types = vec![VariableType::StdLogic, VariableType::Bit];
+        assert_eq!(
+            filter.types_with(VariableType::VCDReg, true),
+            vec![
+                VariableType::VCDReg,
+                VariableType::Bit,
+                VariableType::StdLogic
+            ]
+        );
+        assert_eq!(
+            filter.types_with(VariableType::Bit, false),
+            vec![VariableType::StdLogic]
+        );
+    }
+
+    #[test]
+    fn types_with_uses_empty_list_for_all_types() {
+        let mut filter = VariableFilter::new();
+        let types = filter.types_with(VariableType::VCDWire, false);
+        assert_eq!(
+            types.len(),
+            enum_iterator::cardinality::<VariableType>() - 1
+        );
+        assert!(!types.contains(&VariableType::VCDWire));
+
+        filter.variable_types = types;
+        assert!(filter.types_with(VariableType::VCDWire, true).is_empty());
+    }
+
+    #[test]
+    fn variables_are_sorted_by_name_numerically() {
+        let filter = VariableFilter::new();
+        assert_eq!(
+            sorted(&filter, &variables()),
+            vec!["clk", "data2", "data10", "state"]
+        );
+    }
+
+    #[test]
+    fn variables_are_sorted_by_type_then_name() {
+        let filter = VariableFilter {
+            sort_by: VariableSortType::Type,
+            ..VariableFilter::new()
+        };
+        assert_eq!(
+            sorted(&filter, &variables()),
+            vec!["state", "clk", "data2", "data10"]
+        );
+    }
+
+    #[test]
+    fn variables_are_sorted_by_width_then_name() {
+        let filter = VariableFilter {
+            sort_by: VariableSortType::Width,
+            ..VariableFilter::new()
+        };
+        assert_eq!(
+            sorted(&filter, &variables()),
+            vec!["clk", "state", "data2", "data10"]
+        );
+    }
+
+    #[test]
+    fn direction_groups_come_before_sort_type() {
+        let filter = VariableFilter {
+            sort_by: VariableSortType::Width,
+            group_by_direction: true,
+            ..VariableFilter::new()
+        };
+        let mut variables = variables();
+        variables.push(meta(
+            "en",
+            VariableType::Logic,
+            1,
+            VariableDirection::Output,
+        ));
+        assert_eq!(
+            sorted(&filter, &variables),
+            vec!["clk", "en", "data2", "data10", "state"]
+        );
     }
 }
@@ -43,3 +43,4 @@ impl VariableTypeExt for VariableType {
         }
     }
 }
+
@@ -239,6 +239,14 @@ impl eframe::App for SystemState {
         if let Some(waves) = self.user.waves.as_ref().and_then(|w| w.inner.as_waves()) {
             waves.tick()
         }
+        if self
+            .user
+            .waves
+            .as_mut()
+            .is_some_and(|waves| waves.follow_simulation())
+        {
+            self.invalidate_draw_commands();
+        }
 
         if viewport_is_moving {
             self.invalidate_draw_commands();
@@ -251,6 +259,9 @@ impl eframe::App for SystemState {
         #[cfg(feature = "performance_plot")]
         self.timing.borrow_mut().end("handle_wcp_commands");
 
+        #[cfg(not(target_arch = "wasm32"))]
+        self.autosave_if_due(ctx);
+
         // We can save some user battery life by not redrawing unless needed. At the moment,
         // we only need to continuously redraw to make surfer interactive during loading, otherwise
         // we'll let egui manage repainting. In practice
@@ -314,6 +325,10 @@ impl SystemState {
             self.draw_open_sibling_state_file_dialog(ctx, dialog, &mut msgs);
         }
 
+        if let Some(dialog) = &self.user.show_time_alignment_dialog {
+            self.draw_time_alignment_dialog(ctx, dialog, &mut msgs);
+        }
+
         if self.user.show_performance {
             #[cfg(feature = "performance_plot")]
             self.draw_performance_graph(ctx, &mut msgs);
@@ -325,6 +340,36 @@ impl SystemState {
             }
         }
 
+        if self.user.show_diagnostics_window {
+            if let Some(waves) = &self.user.waves {
+                self.draw_diagnostics_window(waves, ctx, &mut msgs);
+            }
+        }
+
+        if self.user.show_hierarchy_search {
+            if let Some(waves) = &self.user.waves {
+                self.draw_hierarchy_search_window(waves, ctx, &mut msgs);
+            }
+        }
+
+        if self.user.show_transaction_search {
+            if let Some(waves) = &self.user.waves {
+                self.draw_transaction_search_window(waves, ctx, &mut msgs);
+            }
+        }
+
+        #[cfg(feature = "performance_plot")]
+        if let (Some(waves), Some(stream)) = (
+            &self.user.waves,
+            &self.user.transaction_statistics_stream,
+        ) {
+            self.draw_transaction_statistics_window(waves, stream, ctx, &mut msgs);
+        }
+
+        if self.user.disassembly_address.is_some() {
+            self.draw_disassembly_window(ctx, &mut msgs);
+        }
+
         if let Some(idx) = self.user.rename_target {
             draw_rename_window(
                 ctx,
@@ -346,6 +391,10 @@ impl SystemState {
             self.add_toolbar_panel(ctx, &mut msgs);
         }
 
+        if !self.user.other_tabs.is_empty() {
+            self.add_tab_panel(ctx, &mut msgs);
+        }
+
         if self.user.show_url_entry {
             self.draw_load_url(ctx, &mut msgs);
         }
@@ -552,6 +601,7 @@ impl SystemState {
         if !self.user.show_url_entry
             && self.user.rename_target.is_none()
             && self.user.show_reload_suggestion.is_none()
+            && self.user.show_time_alignment_dialog.is_none()
         {
             self.handle_pressed_keys(ctx, &mut msgs);
         }
@@ -703,6 +753,15 @@ impl SystemState {
                 msgs.push(Message::AddScopeAsGroup(scope.clone(), true));
                 ui.close_menu();
             }
+            ui.separator();
+            if ui.button("Save view as template...").clicked() {
+                msgs.push(Message::SaveViewTemplate(scope.clone(), None));
+                ui.close_menu();
+            }
+            if ui.button("Apply template...").clicked() {
+                msgs.push(Message::LoadViewTemplate(scope.clone(), None));
+                ui.close_menu();
+            }
         });
         response
             .clicked()
@@ -1262,24 +1321,30 @@ impl SystemState {
             );
         })
         .body(|ui| {
-            for (id, stream) in &streams.inner.as_transactions().unwrap().inner.tx_streams {
-                let name = stream.name.clone();
-                let response = ui.add(egui::SelectableLabel::new(
-                    streams.active_scope.as_ref().is_some_and(|s| {
-                        if let ScopeType::StreamScope(StreamScopeRef::Stream(scope_stream)) = s {
-                            scope_stream.stream_id == *id
-                        } else {
-                            false
-                        }
-                    }),
-                    name.clone(),
-                ));
-
-                response.clicked().then(|| {
-                    msgs.push(Message::SetActiveScope(ScopeType::StreamScope(
-                        StreamScopeRef::Stream(TransactionStreamRef::new_stream(*id, name)),
-                    )));
-                });
+            for (container, inner) in streams.inner.transaction_containers().iter().enumerate() {
+                for (id, stream) in &inner.inner.tx_streams {
+                    let name = stream.name.clone();
+                    let response = ui.add(egui::SelectableLabel::new(
+                        streams.active_scope.as_ref().is_some_and(|s| {
+                            if let ScopeType::StreamScope(StreamScopeRef::Stream(scope_stream)) = s
+                            {
+                                scope_stream.container == container
+                                    && scope_stream.stream_id == *id
+                            } else {
+                                false
+                            }
+                        }),
+                        name.clone(),
+                    ));
+
+                    response.clicked().then(|| {
+                        msgs.push(Message::SetActiveScope(ScopeType::StreamScope(
+                            StreamScopeRef::Stream(
+                                TransactionStreamRef::new_stream(*id, name).in_container(container),
+                            ),
+                        )));
+                    });
+                }
             }
         });
     }
@@ -1291,29 +1356,36 @@ impl SystemState {
         ui: &mut egui::Ui,
         active_stream: &StreamScopeRef,
     ) {
-        let inner = streams.inner.as_transactions().unwrap();
         match active_stream {
             StreamScopeRef::Root => {
-                for stream in inner.get_streams() {
-                    ui.with_layout(
-                        Layout::top_down(Align::LEFT).with_cross_justify(true),
-                        |ui| {
-                            let response =
-                                ui.add(egui::SelectableLabel::new(false, stream.name.clone()));
-
-                            response.clicked().then(|| {
-                                msgs.push(Message::AddStreamOrGenerator(
-                                    TransactionStreamRef::new_stream(
-                                        stream.id,
-                                        stream.name.clone(),
-                                    ),
-                                ));
-                            });
-                        },
-                    );
+                for (container, inner) in
+                    streams.inner.transaction_containers().iter().enumerate()
+                {
+                    for stream in inner.get_streams() {
+                        ui.with_layout(
+                            Layout::top_down(Align::LEFT).with_cross_justify(true),
+                            |ui| {
+                                let response =
+                                    ui.add(egui::SelectableLabel::new(false, stream.name.clone()));
+
+                                response.clicked().then(|| {
+                                    msgs.push(Message::AddStreamOrGenerator(
+                                        TransactionStreamRef::new_stream(
+                                            stream.id,
+                                            stream.name.clone(),
+                                        )
+                                        .in_container(container),
+                                    ));
+                                });
+                            },
+                        );
+                    }
                 }
             }
             StreamScopeRef::Stream(stream_ref) => {
+                let Some(inner) = streams.inner.transactions(stream_ref.container) else {
+                    return;
+                };
                 for gen_id in &inner.get_stream(stream_ref.stream_id).unwrap().generators {
                     let gen_name = inner.get_generator(*gen_id).unwrap().name.clone();
                     ui.with_layout(
@@ -1327,7 +1399,8 @@ impl SystemState {
                                         stream_ref.stream_id,
                                         *gen_id,
                                         gen_name,
-                                    ),
+                                    )
+                                    .in_container(stream_ref.container),
                                 ));
                             });
                         },
@@ -1352,15 +1425,13 @@ impl SystemState {
                         });
                     })
                     .body(|mut body| {
-                        let focused_transaction = self
-                            .user
-                            .waves
-                            .as_ref()
-                            .unwrap()
+                        let waves = self.user.waves.as_ref().unwrap();
+                        let container = waves
                             .focused_transaction
-                            .1
+                            .0
                             .as_ref()
-                            .unwrap();
+                            .map_or(0, |tx_ref| tx_ref.container);
+                        let focused_transaction = waves.focused_transaction.1.as_ref().unwrap();
                         let row_height = 15.;
                         body.row(row_height, |mut row| {
                             row.col(|ui| {
@@ -1375,13 +1446,9 @@ impl SystemState {
                                 ui.label("Type");
                             });
                             row.col(|ui| {
-                                let gen = self
-                                    .user
-                                    .waves
-                                    .as_ref()
-                                    .unwrap()
+                                let gen = waves
                                     .inner
-                                    .as_transactions()
+                                    .transactions(container)
                                     .unwrap()
                                     .get_generator(focused_transaction.get_gen_id())
                                     .unwrap();
@@ -1393,7 +1460,11 @@ impl SystemState {
                                 ui.label("Start Time");
                             });
                             row.col(|ui| {
-                                ui.label(focused_transaction.get_start_time().to_string());
+                                let time = waves.transaction_time(
+                                    container,
+                                    &focused_transaction.get_start_time(),
+                                );
+                                ui.label(time.to_string());
                             });
                         });
                         body.row(row_height, |mut row| {
@@ -1401,7 +1472,11 @@ impl SystemState {
                                 ui.label("End Time");
                             });
                             row.col(|ui| {
-                                ui.label(focused_transaction.get_end_time().to_string());
+                                let time = waves.transaction_time(
+                                    container,
+                                    &focused_transaction.get_end_time(),
+                                );
+                                ui.label(time.to_string());
                             });
                         });
                         body.row(row_height + 5., |mut row| {
@@ -2147,9 +2222,6 @@ impl SystemState {
             let meta = waves.inner.as_waves().unwrap().variable_meta(variable);
 
             let translation_result = waves
-                .inner
-                .as_waves()
-                .unwrap()
                 .query_variable(variable, ucursor)
                 .ok()
                 .flatten()
@@ -0,0 +1,467 @@
+//! Templates of the displayed items which can be applied to other waveforms, or to other
+//! instances of the same design, by referencing variables relative to a root scope.
+use std::path::PathBuf;
+
+use eyre::Context;
+use itertools::Itertools;
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::async_util::perform_async_work;
+use crate::displayed_item::{DisplayedItem, DisplayedPlaceholder};
+use crate::displayed_item_tree::{ItemIndex, TargetPosition};
+use crate::file_dialog::{load_file_dialog, save_file_dialog};
+use crate::message::Message;
+use crate::translation::TranslatorList;
+use crate::wave_container::{ScopeRef, ScopeRefExt, VariableRef, VariableRefExt, WaveContainer};
+use crate::wave_data::WaveData;
+use crate::wellen::LoadSignalsCmd;
+use crate::SystemState;
+
+pub const TEMPLATE_FILE_EXTENSION: &str = "surt.ron";
+
+/// An item of a [`ViewTemplate`]
+#[derive(Clone, Serialize, Deserialize)]
+pub enum TemplateItem {
+    /// A variable, with its path relative to the root scope
+    Variable(DisplayedPlaceholder),
+    /// All variables below the root scope whose path relative to the root, with the scopes
+    /// separated by `.`, matches the regular expression. The variables are shown with the
+    /// settings of the placeholder. Patterns are not created by [`ViewTemplate::from_view`],
+    /// they are added by editing a saved template by hand.
+    Pattern(String, DisplayedPlaceholder),
+    /// A divider, timeline or group
+    Other(DisplayedItem),
+}
+
+/// Displayed items which are not tied to a specific waveform or scope
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ViewTemplate {
+    /// The items and their nesting level, in display order
+    pub items: Vec<(u8, TemplateItem)>,
+}
+
+impl std::fmt::Debug for ViewTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ViewTemplate {{ {} items }}", self.items.len())
+    }
+}
+
+/// The path of `variable` relative to `root`, if it is below `root`
+fn relative_path(variable: &VariableRef, root: &ScopeRef) -> Option<String> {
+    let scopes = variable.path.strs().strip_prefix(root.strs())?;
+    Some(scopes.iter().chain([&variable.name]).join("."))
+}
+
+/// A regular expression matching the whole path for a template pattern
+fn pattern_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{pattern})$"))
+}
+
+/// `placeholder` moved to `variable`
+fn placeholder_for(placeholder: &DisplayedPlaceholder, variable: VariableRef) -> DisplayedItem {
+    let mut placeholder = placeholder.clone();
+    placeholder.display_name = variable.name.clone();
+    placeholder.variable_ref =
+        VariableRef::new(variable.path.with_id(Default::default()), variable.name);
+    DisplayedItem::Placeholder(placeholder)
+}
+
+impl ViewTemplate {
+    /// Creates a template of the displayed items with the variables relative to `root`.
+    /// Variables outside of `root`, markers and streams are left out.
+    pub fn from_view(waves: &WaveData, root: &ScopeRef) -> Self {
+        let mut skipped = 0;
+        let items = waves
+            .items_tree
+            .iter()
+            .filter_map(|node| {
+                let placeholder = match waves.displayed_items.get(&node.item_ref)? {
+                    DisplayedItem::Variable(variable) => variable.clone().into_placeholder(),
+                    DisplayedItem::Placeholder(placeholder) => placeholder.clone(),
+                    DisplayedItem::Group(group) => {
+                        let mut group = group.clone();
+                        group.content.clear();
+                        return Some((
+                            node.level,
+                            TemplateItem::Other(DisplayedItem::Group(group)),
+                        ));
+                    }
+                    item @ (DisplayedItem::Divider(_) | DisplayedItem::TimeLine(_)) => {
+                        return Some((node.level, TemplateItem::Other(item.clone())));
+                    }
+                    DisplayedItem::Marker(_) | DisplayedItem::Stream(_) => {
+                        skipped += 1;
+                        return None;
+                    }
+                };
+                let Some(path) = placeholder
+                    .variable_ref
+                    .path
+                    .strs()
+                    .strip_prefix(root.strs())
+                else {
+                    skipped += 1;
+                    return None;
+                };
+                let variable = VariableRef::new(
+                    ScopeRef::from_strs(path),
+                    placeholder.variable_ref.name.clone(),
+                );
+                Some((
+                    node.level,
+                    TemplateItem::Variable(DisplayedPlaceholder {
+                        variable_ref: variable,
+                        ..placeholder
+                    }),
+                ))
+            })
+            .collect();
+        if skipped > 0 {
+            warn!(
+                "Left out {skipped} item(s) which are not variables below {root} from the template"
+            );
+        }
+        ViewTemplate { items }
+    }
+}
+
+impl WaveData {
+    /// Adds the items of `template` after the displayed items, with the variables looked up
+    /// below `root`. Variables which are not found are added as placeholders.
+    pub fn apply_template(
+        &mut self,
+        template: &ViewTemplate,
+        root: &ScopeRef,
+        translators: &TranslatorList,
+    ) -> Option<LoadSignalsCmd> {
+        let variables = self
+            .inner
+            .as_waves()
+            .map(WaveContainer::variables)
+            .unwrap_or_default();
+        let mut last_level = None;
+        let mut inserted = vec![];
+        for (level, item) in &template.items {
+            let new_items = match item {
+                TemplateItem::Variable(placeholder) => {
+                    let path = root
+                        .strs()
+                        .iter()
+                        .chain(placeholder.variable_ref.path.strs())
+                        .collect_vec();
+                    vec![placeholder_for(
+                        placeholder,
+                        VariableRef::new(
+                            ScopeRef::from_strs(&path),
+                            placeholder.variable_ref.name.clone(),
+                        ),
+                    )]
+                }
+                TemplateItem::Pattern(pattern, placeholder) => {
+                    let matching = match pattern_regex(pattern) {
+                        Ok(regex) => variables
+                            .iter()
+                            .filter(|variable| {
+                                relative_path(variable, root).is_some_and(|p| regex.is_match(&p))
+                            })
+                            .collect_vec(),
+                        Err(e) => {
+                            warn!("Invalid template pattern '{pattern}': {e}");
+                            vec![]
+                        }
+                    };
+                    if matching.is_empty() {
+                        vec![placeholder_for(
+                            placeholder,
+                            VariableRef::new(root.clone(), pattern.clone()),
+                        )]
+                    } else {
+                        matching
+                            .into_iter()
+                            .map(|variable| placeholder_for(placeholder, variable.clone()))
+                            .collect()
+                    }
+                }
+                TemplateItem::Other(item) => vec![item.clone()],
+            };
+            // Keep the nesting valid, the template may have been edited by hand
+            let level = last_level.map_or(0, |last: u8| (*level).min(last.saturating_add(1)));
+            for new_item in new_items {
+                let position = TargetPosition {
+                    before: ItemIndex(self.items_tree.len()),
+                    level,
+                };
+                inserted.push(self.insert_item(new_item, Some(position), false));
+                last_level = Some(level);
+            }
+        }
+
+        let load_commands = self.resolve_placeholders(&inserted, translators);
+        self.compute_variable_display_names();
+        load_commands
+    }
+}
+
+impl SystemState {
+    /// Saves a template of the displayed items with the variables relative to `root`. Opens a
+    /// save dialog if no path is given.
+    pub(crate) fn save_view_template(&self, root: ScopeRef, path: Option<PathBuf>) {
+        let Some(waves) = &self.user.waves else {
+            return;
+        };
+        let template = ViewTemplate::from_view(waves, &root);
+        let encoded = match ron::Options::default()
+            .to_string_pretty(&template, ron::ser::PrettyConfig::default())
+        {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                log::error!("Failed to encode view template: {e:#}");
+                return;
+            }
+        };
+
+        perform_async_work(async move {
+            let destination = if let Some(path) = path {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    Some(path.into())
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    None
+                }
+            } else {
+                save_file_dialog("Save view template", template_file_filter()).await
+            };
+            let Some(destination) = destination else {
+                return;
+            };
+            destination
+                .write(encoded.as_bytes())
+                .await
+                .map_err(|e| {
+                    log::error!("Failed to write view template to {destination:#?} {e:#?}")
+                })
+                .ok();
+        });
+    }
+
+    /// Loads a template and applies it to `root`. Opens a file dialog if no path is given.
+    pub(crate) fn load_view_template(&self, root: ScopeRef, path: Option<PathBuf>) {
+        let sender = self.channels.msg_sender.clone();
+
+        perform_async_work(async move {
+            let source = if let Some(path) = path {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    Some(path.into())
+                }
+                #[cfg(target_arch = "wasm32")]
+                {
+                    None
+                }
+            } else {
+                load_file_dialog("Apply view template", template_file_filter()).await
+            };
+            let Some(source) = source else {
+                return;
+            };
+            let bytes = source.read().await;
+            match ron::de::from_bytes::<ViewTemplate>(&bytes).context(format!(
+                "Failed loading view template {}",
+                source.file_name()
+            )) {
+                Ok(template) => sender
+                    .send(Message::ApplyViewTemplate(root, template))
+                    .unwrap(),
+                Err(e) => sender.send(Message::Error(e)).unwrap(),
+            }
+        });
+    }
+
+    pub(crate) fn apply_view_template(&mut self, root: ScopeRef, template: ViewTemplate) {
+        if self.user.waves.is_none() {
+            return;
+        }
+        self.save_current_canvas("Apply view template".to_string());
+        let Some(waves) = self.user.waves.as_mut() else {
+            return;
+        };
+        if let Some(cmd) = waves.apply_template(&template, &root, &self.translators) {
+            self.load_variables(cmd);
+        }
+        self.invalidate_draw_commands();
+    }
+}
+
+fn template_file_filter() -> (String, Vec<String>) {
+    (
+        format!("Surfer view templates (*.{TEMPLATE_FILE_EXTENSION})"),
+        vec![TEMPLATE_FILE_EXTENSION.to_string()],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use project_root::get_project_root;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    use super::*;
+    use crate::variable_name_type::VariableNameType;
+    use crate::wave_source::{LoadOptions, WaveFormat, WaveSource};
+
+    fn counter_state() -> SystemState {
+        let mut state = SystemState::new_default_config().unwrap();
+        let path = get_project_root().unwrap().join("examples/counter.vcd");
+        let header = wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap();
+        state.on_waves_loaded(
+            WaveSource::File(path.try_into().unwrap()),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(header.hierarchy))),
+            LoadOptions {
+                keep_variables: false,
+                keep_unavailable: false,
+            },
+        );
+        state
+    }
+
+    fn placeholder(path: &[&str]) -> DisplayedPlaceholder {
+        DisplayedPlaceholder {
+            variable_ref: VariableRef::from_strs(path),
+            color: Some("Blue".to_string()),
+            background_color: None,
+            display_name: path.last().unwrap().to_string(),
+            display_name_type: VariableNameType::Local,
+            manual_name: None,
+            format: None,
+            field_formats: vec![],
+            height_scaling_factor: None,
+            color_rules: vec![],
+        }
+    }
+
+    /// The level and relative path of the variables of `template`, sorted by path
+    fn variables(template: &ViewTemplate) -> Vec<(u8, String)> {
+        template
+            .items
+            .iter()
+            .filter_map(|(level, item)| match item {
+                TemplateItem::Variable(placeholder) => {
+                    Some((*level, placeholder.variable_ref.full_path_string()))
+                }
+                _ => None,
+            })
+            .sorted_by(|a, b| a.1.cmp(&b.1))
+            .collect()
+    }
+
+    #[test]
+    fn templates_round_trip_through_the_view() {
+        let mut state = counter_state();
+        let template = ViewTemplate {
+            items: vec![
+                (0, TemplateItem::Variable(placeholder(&["clk"]))),
+                (0, TemplateItem::Variable(placeholder(&["counter"]))),
+                (0, TemplateItem::Variable(placeholder(&["missing"]))),
+            ],
+        };
+        let root = ScopeRef::from_strs(&["tb", "dut"]);
+        let waves = state.user.waves.as_mut().unwrap();
+        waves.apply_template(&template, &root, &state.translators);
+
+        // The variables which exist are shown as variables below the root
+        let shown = waves
+            .displayed_items
+            .values()
+            .filter_map(|item| match item {
+                DisplayedItem::Variable(variable) => Some(variable.variable_ref.full_path_string()),
+                _ => None,
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(shown, vec!["tb.dut.clk", "tb.dut.counter"]);
+
+        let saved = ViewTemplate::from_view(waves, &root);
+        assert_eq!(variables(&saved), variables(&template));
+        assert!(saved.items.iter().all(|(_, item)| matches!(
+            item,
+            TemplateItem::Variable(placeholder) if placeholder.color.as_deref() == Some("Blue")
+        )));
+
+        // Applied to another root, the variables are looked up there
+        let other_root = ScopeRef::from_strs(&["tb"]);
+        waves.apply_template(&saved, &other_root, &state.translators);
+        assert_eq!(
+            variables(&ViewTemplate::from_view(waves, &other_root)),
+            vec![
+                (0, "clk".to_string()),
+                (0, "counter".to_string()),
+                (0, "dut.clk".to_string()),
+                (0, "dut.counter".to_string()),
+                (0, "dut.missing".to_string()),
+                (0, "missing".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn template_patterns_add_all_matching_variables() {
+        let mut state = counter_state();
+        let template = ViewTemplate {
+            items: vec![(
+                0,
+                TemplateItem::Pattern("c.*".to_string(), placeholder(&["c"])),
+            )],
+        };
+        let root = ScopeRef::from_strs(&["tb", "dut"]);
+        let waves = state.user.waves.as_mut().unwrap();
+        waves.apply_template(&template, &root, &state.translators);
+
+        assert_eq!(
+            variables(&ViewTemplate::from_view(waves, &root)),
+            vec![(0, "clk".to_string()), (0, "counter".to_string())]
+        );
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_root() {
+        let root = ScopeRef::from_strs(&["tb", "dut"]);
+        assert_eq!(
+            relative_path(&VariableRef::from_strs(&["tb", "dut", "core", "pc"]), &root),
+            Some("core.pc".to_string())
+        );
+        assert_eq!(
+            relative_path(&VariableRef::from_strs(&["tb", "dut", "clk"]), &root),
+            Some("clk".to_string())
+        );
+        assert_eq!(
+            relative_path(&VariableRef::from_strs(&["tb", "clk"]), &root),
+            None
+        );
+        assert_eq!(
+            relative_path(&VariableRef::from_strs(&["top", "clk"]), &ScopeRef::empty()),
+            Some("top.clk".to_string())
+        );
+    }
+
+    #[test]
+    fn patterns_match_the_whole_path() {
+        let regex = pattern_regex(r"core\.regs\.x[0-9]+").unwrap();
+        assert!(regex.is_match("core.regs.x12"));
+        assert!(!regex.is_match("core.regs.x12_next"));
+        assert!(!regex.is_match("other.core.regs.x1"));
+
+        let regex = pattern_regex("a|b").unwrap();
+        assert!(regex.is_match("a"));
+        assert!(!regex.is_match("ab"));
+    }
+}
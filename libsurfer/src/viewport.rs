@@ -1,6 +1,8 @@
 use std::ops::RangeInclusive;
 
-use derive_more::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use derive_more::{Add, AddAssign, Display, Div, FromStr, Mul, Neg, Sub, SubAssign};
+use enum_iterator::Sequence;
+use itertools::Itertools;
 use num::{BigInt, BigRational, FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
@@ -135,6 +137,14 @@ impl Viewport {
         BigInt::from(self.curr_right.absolute(num_timestamps).0 as i64)
     }
 
+    /// The time range the viewport will show once any ongoing animation has finished
+    pub fn target_range(&self, num_timestamps: &BigInt) -> (BigInt, BigInt) {
+        (
+            BigInt::from(self.target_left.absolute(num_timestamps).0 as i64),
+            BigInt::from(self.target_right.absolute(num_timestamps).0 as i64),
+        )
+    }
+
     pub fn as_absolute_time(&self, x: f64, view_width: f32, num_timestamps: &BigInt) -> Absolute {
         let time_spacing = self.width_absolute(num_timestamps) / view_width as f64;
 
@@ -295,6 +305,24 @@ impl Viewport {
         self.set_target_right(Relative(1.0));
     }
 
+    /// Keeps the zoom level while moving the viewport so that the end of a growing waveform
+    /// stays at the right edge
+    pub fn follow_end(&mut self, old_num_timestamps: &BigInt, new_num_timestamps: &BigInt) {
+        let width = self
+            .width_absolute(old_num_timestamps)
+            .relative(new_num_timestamps);
+        let (left, right) = if width.0 >= 1.0 {
+            (Relative(0.0), width)
+        } else {
+            (Relative(1.0) - width, Relative(1.0))
+        };
+        self.curr_left = left;
+        self.curr_right = right;
+        self.target_left = left;
+        self.target_right = right;
+        self.move_duration = None;
+    }
+
     pub fn go_to_start(&mut self) {
         let old_width = self.width();
         self.set_target_left(Relative(0.0));
@@ -496,6 +524,81 @@ impl Viewport {
     pub fn is_moving(&self) -> bool {
         self.move_duration.is_some()
     }
+
+    /// The range the viewport is moving towards, or the current range if it is not moving
+    pub fn destination(&self) -> (Relative, Relative) {
+        if self.is_moving() {
+            (self.target_left, self.target_right)
+        } else {
+            (self.curr_left, self.curr_right)
+        }
+    }
+
+    /// A copy of this viewport moved by `delta`, including any ongoing movement
+    fn shifted(&self, delta: Relative) -> Viewport {
+        Viewport {
+            curr_left: self.curr_left + delta,
+            curr_right: self.curr_right + delta,
+            target_left: self.target_left + delta,
+            target_right: self.target_right + delta,
+            move_start_left: self.move_start_left + delta,
+            move_start_right: self.move_start_right + delta,
+            ..*self
+        }
+    }
+}
+
+/// How the viewports follow each other when one of them is zoomed or scrolled
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Display, FromStr, PartialEq, Eq, Sequence, Serialize,
+)]
+pub enum ViewportLock {
+    /// Every viewport zooms and scrolls on its own
+    #[default]
+    Independent,
+
+    /// All viewports show the same zoom level but scroll on their own
+    Zoom,
+
+    /// All viewports zoom and scroll together, keeping the time offset between them
+    Offset,
+}
+
+/// Makes the other viewports follow the one that changed since `before`, which holds the
+/// destinations of all viewports before the change. Nothing is done if zero or several
+/// viewports changed.
+pub fn sync_viewports(
+    viewports: &mut [Viewport],
+    before: &[(Relative, Relative)],
+    lock: ViewportLock,
+) {
+    if lock == ViewportLock::Independent || viewports.len() != before.len() {
+        return;
+    }
+    let mut changed = viewports
+        .iter()
+        .zip(before)
+        .positions(|(viewport, before)| viewport.destination() != *before);
+    let (Some(leader_idx), None) = (changed.next(), changed.next()) else {
+        return;
+    };
+
+    let leader = viewports[leader_idx];
+    let (leader_left, leader_right) = leader.destination();
+    for (idx, viewport) in viewports.iter_mut().enumerate() {
+        if idx == leader_idx {
+            continue;
+        }
+        let delta = match lock {
+            ViewportLock::Independent => unreachable!(),
+            ViewportLock::Zoom => {
+                let (left, right) = viewport.destination();
+                (left + right - leader_left - leader_right) * 0.5
+            }
+            ViewportLock::Offset => before[idx].0 - before[leader_idx].0,
+        };
+        *viewport = leader.shifted(delta);
+    }
 }
 
 pub fn ease_in_out_size(r: RangeInclusive<f64>, t: f64) -> f64 {
@@ -507,3 +610,49 @@ pub enum ViewportStrategy {
     Instant,
     EaseInOut { duration: f32 },
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn viewport(left: f64, right: f64) -> Viewport {
+        let mut viewport = Viewport::new();
+        viewport.curr_left = Relative(left);
+        viewport.curr_right = Relative(right);
+        viewport
+    }
+
+    fn ranges(viewports: &[Viewport]) -> Vec<(f64, f64)> {
+        viewports
+            .iter()
+            .map(|v| (v.curr_left.0, v.curr_right.0))
+            .collect()
+    }
+
+    #[test]
+    fn zoom_lock_keeps_centers() {
+        let mut viewports = [viewport(0.0, 0.25), viewport(0.5, 0.75)];
+        let before = viewports.map(|v| v.destination());
+        viewports[0].zoom_to_fit();
+        sync_viewports(&mut viewports, &before, ViewportLock::Zoom);
+        assert_eq!(ranges(&viewports), vec![(0.0, 1.0), (0.125, 1.125)]);
+    }
+
+    #[test]
+    fn offset_lock_keeps_offsets() {
+        let mut viewports = [viewport(0.0, 0.25), viewport(0.5, 0.625)];
+        let before = viewports.map(|v| v.destination());
+        viewports[1].go_to_start();
+        sync_viewports(&mut viewports, &before, ViewportLock::Offset);
+        assert_eq!(ranges(&viewports), vec![(-0.5, -0.375), (0.0, 0.125)]);
+    }
+
+    #[test]
+    fn independent_viewports_are_not_synced() {
+        let mut viewports = [viewport(0.0, 0.25), viewport(0.5, 0.625)];
+        let before = viewports.map(|v| v.destination());
+        viewports[1].go_to_start();
+        sync_viewports(&mut viewports, &before, ViewportLock::Independent);
+        assert_eq!(ranges(&viewports), vec![(0.0, 0.25), (0.0, 0.125)]);
+    }
+}
@@ -1,6 +1,8 @@
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use chrono::prelude::{DateTime, Utc};
+use derive_more::{Display, FromStr};
+use enum_iterator::Sequence;
 use eyre::{bail, Result};
 use num::BigUint;
 use serde::{Deserialize, Serialize};
@@ -22,6 +24,27 @@ pub enum SimulationStatus {
     Finished,
 }
 
+/// The kind of statement that emitted a [`SimulationDiagnostic`]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, Display, FromStr, Sequence,
+)]
+pub enum DiagnosticKind {
+    Break,
+    Assert,
+    Assume,
+    Print,
+}
+
+/// A message emitted by the simulation, e.g. a failed assertion or a `$display`
+#[derive(Debug, Clone)]
+pub struct SimulationDiagnostic {
+    pub time: BigUint,
+    pub kind: DiagnosticKind,
+    pub text: String,
+    /// Source location of the statement which emitted the diagnostic, if known
+    pub source: Option<String>,
+}
+
 pub struct MetaData {
     pub date: Option<DateTime<Utc>>,
     pub version: Option<String>,
@@ -493,6 +516,52 @@ impl WaveContainer {
         }
     }
 
+    /// Runs the simulation until the absolute time `time`
+    pub fn run_simulation_until(&self, time: &BigUint) {
+        match self {
+            WaveContainer::Wellen(_) => {}
+            WaveContainer::Empty => {}
+            WaveContainer::Cxxrtl(c) => c.lock().unwrap().run_until(time),
+        }
+    }
+
+    /// Runs the simulation for `duration` past the latest simulated time
+    pub fn run_simulation_for(&self, duration: &BigUint) {
+        match self {
+            WaveContainer::Wellen(_) => {}
+            WaveContainer::Empty => {}
+            WaveContainer::Cxxrtl(c) => c.lock().unwrap().run_for(duration),
+        }
+    }
+
+    /// Runs the simulation for `cycles` periods of the clock `clock`
+    pub fn step_simulation_cycles(&self, clock: &VariableRef, cycles: u32) {
+        match self {
+            WaveContainer::Wellen(_) => {}
+            WaveContainer::Empty => {}
+            WaveContainer::Cxxrtl(c) => c.lock().unwrap().step_cycles(clock, cycles),
+        }
+    }
+
+    /// Like [`WaveContainer::unpause_simulation`] but keeps running until the simulation emits
+    /// a diagnostic of one of the `kinds`
+    pub fn run_simulation_until_diagnostic(&self, kinds: &[DiagnosticKind]) {
+        match self {
+            WaveContainer::Wellen(_) => {}
+            WaveContainer::Empty => {}
+            WaveContainer::Cxxrtl(c) => c.lock().unwrap().run_until_diagnostic(kinds),
+        }
+    }
+
+    /// Returns the diagnostics emitted by the simulation up to the currently displayed time
+    pub fn diagnostics(&self) -> Arc<Vec<SimulationDiagnostic>> {
+        match self {
+            WaveContainer::Wellen(_) => Arc::default(),
+            WaveContainer::Empty => Arc::default(),
+            WaveContainer::Cxxrtl(c) => c.lock().unwrap().diagnostics(),
+        }
+    }
+
     /// Called for `wellen` container, when the body of the waveform file has been parsed.
     pub fn wellen_add_body(&mut self, body: BodyResult) -> Result<Option<LoadSignalsCmd>> {
         match self {
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use eyre::{Result, WrapErr};
 use log::{error, info, warn};
 use num::bigint::ToBigInt as _;
-use num::{BigInt, BigUint, Zero};
+use num::{BigInt, BigUint, One, Zero};
 use serde::{Deserialize, Serialize};
 use surfer_translation_types::{TranslationPreference, Translator, VariableValue};
 
@@ -14,12 +14,19 @@ use crate::displayed_item::{
 };
 use crate::displayed_item_tree::{DisplayedItemTree, ItemIndex, TargetPosition, VisibleItemIndex};
 use crate::graphics::{Graphic, GraphicId};
-use crate::transaction_container::{StreamScopeRef, TransactionRef, TransactionStreamRef};
+use crate::navigation::ViewBookmark;
+use crate::time::TimeAlignment;
+use crate::transaction_container::{
+    StreamScopeRef, TransactionContainer, TransactionRef, TransactionStreamRef,
+};
+use crate::transaction_query::TransactionQuery;
 use crate::translation::{DynTranslator, TranslatorList, VariableInfoExt};
 use crate::variable_name_type::VariableNameType;
 use crate::view::ItemDrawingInfo;
-use crate::viewport::Viewport;
-use crate::wave_container::{ScopeRef, VariableMeta, VariableRef, VariableRefExt, WaveContainer};
+use crate::viewport::{Viewport, ViewportLock};
+use crate::wave_container::{
+    QueryResult, ScopeRef, VariableMeta, VariableRef, VariableRefExt, WaveContainer,
+};
 use crate::wave_source::{WaveFormat, WaveSource};
 use crate::wellen::LoadSignalsCmd;
 use ftr_parser::types::Transaction;
@@ -45,6 +52,12 @@ impl std::fmt::Display for ScopeType {
     }
 }
 
+/// The displayed streams from each transaction file which is not loaded yet
+pub(crate) type PendingTransactionItems = Vec<(WaveSource, Vec<DisplayedItemRef>)>;
+
+/// The transaction file referenced by streams whose file is not loaded yet
+const UNLOADED_TRANSACTIONS: usize = usize::MAX;
+
 #[derive(Serialize, Deserialize)]
 pub struct WaveData {
     #[serde(skip, default = "DataContainer::__new_empty")]
@@ -66,6 +79,36 @@ pub struct WaveData {
     pub scroll_offset: f32,
     pub display_variable_indices: bool,
     pub graphics: HashMap<GraphicId, Graphic>,
+    /// Maps the times in the file to the times shown, see [`TimeAlignment`]. This is the
+    /// waveform if there is one, otherwise the transaction file
+    #[serde(default)]
+    pub time_alignment: TimeAlignment,
+    /// How the viewports follow each other when one of them moves
+    #[serde(default)]
+    pub viewport_lock: ViewportLock,
+    /// If set, viewports other than the first have their own cursor, stored in
+    /// `viewport_cursors`, while the first one uses `cursor`
+    #[serde(default)]
+    pub per_viewport_cursors: bool,
+    #[serde(default)]
+    pub viewport_cursors: HashMap<usize, BigInt>,
+    /// Named views, see [`crate::navigation`]
+    #[serde(default)]
+    pub view_bookmarks: Vec<ViewBookmark>,
+    /// Transactions matching this query are highlighted, see [`crate::transaction_query`]
+    #[serde(default)]
+    pub transaction_query: Option<TransactionQuery>,
+    /// Where the transaction files were loaded from, indexed like
+    /// [`DataContainer::transaction_containers`]
+    #[serde(default)]
+    pub transaction_sources: Vec<WaveSource>,
+    /// Alignments of the transaction files loaded next to a waveform, by file. Files
+    /// without an alignment are not aligned
+    #[serde(default)]
+    pub transaction_alignments: Vec<(WaveSource, TimeAlignment)>,
+    /// Streams from transaction files which are still being loaded, by file
+    #[serde(skip)]
+    pub(crate) pending_transaction_items: PendingTransactionItems,
     /// These are just stored during operation, so no need to serialize
     #[serde(skip)]
     pub drawing_infos: Vec<ItemDrawingInfo>,
@@ -76,6 +119,10 @@ pub struct WaveData {
     /// used by the `update_viewports` method after loading a new file
     #[serde(skip)]
     pub old_num_timestamps: Option<BigInt>,
+    /// Set while a running simulation should be followed by the viewports. Holds the number
+    /// of timestamps the viewports were last adjusted to
+    #[serde(skip)]
+    pub followed_num_timestamps: Option<BigInt>,
 }
 
 fn select_preferred_translator(var: &VariableMeta, translators: &TranslatorList) -> String {
@@ -173,8 +220,23 @@ impl WaveData {
         );
 
         let old_num_timestamps = self.num_timestamps();
+
+        // Transaction files loaded alongside the old waveform are kept
+        let mut inner = DataContainer::Waves(*new_waves);
+        let mut transaction_sources = vec![];
+        for (transactions, source) in self
+            .inner
+            .into_transaction_containers()
+            .into_iter()
+            .zip(self.transaction_sources)
+        {
+            if inner.add_transactions(transactions).is_ok() {
+                transaction_sources.push(source);
+            }
+        }
+
         let mut new_wavedata = WaveData {
-            inner: DataContainer::Waves(*new_waves),
+            inner,
             source,
             format,
             active_scope,
@@ -192,8 +254,18 @@ impl WaveData {
             drawing_infos: vec![],
             top_item_draw_offset: 0.,
             graphics: HashMap::new(),
+            time_alignment: self.time_alignment,
+            viewport_lock: self.viewport_lock,
+            per_viewport_cursors: self.per_viewport_cursors,
+            viewport_cursors: self.viewport_cursors,
+            view_bookmarks: self.view_bookmarks,
+            transaction_query: self.transaction_query,
+            transaction_sources,
+            transaction_alignments: self.transaction_alignments,
+            pending_transaction_items: self.pending_transaction_items,
             total_height: 0.,
             old_num_timestamps,
+            followed_num_timestamps: None,
         };
 
         new_wavedata.update_metadata(translators);
@@ -208,12 +280,10 @@ impl WaveData {
         translators: &TranslatorList,
     ) -> Option<LoadSignalsCmd> {
         self.items_tree = items_tree;
-        self.displayed_items = self.update_displayed_items(
-            self.inner.as_waves().unwrap(),
-            new_items,
-            true,
-            translators,
-        );
+        self.displayed_items = match self.inner.as_waves() {
+            Some(waves) => self.update_displayed_items(waves, new_items, true, translators),
+            None => new_items.clone(),
+        };
         self.display_item_ref_counter = self
             .displayed_items
             .keys()
@@ -225,32 +295,83 @@ impl WaveData {
         self.load_waves()
     }
 
+    /// Turns the placeholders among `item_refs` whose variables exist into variables, returning
+    /// the command loading them
+    pub(crate) fn resolve_placeholders(
+        &mut self,
+        item_refs: &[DisplayedItemRef],
+        translators: &TranslatorList,
+    ) -> Option<LoadSignalsCmd> {
+        let waves = self.inner.as_waves()?;
+        let placeholders: HashMap<_, _> = item_refs
+            .iter()
+            .filter_map(|item_ref| match self.displayed_items.get(item_ref)? {
+                item @ DisplayedItem::Placeholder(_) => Some((*item_ref, item.clone())),
+                _ => None,
+            })
+            .collect();
+        let mut resolved = self.update_displayed_items(waves, &placeholders, true, translators);
+        let mut variables = vec![];
+        for item in resolved.values_mut() {
+            if let DisplayedItem::Variable(displayed_variable) = item {
+                retain_valid_field_formats(displayed_variable, waves, translators);
+                variables.push(displayed_variable.variable_ref.clone());
+            }
+        }
+        self.displayed_items.extend(resolved);
+        self.inner
+            .as_waves_mut()?
+            .load_variables(variables.iter())
+            .expect("internal error: failed to load variables")
+    }
+
+    /// Rewrites the transaction files referenced by the streams in `items`, which were saved
+    /// with the transaction files `sources`, to the indices of the files in this session.
+    /// Returns the files which are not loaded, their streams are pointed at them by
+    /// [`Self::add_transaction_source`] once they are.
+    pub(crate) fn map_transaction_sources(
+        &mut self,
+        sources: &[WaveSource],
+        items: &mut HashMap<DisplayedItemRef, DisplayedItem>,
+    ) -> Vec<WaveSource> {
+        self.pending_transaction_items =
+            map_transaction_items(&self.transaction_sources, sources, items);
+        self.pending_transaction_items
+            .iter()
+            .map(|(source, _)| source.clone())
+            .collect()
+    }
+
+    /// Adds the transaction file loaded from `source` next to the waveform, or replaces it if
+    /// it is already loaded
+    pub(crate) fn add_transaction_source(
+        &mut self,
+        source: WaveSource,
+        transactions: TransactionContainer,
+    ) {
+        if let Some(container) = self.transaction_sources.iter().position(|s| *s == source) {
+            self.inner.replace_transactions(container, transactions);
+        } else if let Ok(container) = self.inner.add_transactions(transactions) {
+            attach_transaction_items(
+                &mut self.pending_transaction_items,
+                &source,
+                container,
+                &mut self.displayed_items,
+            );
+            self.transaction_sources.push(source);
+        }
+    }
+
     /// Go through all signals and update the metadata for all signals
     ///
     /// Used after loading new waves, signals or switching a bunch of translators
     fn update_metadata(&mut self, translators: &TranslatorList) {
-        for (_vidx, di) in self.displayed_items.iter_mut() {
-            let DisplayedItem::Variable(displayed_variable) = di else {
-                continue;
-            };
-
-            let meta = self
-                .inner
-                .as_waves()
-                .unwrap()
-                .variable_meta(&displayed_variable.variable_ref.clone())
-                .unwrap();
-            let translator =
-                variable_translator(displayed_variable.get_format(&[]), &[], translators, || {
-                    Ok(meta.clone())
-                });
-            let info = translator.variable_info(&meta).ok();
-
-            match info {
-                Some(info) => displayed_variable
-                    .field_formats
-                    .retain(|ff| info.has_subpath(&ff.field)),
-                _ => displayed_variable.field_formats.clear(),
+        let Some(waves) = self.inner.as_waves() else {
+            return;
+        };
+        for di in self.displayed_items.values_mut() {
+            if let DisplayedItem::Variable(displayed_variable) = di {
+                retain_valid_field_formats(displayed_variable, waves, translators);
             }
         }
     }
@@ -264,8 +385,7 @@ impl WaveData {
             _ => None,
         });
         self.inner
-            .as_waves_mut()
-            .unwrap()
+            .as_waves_mut()?
             .load_variables(variables)
             .expect("internal error: failed to load variables")
     }
@@ -290,6 +410,31 @@ impl WaveData {
         }
     }
 
+    /// Makes the viewports follow the end of the simulation as it progresses, see
+    /// [`WaveData::follow_simulation`]
+    pub fn start_following_simulation(&mut self) {
+        self.followed_num_timestamps = Some(self.num_timestamps().unwrap_or_else(|| 1.into()));
+    }
+
+    /// Moves the viewports along with the end of a running simulation if
+    /// [`WaveData::followed_num_timestamps`] is set. Returns true if the viewports were moved
+    pub fn follow_simulation(&mut self) -> bool {
+        let Some(old_num_timestamps) = &self.followed_num_timestamps else {
+            return false;
+        };
+        let Some(new_num_timestamps) = self.num_timestamps() else {
+            return false;
+        };
+        if &new_num_timestamps == old_num_timestamps {
+            return false;
+        }
+        for viewport in self.viewports.iter_mut() {
+            viewport.follow_end(old_num_timestamps, &new_num_timestamps);
+        }
+        self.followed_num_timestamps = Some(new_num_timestamps);
+        true
+    }
+
     fn update_displayed_items(
         &self,
         waves: &WaveContainer,
@@ -432,6 +577,7 @@ impl WaveData {
                 format: None,
                 field_formats: vec![],
                 height_scaling_factor: None,
+                color_rules: vec![],
             });
 
             indices.push(self.insert_item(new_variable, Some(target_position), true));
@@ -538,35 +684,19 @@ impl WaveData {
 
     pub fn add_generator(&mut self, gen_ref: TransactionStreamRef) {
         let Some(gen_id) = gen_ref.gen_id else { return };
+        let Some(inner) = self.inner.transactions_mut(gen_ref.container) else {
+            return;
+        };
 
-        if self
-            .inner
-            .as_transactions()
-            .unwrap()
-            .get_generator(gen_id)
-            .unwrap()
-            .transactions
-            .is_empty()
-        {
+        if inner.get_generator(gen_id).unwrap().transactions.is_empty() {
             info!("(Generator {})Loading transactions into memory!", gen_id);
-            match self
-                .inner
-                .as_transactions_mut()
-                .unwrap()
-                .inner
-                .load_stream_into_memory(gen_ref.stream_id)
-            {
+            match inner.inner.load_stream_into_memory(gen_ref.stream_id) {
                 Ok(_) => info!("(Generator {}) Finished loading transactions!", gen_id),
                 Err(_) => return,
             }
         }
 
-        let gen = self
-            .inner
-            .as_transactions()
-            .unwrap()
-            .get_generator(gen_id)
-            .unwrap();
+        let gen = inner.get_generator(gen_id).unwrap();
         let mut last_times_on_row = vec![(BigUint::ZERO, BigUint::ZERO)];
         calculate_rows_of_stream(&gen.transactions, &mut last_times_on_row);
 
@@ -583,23 +713,18 @@ impl WaveData {
     }
 
     pub fn add_stream(&mut self, stream_ref: TransactionStreamRef) {
-        if self
-            .inner
-            .as_transactions_mut()
-            .unwrap()
+        let Some(inner) = self.inner.transactions_mut(stream_ref.container) else {
+            return;
+        };
+
+        if inner
             .get_stream(stream_ref.stream_id)
             .unwrap()
             .transactions_loaded
             .not()
         {
             info!("(Stream)Loading transactions into memory!");
-            match self
-                .inner
-                .as_transactions_mut()
-                .unwrap()
-                .inner
-                .load_stream_into_memory(stream_ref.stream_id)
-            {
+            match inner.inner.load_stream_into_memory(stream_ref.stream_id) {
                 Ok(_) => info!(
                     "(Stream {}) Finished loading transactions!",
                     stream_ref.stream_id
@@ -608,21 +733,11 @@ impl WaveData {
             }
         }
 
-        let stream = self
-            .inner
-            .as_transactions()
-            .unwrap()
-            .get_stream(stream_ref.stream_id)
-            .unwrap();
+        let stream = inner.get_stream(stream_ref.stream_id).unwrap();
         let mut last_times_on_row = vec![(BigUint::ZERO, BigUint::ZERO)];
 
         for gen_id in &stream.generators {
-            let gen = self
-                .inner
-                .as_transactions()
-                .unwrap()
-                .get_generator(*gen_id)
-                .unwrap();
+            let gen = inner.get_generator(*gen_id).unwrap();
             calculate_rows_of_stream(&gen.transactions, &mut last_times_on_row);
         }
 
@@ -638,14 +753,23 @@ impl WaveData {
         self.insert_item(new_stream, None, true);
     }
 
+    /// Adds the streams of all loaded transaction files
     pub fn add_all_streams(&mut self) {
-        let mut streams: Vec<(usize, String)> = vec![];
-        for stream in self.inner.as_transactions().unwrap().get_streams() {
-            streams.push((stream.id, stream.name.clone()));
-        }
+        let streams = self
+            .inner
+            .transaction_containers()
+            .iter()
+            .enumerate()
+            .flat_map(|(container, t)| {
+                t.get_streams().into_iter().map(move |stream| {
+                    TransactionStreamRef::new_stream(stream.id, stream.name.clone())
+                        .in_container(container)
+                })
+            })
+            .collect_vec();
 
-        for (id, name) in streams {
-            self.add_stream(TransactionStreamRef::new_stream(id, name));
+        for stream in streams {
+            self.add_stream(stream);
         }
     }
 
@@ -854,7 +978,7 @@ impl WaveData {
                     .get_visible(vidx)
                     .and_then(|node| self.displayed_items.get(&node.item_ref))
                 {
-                    if let Ok(Some(res)) = self.inner.as_waves().unwrap().query_variable(
+                    if let Ok(Some(res)) = self.query_variable(
                         &variable.variable_ref,
                         &cursor.to_biguint().unwrap_or_default(),
                     ) {
@@ -870,17 +994,17 @@ impl WaveData {
                                     "No timestamp count even though waveforms should be loaded",
                                 ));
                             }
-                        } else if let Some(stime) = res.current.unwrap().0.to_bigint() {
+                        } else if let Some(stime) =
+                            res.current.and_then(|current| current.0.to_bigint())
+                        {
                             let bigone = BigInt::from(1);
                             // Check if we are on a transition
                             if stime == *cursor && *cursor >= bigone {
                                 // If so, subtract cursor position by one
-                                if let Ok(Some(newres)) =
-                                    self.inner.as_waves().unwrap().query_variable(
-                                        &variable.variable_ref,
-                                        &(cursor - bigone).to_biguint().unwrap_or_default(),
-                                    )
-                                {
+                                if let Ok(Some(newres)) = self.query_variable(
+                                    &variable.variable_ref,
+                                    &(cursor - bigone).to_biguint().unwrap_or_default(),
+                                ) {
                                     if let Some(current) = newres.current {
                                         let newstime = current.0.to_bigint();
                                         if newstime.is_some() {
@@ -898,7 +1022,7 @@ impl WaveData {
                             // check if the next transition is 0, if so and requested, go to
                             // next positive transition
                             if let Some(time) = &self.cursor {
-                                let next_value = self.inner.as_waves().unwrap().query_variable(
+                                let next_value = self.query_variable(
                                     &variable.variable_ref,
                                     &time.to_biguint().unwrap_or_default(),
                                 );
@@ -929,10 +1053,165 @@ impl WaveData {
     /// number of timestamps as returned by wave sources if they specify 0 timestamps. This is
     /// done to avoid having to consider what happens with the viewport.
     pub fn num_timestamps(&self) -> Option<BigInt> {
-        self.inner
-            .max_timestamp()
-            .and_then(|r| if r == BigUint::zero() { None } else { Some(r) })
-            .and_then(|r| r.to_bigint())
+        if self.inner.max_timestamp()?.is_zero() {
+            return None;
+        }
+        let waves = self
+            .inner
+            .as_waves()
+            .and_then(WaveContainer::max_timestamp)
+            .map(|time| self.time_alignment.to_aligned(&BigInt::from(time)));
+        let transactions = self
+            .inner
+            .transaction_containers()
+            .iter()
+            .enumerate()
+            .filter_map(|(container, transactions)| {
+                Some(self.transaction_time(container, &transactions.max_timestamp()?))
+            });
+        waves
+            .into_iter()
+            .chain(transactions)
+            .max()
+            // A negative offset can move the whole file before zero, which should still
+            // leave something to show
+            .map(|r| r.max(BigInt::one()))
+    }
+
+    /// The alignment of the transaction file `container`, if it is aligned
+    fn transaction_alignment(&self, container: usize) -> Option<&TimeAlignment> {
+        if !self.inner.is_waves() {
+            return Some(&self.time_alignment);
+        }
+        let source = self.transaction_sources.get(container)?;
+        self.transaction_alignments
+            .iter()
+            .find(|(s, _)| s == source)
+            .map(|(_, alignment)| alignment)
+    }
+
+    /// Converts a time in the transaction file `container` to the time shown
+    pub fn transaction_time(&self, container: usize, time: &BigUint) -> BigInt {
+        let time = self.inner.transaction_time(container, time);
+        match self.transaction_alignment(container) {
+            Some(alignment) => alignment.to_aligned(&time),
+            None => time,
+        }
+    }
+
+    /// Converts a time shown to a time in the transaction file `container`
+    pub fn transaction_file_time(&self, container: usize, time: &BigInt) -> BigInt {
+        match self.transaction_alignment(container) {
+            Some(alignment) => self
+                .inner
+                .transaction_file_time(container, &alignment.to_file(time)),
+            None => self.inner.transaction_file_time(container, time),
+        }
+    }
+
+    /// The alignment of the waveform for `None`, or of the transaction file `container`
+    pub fn alignment(&self, container: Option<usize>) -> TimeAlignment {
+        container
+            .map_or(Some(&self.time_alignment), |container| {
+                self.transaction_alignment(container)
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Sets the alignment of the waveform for `None`, or of the transaction file `container`
+    pub fn set_alignment(&mut self, container: Option<usize>, alignment: TimeAlignment) {
+        let source = match container {
+            Some(container) if self.inner.is_waves() => self.transaction_sources.get(container),
+            _ => None,
+        };
+        let Some(source) = source.cloned() else {
+            self.time_alignment = alignment;
+            return;
+        };
+        self.transaction_alignments.retain(|(s, _)| *s != source);
+        if !alignment.is_identity() {
+            self.transaction_alignments.push((source, alignment));
+        }
+    }
+
+    /// Resets alignments which are invalid, for example from a state file that has been
+    /// edited by hand
+    pub(crate) fn reset_invalid_alignments(&mut self) {
+        if !self.time_alignment.is_valid() {
+            warn!(
+                "Ignoring the time scale {} of {}, it must be positive",
+                self.time_alignment.scale, self.source
+            );
+            self.time_alignment = TimeAlignment::default();
+        }
+        self.transaction_alignments.retain(|(source, alignment)| {
+            if !alignment.is_valid() {
+                warn!(
+                    "Ignoring the time scale {} of {source}, it must be positive",
+                    alignment.scale
+                );
+            }
+            alignment.is_valid()
+        });
+    }
+
+    /// Removes markers without a position and the items of the tree which are not displayed
+    /// items, keeping their children, and adds a viewport if there is none, for state files
+    /// where some of them could not be loaded
+    pub(crate) fn repair_partial_state(&mut self) {
+        let markers = &self.markers;
+        self.displayed_items.retain(|_, item| match item {
+            DisplayedItem::Marker(marker) => markers.contains_key(&marker.idx),
+            _ => true,
+        });
+        while let Some(idx) = self
+            .items_tree
+            .iter()
+            .position(|node| !self.displayed_items.contains_key(&node.item_ref))
+        {
+            self.items_tree.remove_dissolve(ItemIndex(idx));
+        }
+        if self.viewports.is_empty() {
+            self.viewports.push(Viewport::new());
+        }
+    }
+
+    /// Query the value of `variable` at the aligned `time`. The times in the result are
+    /// aligned as well.
+    pub fn query_variable(
+        &self,
+        variable: &VariableRef,
+        time: &BigUint,
+    ) -> Result<Option<QueryResult>> {
+        let Some(waves) = self.inner.as_waves() else {
+            return Ok(None);
+        };
+        if self.time_alignment.is_identity() {
+            return waves.query_variable(variable, time);
+        }
+        let file_time = self
+            .time_alignment
+            .to_file(&time.to_bigint().unwrap_or_default());
+        let Some(file_time) = file_time.to_biguint() else {
+            // Before the start of the file there is no value
+            return Ok(Some(QueryResult {
+                current: None,
+                next: self.time_alignment.to_aligned(&BigInt::zero()).to_biguint(),
+            }));
+        };
+        let align = |t: BigUint| {
+            self.time_alignment
+                .to_aligned(&t.to_bigint().unwrap_or_default())
+                .to_biguint()
+                .unwrap_or_default()
+        };
+        Ok(waves
+            .query_variable(variable, &file_time)?
+            .map(|result| QueryResult {
+                current: result.current.map(|(t, value)| (align(t), value)),
+                next: result.next.map(align),
+            }))
     }
 
     pub fn get_displayed_item_index(
@@ -953,6 +1232,29 @@ impl WaveData {
     }
 }
 
+/// Removes the formats of fields which the translator of `displayed_variable` does not have
+fn retain_valid_field_formats(
+    displayed_variable: &mut DisplayedVariable,
+    waves: &WaveContainer,
+    translators: &TranslatorList,
+) {
+    let meta = waves
+        .variable_meta(&displayed_variable.variable_ref.clone())
+        .unwrap();
+    let translator =
+        variable_translator(displayed_variable.get_format(&[]), &[], translators, || {
+            Ok(meta.clone())
+        });
+    let info = translator.variable_info(&meta).ok();
+
+    match info {
+        Some(info) => displayed_variable
+            .field_formats
+            .retain(|ff| info.has_subpath(&ff.field)),
+        _ => displayed_variable.field_formats.clear(),
+    }
+}
+
 fn calculate_rows_of_stream(
     transactions: &Vec<Transaction>,
     last_times_on_row: &mut Vec<(BigUint, BigUint)>,
@@ -973,3 +1275,238 @@ fn calculate_rows_of_stream(
         last_times_on_row[curr_row] = (start_time, end_time);
     }
 }
+
+/// Rewrites the transaction files referenced by the streams in `items` from their indices in
+/// `saved_sources` to their indices in `sources`. Streams of files which are not in `sources`
+/// refer to no file and are returned by the file they are from.
+fn map_transaction_items(
+    sources: &[WaveSource],
+    saved_sources: &[WaveSource],
+    items: &mut HashMap<DisplayedItemRef, DisplayedItem>,
+) -> PendingTransactionItems {
+    let mut pending: PendingTransactionItems = saved_sources
+        .iter()
+        .filter(|source| !sources.contains(source))
+        .map(|source| (source.clone(), vec![]))
+        .collect();
+
+    for (item_ref, item) in items.iter_mut() {
+        let DisplayedItem::Stream(stream) = item else {
+            continue;
+        };
+        let stream_ref = &mut stream.transaction_stream_ref;
+        let Some(source) = saved_sources.get(stream_ref.container) else {
+            continue;
+        };
+        if let Some(container) = sources.iter().position(|s| s == source) {
+            stream_ref.container = container;
+        } else if let Some((_, item_refs)) = pending.iter_mut().find(|(s, _)| s == source) {
+            stream_ref.container = UNLOADED_TRANSACTIONS;
+            item_refs.push(*item_ref);
+        }
+    }
+    pending
+}
+
+/// Points the streams from `source` in `pending` at the transaction file `container`, now
+/// that it is loaded
+fn attach_transaction_items(
+    pending: &mut PendingTransactionItems,
+    source: &WaveSource,
+    container: usize,
+    items: &mut HashMap<DisplayedItemRef, DisplayedItem>,
+) {
+    let Some(index) = pending.iter().position(|(s, _)| s == source) else {
+        return;
+    };
+    let (_, item_refs) = pending.remove(index);
+    for item_ref in item_refs {
+        if let Some(DisplayedItem::Stream(stream)) = items.get_mut(&item_ref) {
+            stream.transaction_stream_ref.container = container;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use project_root::get_project_root;
+    use surver::WELLEN_SURFER_DEFAULT_OPTIONS;
+
+    use super::*;
+    use crate::state_file_io::decode_state;
+    use crate::wave_source::LoadOptions;
+    use crate::SystemState;
+
+    fn source(name: &str) -> WaveSource {
+        WaveSource::File(name.into())
+    }
+
+    fn stream_item(container: usize) -> DisplayedItem {
+        DisplayedItem::Stream(DisplayedStream {
+            transaction_stream_ref: TransactionStreamRef::new_stream(0, "stream".to_string())
+                .in_container(container),
+            color: None,
+            background_color: None,
+            display_name: "stream".to_string(),
+            manual_name: None,
+            rows: 1,
+        })
+    }
+
+    fn containers(items: &HashMap<DisplayedItemRef, DisplayedItem>) -> Vec<usize> {
+        (0..items.len())
+            .map(|i| match &items[&DisplayedItemRef(i)] {
+                DisplayedItem::Stream(stream) => stream.transaction_stream_ref.container,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loaded_transaction_files_are_mapped_by_source() {
+        let saved = [source("a.ftr"), source("b.ftr")];
+        let mut items = HashMap::from([
+            (DisplayedItemRef(0), stream_item(0)),
+            (DisplayedItemRef(1), stream_item(1)),
+        ]);
+
+        let pending =
+            map_transaction_items(&[source("b.ftr"), source("a.ftr")], &saved, &mut items);
+
+        assert!(pending.is_empty());
+        assert_eq!(containers(&items), vec![1, 0]);
+    }
+
+    #[test]
+    fn transaction_files_loaded_later_are_attached_by_source() {
+        let saved = [source("a.ftr"), source("b.ftr"), source("c.ftr")];
+        let mut items = HashMap::from([
+            (DisplayedItemRef(0), stream_item(0)),
+            (DisplayedItemRef(1), stream_item(1)),
+            (DisplayedItemRef(2), stream_item(2)),
+        ]);
+
+        let mut pending = map_transaction_items(&[source("b.ftr")], &saved, &mut items);
+        assert_eq!(
+            pending.iter().map(|(s, _)| s.clone()).collect_vec(),
+            vec![source("a.ftr"), source("c.ftr")]
+        );
+        assert_eq!(
+            containers(&items),
+            vec![UNLOADED_TRANSACTIONS, 0, UNLOADED_TRANSACTIONS]
+        );
+
+        // The files may finish loading in any order
+        attach_transaction_items(&mut pending, &source("c.ftr"), 1, &mut items);
+        assert_eq!(containers(&items), vec![UNLOADED_TRANSACTIONS, 0, 1]);
+        attach_transaction_items(&mut pending, &source("a.ftr"), 2, &mut items);
+        assert_eq!(containers(&items), vec![2, 0, 1]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn transaction_sources_survive_a_state_round_trip() {
+        let sources = vec![source("a.ftr"), source("b.ftr")];
+        let items = HashMap::from([(DisplayedItemRef(0), stream_item(1))]);
+        let saved = ron::to_string(&(&sources, &items)).unwrap();
+
+        let (saved_sources, mut items): (
+            Vec<WaveSource>,
+            HashMap<DisplayedItemRef, DisplayedItem>,
+        ) = ron::from_str(&saved).unwrap();
+        let pending = map_transaction_items(&[source("b.ftr")], &saved_sources, &mut items);
+
+        assert_eq!(pending, vec![(source("a.ftr"), vec![])]);
+        assert_eq!(containers(&items), vec![0]);
+    }
+
+    /// The counter example with a transaction file loaded next to it
+    fn counter_with_transactions() -> SystemState {
+        let mut state = SystemState::new_default_config().unwrap();
+        let root = get_project_root().unwrap();
+        let path = root.join("examples/counter.vcd");
+        let header = wellen::viewers::read_header_from_file(
+            path.to_str().unwrap(),
+            &WELLEN_SURFER_DEFAULT_OPTIONS,
+        )
+        .unwrap();
+        state.on_waves_loaded(
+            WaveSource::File(path.try_into().unwrap()),
+            WaveFormat::Vcd,
+            Box::new(WaveContainer::new_waveform(Arc::new(header.hierarchy))),
+            LoadOptions::clean(),
+        );
+        let path = root.join("examples/my_db.ftr");
+        state.on_transaction_streams_loaded(
+            WaveSource::File(path.clone().try_into().unwrap()),
+            WaveFormat::Ftr,
+            TransactionContainer {
+                inner: ftr_parser::parse::parse_ftr(path).unwrap(),
+            },
+            LoadOptions {
+                keep_variables: true,
+                keep_unavailable: false,
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn transaction_files_are_aligned_on_their_own() {
+        let mut state = counter_with_transactions();
+        let waves = state.user.waves.as_mut().unwrap();
+        let time = BigUint::from(100u32);
+        let unaligned = waves.transaction_time(0, &time);
+        let shifted = TimeAlignment {
+            offset: BigInt::from(1000),
+            scale: 1.,
+        };
+
+        waves.set_alignment(Some(0), shifted.clone());
+        assert!(waves.time_alignment.is_identity());
+        assert_eq!(waves.alignment(Some(0)), shifted);
+        assert_eq!(waves.transaction_time(0, &time), &unaligned + 1000);
+        assert_eq!(
+            waves.transaction_file_time(0, &(&unaligned + 1000)),
+            waves.inner.transaction_file_time(0, &unaligned)
+        );
+
+        // Aligning the waveform leaves the transaction file alone
+        waves.set_alignment(
+            None,
+            TimeAlignment {
+                offset: BigInt::from(-5),
+                scale: 2.,
+            },
+        );
+        assert_eq!(waves.alignment(Some(0)), shifted);
+        assert_eq!(waves.transaction_time(0, &time), &unaligned + 1000);
+
+        waves.set_alignment(Some(0), TimeAlignment::default());
+        assert!(waves.transaction_alignments.is_empty());
+        assert_eq!(waves.transaction_time(0, &time), unaligned);
+    }
+
+    #[test]
+    fn invalid_alignments_in_state_files_are_reset() {
+        let mut state = counter_with_transactions();
+        let mut decoded = decode_state(state.encode_state().unwrap().as_bytes()).unwrap();
+        let saved = decoded.state.waves.as_mut().unwrap();
+        saved.time_alignment.scale = 0.;
+        saved.transaction_alignments = vec![(
+            saved.transaction_sources[0].clone(),
+            TimeAlignment {
+                offset: BigInt::from(10),
+                scale: f64::NAN,
+            },
+        )];
+
+        state.load_state(Box::new(decoded.state), None);
+
+        let waves = state.user.waves.as_ref().unwrap();
+        assert!(waves.time_alignment.is_identity());
+        assert!(waves.transaction_alignments.is_empty());
+    }
+}
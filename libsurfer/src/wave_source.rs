@@ -9,6 +9,8 @@ use std::sync::Mutex;
 use crate::async_util::{perform_async_work, perform_work, sleep_ms};
 use crate::cxxrtl_container::CxxrtlContainer;
 use crate::spawn;
+use crate::state_file_io::decode_state;
+use crate::tabs::TabId;
 use crate::util::get_multi_extension;
 use camino::{Utf8Path, Utf8PathBuf};
 use eyre::Result;
@@ -29,14 +31,28 @@ use surver::{Status, HTTP_SERVER_KEY, HTTP_SERVER_VALUE_SURFER, WELLEN_SURFER_DE
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum CxxrtlKind {
-    Tcp { url: String },
+    Tcp {
+        url: String,
+    },
     Mailbox,
+    /// Spawn the simulation binary and talk to it over its stdin/stdout
+    Process {
+        cmd: String,
+        args: Vec<String>,
+    },
 }
 impl std::fmt::Display for CxxrtlKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             CxxrtlKind::Tcp { url } => write!(f, "cxxrtl+tcp://{url}"),
             CxxrtlKind::Mailbox => write!(f, "cxxrtl mailbox"),
+            CxxrtlKind::Process { cmd, args } => {
+                let words = std::iter::once(cmd).chain(args).map(String::as_str);
+                match shlex::try_join(words) {
+                    Ok(command) => write!(f, "cxxrtl+exec://{command}"),
+                    Err(_) => write!(f, "cxxrtl+exec://{cmd} {}", args.join(" ")),
+                }
+            }
         }
     }
 }
@@ -117,6 +133,31 @@ pub fn url_to_wavesource(url: &str) -> Option<WaveSource> {
             log::warn!("Loading waves from cxxrtl via tcp is unsupported in WASM builds.");
             None
         }
+    } else if url.starts_with("cxxrtl+exec://") {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            info!("Wave source is cxxrtl process");
+            let command = url.replace("cxxrtl+exec://", "");
+            // Arguments are split like a POSIX shell would, so paths with spaces can be quoted
+            let Some(words) = shlex::split(&command) else {
+                log::warn!("Unbalanced quotes in {url}");
+                return None;
+            };
+            let mut words = words.into_iter();
+            let Some(cmd) = words.next() else {
+                log::warn!("No simulation binary given in {url}");
+                return None;
+            };
+            Some(WaveSource::Cxxrtl(CxxrtlKind::Process {
+                cmd,
+                args: words.collect(),
+            }))
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            log::warn!("Spawning cxxrtl processes is unsupported in WASM builds.");
+            None
+        }
     } else {
         None
     }
@@ -139,8 +180,7 @@ impl Display for WaveSource {
             WaveSource::DragAndDrop(None) => write!(f, "Dropped file"),
             WaveSource::DragAndDrop(Some(filename)) => write!(f, "Dropped file ({filename})"),
             WaveSource::Url(url) => write!(f, "{url}"),
-            WaveSource::Cxxrtl(CxxrtlKind::Tcp { url }) => write!(f, "cxxrtl+tcp://{url}"),
-            WaveSource::Cxxrtl(CxxrtlKind::Mailbox) => write!(f, "cxxrtl mailbox"),
+            WaveSource::Cxxrtl(kind) => write!(f, "{kind}"),
         }
     }
 }
@@ -245,6 +285,7 @@ impl SystemState {
         let source = WaveSource::File(filename.clone());
         let source_copy = source.clone();
         let sender = self.channels.msg_sender.clone();
+        let tab = self.active_tab_id;
 
         perform_work(move || {
             let header_result = wellen::viewers::read_header_from_file(
@@ -258,6 +299,7 @@ impl SystemState {
                 Ok(header) => {
                     let msg = Message::WaveHeaderLoaded(
                         start,
+                        tab,
                         source,
                         load_options,
                         HeaderResult::LocalFile(Box::new(header)),
@@ -292,19 +334,22 @@ impl SystemState {
                     if get_multi_extension(&path) == Some(STATE_FILE_EXTENSION.to_string()) {
                         let sender = self.channels.msg_sender.clone();
                         perform_async_work(async move {
-                            let new_state = match ron::de::from_bytes(&bytes)
+                            let decoded = match decode_state(&bytes)
                                 .context(format!("Failed loading {}", path))
                             {
-                                Ok(s) => s,
+                                Ok(decoded) => decoded,
                                 Err(e) => {
-                                    error!("Failed to load state: {e:#?}");
+                                    sender.send(Message::Error(e)).unwrap();
                                     return;
                                 }
                             };
+                            if decoded.report(path.as_str()) {
+                                sender.send(Message::SetLogsVisible(true)).unwrap();
+                            }
 
                             sender
                                 .send(Message::LoadState(
-                                    new_state,
+                                    Box::new(decoded.state),
                                     Some(path.into_std_path_buf()),
                                 ))
                                 .unwrap();
@@ -346,6 +391,7 @@ impl SystemState {
             // a url even if it isn't auto detected as a url.
             _ => {
                 let sender = self.channels.msg_sender.clone();
+                let tab = self.active_tab_id;
                 let url_ = url.clone();
                 let task = async move {
                     let maybe_response = reqwest::get(&url)
@@ -367,6 +413,7 @@ impl SystemState {
                             Self::get_server_status(sender.clone(), url.clone(), 0);
                             Self::get_hierarchy_from_server(
                                 sender.clone(),
+                                tab,
                                 url.clone(),
                                 load_options,
                             );
@@ -411,6 +458,7 @@ impl SystemState {
         match result {
             Ok(ftr) => sender
                 .send(Message::TransactionStreamsLoaded(
+                    self.active_tab_id,
                     source,
                     format,
                     TransactionContainer { inner: ftr },
@@ -436,6 +484,7 @@ impl SystemState {
         match result {
             Ok(ftr) => sender
                 .send(Message::TransactionStreamsLoaded(
+                    self.active_tab_id,
                     source,
                     WaveFormat::Ftr,
                     TransactionContainer { inner: ftr },
@@ -447,6 +496,7 @@ impl SystemState {
     }
     fn get_hierarchy_from_server(
         sender: Sender<Message>,
+        tab: TabId,
         server: String,
         load_options: LoadOptions,
     ) {
@@ -464,7 +514,7 @@ impl SystemState {
             match res {
                 Ok(h) => {
                     let header = HeaderResult::Remote(Arc::new(h.hierarchy), h.file_format, server);
-                    let msg = Message::WaveHeaderLoaded(start, source, load_options, header);
+                    let msg = Message::WaveHeaderLoaded(start, tab, source, load_options, header);
                     sender.send(msg).unwrap();
                 }
                 Err(e) => sender.send(Message::Error(e)).unwrap(),
@@ -473,7 +523,7 @@ impl SystemState {
         spawn!(task);
     }
 
-    pub fn get_time_table_from_server(sender: Sender<Message>, server: String) {
+    pub fn get_time_table_from_server(sender: Sender<Message>, tab: TabId, server: String) {
         let start = web_time::Instant::now();
         let source = WaveSource::Url(server.clone());
 
@@ -487,8 +537,12 @@ impl SystemState {
 
             match res {
                 Ok(table) => {
-                    let msg =
-                        Message::WaveBodyLoaded(start, source, BodyResult::Remote(table, server));
+                    let msg = Message::WaveBodyLoaded(
+                        start,
+                        tab,
+                        source,
+                        BodyResult::Remote(table, server),
+                    );
                     sender.send(msg).unwrap();
                 }
                 Err(e) => sender.send(Message::Error(e)).unwrap(),
@@ -541,6 +595,7 @@ impl SystemState {
 
     pub fn connect_to_cxxrtl(&mut self, kind: CxxrtlKind, keep_variables: bool) {
         let sender = self.channels.msg_sender.clone();
+        let tab = self.active_tab_id;
 
         self.progress_tracker = Some(LoadProgress::new(LoadProgressStatus::Connecting(format!(
             "{kind}"
@@ -564,10 +619,20 @@ impl SystemState {
                 }
                 #[cfg(target_arch = "wasm32")]
                 CxxrtlKind::Mailbox => CxxrtlContainer::new_wasm_mailbox(sender.clone()).await,
+                #[cfg(not(target_arch = "wasm32"))]
+                CxxrtlKind::Process { cmd, args } => {
+                    CxxrtlContainer::new_process(cmd, args, self.channels.msg_sender.clone()).await
+                }
+                #[cfg(target_arch = "wasm32")]
+                CxxrtlKind::Process { .. } => {
+                    error!("Cxxrtl processes are not supported on wasm");
+                    return;
+                }
             };
 
             match container {
                 Ok(c) => sender.send(Message::WavesLoaded(
+                    tab,
                     WaveSource::Cxxrtl(kind),
                     WaveFormat::CxxRtl,
                     Box::new(WaveContainer::Cxxrtl(Box::new(Mutex::new(c)))),
@@ -594,6 +659,7 @@ impl SystemState {
     ) {
         let start = web_time::Instant::now();
         let sender = self.channels.msg_sender.clone();
+        let tab = self.active_tab_id;
         let source_copy = source.clone();
         perform_work(move || {
             let header_result =
@@ -605,6 +671,7 @@ impl SystemState {
                 Ok(header) => {
                     let msg = Message::WaveHeaderLoaded(
                         start,
+                        tab,
                         source,
                         load_options,
                         HeaderResult::LocalBytes(Box::new(header)),
@@ -635,6 +702,7 @@ impl SystemState {
 
     pub fn load_wave_body<R: std::io::BufRead + std::io::Seek + Sync + Send + 'static>(
         &mut self,
+        tab: TabId,
         source: WaveSource,
         cont: wellen::viewers::ReadBodyContinuation<R>,
         body_len: u64,
@@ -656,7 +724,8 @@ impl SystemState {
 
                 match body_result {
                     Ok(body) => {
-                        let msg = Message::WaveBodyLoaded(start, source, BodyResult::Local(body));
+                        let msg =
+                            Message::WaveBodyLoaded(start, tab, source, BodyResult::Local(body));
                         sender.send(msg).unwrap();
                     }
                     Err(e) => sender.send(Message::Error(e)).unwrap(),
@@ -677,6 +746,11 @@ impl SystemState {
     }
 
     pub fn load_variables(&mut self, cmd: LoadSignalsCmd) {
+        self.load_variables_in_tab(self.active_tab_id, cmd);
+    }
+
+    /// Loads variables of the waveform in `tab`, which does not have to be the active tab.
+    pub(crate) fn load_variables_in_tab(&mut self, tab: TabId, cmd: LoadSignalsCmd) {
         let (signals, from_unique_id, payload) = cmd.destruct();
         if signals.is_empty() {
             return;
@@ -693,7 +767,7 @@ impl SystemState {
                     let action = || {
                         let loaded = source.load_signals(&signals, &hierarchy, true);
                         let res = LoadSignalsResult::local(source, loaded, from_unique_id);
-                        let msg = Message::SignalsLoaded(start, res);
+                        let msg = Message::SignalsLoaded(start, tab, res);
                         sender.send(msg).unwrap();
                     };
                     if let Some(pool) = pool {
@@ -715,7 +789,7 @@ impl SystemState {
                     match res {
                         Ok(loaded) => {
                             let res = LoadSignalsResult::remote(server, loaded, from_unique_id);
-                            let msg = Message::SignalsLoaded(start, res);
+                            let msg = Message::SignalsLoaded(start, tab, res);
                             sender.send(msg).unwrap();
                         }
                         Err(e) => sender.send(Message::Error(e)).unwrap(),
@@ -772,3 +846,40 @@ pub fn draw_progress_information(ui: &mut egui::Ui, progress_data: &LoadProgress
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn exec_arguments_can_be_quoted() {
+        let source = url_to_wavesource("cxxrtl+exec://./sim --trace 'out dir/trace.vcd' \"a b\" c");
+        assert_eq!(
+            source,
+            Some(WaveSource::Cxxrtl(CxxrtlKind::Process {
+                cmd: "./sim".to_string(),
+                args: vec![
+                    "--trace".to_string(),
+                    "out dir/trace.vcd".to_string(),
+                    "a b".to_string(),
+                    "c".to_string()
+                ]
+            }))
+        );
+        assert_eq!(url_to_wavesource("cxxrtl+exec://./sim 'unbalanced"), None);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn exec_source_round_trips_through_display() {
+        let kind = CxxrtlKind::Process {
+            cmd: "/path with space/sim".to_string(),
+            args: vec!["a b".to_string(), "c".to_string()],
+        };
+        assert_eq!(
+            url_to_wavesource(&kind.to_string()),
+            Some(WaveSource::Cxxrtl(kind))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     displayed_item::{DisplayedItem, DisplayedItemRef},
+    displayed_item_tree::ItemIndex,
     message::{Message, MessageTarget},
     wave_container::{ScopeRefExt, VariableRef, VariableRefExt},
     wave_data::WaveData,
@@ -10,10 +11,14 @@ use crate::{
 use futures::executor::block_on;
 use itertools::Itertools;
 use log::{trace, warn};
+use num::BigInt;
 use std::sync::atomic::Ordering;
 use surfer_translation_types::ScopeRef;
 
-use super::proto::{ItemInfo, WcpCSMessage, WcpCommand, WcpResponse, WcpSCMessage};
+use super::proto::{
+    ItemInfo, MarkerInfo, VariableInfo, WcpCSMessage, WcpCommand, WcpEvent, WcpResponse,
+    WcpSCMessage,
+};
 
 impl SystemState {
     pub fn handle_wcp_commands(&mut self) {
@@ -36,6 +41,8 @@ impl SystemState {
         }
         for message in messages {
             self.handle_wcp_cs_message(&message);
+            // Some commands modify the state directly rather than through messages
+            self.send_wcp_state_events();
         }
     }
 
@@ -232,7 +239,18 @@ impl SystemState {
 
                         self.send_response(WcpResponse::ack);
                     }
-                    WcpCommand::load { source } => {
+                    WcpCommand::load { source, tab } => {
+                        if let Some(tab) = tab {
+                            if *tab > self.num_tabs() {
+                                self.send_error(
+                                    "load",
+                                    vec![],
+                                    format!("Tab {tab} does not exist").as_str(),
+                                );
+                                return;
+                            }
+                            self.update(Message::SwitchTab(*tab));
+                        }
                         match string_to_wavesource(source) {
                             WaveSource::Url(url) => {
                                 self.update(Message::LoadWaveformFileFromUrl(
@@ -262,6 +280,236 @@ impl SystemState {
                         });
                         self.send_response(WcpResponse::ack);
                     }
+                    WcpCommand::zoom_to_range {
+                        start,
+                        end,
+                        viewport_idx,
+                    } => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("zoom_to_range", vec![], "No waveform loaded");
+                            return;
+                        };
+                        if *viewport_idx >= waves.viewports.len() {
+                            self.send_error(
+                                "zoom_to_range",
+                                vec![],
+                                &format!("No viewport with index {viewport_idx}"),
+                            );
+                            return;
+                        }
+                        self.update(Message::ZoomToRange {
+                            start: start.clone(),
+                            end: end.clone(),
+                            viewport_idx: *viewport_idx,
+                        });
+                        self.send_response(WcpResponse::ack);
+                    }
+                    WcpCommand::get_value_at { id, time } => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("get_value_at", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let Some(time) = time.to_biguint() else {
+                            self.send_error(
+                                "get_value_at",
+                                vec![],
+                                &format!("Negative timestamp {time}"),
+                            );
+                            return;
+                        };
+                        let item_ref: DisplayedItemRef = id.into();
+                        if let Some(DisplayedItem::Variable(_)) =
+                            waves.displayed_items.get(&item_ref)
+                        {
+                            let value =
+                                self.get_variable_value(waves, &item_ref.into(), &Some(time));
+                            self.send_response(WcpResponse::get_value_at { value });
+                        } else {
+                            self.send_error(
+                                "get_value_at",
+                                vec![],
+                                &format!("No variable with id {id:?}"),
+                            );
+                        }
+                    }
+                    WcpCommand::set_cursor { timestamp } => {
+                        if self.user.waves.is_none() {
+                            self.send_error("set_cursor", vec![], "No waveform loaded");
+                            return;
+                        }
+                        self.update(Message::CursorSet(timestamp.clone()));
+                        self.send_response(WcpResponse::ack);
+                    }
+                    WcpCommand::add_marker { timestamp, name } => {
+                        if self.user.waves.is_none() {
+                            self.send_error("add_marker", vec![], "No waveform loaded");
+                            return;
+                        }
+                        self.save_current_canvas(format!("Add marker at {timestamp}"));
+                        let waves = self.user.waves.as_mut().unwrap();
+                        if let Some(id) = waves.add_marker(timestamp, name.clone(), false) {
+                            self.invalidate_draw_commands();
+                            self.send_response(WcpResponse::add_marker { id });
+                        } else {
+                            self.send_error("add_marker", vec![], "Too many markers");
+                        }
+                    }
+                    WcpCommand::get_markers => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("get_markers", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let markers = marker_infos(waves);
+                        self.send_response(WcpResponse::get_markers { markers });
+                    }
+                    WcpCommand::set_item_format { id, format } => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("set_item_format", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let item_ref: DisplayedItemRef = id.into();
+                        if !matches!(
+                            waves.displayed_items.get(&item_ref),
+                            Some(DisplayedItem::Variable(_))
+                        ) {
+                            self.send_error(
+                                "set_item_format",
+                                vec![],
+                                &format!("No variable with id {id:?}"),
+                            );
+                            return;
+                        }
+                        if !self
+                            .translators
+                            .all_translator_names()
+                            .contains(&format.as_str())
+                        {
+                            self.send_error(
+                                "set_item_format",
+                                vec![],
+                                &format!("No format named {format}"),
+                            );
+                            return;
+                        }
+                        self.update(Message::VariableFormatChange(
+                            MessageTarget::Explicit(item_ref.into()),
+                            format.clone(),
+                        ));
+                        self.send_response(WcpResponse::ack);
+                    }
+                    WcpCommand::add_group { name, ids } => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("add_group", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let item_refs = ids.iter().map(|id| id.into()).collect_vec();
+                        if let Some(missing) = item_refs
+                            .iter()
+                            .find(|item_ref| !waves.displayed_items.contains_key(item_ref))
+                        {
+                            self.send_error(
+                                "add_group",
+                                vec![],
+                                &format!("No item with id {missing:?}"),
+                            );
+                            return;
+                        }
+                        if item_refs.is_empty() {
+                            self.save_current_canvas(format!("Create group {name}"));
+                            let waves = self.user.waves.as_mut().unwrap();
+                            let group_ref = waves.add_group(name.clone(), None);
+                            self.invalidate_draw_commands();
+                            self.send_response(WcpResponse::add_group {
+                                id: group_ref.into(),
+                            });
+                        } else {
+                            // Insert the group where the first of the items currently is
+                            let before = waves
+                                .items_tree
+                                .iter()
+                                .position(|node| item_refs.contains(&node.item_ref))
+                                .map(ItemIndex);
+                            self.update(Message::GroupNew {
+                                name: Some(name.clone()),
+                                before,
+                                items: Some(item_refs),
+                            });
+                            let waves = self.user.waves.as_ref().unwrap();
+                            // The group is the most recently created item
+                            let group_ref = DisplayedItemRef(waves.display_item_ref_counter);
+                            if let Some(DisplayedItem::Group(_)) =
+                                waves.displayed_items.get(&group_ref)
+                            {
+                                self.send_response(WcpResponse::add_group {
+                                    id: group_ref.into(),
+                                });
+                            } else {
+                                self.send_error("add_group", vec![], "Failed to create group");
+                            }
+                        }
+                    }
+                    WcpCommand::rename_item { id, name } => {
+                        let Some(waves) = &self.user.waves else {
+                            self.send_error("rename_item", vec![], "No waveform loaded");
+                            return;
+                        };
+                        if let Some(vidx) = waves.get_displayed_item_index(&id.into()) {
+                            self.update(Message::ItemNameChange(Some(vidx), Some(name.clone())));
+                            self.send_response(WcpResponse::ack);
+                        } else {
+                            self.send_error(
+                                "rename_item",
+                                vec![],
+                                &format!("No item with id {id:?}"),
+                            );
+                        }
+                    }
+                    WcpCommand::get_hierarchy => {
+                        let Some(wave_container) =
+                            self.user.waves.as_ref().and_then(|w| w.inner.as_waves())
+                        else {
+                            self.send_error("get_hierarchy", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let mut scopes = vec![];
+                        let mut stack = wave_container.root_scopes();
+                        while let Some(scope) = stack.pop() {
+                            if let Ok(children) = wave_container.child_scopes(&scope) {
+                                stack.extend(children.into_iter().rev());
+                            }
+                            scopes.push(scope.strs().join("."));
+                        }
+                        self.send_response(WcpResponse::get_hierarchy { scopes });
+                    }
+                    WcpCommand::list_variables { scope } => {
+                        let Some(wave_container) =
+                            self.user.waves.as_ref().and_then(|w| w.inner.as_waves())
+                        else {
+                            self.send_error("list_variables", vec![], "No waveform loaded");
+                            return;
+                        };
+                        let scope_ref = ScopeRef::from_hierarchy_string(scope);
+                        if !wave_container.scope_exists(&scope_ref) {
+                            self.send_error(
+                                "list_variables",
+                                vec![],
+                                &format!("No scope named {scope}"),
+                            );
+                            return;
+                        }
+                        let variables = wave_container
+                            .variables_in_scope(&scope_ref)
+                            .iter()
+                            .map(|variable| VariableInfo {
+                                name: variable.full_path_string(),
+                                width: wave_container
+                                    .variable_meta(variable)
+                                    .ok()
+                                    .and_then(|meta| meta.num_bits),
+                            })
+                            .collect_vec();
+                        self.send_response(WcpResponse::list_variables { variables });
+                    }
                     WcpCommand::shutdowmn => {
                         warn!("WCP Shutdown message should not reach this place")
                     }
@@ -291,6 +539,23 @@ impl SystemState {
                     if commands.iter().any(|s| s == "add_loads") {
                         self.wcp_client_capabilities.add_loads = true;
                     }
+                    if commands.iter().any(|s| s == "cursor_changed") {
+                        self.wcp_client_capabilities.cursor_changed = true;
+                    }
+                    if commands.iter().any(|s| s == "markers_changed") {
+                        self.wcp_client_capabilities.markers_changed = true;
+                    }
+                    if commands.iter().any(|s| s == "focus_changed") {
+                        self.wcp_client_capabilities.focus_changed = true;
+                    }
+                    if commands.iter().any(|s| s == "selection_changed") {
+                        self.wcp_client_capabilities.selection_changed = true;
+                    }
+                    if commands.iter().any(|s| s == "viewport_changed") {
+                        self.wcp_client_capabilities.viewport_changed = true;
+                    }
+                    // Only report changes that happen after the greeting
+                    self.wcp_observed_state = WcpObservedState::new(self);
                     self.wcp_greeted_signal.store(true, Ordering::Relaxed);
                     self.wcp_greeted_signal.store(true, Ordering::Relaxed);
                     self.send_greeting()
@@ -299,6 +564,26 @@ impl SystemState {
         }
     }
 
+    /// Sends events for the subscribed parts of the state that have changed since the
+    /// last events were sent
+    pub(crate) fn send_wcp_state_events(&mut self) {
+        if !self.wcp_greeted_signal.load(Ordering::Relaxed)
+            || !self.wcp_client_capabilities.wants_state_events()
+        {
+            return;
+        }
+        let current = WcpObservedState::new(self);
+        let previous = std::mem::replace(&mut self.wcp_observed_state, current);
+        let events = self.wcp_observed_state.changes_since(&previous);
+
+        for event in events {
+            self.channels
+                .wcp_s2c_sender
+                .as_ref()
+                .map(|ch| block_on(ch.send(WcpSCMessage::event(event))));
+        }
+    }
+
     fn send_greeting(&self) {
         let commands = vec![
             "add_variables",
@@ -314,6 +599,16 @@ impl SystemState {
             "clear",
             "load",
             "zoom_to_fit",
+            "zoom_to_range",
+            "get_value_at",
+            "set_cursor",
+            "add_marker",
+            "get_markers",
+            "set_item_format",
+            "add_group",
+            "rename_item",
+            "get_hierarchy",
+            "list_variables",
         ]
         .into_iter()
         .map(str::to_string)
@@ -353,3 +648,175 @@ impl SystemState {
             .collect_vec()
     }
 }
+
+/// The parts of the state that WCP clients can subscribe to changes of. Parts that the
+/// client has not subscribed to are left at their defaults, so they are never built or
+/// reported.
+#[derive(Default)]
+pub(crate) struct WcpObservedState {
+    cursor: Option<BigInt>,
+    markers: Vec<MarkerInfo>,
+    focused: Option<DisplayedItemRef>,
+    selected: Vec<DisplayedItemRef>,
+    viewports: Vec<(BigInt, BigInt)>,
+}
+
+impl WcpObservedState {
+    fn new(state: &SystemState) -> Self {
+        let Some(waves) = &state.user.waves else {
+            return Self::default();
+        };
+        let capabilities = &state.wcp_client_capabilities;
+        let mut result = Self::default();
+        if capabilities.cursor_changed {
+            result.cursor.clone_from(&waves.cursor);
+        }
+        if capabilities.markers_changed {
+            result.markers = marker_infos(waves);
+        }
+        if capabilities.focus_changed {
+            result.focused = waves
+                .focused_item
+                .and_then(|vidx| waves.items_tree.get_visible(vidx))
+                .map(|node| node.item_ref);
+        }
+        if capabilities.selection_changed {
+            result.selected = waves
+                .items_tree
+                .iter_visible_selected()
+                .map(|node| node.item_ref)
+                .collect();
+        }
+        if capabilities.viewport_changed {
+            let num_timestamps = waves.num_timestamps().unwrap_or(1.into());
+            result.viewports = waves
+                .viewports
+                .iter()
+                .map(|viewport| viewport.target_range(&num_timestamps))
+                .collect();
+        }
+        result
+    }
+
+    /// The events describing how `self` differs from the `previous` state
+    fn changes_since(&self, previous: &WcpObservedState) -> Vec<WcpEvent> {
+        let mut events = vec![];
+        if self.cursor != previous.cursor {
+            events.push(match &self.cursor {
+                Some(time) => WcpEvent::cursor_changed { time: time.clone() },
+                None => WcpEvent::cursor_cleared,
+            });
+        }
+        if self.markers != previous.markers {
+            let markers = self
+                .markers
+                .iter()
+                .filter(|marker| !previous.markers.contains(marker))
+                .cloned()
+                .collect_vec();
+            let removed = previous
+                .markers
+                .iter()
+                .map(|marker| marker.id)
+                .filter(|id| !self.markers.iter().any(|marker| marker.id == *id))
+                .collect_vec();
+            events.push(WcpEvent::markers_changed { markers, removed });
+        }
+        if self.focused != previous.focused {
+            events.push(WcpEvent::focus_changed {
+                id: self.focused.map(|id| id.into()),
+            });
+        }
+        if self.selected != previous.selected {
+            events.push(WcpEvent::selection_changed {
+                ids: self.selected.iter().map(|id| id.into()).collect(),
+            });
+        }
+        for (viewport_idx, (start, end)) in self.viewports.iter().enumerate() {
+            if previous.viewports.get(viewport_idx) != Some(&(start.clone(), end.clone())) {
+                events.push(WcpEvent::viewport_changed {
+                    viewport_idx,
+                    start: start.clone(),
+                    end: end.clone(),
+                });
+            }
+        }
+        events
+    }
+}
+
+fn marker_infos(waves: &WaveData) -> Vec<MarkerInfo> {
+    waves
+        .displayed_items
+        .values()
+        .filter_map(|item| match item {
+            DisplayedItem::Marker(marker) => Some(MarkerInfo {
+                id: marker.idx,
+                name: marker.name.clone(),
+                time: waves.markers.get(&marker.idx)?.clone(),
+            }),
+            _ => None,
+        })
+        .sorted_by_key(|marker| marker.id)
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn marker(id: u8, time: u32) -> MarkerInfo {
+        MarkerInfo {
+            id,
+            name: None,
+            time: BigInt::from(time),
+        }
+    }
+
+    #[test]
+    fn unchanged_state_sends_no_events() {
+        let state = WcpObservedState {
+            cursor: Some(BigInt::from(10)),
+            markers: vec![marker(0, 5)],
+            ..Default::default()
+        };
+        let same = WcpObservedState {
+            cursor: Some(BigInt::from(10)),
+            markers: vec![marker(0, 5)],
+            ..Default::default()
+        };
+        assert_eq!(state.changes_since(&same), vec![]);
+    }
+
+    #[test]
+    fn clearing_cursor_sends_event() {
+        let previous = WcpObservedState {
+            cursor: Some(BigInt::from(10)),
+            ..Default::default()
+        };
+        let current = WcpObservedState::default();
+        assert_eq!(
+            current.changes_since(&previous),
+            vec![WcpEvent::cursor_cleared]
+        );
+    }
+
+    #[test]
+    fn only_changed_markers_are_sent() {
+        let previous = WcpObservedState {
+            markers: vec![marker(0, 5), marker(1, 10), marker(2, 15)],
+            ..Default::default()
+        };
+        let current = WcpObservedState {
+            markers: vec![marker(0, 5), marker(2, 20), marker(3, 25)],
+            ..Default::default()
+        };
+        assert_eq!(
+            current.changes_since(&previous),
+            vec![WcpEvent::markers_changed {
+                markers: vec![marker(2, 20), marker(3, 25)],
+                removed: vec![1],
+            }]
+        );
+    }
+}
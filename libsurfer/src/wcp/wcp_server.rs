@@ -1,20 +1,24 @@
 use bytes::{Buf, BytesMut};
 use eframe::egui::Context;
 use eyre::Result;
-use serde::Serialize;
+use futures::stream::{SplitSink, SplitStream};
+use futures::SinkExt;
 use serde_json::Error as serde_Error;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 use std::time::Duration;
-use tokio::net::tcp::{ReadHalf, WriteHalf};
 use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 #[cfg(target_arch = "wasm32")]
 use crate::channels::IngressSender;
 use log::{error, info, warn};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc::Receiver;
 #[cfg(not(target_arch = "wasm32"))]
 use tokio::sync::mpsc::Sender;
@@ -22,21 +26,50 @@ use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 
 use super::{proto::WcpCSMessage, proto::WcpCommand, proto::WcpSCMessage};
+use crate::config::WcpTransport;
 
-struct WcpCSReader<'a> {
-    reader: BufReader<ReadHalf<'a>>,
+/// Reads client to server messages from a connection, regardless of how they are framed
+trait WcpCSReader {
+    async fn read_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error>;
+}
+
+/// Writes server to client messages to a connection, regardless of how they are framed
+trait WcpSCWriter {
+    async fn write_message(&mut self, message: &WcpSCMessage);
+}
+
+/// Reads null terminated JSON messages from a byte stream
+struct NullFramedReader<R> {
+    reader: BufReader<R>,
     buffer: BytesMut,
 }
 
-impl<'a> WcpCSReader<'a> {
-    pub fn new(stream: ReadHalf<'a>) -> Self {
-        WcpCSReader {
+impl<R: AsyncRead + Unpin> NullFramedReader<R> {
+    pub fn new(stream: R) -> Self {
+        NullFramedReader {
             reader: BufReader::new(stream),
             buffer: BytesMut::with_capacity(8 * 1024),
         }
     }
 
-    pub async fn read_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error> {
+    fn try_decode_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error> {
+        match self.buffer.iter().position(|&x| x == 0) {
+            Some(position) => {
+                let frame_data = self.buffer.split_to(position);
+                self.buffer.advance(1);
+                let msg: Result<WcpCSMessage, _> = serde_json::from_slice(&frame_data);
+                match msg {
+                    Ok(msg) => Ok(Some(msg)),
+                    Err(e) => Err(e),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> WcpCSReader for NullFramedReader<R> {
+    async fn read_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error> {
         loop {
             if let Some(frame) = self.try_decode_frame()? {
                 return Ok(Some(frame));
@@ -54,26 +87,79 @@ impl<'a> WcpCSReader<'a> {
             }
         }
     }
+}
 
-    fn try_decode_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error> {
-        match self.buffer.iter().position(|&x| x == 0) {
-            Some(position) => {
-                let frame_data = self.buffer.split_to(position);
-                self.buffer.advance(1);
-                let msg: Result<WcpCSMessage, _> = serde_json::from_slice(&frame_data);
-                match msg {
-                    Ok(msg) => Ok(Some(msg)),
-                    Err(e) => Err(e),
+/// Writes null terminated JSON messages to a byte stream
+struct NullFramedWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> WcpSCWriter for NullFramedWriter<W> {
+    async fn write_message(&mut self, message: &WcpSCMessage) {
+        match serde_json::to_string(message) {
+            Ok(message) => {
+                if let Err(error) = self.writer.write_all(message.as_bytes()).await {
+                    warn!("WCP Sending of message failed: {error:#?}")
                 }
             }
-            None => Ok(None),
+            Err(error) => warn!("Serializing message failed: {error:#?}"),
+        }
+        if let Err(e) = self.writer.write_all(b"\0").await {
+            warn!("Failed to send WCP message: {e:#?}");
+        }
+        if let Err(e) = self.writer.flush().await {
+            warn!("Failed to send WCP message: {e:#?}");
+        }
+    }
+}
+
+/// Web sockets carry one JSON message per text frame, no null termination is needed
+impl<S: AsyncRead + AsyncWrite + Unpin> WcpCSReader for SplitStream<WebSocketStream<S>> {
+    async fn read_frame(&mut self) -> Result<Option<WcpCSMessage>, serde_Error> {
+        match self.next().await {
+            Some(Ok(WsMessage::Text(text))) => serde_json::from_str(&text).map(Some),
+            Some(Ok(WsMessage::Binary(data))) => serde_json::from_slice(&data).map(Some),
+            Some(Ok(WsMessage::Close(_))) | None => Err(serde_Error::io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Web socket closed",
+            ))),
+            // Pings are answered by tungstenite itself
+            Some(Ok(_)) => Ok(None),
+            Some(Err(e)) => Err(serde_Error::io(std::io::Error::other(e))),
         }
     }
 }
 
+impl<S: AsyncRead + AsyncWrite + Unpin> WcpSCWriter for SplitSink<WebSocketStream<S>, WsMessage> {
+    async fn write_message(&mut self, message: &WcpSCMessage) {
+        match serde_json::to_string(message) {
+            Ok(message) => {
+                if let Err(error) = self.send(WsMessage::Text(message.into())).await {
+                    warn!("WCP Sending of message failed: {error:#?}")
+                }
+            }
+            Err(error) => warn!("Serializing message failed: {error:#?}"),
+        }
+    }
+}
+
+pub(crate) enum WcpListener {
+    Tcp(TcpListener),
+    WebSocket(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+enum WcpStream {
+    Tcp(TcpStream),
+    WebSocket(WebSocketStream<MaybeTlsStream<TcpStream>>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
 pub struct WcpServer {
-    listener: Option<TcpListener>,
-    stream: Option<TcpStream>,
+    listener: Option<WcpListener>,
+    stream: Option<WcpStream>,
     #[cfg(target_arch = "wasm32")]
     sender: IngressSender<WcpCSMessage>,
     #[cfg(not(target_arch = "wasm32"))]
@@ -90,6 +176,7 @@ impl WcpServer {
     pub async fn new(
         address: String,
         initiate: bool,
+        transport: WcpTransport,
         #[cfg(target_arch = "wasm32")] c2s_sender: IngressSender<WcpCSMessage>,
         #[cfg(not(target_arch = "wasm32"))] c2s_sender: Sender<WcpCSMessage>,
         s2c_receiver: Receiver<WcpSCMessage>,
@@ -101,16 +188,10 @@ impl WcpServer {
         let listener;
         let stream;
         if initiate {
-            let the_stream = TcpStream::connect(address).await?;
-            stream = Some(the_stream);
+            stream = Some(Self::connect(address, transport).await?);
             listener = None;
         } else {
-            let the_listener = TcpListener::bind(address).await?;
-            info!(
-                "WCP Server listening on port {}",
-                the_listener.local_addr().unwrap()
-            );
-            listener = Some(the_listener);
+            listener = Some(Self::bind(address, transport).await?);
             stream = None;
         }
         Ok(WcpServer {
@@ -125,6 +206,65 @@ impl WcpServer {
         })
     }
 
+    async fn connect(address: String, transport: WcpTransport) -> Result<WcpStream> {
+        match transport {
+            WcpTransport::Tcp => Ok(WcpStream::Tcp(TcpStream::connect(address).await?)),
+            WcpTransport::WebSocket => {
+                let url = if address.contains("://") {
+                    address
+                } else {
+                    format!("ws://{address}")
+                };
+                let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+                Ok(WcpStream::WebSocket(stream))
+            }
+            #[cfg(unix)]
+            WcpTransport::Unix => Ok(WcpStream::Unix(UnixStream::connect(address).await?)),
+            #[cfg(not(unix))]
+            WcpTransport::Unix => {
+                eyre::bail!("Unix domain sockets are not supported on this platform")
+            }
+        }
+    }
+
+    pub(crate) async fn bind(address: String, transport: WcpTransport) -> Result<WcpListener> {
+        match transport {
+            WcpTransport::Tcp | WcpTransport::WebSocket => {
+                let listener = TcpListener::bind(address).await?;
+                info!(
+                    "WCP Server listening on port {} ({transport})",
+                    listener.local_addr().unwrap()
+                );
+                if transport == WcpTransport::Tcp {
+                    Ok(WcpListener::Tcp(listener))
+                } else {
+                    Ok(WcpListener::WebSocket(listener))
+                }
+            }
+            #[cfg(unix)]
+            WcpTransport::Unix => {
+                use std::os::unix::fs::FileTypeExt;
+
+                // A socket file left behind by a previous instance prevents binding, but
+                // anything else at the path is left alone
+                if let Ok(metadata) = std::fs::symlink_metadata(&address) {
+                    if !metadata.file_type().is_socket() {
+                        eyre::bail!("Cannot bind WCP socket, {address} exists and is not a socket");
+                    }
+                    info!("Removing stale WCP socket {address}");
+                    std::fs::remove_file(&address)?;
+                }
+                let listener = UnixListener::bind(&address)?;
+                info!("WCP Server listening on {address}");
+                Ok(WcpListener::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            WcpTransport::Unix => {
+                eyre::bail!("Unix domain sockets are not supported on this platform")
+            }
+        }
+    }
+
     pub async fn run(&mut self) {
         if self.listener.is_some() {
             self.listen().await;
@@ -139,6 +279,30 @@ impl WcpServer {
         self.stop_signal.store(true, Ordering::Relaxed);
     }
 
+    async fn accept(listener: &WcpListener) -> std::io::Result<WcpStream> {
+        match listener {
+            WcpListener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                info!("WCP New connection: {addr}");
+                Ok(WcpStream::Tcp(stream))
+            }
+            WcpListener::WebSocket(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                info!("WCP New web socket connection: {addr}");
+                let stream = tokio_tungstenite::accept_async(MaybeTlsStream::Plain(stream))
+                    .await
+                    .map_err(std::io::Error::other)?;
+                Ok(WcpStream::WebSocket(stream))
+            }
+            #[cfg(unix)]
+            WcpListener::Unix(listener) => {
+                let (stream, _addr) = listener.accept().await?;
+                info!("WCP New connection on unix socket");
+                Ok(WcpStream::Unix(stream))
+            }
+        }
+    }
+
     async fn listen(&mut self) {
         let listener = self.listener.take().unwrap();
         loop {
@@ -150,9 +314,9 @@ impl WcpServer {
             };
 
             tokio::select! {
-                result = listener.accept() => {
+                result = Self::accept(&listener) => {
                     match result {
-                        Ok((stream, _addr)) => self.handle_connection(stream).await,
+                        Ok(stream) => self.handle_connection(stream).await,
                         Err(ref e)
                             if [std::io::ErrorKind::WouldBlock, std::io::ErrorKind::TimedOut]
                                 .contains(&e.kind()) =>
@@ -173,43 +337,39 @@ impl WcpServer {
 
     async fn initiate(&mut self) {
         let stream = self.stream.take().unwrap();
-        match self.handle_client(stream).await {
-            Err(error) => warn!("WCP Client disconnected with error: {error:#?}"),
-            Ok(()) => info!("WCP client disconnected"),
-        }
+        self.handle_connection(stream).await;
     }
 
-    async fn handle_connection(&mut self, stream: TcpStream) {
-        info!("WCP New connection: {}", stream.peer_addr().unwrap());
-
+    async fn handle_connection(&mut self, stream: WcpStream) {
         //handle connection from client
-        match self.handle_client(stream).await {
+        let result = match stream {
+            WcpStream::Tcp(stream) => {
+                let (reader, writer) = tokio::io::split(stream);
+                self.handle_client(NullFramedReader::new(reader), NullFramedWriter { writer })
+                    .await
+            }
+            WcpStream::WebSocket(stream) => {
+                let (writer, reader) = futures::StreamExt::split(stream);
+                self.handle_client(reader, writer).await
+            }
+            #[cfg(unix)]
+            WcpStream::Unix(stream) => {
+                let (reader, writer) = tokio::io::split(stream);
+                self.handle_client(NullFramedReader::new(reader), NullFramedWriter { writer })
+                    .await
+            }
+        };
+        match result {
             Err(error) => warn!("WCP Client disconnected with error: {error:#?}"),
             Ok(()) => info!("WCP client disconnected"),
         }
     }
 
-    async fn send_message<M: Serialize>(&mut self, stream: &mut WriteHalf<'_>, message: &M) {
-        match serde_json::to_string(message) {
-            Ok(message) => {
-                if let Err(error) = stream.write_all(message.as_bytes()).await {
-                    warn!("WCP Sending of message failed: {error:#?}")
-                }
-            }
-            Err(error) => warn!("Serializing message failed: {error:#?}"),
-        }
-        if let Err(e) = stream.write_all(b"\0").await {
-            warn!("Failed to send WCP message: {e:#?}");
-        }
-        if let Err(e) = stream.flush().await {
-            warn!("Failed to send WCP message: {e:#?}");
-        }
-    }
-
-    async fn handle_client(&mut self, mut stream: TcpStream) -> Result<(), serde_Error> {
-        let (reader, mut writer) = stream.split();
-        let mut reader = WcpCSReader::new(reader);
-
+    async fn handle_client(
+        &mut self,
+        mut reader: impl WcpCSReader,
+        mut writer: impl WcpSCWriter,
+    ) -> Result<(), serde_Error> {
         loop {
             let stop_signal_clone = self.stop_signal.clone();
             let stop_signal_waiter = async {
@@ -240,7 +400,7 @@ impl WcpServer {
                 }
 
                 Some(s2c) = self.receiver.next() => {
-                    self.send_message(&mut writer, &s2c).await;
+                    writer.write_message(&s2c).await;
                 }
 
                 _ = stop_signal_waiter => {
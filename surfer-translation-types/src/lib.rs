@@ -10,6 +10,7 @@ mod variable_ref;
 
 use derive_more::Display;
 use ecolor::Color32;
+use enum_iterator::Sequence;
 use extism_convert::{FromBytes, Json, ToBytes};
 use num::BigUint;
 use serde::{Deserialize, Serialize};
@@ -150,7 +151,7 @@ pub enum VariableInfo {
     Real,
 }
 
-#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Sequence)]
 pub enum VariableType {
     // VCD-specific types
     #[display("event")]
@@ -0,0 +1,567 @@
+//! An async client for WCP servers such as Surfer.
+//!
+//! The client takes care of the greeting, framing and of matching responses to the
+//! commands that caused them. Events sent by the server are forwarded to a separate
+//! channel which is available through [`WcpClient::take_events`].
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use eyre::{bail, eyre, Result};
+use num::BigInt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::proto::{
+    DisplayedItemRef, ItemInfo, MarkerInfo, WcpCSMessage, WcpCommand, WcpEvent, WcpResponse,
+    WcpSCMessage,
+};
+
+/// The protocol version sent in the greeting
+pub const WCP_VERSION: usize = 0;
+
+/// An error reported by the server in response to a command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WcpServerError {
+    pub error: String,
+    pub arguments: Vec<String>,
+    pub message: String,
+}
+
+impl fmt::Display for WcpServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.message)
+    }
+}
+
+impl std::error::Error for WcpServerError {}
+
+type PendingResponses = Arc<Mutex<VecDeque<oneshot::Sender<Result<WcpResponse>>>>>;
+
+/// A connection to a WCP server.
+///
+/// The server answers commands in the order they were sent, so commands may be sent
+/// concurrently from several tasks.
+pub struct WcpClient {
+    writer: tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    pending: PendingResponses,
+    events: Option<mpsc::UnboundedReceiver<WcpEvent>>,
+    version: String,
+    server_commands: Vec<String>,
+    reader_task: JoinHandle<()>,
+}
+
+impl WcpClient {
+    /// Connects to a server listening on `address`. `commands` are the events the client
+    /// wants to receive, for example `waveforms_loaded`.
+    pub async fn connect(address: impl ToSocketAddrs, commands: &[&str]) -> Result<Self> {
+        let stream = TcpStream::connect(address).await?;
+        Self::from_stream(stream, commands).await
+    }
+
+    /// Connects to a server listening on the Unix domain socket at `path`.
+    #[cfg(unix)]
+    pub async fn connect_unix(
+        path: impl AsRef<std::path::Path>,
+        commands: &[&str],
+    ) -> Result<Self> {
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        Self::from_stream(stream, commands).await
+    }
+
+    /// Waits for a server to connect to `listener`. This is used when the server initiates
+    /// the connection, e.g. when Surfer is started with `--wcp-initiate`.
+    pub async fn accept(listener: &TcpListener, commands: &[&str]) -> Result<Self> {
+        let (stream, _) = listener.accept().await?;
+        Self::from_stream(stream, commands).await
+    }
+
+    /// Performs the greeting over an already established connection using null terminated
+    /// JSON framing.
+    pub async fn from_stream<S>(stream: S, commands: &[&str]) -> Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut writer: Box<dyn AsyncWrite + Send + Unpin> = Box::new(writer);
+
+        let greeting = WcpCSMessage::create_greeting(
+            WCP_VERSION,
+            commands.iter().map(|c| c.to_string()).collect(),
+        );
+        write_message(&mut writer, &greeting).await?;
+
+        let (version, server_commands) = match read_message(&mut reader).await? {
+            Some(WcpSCMessage::greeting { version, commands }) => (version, commands),
+            Some(other) => bail!("Expected greeting from server, got {other:?}"),
+            None => bail!("Server closed the connection during greeting"),
+        };
+
+        let pending = PendingResponses::default();
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let reader_task = tokio::spawn(read_messages(reader, pending.clone(), event_sender));
+
+        Ok(WcpClient {
+            writer: tokio::sync::Mutex::new(writer),
+            pending,
+            events: Some(event_receiver),
+            version,
+            server_commands,
+            reader_task,
+        })
+    }
+
+    /// The protocol version reported by the server
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The commands supported by the server
+    pub fn server_commands(&self) -> &[String] {
+        &self.server_commands
+    }
+
+    /// Returns true if the server supports `command`
+    pub fn supports(&self, command: &str) -> bool {
+        self.server_commands.iter().any(|c| c == command)
+    }
+
+    /// Takes the receiver for events sent by the server. Returns `None` if it has already
+    /// been taken. The receiver is closed when the connection is closed.
+    pub fn take_events(&mut self) -> Option<mpsc::UnboundedReceiver<WcpEvent>> {
+        self.events.take()
+    }
+
+    /// Sends `command` and waits for its response. Errors reported by the server are
+    /// returned as a [`WcpServerError`].
+    pub async fn send(&self, command: WcpCommand) -> Result<WcpResponse> {
+        let (sender, receiver) = oneshot::channel();
+        {
+            // Hold the writer while queueing to keep the queue in the order of the commands
+            let mut writer = self.writer.lock().await;
+            self.pending.lock().unwrap().push_back(sender);
+            if let Err(e) = write_message(&mut *writer, &WcpCSMessage::command(command)).await {
+                // Nothing else can have been queued while we hold the writer, so the last
+                // sender is ours. Leaving it would hand the next response to this command.
+                self.pending.lock().unwrap().pop_back();
+                return Err(e);
+            }
+        }
+        receiver
+            .await
+            .map_err(|_| eyre!("Connection closed before a response was received"))?
+    }
+
+    /// Sends `command` and checks that the server acknowledged it
+    async fn send_expect_ack(&self, command: WcpCommand) -> Result<()> {
+        match self.send(command).await? {
+            WcpResponse::ack => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Loads a waveform. The server acknowledges immediately and emits
+    /// [`WcpEvent::waveforms_loaded`] once loading has finished.
+    pub async fn load(&self, source: impl Into<String>) -> Result<()> {
+        self.send_expect_ack(WcpCommand::load {
+            source: source.into(),
+            tab: None,
+        })
+        .await
+    }
+
+    /// Loads a waveform in the given tab. Passing the number of open tabs loads it in a
+    /// new tab.
+    pub async fn load_in_tab(&self, source: impl Into<String>, tab: usize) -> Result<()> {
+        self.send_expect_ack(WcpCommand::load {
+            source: source.into(),
+            tab: Some(tab),
+        })
+        .await
+    }
+
+    /// Reloads the current waveform
+    pub async fn reload(&self) -> Result<()> {
+        self.send_expect_ack(WcpCommand::reload).await
+    }
+
+    /// Returns the references of all displayed items
+    pub async fn get_item_list(&self) -> Result<Vec<DisplayedItemRef>> {
+        match self.send(WcpCommand::get_item_list).await? {
+            WcpResponse::get_item_list { ids } => Ok(ids),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Returns information about the specified items
+    pub async fn get_item_info(&self, ids: Vec<DisplayedItemRef>) -> Result<Vec<ItemInfo>> {
+        match self.send(WcpCommand::get_item_info { ids }).await? {
+            WcpResponse::get_item_info { results } => Ok(results),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Adds variables by their full path and returns references to the new items
+    pub async fn add_variables(&self, variables: Vec<String>) -> Result<Vec<DisplayedItemRef>> {
+        match self.send(WcpCommand::add_variables { variables }).await? {
+            WcpResponse::add_variables { ids } => Ok(ids),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Adds all variables in `scope` and returns references to the new items
+    pub async fn add_scope(
+        &self,
+        scope: impl Into<String>,
+        recursive: bool,
+    ) -> Result<Vec<DisplayedItemRef>> {
+        let scope = scope.into();
+        match self
+            .send(WcpCommand::add_scope { scope, recursive })
+            .await?
+        {
+            WcpResponse::add_scope { ids } => Ok(ids),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Removes the specified items
+    pub async fn remove_items(&self, ids: Vec<DisplayedItemRef>) -> Result<()> {
+        self.send_expect_ack(WcpCommand::remove_items { ids }).await
+    }
+
+    /// Changes the color of an item
+    pub async fn set_item_color(
+        &self,
+        id: DisplayedItemRef,
+        color: impl Into<String>,
+    ) -> Result<()> {
+        self.send_expect_ack(WcpCommand::set_item_color {
+            id,
+            color: color.into(),
+        })
+        .await
+    }
+
+    /// Changes the format of an item
+    pub async fn set_item_format(
+        &self,
+        id: DisplayedItemRef,
+        format: impl Into<String>,
+    ) -> Result<()> {
+        self.send_expect_ack(WcpCommand::set_item_format {
+            id,
+            format: format.into(),
+        })
+        .await
+    }
+
+    /// Returns the value of a variable at `time`, formatted as displayed
+    pub async fn get_value_at(
+        &self,
+        id: DisplayedItemRef,
+        time: impl Into<BigInt>,
+    ) -> Result<Option<String>> {
+        let time = time.into();
+        match self.send(WcpCommand::get_value_at { id, time }).await? {
+            WcpResponse::get_value_at { value } => Ok(value),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Moves the cursor
+    pub async fn set_cursor(&self, timestamp: impl Into<BigInt>) -> Result<()> {
+        self.send_expect_ack(WcpCommand::set_cursor {
+            timestamp: timestamp.into(),
+        })
+        .await
+    }
+
+    /// Adds a marker and returns its id
+    pub async fn add_marker(
+        &self,
+        timestamp: impl Into<BigInt>,
+        name: Option<String>,
+    ) -> Result<u8> {
+        let timestamp = timestamp.into();
+        match self
+            .send(WcpCommand::add_marker { timestamp, name })
+            .await?
+        {
+            WcpResponse::add_marker { id } => Ok(id),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Returns all markers
+    pub async fn get_markers(&self) -> Result<Vec<MarkerInfo>> {
+        match self.send(WcpCommand::get_markers).await? {
+            WcpResponse::get_markers { markers } => Ok(markers),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Zooms out to show the whole waveform
+    pub async fn zoom_to_fit(&self, viewport_idx: usize) -> Result<()> {
+        self.send_expect_ack(WcpCommand::zoom_to_fit { viewport_idx })
+            .await
+    }
+
+    /// Zooms to show the time range between `start` and `end`
+    pub async fn zoom_to_range(
+        &self,
+        start: impl Into<BigInt>,
+        end: impl Into<BigInt>,
+        viewport_idx: usize,
+    ) -> Result<()> {
+        self.send_expect_ack(WcpCommand::zoom_to_range {
+            start: start.into(),
+            end: end.into(),
+            viewport_idx,
+        })
+        .await
+    }
+
+    /// Removes all displayed items
+    pub async fn clear(&self) -> Result<()> {
+        self.send_expect_ack(WcpCommand::clear).await
+    }
+
+    /// Asks the server to close the connection. No response is sent for this command.
+    pub async fn shutdown(self) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        write_message(&mut *writer, &WcpCSMessage::command(WcpCommand::shutdowmn)).await
+    }
+}
+
+impl Drop for WcpClient {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+fn unexpected(response: WcpResponse) -> eyre::Report {
+    eyre!("Unexpected response from server: {response:?}")
+}
+
+async fn write_message(
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    message: &WcpCSMessage,
+) -> Result<()> {
+    let mut data = serde_json::to_vec(message)?;
+    data.push(0);
+    writer.write_all(&data).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads one null terminated frame. Returns `None` at the end of the stream.
+async fn read_frame(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let mut data = vec![];
+    if reader.read_until(0, &mut data).await? == 0 {
+        return Ok(None);
+    }
+    if data.last() == Some(&0) {
+        data.pop();
+    }
+    Ok(Some(data))
+}
+
+/// Reads one null terminated message. Returns `None` at the end of the stream.
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<WcpSCMessage>> {
+    match read_frame(reader).await? {
+        Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        None => Ok(None),
+    }
+}
+
+/// The `type` field of a message that could not be decoded, if it has one
+fn message_type(data: &[u8]) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_slice(data).ok()?;
+    Some(value.get("type")?.as_str()?.to_string())
+}
+
+/// Hands `result` to the oldest command waiting for a response
+fn respond(pending: &PendingResponses, result: Result<WcpResponse>) {
+    match pending.lock().unwrap().pop_front() {
+        Some(sender) => {
+            let _ = sender.send(result);
+        }
+        None => log::warn!("Received a response without a pending command: {result:?}"),
+    }
+}
+
+/// Dispatches messages from the server until the connection is closed. Dropping the
+/// pending senders when returning wakes up any command still waiting for a response.
+async fn read_messages(
+    mut reader: impl AsyncBufRead + Unpin,
+    pending: PendingResponses,
+    events: mpsc::UnboundedSender<WcpEvent>,
+) {
+    loop {
+        let data = match read_frame(&mut reader).await {
+            Ok(Some(data)) => data,
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("Failed to read WCP message: {e:#}");
+                break;
+            }
+        };
+        let message = match serde_json::from_slice(&data) {
+            Ok(message) => message,
+            Err(e) => {
+                // Responses must still be consumed to keep later responses matched to
+                // their commands. Anything else, such as events added in newer servers,
+                // is skipped.
+                match message_type(&data).as_deref() {
+                    Some("response" | "error") => {
+                        respond(&pending, Err(eyre!("Failed to decode response: {e}")))
+                    }
+                    _ => log::warn!("Ignoring WCP message that could not be decoded: {e}"),
+                }
+                continue;
+            }
+        };
+        let result = match message {
+            WcpSCMessage::response(response) => Ok(response),
+            WcpSCMessage::error {
+                error,
+                arguments,
+                message,
+            } => Err(WcpServerError {
+                error,
+                arguments,
+                message,
+            }
+            .into()),
+            WcpSCMessage::event(event) => {
+                // Nobody listening for events is fine
+                let _ = events.send(event);
+                continue;
+            }
+            WcpSCMessage::greeting { .. } => {
+                log::warn!("Ignoring unexpected greeting from server");
+                continue;
+            }
+        };
+        respond(&pending, result);
+    }
+    pending.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reads commands from the client end of `stream` and answers them using `respond`
+    async fn fake_server(
+        stream: tokio::io::DuplexStream,
+        respond: impl Fn(WcpCSMessage) -> Vec<WcpSCMessage>,
+    ) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut data = vec![];
+            if reader.read_until(0, &mut data).await.unwrap() == 0 {
+                return;
+            }
+            data.pop();
+            for response in respond(serde_json::from_slice(&data).unwrap()) {
+                let mut data = serde_json::to_vec(&response).unwrap();
+                data.push(0);
+                writer.write_all(&data).await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_and_events_are_dispatched() {
+        let (client_stream, server_stream) = tokio::io::duplex(4096);
+        tokio::spawn(fake_server(server_stream, |msg| match msg {
+            WcpCSMessage::greeting { .. } => vec![WcpSCMessage::create_greeting(
+                0,
+                vec!["load".to_string(), "get_item_list".to_string()],
+            )],
+            WcpCSMessage::command(WcpCommand::load { source, .. }) => vec![
+                WcpSCMessage::response(WcpResponse::ack),
+                WcpSCMessage::event(WcpEvent::waveforms_loaded { source }),
+            ],
+            WcpCSMessage::command(WcpCommand::get_item_list) => {
+                vec![WcpSCMessage::response(WcpResponse::get_item_list {
+                    ids: vec![DisplayedItemRef(1), DisplayedItemRef(3)],
+                })]
+            }
+            WcpCSMessage::command(_) => vec![WcpSCMessage::create_error(
+                "unsupported".to_string(),
+                vec![],
+                "Not supported".to_string(),
+            )],
+        }));
+
+        let mut client = WcpClient::from_stream(client_stream, &["waveforms_loaded"])
+            .await
+            .unwrap();
+        assert!(client.supports("load"));
+        assert!(!client.supports("reload"));
+        let mut events = client.take_events().unwrap();
+
+        client.load("counter.vcd").await.unwrap();
+        assert_eq!(
+            events.recv().await,
+            Some(WcpEvent::waveforms_loaded {
+                source: "counter.vcd".to_string()
+            })
+        );
+        assert_eq!(
+            client.get_item_list().await.unwrap(),
+            vec![DisplayedItemRef(1), DisplayedItemRef(3)]
+        );
+
+        let error = client.clear().await.unwrap_err();
+        assert_eq!(
+            error
+                .downcast_ref::<WcpServerError>()
+                .map(|e| e.error.as_str()),
+            Some("unsupported")
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_events_are_skipped() {
+        let (client_stream, server_stream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server_stream);
+            let mut reader = BufReader::new(reader);
+            let mut data = vec![];
+            reader.read_until(0, &mut data).await.unwrap();
+            let mut greeting =
+                serde_json::to_vec(&WcpSCMessage::create_greeting(0, vec![])).unwrap();
+            greeting.push(0);
+            writer.write_all(&greeting).await.unwrap();
+
+            data.clear();
+            reader.read_until(0, &mut data).await.unwrap();
+            writer
+                .write_all(b"{\"type\":\"event\",\"event\":\"from_the_future\"}\0")
+                .await
+                .unwrap();
+            writer
+                .write_all(b"{\"type\":\"response\",\"command\":\"from_the_future\"}\0")
+                .await
+                .unwrap();
+            let mut ack = serde_json::to_vec(&WcpSCMessage::response(WcpResponse::ack)).unwrap();
+            ack.push(0);
+            data.clear();
+            reader.read_until(0, &mut data).await.unwrap();
+            writer.write_all(&ack).await.unwrap();
+        });
+
+        let client = WcpClient::from_stream(client_stream, &[]).await.unwrap();
+        // The undecodable response still answers the first command
+        assert!(client.clear().await.is_err());
+        client.clear().await.unwrap();
+    }
+}
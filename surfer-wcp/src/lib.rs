@@ -0,0 +1,18 @@
+//! Types and an async client for the Waveform Control Protocol (WCP), used to control
+//! waveform viewers such as Surfer from other programs.
+//!
+//! The client is enabled by the default `client` feature. Without it, only the message
+//! types are available, which also work on wasm32.
+//!
+//! ```no_run
+//! # async fn example() -> eyre::Result<()> {
+//! use surfer_wcp::client::WcpClient;
+//!
+//! let client = WcpClient::connect("127.0.0.1:54321", &["waveforms_loaded"]).await?;
+//! client.load("examples/counter.vcd").await?;
+//! # Ok(())
+//! # }
+//! ```
+#[cfg(feature = "client")]
+pub mod client;
+pub mod proto;
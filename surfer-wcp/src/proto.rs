@@ -0,0 +1,366 @@
+//! Message types of the Waveform Control Protocol.
+use num::{BigInt, FromPrimitive, ToPrimitive};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Number;
+
+/// A reference to a currently displayed item. From the protocol perspective,
+/// This can be any integer or a string and what it is is decided by the server,
+/// in this case surfer.
+/// Since the representation is up to the server, clients cannot generate these on its
+/// own, it can only use the ones it has received from the server.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(transparent)]
+pub struct DisplayedItemRef(pub usize);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ItemInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub t: String,
+    pub id: DisplayedItemRef,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MarkerInfo {
+    pub id: u8,
+    pub name: Option<String>,
+    #[serde(
+        serialize_with = "serialize_timestamp",
+        deserialize_with = "deserialize_timestamp"
+    )]
+    pub time: BigInt,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct VariableInfo {
+    /// Full path of the variable, separated by `.`
+    pub name: String,
+    pub width: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "command")]
+#[allow(non_camel_case_types)]
+pub enum WcpResponse {
+    get_item_list { ids: Vec<DisplayedItemRef> },
+    get_item_info { results: Vec<ItemInfo> },
+    add_variables { ids: Vec<DisplayedItemRef> },
+    add_scope { ids: Vec<DisplayedItemRef> },
+    get_value_at { value: Option<String> },
+    add_marker { id: u8 },
+    get_markers { markers: Vec<MarkerInfo> },
+    add_group { id: DisplayedItemRef },
+    get_hierarchy { scopes: Vec<String> },
+    list_variables { variables: Vec<VariableInfo> },
+    ack,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "event")]
+#[allow(non_camel_case_types)]
+pub enum WcpEvent {
+    waveforms_loaded {
+        source: String,
+    },
+    goto_declaration {
+        variable: String,
+    },
+    add_drivers {
+        variable: String,
+    },
+    add_loads {
+        variable: String,
+    },
+    open_source_request {
+        signal_name: String,
+        full_path: String,
+    },
+    /// Emitted when the cursor is moved, if subscribed to with `cursor_changed`
+    cursor_changed {
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        time: BigInt,
+    },
+    /// Emitted when the cursor is removed, if subscribed to with `cursor_changed`
+    cursor_cleared,
+    /// Emitted when markers are added, moved, renamed or removed, if subscribed to with
+    /// `markers_changed`. Contains only the markers that were added or changed since the
+    /// last event, and the ids of the removed ones.
+    markers_changed {
+        markers: Vec<MarkerInfo>,
+        removed: Vec<u8>,
+    },
+    /// Emitted when the focused item changes, if subscribed to with `focus_changed`
+    focus_changed {
+        id: Option<DisplayedItemRef>,
+    },
+    /// Emitted when the set of selected items changes, if subscribed to with
+    /// `selection_changed`
+    selection_changed {
+        ids: Vec<DisplayedItemRef>,
+    },
+    /// Emitted when a viewport is zoomed or scrolled, if subscribed to with
+    /// `viewport_changed`
+    viewport_changed {
+        viewport_idx: usize,
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        start: BigInt,
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        end: BigInt,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+#[allow(non_camel_case_types)]
+pub enum WcpSCMessage {
+    greeting {
+        version: String,
+        commands: Vec<String>,
+    },
+    response(WcpResponse),
+    error {
+        error: String,
+        arguments: Vec<String>,
+        message: String,
+    },
+    event(WcpEvent),
+}
+
+impl WcpSCMessage {
+    pub fn create_greeting(version: usize, commands: Vec<String>) -> Self {
+        Self::greeting {
+            version: version.to_string(),
+            commands,
+        }
+    }
+
+    pub fn create_error(error: String, arguments: Vec<String>, message: String) -> Self {
+        Self::error {
+            error,
+            arguments,
+            message,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "command")]
+#[allow(non_camel_case_types)]
+pub enum WcpCommand {
+    /// Responds with [WcpResponse::get_item_list] which contains a list of the items
+    /// in the currently loaded waveforms
+    get_item_list,
+    /// Responds with [WcpResponse::get_item_info] which contains information about
+    /// each item specified in `ids` in the same order as in the `ids` array.
+    /// Responds with an error if any of the specified IDs are not items in the currently loaded
+    /// waveform.
+    get_item_info { ids: Vec<DisplayedItemRef> },
+    /// Changes the color of the specified item to the specified color.
+    /// Responds with [WcpResponse::ack]
+    /// Responds with an error if the `id` does not exist in the currently loaded waveform.
+    set_item_color { id: DisplayedItemRef, color: String },
+    /// Adds the specified variables to the view.
+    /// Responds with [WcpResponse::add_variables] which contains a list of the item references
+    /// that can be used to reference the added items later
+    /// Responds with an error if no waveforms are loaded
+    add_variables { variables: Vec<String> },
+    /// Adds all variables in the specified scope to the view.
+    /// Does so recursively if specified
+    /// Responds with [WcpResponse::add_variables] which contains a list of the item references
+    /// that can be used to reference the added items later
+    /// Responds with an error if no waveforms are loaded
+    add_scope {
+        scope: String,
+        #[serde(default)]
+        recursive: bool,
+    },
+    /// Reloads the waveform from disk if this is possible for the current waveform format.
+    /// If it is not possible, this has no effect.
+    /// Responds instantly with [WcpResponse::ack]
+    /// Once the waveforms have been loaded, a separate event is triggered
+    reload,
+    /// Moves the viewport to center it on the specified timestamp. Does not affect the zoom
+    /// level.
+    /// Responds with [WcpResponse::ack]
+    set_viewport_to {
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        timestamp: BigInt,
+    },
+    /// Removes the specified items from the view.
+    /// Responds with [WcpResponse::ack]
+    /// Does not error if some of the IDs do not exist
+    remove_items { ids: Vec<DisplayedItemRef> },
+    /// Sets the specified ID as the _focused_ item.
+    /// Responds with [WcpResponse::ack]
+    /// Responds with an error if no waveforms are loaded or if the item reference
+    /// does not exist
+    // FIXME: What does this mean in the context of the protocol in general, feels kind
+    // of like a Surfer specific thing. Do we have a use case for it
+    focus_item { id: DisplayedItemRef },
+    /// Removes all currently displayed items
+    /// Responds with [WcpResponse::ack]
+    clear,
+    /// Loads a waveform from the specified file. If `tab` is set, the waveform is loaded
+    /// in that tab, where the index after the last tab opens a new tab. Otherwise it
+    /// replaces the waveform in the current tab.
+    /// Responds instantly with [WcpResponse::ack]
+    /// Responds with an error if `tab` does not exist
+    /// Once the file is loaded, a [WcpEvent::waveforms_loaded] is emitted.
+    load {
+        source: String,
+        #[serde(default)]
+        tab: Option<usize>,
+    },
+    /// Zooms out fully to fit the whole waveform in the view
+    /// Responds instantly with [WcpResponse::ack]
+    zoom_to_fit { viewport_idx: usize },
+    /// Zooms the viewport to show the time range between `start` and `end`
+    /// Responds with [WcpResponse::ack]
+    zoom_to_range {
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        start: BigInt,
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        end: BigInt,
+        #[serde(default)]
+        viewport_idx: usize,
+    },
+    /// Responds with [WcpResponse::get_value_at] which contains the value of the variable
+    /// at the specified time, formatted using the current format of the item.
+    /// Responds with an error if the `id` does not refer to a variable.
+    get_value_at {
+        id: DisplayedItemRef,
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        time: BigInt,
+    },
+    /// Moves the cursor to the specified timestamp.
+    /// Responds with [WcpResponse::ack]
+    set_cursor {
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        timestamp: BigInt,
+    },
+    /// Adds a marker at the specified timestamp.
+    /// Responds with [WcpResponse::add_marker] which contains the id of the new marker
+    /// Responds with an error if no more markers can be added
+    add_marker {
+        #[serde(
+            serialize_with = "serialize_timestamp",
+            deserialize_with = "deserialize_timestamp"
+        )]
+        timestamp: BigInt,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Responds with [WcpResponse::get_markers] which contains all markers
+    get_markers,
+    /// Changes the format (translator) of the specified item.
+    /// Responds with [WcpResponse::ack]
+    /// Responds with an error if the `id` does not exist or if there is no such format
+    set_item_format {
+        id: DisplayedItemRef,
+        format: String,
+    },
+    /// Adds a group containing the specified items. If `ids` is empty, an empty group is
+    /// added at the end.
+    /// Responds with [WcpResponse::add_group] which contains the reference of the group
+    add_group {
+        name: String,
+        #[serde(default)]
+        ids: Vec<DisplayedItemRef>,
+    },
+    /// Changes the displayed name of the specified item.
+    /// Responds with [WcpResponse::ack]
+    /// Responds with an error if the `id` does not exist
+    rename_item { id: DisplayedItemRef, name: String },
+    /// Responds with [WcpResponse::get_hierarchy] which contains the full path of all scopes
+    /// in the currently loaded waveform
+    get_hierarchy,
+    /// Responds with [WcpResponse::list_variables] which contains the variables in the scope
+    /// Responds with an error if the scope does not exist
+    list_variables { scope: String },
+    /// Shut down the WCP server.
+    // FIXME: What does this mean? Does it kill the server, the current connection or surfer itself?
+    shutdowmn,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+#[allow(non_camel_case_types)]
+pub enum WcpCSMessage {
+    #[serde(rename = "greeting")]
+    greeting {
+        version: String,
+        commands: Vec<String>,
+    },
+    command(WcpCommand),
+}
+
+impl WcpCSMessage {
+    pub fn create_greeting(version: usize, commands: Vec<String>) -> Self {
+        Self::greeting {
+            version: version.to_string(),
+            commands,
+        }
+    }
+}
+
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let num = Number::deserialize(deserializer)?;
+    if let Some(timestamp) = num.as_u128() {
+        Ok(BigInt::from(timestamp))
+    } else if let Some(timestamp) = num.as_i128() {
+        Ok(BigInt::from(timestamp))
+    } else if let Some(timestamp) = num.as_f64() {
+        BigInt::from_f64(timestamp).ok_or_else(|| {
+            <D::Error as serde::de::Error>::invalid_value(
+                serde::de::Unexpected::Float(timestamp),
+                &"a finite value",
+            )
+        })
+    } else {
+        Err(de::Error::custom(
+            "Error durian deserialization of timestamp value {num}",
+        ))
+    }
+}
+
+fn serialize_timestamp<S>(timestamp: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if let Some(timestamp) = timestamp.to_u64() {
+        serializer.serialize_u64(timestamp)
+    } else if let Some(timestamp) = timestamp.to_i64() {
+        serializer.serialize_i64(timestamp)
+    } else {
+        Err(ser::Error::custom(format!(
+            "Timestamp {timestamp} does not fit in 64 bits"
+        )))
+    }
+}
@@ -95,7 +95,7 @@ mod main_impl {
     #[cfg(not(target_arch = "wasm32"))]
     pub(crate) fn main() -> Result<()> {
         use libsurfer::{
-            state::UserState, translation::wasm_translator::discover_wasm_translators,
+            state_file_io::decode_state, translation::wasm_translator::discover_wasm_translators,
         };
         simple_eyre::install()?;
 
@@ -140,15 +140,22 @@ mod main_impl {
         let waves = startup_params.waves.clone();
 
         let state = match &state_file {
-            Some(file) => std::fs::read_to_string(file)
+            Some(file) => std::fs::read(file)
                 .with_context(|| format!("Failed to read state from {file}"))
                 .and_then(|content| {
-                    ron::from_str::<UserState>(&content)
+                    decode_state(&content)
                         .with_context(|| format!("Failed to decode state from {file}"))
                 })
-                .map(SystemState::from)
-                .map(|mut s| {
+                .map(|decoded| {
+                    let partially_loaded = decoded.report(file.as_str());
+                    let mut s = SystemState::from(decoded.state);
                     s.user.state_file = Some(file.into());
+                    if partially_loaded {
+                        s.channels
+                            .msg_sender
+                            .send(Message::SetLogsVisible(true))
+                            .ok();
+                    }
                     s
                 })
                 .or_else(|e| {
@@ -165,6 +172,9 @@ mod main_impl {
         for message in discover_wasm_translators() {
             sender.send(message).unwrap();
         }
+        if let Some(elf_file) = &state.user.config.elf_file {
+            sender.send(Message::LoadElfFile(elf_file.clone())).unwrap();
+        }
 
         // install a file watcher that emits a `SuggestReloadWaveform` message
         // whenever the user-provided file changes.